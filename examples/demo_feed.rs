@@ -0,0 +1,17 @@
+//! Writes the made up demo feed, to try out other tools on it
+//!
+//! `cargo run --example demo_feed -- <dir>`
+
+use std::path::PathBuf;
+use transit_radar::gtfs::demo::write_demo_feed;
+
+fn main() -> std::io::Result<()> {
+    let dir: PathBuf = std::env::args_os()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| "demo-gtfs".into());
+    std::fs::create_dir_all(&dir)?;
+    write_demo_feed(&dir)?;
+    eprintln!("Wrote the demo feed to {}", dir.display());
+    Ok(())
+}
@@ -0,0 +1,47 @@
+//! Times building and searching a departures index, run with and without `--features binned-departures` to compare them.
+
+use std::num::NonZeroU32;
+use std::time::Instant;
+
+use radar_search::departures::Departures;
+use radar_search::time::{Period, Time};
+
+const STOPS: u32 = 1_000;
+/// A departure every 5 minutes from 4am to 1am
+const DEPARTURES_PER_STOP: u32 = 21 * 12;
+const SEARCHES: u32 = 100;
+
+fn main() {
+    let start = Instant::now();
+    let mut stops: Vec<Departures> = (0..STOPS).map(|_| Departures::default()).collect();
+    for (stop, departures) in stops.iter_mut().enumerate() {
+        for departure in 0..DEPARTURES_PER_STOP {
+            let trip_id = NonZeroU32::new(departure * STOPS + stop as u32 + 1).unwrap();
+            let time = Time::from_seconds_since_midnight(4 * 3600 + departure * 300 + stop as u32);
+            departures.insert(time, (trip_id, 0));
+        }
+        departures.shrink_to_fit();
+    }
+    println!(
+        "built {} departures in {:?}",
+        STOPS * DEPARTURES_PER_STOP,
+        start.elapsed()
+    );
+
+    let start = Instant::now();
+    let mut found = 0;
+    for search in 0..SEARCHES {
+        let from = Time::from_hms(6 + search % 16, 0, 0);
+        let period = Period::between(from, from + chrono::Duration::minutes(30));
+        for departures in &stops {
+            found += departures.range(period).count();
+        }
+    }
+    println!(
+        "{} searches of {} stops found {} departures in {:?}",
+        SEARCHES,
+        STOPS,
+        found,
+        start.elapsed()
+    );
+}
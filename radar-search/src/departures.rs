@@ -0,0 +1,162 @@
+//! Index of the departures from a stop by their departure time.
+//!
+//! By default this is a `BTreeMap`, with the `binned-departures` feature it is a vector of sorted bins of departures, one for each 10 minutes of the day. The bins have less overhead per departure and the departures of a search period are next to each other in memory.
+//! `cargo bench -p radar-search` compares the two.
+
+use crate::search_data::TripStopRef;
+use crate::size::EstimateSize;
+use crate::time::{Period, Time};
+
+#[cfg(not(feature = "binned-departures"))]
+pub use btree::Departures;
+
+#[cfg(feature = "binned-departures")]
+pub use binned::Departures;
+
+#[cfg(not(feature = "binned-departures"))]
+mod btree {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct Departures(BTreeMap<Time, Vec<TripStopRef>>);
+
+    impl Departures {
+        pub fn insert(&mut self, departure_time: Time, stop_ref: TripStopRef) {
+            self.0.entry(departure_time).or_default().push(stop_ref);
+        }
+
+        /// All the departures within the period, in order of departure time
+        pub fn range(&self, period: Period) -> impl Iterator<Item = &TripStopRef> {
+            self.0
+                .range(period)
+                .flat_map(|(_time, trip_stop_refs)| trip_stop_refs)
+        }
+
+        /// Number of distinct departure times
+        pub fn num_times(&self) -> usize {
+            self.0.len()
+        }
+
+        /// Called once all the departures are inserted
+        pub fn shrink_to_fit(&mut self) {
+            for trip_stop_refs in self.0.values_mut() {
+                trip_stop_refs.shrink_to_fit();
+            }
+        }
+    }
+
+    impl EstimateSize for Departures {
+        fn heap_size(&self) -> usize {
+            // a btree node holds up to 11 entries, assume they are about 2/3 full
+            self.0
+                .values()
+                .map(|trip_stop_refs| {
+                    (std::mem::size_of::<(Time, Vec<TripStopRef>)>() * 3 / 2)
+                        + trip_stop_refs.heap_size()
+                })
+                .sum()
+        }
+    }
+}
+
+#[cfg(feature = "binned-departures")]
+mod binned {
+    use super::*;
+
+    const BIN_SECONDS: u32 = 10 * 60;
+
+    fn bin_of(time: Time) -> usize {
+        (time.seconds_since_midnight() / BIN_SECONDS) as usize
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct Departures {
+        /// Each bin is sorted by time once all departures are inserted
+        bins: Vec<Vec<(Time, TripStopRef)>>,
+        num_times: usize,
+    }
+
+    impl Departures {
+        pub fn insert(&mut self, departure_time: Time, stop_ref: TripStopRef) {
+            let bin = bin_of(departure_time);
+            if self.bins.len() <= bin {
+                self.bins.resize_with(bin + 1, Vec::new);
+            }
+            self.bins[bin].push((departure_time, stop_ref));
+        }
+
+        /// All the departures within the period, in order of departure time
+        pub fn range(&self, period: Period) -> impl Iterator<Item = &TripStopRef> {
+            let first_bin = bin_of(period.start()).min(self.bins.len());
+            let last_bin = bin_of(period.end()).min(self.bins.len().saturating_sub(1));
+            self.bins
+                .get(first_bin..=last_bin)
+                .unwrap_or_default()
+                .iter()
+                .flat_map(move |bin| {
+                    let start = bin.partition_point(|(time, _)| *time < period.start());
+                    let end = bin.partition_point(|(time, _)| *time < period.end());
+                    &bin[start..end.max(start)]
+                })
+                .map(|(_time, stop_ref)| stop_ref)
+        }
+
+        /// Number of distinct departure times
+        pub fn num_times(&self) -> usize {
+            self.num_times
+        }
+
+        /// Called once all the departures are inserted, sorts the bins
+        pub fn shrink_to_fit(&mut self) {
+            self.bins.shrink_to_fit();
+            let mut num_times = 0;
+            for bin in &mut self.bins {
+                bin.sort_by_key(|&(time, _)| time);
+                bin.shrink_to_fit();
+                num_times += bin.len();
+                num_times -= bin.windows(2).filter(|w| w[0].0 == w[1].0).count();
+            }
+            self.num_times = num_times;
+        }
+    }
+
+    impl EstimateSize for Departures {
+        fn heap_size(&self) -> usize {
+            self.bins.heap_size()
+        }
+    }
+}
+
+#[test]
+fn departures_in_range() {
+    use std::num::NonZeroU32;
+    let trip = |id| (NonZeroU32::new(id).unwrap(), 0);
+    let mut departures = Departures::default();
+    departures.insert(Time::from_hms(10, 20, 0), trip(3));
+    departures.insert(Time::from_hms(9, 55, 0), trip(1));
+    departures.insert(Time::from_hms(10, 0, 0), trip(2));
+    departures.insert(Time::from_hms(10, 20, 0), trip(4));
+    departures.insert(Time::from_hms(25, 0, 0), trip(5));
+    departures.shrink_to_fit();
+    let in_range = |start, end| {
+        departures
+            .range(Period::between(start, end))
+            .map(|(trip_id, _)| trip_id.get())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        in_range(Time::from_hms(10, 0, 0), Time::from_hms(10, 20, 0)),
+        vec![2]
+    );
+    assert_eq!(
+        in_range(Time::from_hms(9, 0, 0), Time::from_hms(10, 21, 0)),
+        vec![1, 2, 3, 4]
+    );
+    assert_eq!(
+        in_range(Time::from_hms(24, 0, 0), Time::from_hms(26, 0, 0)),
+        vec![5]
+    );
+    assert!(in_range(Time::from_hms(26, 0, 0), Time::from_hms(27, 0, 0)).is_empty());
+    assert_eq!(departures.num_times(), 4);
+}
@@ -1,29 +1,55 @@
 use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
 use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::fmt;
-use std::iter::FromIterator;
+
+use chrono::NaiveDate;
 
 use crate::search_data::{
-    Day, GTFSData, RequiredData, Route, RouteType, ServiceId, Stop, StopId, TripId,
+    DirectionId, GTFSData, PatternId, RequiredData, Route, RouteType, ServiceId, Stop, StopId,
+    StopStereoType, StopTime, Trip, TripId,
 };
 use crate::time::{Period, Time};
 
+/// The walk from an origin entrance down to each platform of it's station, when the feed has no transfers from the entrance
+const ENTRANCE_WALK_MINS: i64 = 3;
+
 /// Runs an algoritm to build a tree of all fastest journeys from a start point
 pub struct Plotter<'r> {
     period: Period, // Search of journeys is within this period
     route_types: HashSet<RouteType>,
+    /// only trips in this direction are searched, if set
+    direction: Option<DirectionId>,
+    /// the most legs which can be ridden on any of some route types
+    leg_limits: Vec<(Vec<RouteType>, u8)>,
+    origin_stations: HashSet<StopId>,
+    /// trips which departed an origin this long before the start are boarded there as if they were running late
+    origin_grace: chrono::Duration,
     data: &'r GTFSData,
     services: HashSet<ServiceId>, // these services are searched
 
     queue: BinaryHeap<QueueItem<'r>>,
     /// items which were skipped earlier as it didn't seem they would be part of any minimum span but now are, these have already been processed and ordered and are iterated before any more processing from the queue takes place
-    catch_up: VecDeque<Item<'r>>,
-    enqueued_trips: HashSet<TripId>,
+    catch_up: VecDeque<(Item<'r>, Why<'r>)>,
+    /// the legs each trip was boarded with, it's boarded again only with legs none of these have as few or fewer of on each limit
+    enqueued_trips: HashMap<TripId, Vec<Legs>>,
     /// trips which so far have only gotten us late to stops, but they may end up leading to useful stops - will need to clean this up when the last stop in a trip is reached as it will probably grow badly
     slow_trips: HashMap<TripId, Vec<QueueItem<'r>>>,
     // stops that have been arrived at and the earliest time they are arrived at
     stops: HashMap<StopId, Time>,
+    /// earliest arrival at each stop of each stopping pattern and the trip that arrived then, with the legs ridden on it
+    pattern_arrivals: HashMap<(PatternId, usize), (Time, TripId, Legs)>,
+    /// trips which have been overtaken by another trip with the same stopping pattern, so they won't get anywhere first
+    overtaken_trips: HashSet<TripId>,
     emitted_stations: HashSet<StopId>,
+    /// the legs ridden to each stop on the ways there which no other way there rode as few or fewer of on each limit
+    least_legs: HashMap<StopId, Vec<Legs>>,
+    /// trips taken first of the items arriving at the same time, such as those drawn on the last radar so that it's redrawn the same
+    preferred_trips: HashSet<TripId>,
+    /// the trip which first arrived at each stop reached by a trip
+    arrival_trips: HashMap<StopId, TripId>,
+    /// trips which arrived at a stop at the same time as another trip taken after them
+    tied_trips: HashSet<TripId>,
 }
 
 /// Output of the algorithm, Items are produced in order of arrival time
@@ -31,41 +57,53 @@ impl<'r> Iterator for Plotter<'r> {
     type Item = Item<'r>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(item) = self.catch_up.pop_front() {
-            return Some(item);
-        }
-        let mut next_block = self.next_block().into_iter();
-        if let Some(first) = next_block.next() {
-            self.catch_up.extend(next_block);
-            Some(first)
-        } else {
-            None // we exhausted the queues
-        }
+        self.next_with_why().map(|(item, _why)| item)
     }
 }
 
+/// An item of the output with why it was emitted, for reports of the planner's bugs
+#[derive(Debug)]
+pub struct Explained<'r> {
+    pub item: Item<'r>,
+    pub explanation: String,
+}
+
 impl<'r> Plotter<'r> {
-    pub fn new(day: Day, period: Period, data: &'r GTFSData) -> Plotter<'r> {
+    /// A search of the services running on a date, with the exceptions to their calendars on that date
+    pub fn new(date: NaiveDate, period: Period, data: &'r GTFSData) -> Plotter<'r> {
         Plotter {
             period,
-            services: data.services_of_day(day),
+            services: data.services_on(date),
             queue: BinaryHeap::new(),
             catch_up: VecDeque::new(),
-            enqueued_trips: HashSet::new(),
+            enqueued_trips: HashMap::new(),
             slow_trips: HashMap::new(),
             stops: HashMap::new(),
+            pattern_arrivals: HashMap::new(),
+            overtaken_trips: HashSet::new(),
             emitted_stations: HashSet::new(),
+            least_legs: HashMap::new(),
+            preferred_trips: HashSet::new(),
+            arrival_trips: HashMap::new(),
+            tied_trips: HashSet::new(),
             data,
             route_types: HashSet::new(),
+            direction: None,
+            leg_limits: vec![],
+            origin_stations: HashSet::new(),
+            origin_grace: chrono::Duration::zero(),
         }
     }
 
-    /// Add an origin station to start the search from
+    /// Add an origin station to start the search from, an entrance of a station is walked from to the station's platforms before anything is boarded
     pub fn add_origin_station(&mut self, origin: &'r Stop) {
+        self.origin_stations.insert(origin.station_id());
         self.queue.push(QueueItem {
             arrival_time: self.period.start(),
             to_stop: origin,
             variant: QueueItemVariant::OriginStation,
+            legs: Legs::default(),
+            preferred: false,
         });
     }
 
@@ -74,6 +112,63 @@ impl<'r> Plotter<'r> {
         self.route_types.insert(route_type);
     }
 
+    /// Only search trips going in one direction, trips with no direction are then excluded
+    pub fn set_direction(&mut self, direction_id: DirectionId) {
+        self.direction = Some(direction_id);
+    }
+
+    /// Ride at most this many legs on any of the route types, such as one bus leg on either kind of bus
+    ///
+    /// A stop is searched on from when it's first reached and again from each later arrival which none of the earlier ones rode as few or fewer of each limit's legs as, and so is a trip boarded again, though only from the first stop it's boarded at with those legs
+    pub fn limit_legs(&mut self, route_types: &[RouteType], max_legs: u8) {
+        self.leg_limits.push((route_types.to_vec(), max_legs));
+    }
+
+    /// Also board the trips which departed an origin up to this long before the start, as if the rider were already at the platform and the trip were running late, it's delayed to depart at the start
+    pub fn set_origin_grace(&mut self, grace: chrono::Duration) {
+        self.origin_grace = grace;
+    }
+
+    /// Prefer these trips to others arriving at a stop at the same time, otherwise the tie is broken by the rest of the queue's ordering, which can change as the start of the search moves on, so a radar redrawn every few seconds would jump between equally fast branches
+    pub fn prefer_trips(&mut self, trip_ids: impl IntoIterator<Item = TripId>) {
+        self.preferred_trips.extend(trip_ids);
+    }
+
+    /// The trips which won a tie so far, arriving at a stop at the same time as another trip, these are the ones to prefer to search the same again
+    pub fn tied_trips(&self) -> &HashSet<TripId> {
+        &self.tied_trips
+    }
+
+    fn direction_of(&self, trip_id: TripId) -> Option<DirectionId> {
+        self.data
+            .trips
+            .get(&trip_id)
+            .and_then(|trip| trip.direction_id)
+    }
+
+    /// The output of the algorithm with an explanation of why each item was emitted, which is slower as each explanation is written out
+    pub fn explained(mut self) -> impl Iterator<Item = Explained<'r>> {
+        std::iter::from_fn(move || {
+            self.next_with_why().map(|(item, why)| Explained {
+                explanation: why.explain(&item),
+                item,
+            })
+        })
+    }
+
+    fn next_with_why(&mut self) -> Option<(Item<'r>, Why<'r>)> {
+        if let Some(item) = self.catch_up.pop_front() {
+            return Some(item);
+        }
+        let mut next_block = self.next_block().into_iter();
+        if let Some(first) = next_block.next() {
+            self.catch_up.extend(next_block);
+            Some(first)
+        } else {
+            None // we exhausted the queues
+        }
+    }
+
     /// Performs the whole search, producing a filtered search data object with only the stops and trips needed for the search
     pub fn filtered_data(mut self) -> RequiredData {
         let mut builder = self.data.build_from();
@@ -82,11 +177,16 @@ impl<'r> Plotter<'r> {
             if items.is_empty() {
                 break;
             } else {
-                for QueueItem {
-                    arrival_time: _,
-                    to_stop,
-                    variant,
-                } in items
+                for (
+                    QueueItem {
+                        arrival_time: _,
+                        to_stop,
+                        variant,
+                        legs: _,
+                        preferred: _,
+                    },
+                    _why,
+                ) in items
                 {
                     builder.keep_stop(to_stop.stop_id);
                     if let Some(parent_id) = to_stop.parent_station() {
@@ -102,33 +202,38 @@ impl<'r> Plotter<'r> {
     }
 
     /// returns the next items to be emitted in order, or empty if there are no more and the process halts
-    fn next_block(&mut self) -> Vec<Item<'r>> {
+    fn next_block(&mut self) -> Vec<(Item<'r>, Why<'r>)> {
         self.next_block_raw()
             .into_iter()
-            .flat_map(|item| {
+            .flat_map(|(item, why)| {
                 let mut to_emit = vec![];
                 // if this arrives at a new station, emit that first
                 if self.emitted_stations.insert(item.to_stop.station_id()) {
-                    to_emit.push(Item::Station {
-                        stop: item.to_stop,
-                        earliest_arrival: item.arrival_time,
-                        name_trunk_length: if item.variant.is_stop_on_trip() {
-                            item.variant
-                                .get_from_stop()
-                                .and_then(|from_stop| {
-                                    item.to_stop
-                                        .short_stop_name
-                                        .starts_with(&from_stop.short_stop_name)
-                                        .then_some(from_stop.short_stop_name.len())
-                                })
-                                .unwrap_or_default()
-                        } else {
-                            0
+                    to_emit.push((
+                        Item::Station {
+                            stop: item.to_stop,
+                            earliest_arrival: item.arrival_time,
+                            name_trunk_length: if item.variant.is_stop_on_trip() {
+                                item.variant
+                                    .get_from_stop()
+                                    .and_then(|from_stop| {
+                                        item.to_stop
+                                            .short_stop_name()
+                                            .starts_with(from_stop.short_stop_name())
+                                            .then_some(from_stop.short_stop_name().len())
+                                    })
+                                    .unwrap_or_default()
+                            } else {
+                                0
+                            },
+                            legs: item.legs.total(),
+                            transfers_so_far: item.legs.total().saturating_sub(1),
                         },
-                    });
+                        why,
+                    ));
                 }
                 if let Some(item) = self.convert_item(item) {
-                    to_emit.push(item);
+                    to_emit.push((item, why));
                 }
                 to_emit // we found something that's worth drawing
             })
@@ -136,12 +241,31 @@ impl<'r> Plotter<'r> {
     }
 
     /// returns the next processed items in order, or empty if there are no more and the process halts
-    fn next_block_raw(&mut self) -> Vec<QueueItem<'r>> {
+    fn next_block_raw(&mut self) -> Vec<(QueueItem<'r>, Why<'r>)> {
         while let Some(item) = self.queue.pop() {
             if self.period.contains(item.arrival_time) {
-                let processed: Vec<QueueItem<'r>> = self.process_queue_item(item);
+                let queued = self.queue.len();
+                let tied = self
+                    .queue
+                    .peek()
+                    .is_some_and(|next| next.arrival_time == item.arrival_time);
+                let preferred = item.preferred;
+                let processed = self.process_queue_item(item);
                 if !processed.is_empty() {
-                    return processed;
+                    return processed
+                        .into_iter()
+                        .map(|(item, reason)| {
+                            (
+                                item,
+                                Why {
+                                    reason,
+                                    queued,
+                                    tied,
+                                    preferred,
+                                },
+                            )
+                        })
+                        .collect();
                 }
             } else {
                 return vec![]; // we ran out of the time period
@@ -157,6 +281,8 @@ impl<'r> Plotter<'r> {
             to_stop,
             mut arrival_time,
             variant,
+            legs: _,
+            preferred: _,
         }: QueueItem<'r>,
     ) -> Option<Item<'r>> {
         match variant {
@@ -184,6 +310,7 @@ impl<'r> Plotter<'r> {
                 route_type: route.route_type,
                 route_color: &route.route_color,
                 trip_id,
+                direction_id: self.direction_of(trip_id),
             }),
             QueueItemVariant::StopOnTrip {
                 trip_id,
@@ -192,6 +319,7 @@ impl<'r> Plotter<'r> {
                 next_departure_time,
                 from_stop,
                 mut departure_time,
+                stop_index: _,
             } => {
                 // we don't show the stop time at each station along the trip, so we use one time
                 // at each stop. If the stop is the earliest arrival at the station, we use the
@@ -211,15 +339,16 @@ impl<'r> Plotter<'r> {
                     route_name: &route.route_short_name,
                     route_type: route.route_type,
                     route_color: &route.route_color,
+                    direction_id: self.direction_of(trip_id),
                 })
             }
         }
     }
 
-    fn enqueue_transfers_from_stop(&mut self, stop: &'r Stop, departure_time: Time) {
+    fn enqueue_transfers_from_stop(&mut self, stop: &'r Stop, departure_time: Time, legs: Legs) {
         let mut to_add = vec![];
         for transfer in &stop.transfers {
-            if !self.stops.contains_key(&transfer.to_stop_id) {
+            if self.worth_reaching(transfer.to_stop_id, legs) {
                 if let Some(to_stop) = self.data.get_stop(transfer.to_stop_id) {
                     to_add.push(QueueItem {
                         to_stop,
@@ -231,6 +360,8 @@ impl<'r> Plotter<'r> {
                             from_stop: stop,
                             departure_time,
                         },
+                        legs,
+                        preferred: false,
                     });
                 }
             }
@@ -238,31 +369,27 @@ impl<'r> Plotter<'r> {
         self.queue.extend(to_add);
     }
 
-    fn enqueue_transfers_from_station(&mut self, station: &'r Stop, departure_time: Time) {
+    fn enqueue_transfers_from_station(
+        &mut self,
+        station: &'r Stop,
+        departure_time: Time,
+        legs: Legs,
+    ) {
         let mut to_add = vec![];
-        for transfer in &station.transfers {
-            if !self.stops.contains_key(&transfer.to_stop_id) {
-                // parent stations transfer to parents, so transfer to the children as well (but aybe they hav entries in transfer to use without this implicit transfer?)
-                // we ignore any missing stops in case this is a partial data set
-                let to_stop = self.data.get_stop(transfer.to_stop_id);
-                let iter = to_stop
-                    .iter()
-                    .map(|stop| &stop.stop_id)
-                    .chain(to_stop.iter().flat_map(|stop| stop.children()));
-                for &to_stop_id in iter {
-                    if let Some(to_stop) = self.data.get_stop(to_stop_id) {
-                        to_add.push(QueueItem {
-                            to_stop,
-                            arrival_time: departure_time
-                                + transfer
-                                    .min_transfer_time
-                                    .unwrap_or_else(chrono::Duration::zero),
-                            variant: QueueItemVariant::Transfer {
-                                from_stop: station,
-                                departure_time,
-                            },
-                        });
-                    }
+        for walk in &station.walks {
+            // parent stations transfer to parents, the walks include the children as well (but aybe they hav entries in transfer to use without this implicit transfer?)
+            if self.worth_reaching(walk.via_stop_id, legs) {
+                if let Some(to_stop) = self.data.get_stop(walk.to_stop_id) {
+                    to_add.push(QueueItem {
+                        to_stop,
+                        arrival_time: departure_time + walk.duration,
+                        variant: QueueItemVariant::Transfer {
+                            from_stop: station,
+                            departure_time,
+                        },
+                        legs,
+                        preferred: false,
+                    });
                 }
             }
         }
@@ -286,12 +413,50 @@ impl<'r> Plotter<'r> {
                             from_stop: stop,
                             departure_time: arrival_time,
                         },
+                        legs: Legs::default(),
+                        preferred: false,
                     })
             })
             .collect();
         self.queue.extend(to_add);
     }
 
+    /// The platforms are reached by the transfers from the entrance, which are it's pathways, or else each platform of the station is a default walk away
+    fn enqueue_walks_from_entrance(
+        &mut self,
+        entrance: &'r Stop,
+        station_id: StopId,
+        arrival_time: Time,
+    ) {
+        if !entrance.walks.is_empty() {
+            self.enqueue_transfers_from_station(entrance, arrival_time, Legs::default());
+            return;
+        }
+        let station = if let Some(station) = self.data.get_stop(station_id) {
+            station
+        } else {
+            return;
+        };
+        let at_platform = arrival_time + chrono::Duration::minutes(ENTRANCE_WALK_MINS);
+        let to_add: Vec<QueueItem> = station
+            .children()
+            .filter_map(|&stop_id| self.data.get_stop(stop_id))
+            .filter(|stop| matches!(stop.stereotype, StopStereoType::StopOrPlatform { .. }))
+            .map(|platform| QueueItem {
+                to_stop: platform,
+                arrival_time: at_platform,
+                variant: QueueItemVariant::Transfer {
+                    from_stop: entrance,
+                    departure_time: arrival_time,
+                },
+                legs: Legs::default(),
+                preferred: false,
+            })
+            .collect();
+        self.queue.extend(to_add);
+        self.enqueue_transfers_from_station(station, at_platform, Legs::default());
+    }
+
     fn enqueue_connections_and_trips(
         &mut self,
         item: &QueueItem<'r>,
@@ -302,58 +467,26 @@ impl<'r> Plotter<'r> {
         for (trip, stops) in self.data.trips_from(
             item.to_stop,
             &self.services,
-            self.period.with_start(item.arrival_time),
+            self.period.with_start(self.boardable_since(item)),
         ) {
-            let stops = Vec::from_iter(stops);
-            let trip_id = trip.trip_id;
-            let mut trip_to_add = vec![];
-            // check that route type is allowed
-            let route = &trip.route;
-            if self.route_types.contains(&route.route_type) {
-                // enqueue connection (transfer + wait)
-                trip_to_add.push(QueueItem {
-                    to_stop: item.to_stop,
-                    arrival_time: stops[0].departure_time,
-                    variant: QueueItemVariant::Connection {
-                        trip_id,
-                        route,
-                        from_stop,
-                        departure_time,
-                    },
-                });
-                for window in stops.windows(2) {
-                    if let [from_stop, to_stop] = window {
-                        if self.period.contains(to_stop.arrival_time) {
-                            // these stops wont be there if this stoptime is going to be filtered out later anyway
-                            if let (Some(to_stop_stop), Some(from_stop_stop)) = (
-                                self.data.get_stop(to_stop.stop_id),
-                                self.data.get_stop(from_stop.stop_id),
-                            ) {
-                                trip_to_add.push(QueueItem {
-                                    to_stop: to_stop_stop,
-                                    arrival_time: to_stop.arrival_time,
-                                    variant: QueueItemVariant::StopOnTrip {
-                                        trip_id,
-                                        route,
-                                        previous_arrival_time: from_stop.arrival_time,
-                                        next_departure_time: to_stop.departure_time,
-                                        from_stop: from_stop_stop,
-                                        departure_time: from_stop.departure_time,
-                                    },
-                                });
-                            }
-                        }
-                    } else {
-                        panic!("Bad window");
-                    }
-                }
-                to_add.push((trip_id, trip_to_add));
+            let first_stop_index = trip.stop_times.len() - stops.count();
+            if self.searches_trip(trip) && self.may_board(trip, item.legs) {
+                let trip_to_add = self.trip_items(
+                    trip,
+                    first_stop_index,
+                    item.to_stop,
+                    from_stop,
+                    departure_time,
+                    item.arrival_time,
+                    item.legs.boarding(trip.route.route_type),
+                );
+                to_add.push((trip.trip_id, trip_to_add));
             }
         }
         let mut extended = false;
         for (trip_id, to_add) in to_add {
-            // make sure we only add each trip once
-            if self.enqueued_trips.insert(trip_id) {
+            // make sure we only add each trip once, unless it's boarded with fewer of some limited legs
+            if self.board(trip_id, to_add[0].legs) {
                 extended = true;
                 self.queue.extend(to_add);
             }
@@ -361,11 +494,179 @@ impl<'r> Plotter<'r> {
         extended
     }
 
+    /// The earliest departures which can be boarded from an item, those before it's arrival only at an origin at the start, within the grace
+    fn boardable_since(&self, item: &QueueItem<'r>) -> Time {
+        let at_origin = item.arrival_time == self.period.start()
+            && self.origin_stations.contains(&item.to_stop.station_id());
+        if !at_origin {
+            return item.arrival_time;
+        }
+        // the day's schedule doesn't go back before midnight
+        let since_midnight = item.arrival_time - Time::from_hms(0, 0, 0);
+        item.arrival_time + -self.origin_grace.min(since_midnight)
+    }
+
+    /// Whether the route type and direction of a trip are allowed
+    fn searches_trip(&self, trip: &Trip) -> bool {
+        self.route_types.contains(&trip.route.route_type)
+            && (self.direction.is_none() || self.direction == trip.direction_id)
+    }
+
+    /// Whether another leg can be ridden on the trip's route type after these legs
+    fn may_board(&self, trip: &Trip, legs: Legs) -> bool {
+        self.leg_limits.iter().all(|(route_types, max_legs)| {
+            !route_types.contains(&trip.route.route_type) || legs.on(route_types) < *max_legs
+        })
+    }
+
+    /// Whether a stop hasn't been reached yet, or only on ways which each rode more of some limited legs
+    fn worth_reaching(&self, stop_id: StopId, legs: Legs) -> bool {
+        !self.stops.contains_key(&stop_id)
+            || self.least_legs.get(&stop_id).is_some_and(|least| {
+                !least
+                    .iter()
+                    .any(|earlier| earlier.dominates(legs, &self.leg_limits))
+            })
+    }
+
+    /// Records the legs ridden to a stop, returns true if each earlier way there rode more of some limited legs
+    fn record_legs(&mut self, stop_id: StopId, legs: Legs) -> bool {
+        if self.leg_limits.is_empty() {
+            return false;
+        }
+        let limits = &self.leg_limits;
+        let least = self.least_legs.entry(stop_id).or_default();
+        if least.iter().any(|earlier| earlier.dominates(legs, limits)) {
+            return false;
+        }
+        least.retain(|earlier| !legs.dominates(*earlier, limits));
+        least.push(legs);
+        true
+    }
+
+    /// Whether the trip was first boarded with the legs, rather than boarded again with fewer of some limited legs, whose items are searched on from but not drawn
+    fn first_boarded_with(&self, trip_id: TripId, legs: Legs) -> bool {
+        self.enqueued_trips
+            .get(&trip_id)
+            .and_then(|boarded| boarded.first())
+            .is_none_or(|&first| first == legs)
+    }
+
+    /// Records boarding a trip with the legs, returns false if it was already boarded with as few or fewer of each limited legs
+    fn board(&mut self, trip_id: TripId, legs: Legs) -> bool {
+        let limits = &self.leg_limits;
+        let boarded = self.enqueued_trips.entry(trip_id).or_default();
+        if boarded
+            .iter()
+            .any(|earlier| earlier.dominates(legs, limits))
+        {
+            return false;
+        }
+        boarded.push(legs);
+        true
+    }
+
+    /// Items for boarding a trip at one of it's stops and riding it to each following stop, with the legs ridden on it
+    ///
+    /// A trip which departs before the rider is ready to board it is running late, it's times from the boarding stop on are delayed for it to depart when the rider is ready
+    #[allow(clippy::too_many_arguments)]
+    fn trip_items(
+        &self,
+        trip: &'r Trip,
+        first_stop_index: usize,
+        boarding_stop: &'r Stop,
+        from_stop: &'r Stop,
+        departure_time: Time,
+        ready_at: Time,
+        legs: Legs,
+    ) -> Vec<QueueItem<'r>> {
+        let stops = &trip.stop_times[first_stop_index..];
+        let trip_id = trip.trip_id;
+        let route = &trip.route;
+        let preferred = self.preferred_trips.contains(&trip_id);
+        let delay = (ready_at - stops[0].departure_time).max(chrono::Duration::zero());
+        // enqueue connection (transfer + wait)
+        let mut items = vec![QueueItem {
+            to_stop: boarding_stop,
+            arrival_time: stops[0].departure_time + delay,
+            variant: QueueItemVariant::Connection {
+                trip_id,
+                route,
+                from_stop,
+                departure_time,
+            },
+            legs,
+            preferred,
+        }];
+        for (i, window) in stops.windows(2).enumerate() {
+            if let [from_stop, to_stop] = window {
+                if self.period.contains(to_stop.arrival_time + delay) {
+                    // these stops wont be there if this stoptime is going to be filtered out later anyway
+                    if let (Some(to_stop_stop), Some(from_stop_stop)) = (
+                        self.data.get_stop(to_stop.stop_id),
+                        self.data.get_stop(from_stop.stop_id),
+                    ) {
+                        items.push(QueueItem {
+                            to_stop: to_stop_stop,
+                            arrival_time: to_stop.arrival_time + delay,
+                            variant: QueueItemVariant::StopOnTrip {
+                                trip_id,
+                                route,
+                                previous_arrival_time: from_stop.arrival_time + delay,
+                                next_departure_time: to_stop.departure_time + delay,
+                                from_stop: from_stop_stop,
+                                departure_time: from_stop.departure_time + delay,
+                                stop_index: first_stop_index + i + 1,
+                            },
+                            legs,
+                            preferred,
+                        });
+                    }
+                }
+            } else {
+                panic!("Bad window");
+            }
+        }
+        items
+    }
+
+    /// At the last stop of a trip, riders can stay seated as the vehicle continues as the next trip in it's block, without needing a transfer
+    fn enqueue_block_continuation(
+        &mut self,
+        trip_id: TripId,
+        stop_index: usize,
+        stop: &'r Stop,
+        arrival_time: Time,
+        legs: Legs,
+    ) {
+        let data = self.data;
+        let next_trip = data
+            .trips
+            .get(&trip_id)
+            .filter(|trip| stop_index + 1 == trip.stop_times.len())
+            .and_then(|trip| trip.next_in_block)
+            .and_then(|next_trip_id| data.trips.get(&next_trip_id));
+        if let Some(next_trip) = next_trip {
+            if self.searches_trip(next_trip) && self.board(next_trip.trip_id, legs) {
+                // staying seated isn't another leg
+                let items =
+                    self.trip_items(next_trip, 0, stop, stop, arrival_time, arrival_time, legs);
+                self.queue.extend(items);
+            }
+        }
+    }
+
     fn earliest_arrival_at(&self, stop_id: StopId) -> Option<Time> {
         self.stops.get(&stop_id).cloned()
     }
 
-    fn filter_slow_trip(&mut self, slow_trip: Vec<QueueItem<'r>>) -> Vec<QueueItem<'r>> {
+    /// The items of a slow trip to emit now that it has reached a new station
+    fn filter_slow_trip(
+        &mut self,
+        slow_trip: Vec<QueueItem<'r>>,
+        reached: &'r Stop,
+    ) -> Vec<(QueueItem<'r>, Reason<'r>)> {
+        let slow = |item| (item, Reason::SlowTrip { reached });
         // this trip became useful but it might be that we don't board at the first stop where we encountered it, we should board at the stop we can get to the earliest, not the earliest we can board this trip
         let boarding_opportunities = slow_trip.iter().enumerate().filter_map(|(i, item)| {
             // Each item must only be a StopOnTrip or a Connection
@@ -387,6 +688,7 @@ impl<'r> Plotter<'r> {
                     route,
                     previous_arrival_time: _,
                     next_departure_time: _,
+                    stop_index: _,
                 } = item.variant
                 {
                     // we board later and so need a new connection for that
@@ -399,19 +701,21 @@ impl<'r> Plotter<'r> {
                             trip_id,
                             route,
                         },
+                        legs: item.legs,
+                        preferred: item.preferred,
                     };
-                    Some(connection)
+                    Some((connection, Reason::LaterBoarding { reached }))
                         .into_iter()
-                        .chain(slow_trip.into_iter().skip(boarding_idx))
+                        .chain(slow_trip.into_iter().skip(boarding_idx).map(slow))
                         .collect()
                 } else {
                     panic!("expected {:?} to be a StopOnTrip", item);
                 }
             } else {
-                slow_trip
+                slow_trip.into_iter().map(slow).collect()
             }
         } else {
-            slow_trip
+            slow_trip.into_iter().map(slow).collect()
         }
     }
 
@@ -419,9 +723,7 @@ impl<'r> Plotter<'r> {
         let new_arrival_is_earlier = self
             .stops
             .get(&stop_id)
-            .map_or(true, |&previous_earliest_arrival| {
-                new_arrival_time < previous_earliest_arrival
-            });
+            .is_none_or(|&previous_earliest_arrival| new_arrival_time < previous_earliest_arrival);
         if new_arrival_is_earlier {
             self.stops.insert(stop_id, new_arrival_time);
             true
@@ -430,8 +732,114 @@ impl<'r> Plotter<'r> {
         }
     }
 
+    /// Records which trip arrives at a stop first, and the trip which did if this one arrives at the same time
+    fn record_tie(&mut self, item: &QueueItem<'r>) {
+        let trip_id = if let QueueItemVariant::StopOnTrip { trip_id, .. } = item.variant {
+            trip_id
+        } else {
+            return;
+        };
+        let stop_id = item.to_stop.stop_id;
+        match self.earliest_arrival_at(stop_id) {
+            None => {
+                self.arrival_trips.insert(stop_id, trip_id);
+            }
+            Some(earliest) if earliest == item.arrival_time => {
+                if let Some(&first) = self.arrival_trips.get(&stop_id) {
+                    if first != trip_id {
+                        self.tied_trips.insert(first);
+                    }
+                }
+            }
+            Some(_) => (),
+        }
+    }
+
+    /// Records the arrival of a trip at a stop of its stopping pattern, returns true if another trip of the same pattern got there first with as few or fewer of each limited legs and also gets to all the following stops first, such a trip is a duplicate or has been overtaken and can't get anywhere first
+    fn is_overtaken(
+        &mut self,
+        trip_id: TripId,
+        stop_index: usize,
+        arrival_time: Time,
+        legs: Legs,
+    ) -> bool {
+        let data = self.data;
+        let trip = match data.trips.get(&trip_id) {
+            Some(trip) => trip,
+            None => return false,
+        };
+        match self.pattern_arrivals.entry((trip.pattern_id, stop_index)) {
+            Entry::Occupied(earliest) if earliest.get().0 <= arrival_time => {
+                let (ahead_arrival_time, ahead_trip_id, ahead_legs) = *earliest.get();
+                // either trip may have been boarded late within the origin grace, so it's that much later at each stop
+                let delay = |trip: &Trip, arrival_time: Time| {
+                    arrival_time - trip.stop_times[stop_index].arrival_time
+                };
+                let overtaken = ahead_legs.dominates(legs, &self.leg_limits)
+                    && data.trips.get(&ahead_trip_id).is_some_and(|ahead| {
+                        stays_ahead(
+                            &ahead.stop_times,
+                            delay(ahead, ahead_arrival_time),
+                            &trip.stop_times,
+                            delay(trip, arrival_time),
+                            stop_index,
+                        )
+                    });
+                if overtaken && ahead_arrival_time == arrival_time {
+                    self.tied_trips.insert(ahead_trip_id);
+                }
+                overtaken
+            }
+            Entry::Occupied(mut earliest) => {
+                earliest.insert((arrival_time, trip_id, legs));
+                false
+            }
+            Entry::Vacant(earliest) => {
+                earliest.insert((arrival_time, trip_id, legs));
+                false
+            }
+        }
+    }
+
+    /// Enqueues the transfers from a stop reached by a trip, to other stops and through it's station
+    fn enqueue_transfers_from_trip(&mut self, item: &QueueItem<'r>) {
+        if !item.to_stop.is_station() {
+            self.enqueue_transfers_from_stop(item.to_stop, item.arrival_time, item.legs);
+        }
+        if let Some(to_station) = self.data.get_stop(item.to_stop.station_id()) {
+            self.enqueue_transfers_from_station(to_station, item.arrival_time, item.legs);
+        }
+    }
+
     /// Processes the item, enqueuing any following segments and possibly returning the processed items to be converted and emitted
-    fn process_queue_item(&mut self, item: QueueItem<'r>) -> Vec<QueueItem<'r>> {
+    fn process_queue_item(&mut self, item: QueueItem<'r>) -> Vec<(QueueItem<'r>, Reason<'r>)> {
+        if let Some(trip_id) = item.variant.get_trip_id() {
+            if self.overtaken_trips.contains(&trip_id) {
+                return vec![];
+            }
+        }
+        if let QueueItemVariant::StopOnTrip {
+            trip_id,
+            stop_index,
+            ..
+        } = item.variant
+        {
+            if self.is_overtaken(trip_id, stop_index, item.arrival_time, item.legs) {
+                // a duplicate, or a trip that has been overtaken by one on the same pattern, it won't get anywhere first so we drop it and any of it's earlier stops
+                self.overtaken_trips.insert(trip_id);
+                self.slow_trips.remove(&trip_id);
+                return vec![];
+            }
+            self.enqueue_block_continuation(
+                trip_id,
+                stop_index,
+                item.to_stop,
+                item.arrival_time,
+                item.legs,
+            );
+        }
+        let fewer_legs = self.record_legs(item.to_stop.stop_id, item.legs);
+        self.record_tie(&item);
         if self.set_arrival_time(item.to_stop.stop_id, item.arrival_time) {
             // if this changes the earliest arrival time for this stop, we possibly have new connections / trips
             match item.variant {
@@ -442,15 +850,14 @@ impl<'r> Plotter<'r> {
                     next_departure_time: _,
                     from_stop: _,
                     departure_time: _,
+                    stop_index: _,
                 } => {
-                    if !item.to_stop.is_station() {
-                        self.enqueue_transfers_from_stop(item.to_stop, item.arrival_time);
-                    }
-                    if let Some(to_station) = self.data.get_stop(item.to_stop.station_id()) {
-                        self.enqueue_transfers_from_station(to_station, item.arrival_time);
-                    }
-                    // only emit if we got to a new station
-                    if self.emitted_stations.contains(&item.to_stop.station_id()) {
+                    self.enqueue_transfers_from_trip(&item);
+                    if item.is_loop() {
+                        // the trip loops back to the station it just left (eg. between platforms), this makes no progress and is never drawn, though arriving on the other platform may still lead somewhere
+                        vec![]
+                    } else if self.emitted_stations.contains(&item.to_stop.station_id()) {
+                        // only emit if we got to a new station
                         let slow_trip = self.slow_trips.entry(trip_id).or_default();
                         slow_trip.push(item);
                         vec![]
@@ -458,11 +865,11 @@ impl<'r> Plotter<'r> {
                         // if this now made some slow stops on the trip relevant, they should be emitted as well
                         let slow_trip = self.slow_trips.remove(&trip_id);
                         if let Some(slow_trip) = slow_trip {
-                            let mut to_emit = self.filter_slow_trip(slow_trip);
-                            to_emit.push(item);
+                            let mut to_emit = self.filter_slow_trip(slow_trip, item.to_stop);
+                            to_emit.push((item, Reason::NewStation));
                             to_emit
                         } else {
-                            vec![item]
+                            vec![(item, Reason::NewStation)]
                         }
                     }
                 }
@@ -484,19 +891,40 @@ impl<'r> Plotter<'r> {
                     if !extended || from_stop.station_id() == item.to_stop.station_id() {
                         vec![]
                     } else {
-                        vec![item]
+                        vec![(item, Reason::Transfer)]
                     }
                 }
                 QueueItemVariant::OriginStation => {
-                    self.enqueue_immediate_transfers_to_children_of(
-                        item.to_stop,
-                        item.arrival_time,
-                    );
-                    self.enqueue_transfers_from_station(item.to_stop, item.arrival_time);
-                    vec![item]
+                    if let StopStereoType::EntranceExit { station } = item.to_stop.stereotype {
+                        self.enqueue_walks_from_entrance(item.to_stop, station, item.arrival_time);
+                    } else {
+                        self.enqueue_immediate_transfers_to_children_of(
+                            item.to_stop,
+                            item.arrival_time,
+                        );
+                        self.enqueue_transfers_from_station(
+                            item.to_stop,
+                            item.arrival_time,
+                            item.legs,
+                        );
+                    }
+                    vec![(item, Reason::Origin)]
                 }
             }
         } else {
+            if fewer_legs {
+                // a later arrival, but with legs left to ride on which the earlier ones had used up, so it's searched on from without being emitted
+                match item.variant {
+                    QueueItemVariant::StopOnTrip { .. } => self.enqueue_transfers_from_trip(&item),
+                    QueueItemVariant::Transfer {
+                        from_stop,
+                        departure_time,
+                    } => {
+                        self.enqueue_connections_and_trips(&item, from_stop, departure_time);
+                    }
+                    _ => (),
+                }
+            }
             match item.variant {
                 // late arrival by trip, we want it if this trip will take us somewhere new eventually, so save it for later
                 QueueItemVariant::StopOnTrip {
@@ -506,27 +934,255 @@ impl<'r> Plotter<'r> {
                     next_departure_time: _,
                     departure_time: _,
                     from_stop: _,
+                    stop_index: _,
                 }
                 | QueueItemVariant::Connection {
                     trip_id,
                     route: _,
                     departure_time: _,
                     from_stop: _,
-                } => {
+                } if !item.is_loop() && self.first_boarded_with(trip_id, item.legs) => {
                     let slow_trip = self.slow_trips.entry(trip_id).or_default();
                     slow_trip.push(item);
                 }
-                _ => (), // late arrival by transfer - drop it
+                _ => (), // late arrival by transfer or a loop on a trip - drop it
             }
             vec![] // the item will not be emitted
         }
     }
 }
 
+/// Whether a trip arrives at each stop from `from_index` onwards no later than another trip with the same stopping pattern, each running the given delay behind it's stop times
+fn stays_ahead(
+    ahead: &[StopTime],
+    ahead_delay: chrono::Duration,
+    behind: &[StopTime],
+    behind_delay: chrono::Duration,
+    from_index: usize,
+) -> bool {
+    ahead
+        .iter()
+        .zip(behind)
+        .skip(from_index)
+        .all(|(ahead, behind)| {
+            ahead.arrival_time + ahead_delay <= behind.arrival_time + behind_delay
+        })
+}
+
+/// Why an item was emitted by the planner
+#[derive(Debug, Clone, Copy)]
+enum Reason<'r> {
+    /// The station the search starts from
+    Origin,
+    /// The earliest arrival at a station which hadn't been reached
+    NewStation,
+    /// A transfer to a station which boards trips that weren't searched before
+    Transfer,
+    /// A stop of a trip which reached it late, kept aside until the trip reached a new station
+    SlowTrip { reached: &'r Stop },
+    /// Boarding a slow trip at the stop which is reached first, rather than where it was first found
+    LaterBoarding { reached: &'r Stop },
+}
+
+/// Why an item was emitted and how it was taken from the queue
+#[derive(Debug, Clone, Copy)]
+struct Why<'r> {
+    reason: Reason<'r>,
+    /// Items left in the queue when it's item was taken as the earliest
+    queued: usize,
+    /// The next item in the queue arrives at the same time, so it was ordered by the rest of the queue's ordering
+    tied: bool,
+    /// The item was on a preferred trip, which is ordered first of a tie
+    preferred: bool,
+}
+
+impl<'r> Why<'r> {
+    fn explain(&self, item: &Item<'r>) -> String {
+        let queue = if self.tied && self.preferred {
+            format!(
+                "it was the earliest of {} queued items, tied on arrival with the next one and ordered before it as it's trip is preferred",
+                self.queued + 1
+            )
+        } else if self.tied {
+            format!(
+                "it was the earliest of {} queued items, tied on arrival with the next one and ordered before it by the times along the trip, the kind of item and then the stop",
+                self.queued + 1
+            )
+        } else {
+            format!("it was the earliest of {} queued items", self.queued + 1)
+        };
+        match (item, self.reason) {
+            (
+                Item::Station {
+                    stop,
+                    earliest_arrival,
+                    ..
+                },
+                Reason::Origin,
+            ) => format!(
+                "{} is the origin of the search, departing at {}",
+                stop.short_stop_name(),
+                earliest_arrival
+            ),
+            (
+                Item::Station {
+                    stop,
+                    earliest_arrival,
+                    legs,
+                    ..
+                },
+                _,
+            ) => format!(
+                "{} is first reached at {} after {} legs, {}",
+                stop.short_stop_name(),
+                earliest_arrival,
+                legs,
+                queue
+            ),
+            (
+                Item::Transfer {
+                    from_stop, to_stop, ..
+                },
+                _,
+            ) => format!(
+                "walking from {} to {} {} boards trips which weren't searched yet, {}",
+                from_stop.short_stop_name(),
+                to_stop.short_stop_name(),
+                transfer_source(from_stop, to_stop),
+                queue
+            ),
+            (
+                Item::ConnectionToTrip {
+                    from_stop,
+                    route_name,
+                    trip_id,
+                    ..
+                },
+                Reason::LaterBoarding { reached },
+            ) => format!(
+                "boarding {} trip {} at {}, where it's reached earliest rather than where the trip was first found, as the trip went on to first reach {}",
+                route_name,
+                trip_id,
+                from_stop.short_stop_name(),
+                reached.short_stop_name()
+            ),
+            (
+                Item::ConnectionToTrip {
+                    to_stop,
+                    route_name,
+                    trip_id,
+                    ..
+                },
+                Reason::SlowTrip { reached },
+            )
+            | (
+                Item::SegmentOfTrip {
+                    to_stop,
+                    route_name,
+                    trip_id,
+                    ..
+                },
+                Reason::SlowTrip { reached },
+            ) => format!(
+                "{} trip {} reached {} after it had been reached another way, so it was kept aside as a slow trip until the trip first reached {}",
+                route_name,
+                trip_id,
+                to_stop.short_stop_name(),
+                reached.short_stop_name()
+            ),
+            (
+                Item::ConnectionToTrip {
+                    to_stop,
+                    route_name,
+                    trip_id,
+                    ..
+                },
+                _,
+            )
+            | (
+                Item::SegmentOfTrip {
+                    to_stop,
+                    route_name,
+                    trip_id,
+                    ..
+                },
+                _,
+            ) => format!(
+                "{} trip {} is the earliest arrival at {}, which hadn't been reached, {}",
+                route_name,
+                trip_id,
+                to_stop.short_stop_name(),
+                queue
+            ),
+        }
+    }
+}
+
+/// Where a transfer between the stops comes from
+fn transfer_source(from_stop: &Stop, to_stop: &Stop) -> &'static str {
+    match from_stop.stereotype {
+        StopStereoType::EntranceExit { .. } => "through the entrance",
+        _ if from_stop.is_station() && to_stop.station_id() == from_stop.stop_id => {
+            "onto a platform of the station"
+        }
+        _ if from_stop.is_station() => "on the walk between the stations",
+        _ => "on a transfer of the feed",
+    }
+}
+
 struct QueueItem<'r> {
     arrival_time: Time,
     to_stop: &'r Stop,
     variant: QueueItemVariant<'r>,
+    /// the legs ridden to the stop, which aren't ordered on
+    legs: Legs,
+    /// on a preferred trip, which is ordered before the others arriving at the same time
+    preferred: bool,
+}
+
+/// The number of legs ridden on each route type
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Legs([u8; RouteType::ALL.len()]);
+
+impl Legs {
+    /// The legs after boarding a trip of the route type
+    fn boarding(mut self, route_type: RouteType) -> Self {
+        let legs = &mut self.0[route_type as usize];
+        *legs = legs.saturating_add(1);
+        self
+    }
+
+    /// The number of legs ridden on any of the route types
+    fn on(&self, route_types: &[RouteType]) -> u8 {
+        route_types
+            .iter()
+            .map(|&route_type| self.0[route_type as usize])
+            .fold(0, u8::saturating_add)
+    }
+
+    /// The number of legs ridden on all route types
+    fn total(&self) -> u8 {
+        self.0.iter().copied().fold(0, u8::saturating_add)
+    }
+
+    /// Whether these legs ride as few or fewer as the other legs on each of the limits, so the other legs can't get anywhere these can't
+    fn dominates(self, other: Legs, limits: &[(Vec<RouteType>, u8)]) -> bool {
+        limits
+            .iter()
+            .all(|(route_types, _)| self.on(route_types) <= other.on(route_types))
+    }
+}
+
+impl<'r> QueueItem<'r> {
+    /// A stop on a trip at the same station as the previous stop, eg. a bus which leaves from one stop at a station and calls at another stop there
+    fn is_loop(&self) -> bool {
+        match self.variant {
+            QueueItemVariant::StopOnTrip { from_stop, .. } => {
+                from_stop.station_id() == self.to_stop.station_id()
+            }
+            _ => false,
+        }
+    }
 }
 
 impl<'r> fmt::Debug for QueueItem<'r> {
@@ -568,6 +1224,7 @@ impl<'r> fmt::Debug for QueueItem<'r> {
                 next_departure_time: _,
                 from_stop,
                 departure_time,
+                stop_index: _,
             } => f
                 .debug_struct("StopOnTrip")
                 .field("route", &route)
@@ -582,13 +1239,14 @@ impl<'r> fmt::Debug for QueueItem<'r> {
 }
 
 /// The ordering on the queue items puts those with the earliest arrival times as the greatest,
-/// so that they will be highest priority in the `BinaryHeap`, then (as an occasional bus route has sub-minute arrival times), it does the same thong each with previous arrival time, departure time and next departure time in the case of a stop. Then all the other fields need to be
+/// so that they will be highest priority in the `BinaryHeap`, of those arriving at the same time those on preferred trips are greatest, then (as an occasional bus route has sub-minute arrival times), it does the same thong each with previous arrival time, departure time and next departure time in the case of a stop. Then all the other fields need to be
 /// taken into account for a full ordering
 impl<'r> Ord for QueueItem<'r> {
     fn cmp(&self, other: &Self) -> Ordering {
         self.arrival_time
             .cmp(&other.arrival_time)
             .reverse()
+            .then_with(|| self.preferred.cmp(&other.preferred))
             .then_with(|| match (&self.variant, &other.variant) {
                 (
                     QueueItemVariant::StopOnTrip {
@@ -638,6 +1296,7 @@ enum QueueItemVariant<'r> {
         route: &'r Route,
         previous_arrival_time: Time, // arrival at the from stop
         next_departure_time: Time,   // departure from the to stop
+        stop_index: usize,           // index of the to stop in the trip
     },
     Connection {
         departure_time: Time,
@@ -672,6 +1331,7 @@ impl<'r> QueueItemVariant<'r> {
                 route: _,
                 previous_arrival_time: _,
                 next_departure_time: _,
+                stop_index: _,
             }
             | QueueItemVariant::Transfer {
                 departure_time: _,
@@ -696,6 +1356,7 @@ impl<'r> QueueItemVariant<'r> {
                 route: _,
                 previous_arrival_time: _,
                 next_departure_time: _,
+                stop_index: _,
             } => Some(*trip_id),
             QueueItemVariant::Transfer {
                 departure_time: _,
@@ -723,6 +1384,7 @@ pub enum Item<'r> {
         route_type: RouteType,
         route_color: &'r str,
         trip_id: TripId,
+        direction_id: Option<DirectionId>,
     },
     SegmentOfTrip {
         departure_time: Time,
@@ -733,10 +1395,912 @@ pub enum Item<'r> {
         route_name: &'r str,
         route_type: RouteType,
         route_color: &'r str,
+        direction_id: Option<DirectionId>,
     },
     Station {
         stop: &'r Stop,
         earliest_arrival: Time,
         name_trunk_length: usize,
+        /// The trips ridden to first reach the station
+        legs: u8,
+        /// The changes between those trips
+        transfers_so_far: u8,
     },
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Item, NaiveDate, Plotter};
+    use crate::search_data::{Builder, Day, GTFSData, RouteType, StopId, TripId};
+    use crate::time::{Period, Time};
+    use std::collections::HashMap;
+
+    /// A line of stations, each with a single platform, numbered along the line
+    fn line_builder(station_names: &[&str]) -> Builder {
+        line_builder_with_transfer_time(station_names, None)
+    }
+
+    fn line_builder_with_transfer_time(
+        station_names: &[&str],
+        min_transfer_time: Option<chrono::Duration>,
+    ) -> Builder {
+        let mut services_by_day = HashMap::new();
+        services_by_day.insert(Day::Monday, [1].iter().copied().collect());
+        let mut builder = GTFSData::builder(services_by_day, "20200101".to_owned());
+        for (i, name) in station_names.iter().enumerate() {
+            let location = geo::Point::new(52.5, 13.4 + 0.01 * i as f64);
+            builder.add_station(station(i), name.to_string(), name.to_string(), location);
+            builder.add_stop_or_platform(
+                platform(i),
+                name.to_string(),
+                name.to_string(),
+                location,
+                Some(station(i)),
+            );
+            builder
+                .add_transfer(station(i), station(i), min_transfer_time)
+                .unwrap();
+        }
+        builder
+    }
+
+    /// A date the test services run on
+    fn monday() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2020, 1, 6).unwrap()
+    }
+
+    fn station(i: usize) -> StopId {
+        StopId::new(100 + i as u32 * 10).unwrap()
+    }
+
+    fn platform(i: usize) -> StopId {
+        StopId::new(101 + i as u32 * 10).unwrap()
+    }
+
+    /// Adds a trip calling at the numbered stations at the given (arrival, departure) minutes past 10:00
+    fn add_trip(builder: &mut Builder, trip_id: u32, route_id: u32, calls: &[(usize, u32, u32)]) {
+        let trip_id = TripId::new(trip_id).unwrap();
+        builder.add_trip(trip_id, route_id, 1, None, None).unwrap();
+        for &(i, arrival, departure) in calls {
+            builder
+                .add_trip_stop(
+                    trip_id,
+                    Time::from_hms(10, arrival, 0),
+                    Time::from_hms(10, departure, 0),
+                    platform(i),
+                )
+                .unwrap();
+        }
+    }
+
+    /// A search from the first station from 10:00 for 30 minutes on the S-Bahn
+    fn plotter(data: &GTFSData) -> Plotter<'_> {
+        let mut plotter = Plotter::new(
+            monday(),
+            Period::between(Time::from_hms(10, 0, 0), Time::from_hms(10, 30, 0)),
+            data,
+        );
+        plotter.add_origin_station(data.get_stop(station(0)).unwrap());
+        plotter.add_route_type(RouteType::SuburbanRailway);
+        plotter
+    }
+
+    /// Runs a search from the first station from 10:00 for 30 minutes and describes the emitted items
+    fn search(data: &GTFSData) -> Vec<String> {
+        search_with(data, |_plotter| {})
+    }
+
+    /// Runs a search as `search` does, with the S-Bahn and any other settings
+    fn search_with(data: &GTFSData, set_up: impl FnOnce(&mut Plotter)) -> Vec<String> {
+        let mut plotter = plotter(data);
+        set_up(&mut plotter);
+        plotter
+            .filter_map(|item| match item {
+                Item::Station {
+                    stop,
+                    earliest_arrival,
+                    ..
+                } => Some(format!("{} {}", stop.short_stop_name(), earliest_arrival)),
+                Item::ConnectionToTrip {
+                    from_stop,
+                    departure_time,
+                    trip_id,
+                    ..
+                } => Some(format!(
+                    "board {} at {} {}",
+                    trip_id,
+                    from_stop.short_stop_name(),
+                    departure_time
+                )),
+                Item::SegmentOfTrip {
+                    from_stop,
+                    to_stop,
+                    trip_id,
+                    ..
+                } => Some(format!(
+                    "{} {}-{}",
+                    trip_id,
+                    from_stop.short_stop_name(),
+                    to_stop.short_stop_name()
+                )),
+                Item::Transfer { .. } => None,
+            })
+            .collect()
+    }
+
+    /// The peak hour express leaves after the local, overtakes it while it calls at the intermediate stations, and
+    /// terminates at Ostkreuz, the local is still the only way on to Karlshorst
+    fn express_and_local() -> GTFSData {
+        let mut builder = line_builder(&[
+            "Ostbahnhof",
+            "Warschauer Str",
+            "Ostkreuz",
+            "Rummelsburg",
+            "Karlshorst",
+        ]);
+        builder.add_route(
+            1,
+            "S3".to_owned(),
+            RouteType::SuburbanRailway,
+            "#026597".to_owned(),
+        );
+        builder.add_route(
+            2,
+            "S3X".to_owned(),
+            RouteType::SuburbanRailway,
+            "#026597".to_owned(),
+        );
+        add_trip(
+            &mut builder,
+            1,
+            1,
+            &[(0, 0, 0), (1, 3, 3), (2, 9, 10), (3, 12, 12), (4, 15, 15)],
+        );
+        add_trip(&mut builder, 2, 2, &[(0, 2, 2), (2, 7, 7)]);
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn express_overtakes_local() {
+        let data = express_and_local();
+
+        let items = search(&data);
+        for expected in &[
+            "2 Ostbahnhof-Ostkreuz",
+            "Ostkreuz 10:07:00",
+            "1 Ostkreuz-Rummelsburg",
+            "Karlshorst 10:15:00",
+        ] {
+            assert!(items.contains(&expected.to_string()), "{:?}", items);
+        }
+    }
+
+    #[test]
+    fn explained() {
+        let data = express_and_local();
+
+        let explanations: Vec<String> = plotter(&data)
+            .explained()
+            .map(|explained| explained.explanation)
+            .collect();
+        assert_eq!(
+            explanations[0],
+            "Ostbahnhof is the origin of the search, departing at 10:00:00"
+        );
+        for expected in &[
+            "Ostkreuz is first reached at 10:07:00 after 1 legs, it was the earliest of ",
+            "S3X trip 2 is the earliest arrival at Ostkreuz, which hadn't been reached, it was the earliest of ",
+            "S3 trip 1 reached Ostkreuz after it had been reached another way, so it was kept aside as a slow trip until the trip first reached Rummelsburg",
+        ] {
+            assert!(
+                explanations
+                    .iter()
+                    .any(|explanation| explanation.starts_with(expected)),
+                "{:#?}",
+                explanations
+            );
+        }
+        // the explanations don't change what's emitted
+        assert_eq!(plotter(&data).explained().count(), plotter(&data).count());
+    }
+
+    #[test]
+    fn preferred_trip_wins_a_tie() {
+        let mut builder = line_builder(&["Westkreuz", "Charlottenburg", "Savignyplatz"]);
+        builder.add_route(
+            1,
+            "S5".to_owned(),
+            RouteType::SuburbanRailway,
+            "#ea561c".to_owned(),
+        );
+        builder.add_route(
+            2,
+            "S7".to_owned(),
+            RouteType::SuburbanRailway,
+            "#816da6".to_owned(),
+        );
+        // both arrive at Savignyplatz at 10:06
+        add_trip(&mut builder, 1, 1, &[(0, 0, 0), (2, 6, 6)]);
+        add_trip(&mut builder, 2, 2, &[(0, 1, 1), (1, 3, 3), (2, 6, 6)]);
+        let data = builder.build().unwrap();
+
+        let items = search(&data);
+        assert!(
+            items.contains(&"1 Westkreuz-Savignyplatz".to_owned()),
+            "{:?}",
+            items
+        );
+        let items = search_with(&data, |plotter| {
+            plotter.prefer_trips(Some(TripId::new(2).unwrap()))
+        });
+        assert!(
+            items.contains(&"2 Charlottenburg-Savignyplatz".to_owned()),
+            "{:?}",
+            items
+        );
+        assert!(
+            !items.contains(&"1 Westkreuz-Savignyplatz".to_owned()),
+            "{:?}",
+            items
+        );
+        assert!(
+            items.contains(&"Savignyplatz 10:06:00".to_owned()),
+            "{:?}",
+            items
+        );
+
+        // the trip taken first of the tie is the one to prefer to search the same again
+        let tied_trips = |preferred: Option<u32>| {
+            let mut searched = plotter(&data);
+            searched.prefer_trips(preferred.and_then(TripId::new));
+            searched.by_ref().for_each(drop);
+            searched
+                .tied_trips()
+                .iter()
+                .map(|trip_id| trip_id.get())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tied_trips(None), vec![1]);
+        assert_eq!(tied_trips(Some(2)), vec![2]);
+    }
+
+    #[test]
+    fn duplicate_trips_are_emitted_once() {
+        // feeds sometimes contain the same journey twice under different trip ids
+        let mut builder = line_builder(&["Westkreuz", "Charlottenburg", "Savignyplatz"]);
+        builder.add_route(
+            1,
+            "S5".to_owned(),
+            RouteType::SuburbanRailway,
+            "#ea561c".to_owned(),
+        );
+        add_trip(&mut builder, 1, 1, &[(0, 1, 1), (1, 3, 3), (2, 5, 5)]);
+        add_trip(&mut builder, 2, 1, &[(0, 1, 1), (1, 3, 3), (2, 5, 5)]);
+        // and a later train on the same line that can't get anywhere first
+        add_trip(&mut builder, 3, 1, &[(0, 4, 4), (1, 6, 6), (2, 8, 8)]);
+        let data = builder.build().unwrap();
+
+        let items = search(&data);
+        let segments: Vec<_> = items
+            .iter()
+            .filter(|item| item.ends_with("Charlottenburg-Savignyplatz"))
+            .collect();
+        assert_eq!(segments.len(), 1, "{:?}", items);
+        assert!(!segments[0].starts_with('3'), "{:?}", items);
+        assert!(
+            items.contains(&"Savignyplatz 10:05:00".to_owned()),
+            "{:?}",
+            items
+        );
+    }
+
+    #[test]
+    fn trips_with_hundreds_of_calls_are_ridden_to_the_end() {
+        // a stop index past 255 mustn't be mistaken for an earlier stop of the same pattern
+        let names: Vec<String> = (0..300).map(|i| format!("Halt {}", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut builder = line_builder(&names);
+        builder.add_route(
+            1,
+            "S1".to_owned(),
+            RouteType::SuburbanRailway,
+            "#dc6ba6".to_owned(),
+        );
+        let trip_id = TripId::new(1).unwrap();
+        builder.add_trip(trip_id, 1, 1, None, None).unwrap();
+        for i in 0..names.len() {
+            let time = Time::from_hms(10, 0, 0) + chrono::Duration::seconds(5 * i as i64);
+            builder
+                .add_trip_stop(trip_id, time, time, platform(i))
+                .unwrap();
+        }
+        let data = builder.build().unwrap();
+
+        let items = search(&data);
+        assert!(
+            items.contains(&"Halt 299 10:24:55".to_owned()),
+            "{:?}",
+            items
+        );
+    }
+
+    #[test]
+    fn trips_are_boarded_past_the_255th_call() {
+        // the long trip left before the search, it's caught at its 270th stop from a trip on another platform there
+        let names: Vec<String> = (0..300).map(|i| format!("Halt {}", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut builder = line_builder(&names);
+        let other_platform = StopId::new(102 + 270 * 10).unwrap();
+        builder.add_stop_or_platform(
+            other_platform,
+            "Halt 270".to_owned(),
+            "Halt 270".to_owned(),
+            geo::Point::new(52.5, 13.4 + 0.01 * 270.0),
+            Some(station(270)),
+        );
+        builder.add_route(
+            1,
+            "S1".to_owned(),
+            RouteType::SuburbanRailway,
+            "#dc6ba6".to_owned(),
+        );
+        builder.add_route(
+            2,
+            "S2".to_owned(),
+            RouteType::SuburbanRailway,
+            "#007734".to_owned(),
+        );
+        let trip_id = TripId::new(1).unwrap();
+        builder.add_trip(trip_id, 1, 1, None, None).unwrap();
+        for i in 0..names.len() {
+            let time = Time::from_hms(9, 50, 0) + chrono::Duration::seconds(5 * i as i64);
+            builder
+                .add_trip_stop(trip_id, time, time, platform(i))
+                .unwrap();
+        }
+        let trip_id = TripId::new(2).unwrap();
+        builder.add_trip(trip_id, 2, 1, None, None).unwrap();
+        for &(stop_id, time) in &[(platform(0), 1), (other_platform, 5)] {
+            let time = Time::from_hms(10, time, 0);
+            builder.add_trip_stop(trip_id, time, time, stop_id).unwrap();
+        }
+        let data = builder.build().unwrap();
+
+        let items = search(&data);
+        for expected in &["Halt 271 10:12:35", "Halt 299 10:14:55"] {
+            assert!(items.contains(&expected.to_string()), "{:?}", items);
+        }
+    }
+
+    #[test]
+    fn later_trip_on_same_pattern_can_still_overtake() {
+        // the first train waits at Charlottenburg long enough that the next one on the same pattern arrives at Savignyplatz first
+        let mut builder = line_builder(&["Westkreuz", "Charlottenburg", "Savignyplatz"]);
+        builder.add_route(
+            1,
+            "S5".to_owned(),
+            RouteType::SuburbanRailway,
+            "#ea561c".to_owned(),
+        );
+        add_trip(&mut builder, 1, 1, &[(0, 1, 1), (1, 3, 12), (2, 14, 14)]);
+        add_trip(&mut builder, 2, 1, &[(0, 4, 4), (1, 6, 6), (2, 8, 8)]);
+        let data = builder.build().unwrap();
+
+        let items = search(&data);
+        assert!(
+            items.contains(&"Savignyplatz 10:08:00".to_owned()),
+            "{:?}",
+            items
+        );
+        assert!(
+            items.contains(&"2 Charlottenburg-Savignyplatz".to_owned()),
+            "{:?}",
+            items
+        );
+    }
+
+    #[test]
+    fn loops_back_to_a_station_are_dropped() {
+        // bus 118 leaves Wannsee and arrives at another Wannsee stop 2 minutes later, and another bus does the same mid route
+        let station_names = ["Wannsee", "Kohlhasenbrück", "Griebnitzsee"];
+        let mut builder = line_builder(&station_names);
+        for (i, name) in station_names.iter().enumerate() {
+            let location = geo::Point::new(52.5, 13.4 + 0.01 * i as f64);
+            builder.add_stop_or_platform(
+                StopId::new(102 + i as u32 * 10).unwrap(),
+                name.to_string(),
+                name.to_string(),
+                location,
+                Some(station(i)),
+            );
+        }
+        builder.add_route(
+            1,
+            "118".to_owned(),
+            RouteType::SuburbanRailway,
+            "#a01c7d".to_owned(),
+        );
+        builder
+            .add_trip(TripId::new(1).unwrap(), 1, 1, None, None)
+            .unwrap();
+        builder
+            .add_trip(TripId::new(2).unwrap(), 1, 1, None, None)
+            .unwrap();
+        for &(trip_id, stop_id, time) in &[
+            (1, 101, 1),
+            (1, 102, 3),
+            (1, 111, 5),
+            (1, 121, 9),
+            (2, 101, 2),
+            (2, 111, 6),
+            (2, 112, 7),
+            (2, 122, 8),
+        ] {
+            builder
+                .add_trip_stop(
+                    TripId::new(trip_id).unwrap(),
+                    Time::from_hms(10, time, 0),
+                    Time::from_hms(10, time, 0),
+                    StopId::new(stop_id).unwrap(),
+                )
+                .unwrap();
+        }
+        let data = builder.build().unwrap();
+
+        let items = search(&data);
+        for expected in &[
+            "1 Wannsee-Kohlhasenbrück",
+            "Kohlhasenbrück 10:05:00",
+            "2 Wannsee-Kohlhasenbrück",
+            "2 Kohlhasenbrück-Griebnitzsee",
+            "Griebnitzsee 10:08:00",
+        ] {
+            assert!(items.contains(&expected.to_string()), "{:?}", items);
+        }
+        for unexpected in &["1 Wannsee-Wannsee", "2 Kohlhasenbrück-Kohlhasenbrück"] {
+            assert!(!items.contains(&unexpected.to_string()), "{:?}", items);
+        }
+    }
+
+    #[test]
+    fn direction_filter() {
+        let mut builder = line_builder(&["Westend", "Jungfernheide", "Beusselstr"]);
+        builder.add_route(
+            1,
+            "S41".to_owned(),
+            RouteType::SuburbanRailway,
+            "#ad5937".to_owned(),
+        );
+        builder
+            .add_trip(TripId::new(1).unwrap(), 1, 1, Some(0), None)
+            .unwrap();
+        builder
+            .add_trip(TripId::new(2).unwrap(), 1, 1, Some(1), None)
+            .unwrap();
+        for &(trip_id, i, time) in &[(1, 0, 1), (1, 1, 3), (2, 0, 2), (2, 2, 5)] {
+            builder
+                .add_trip_stop(
+                    TripId::new(trip_id).unwrap(),
+                    Time::from_hms(10, time, 0),
+                    Time::from_hms(10, time, 0),
+                    platform(i),
+                )
+                .unwrap();
+        }
+        let data = builder.build().unwrap();
+
+        let mut plotter = Plotter::new(
+            monday(),
+            Period::between(Time::from_hms(10, 0, 0), Time::from_hms(10, 30, 0)),
+            &data,
+        );
+        plotter.add_origin_station(data.get_stop(station(0)).unwrap());
+        plotter.add_route_type(RouteType::SuburbanRailway);
+        plotter.set_direction(1);
+        let directions: Vec<_> = plotter
+            .filter_map(|item| match item {
+                Item::SegmentOfTrip {
+                    trip_id,
+                    direction_id,
+                    ..
+                } => Some((trip_id.get(), direction_id)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(directions, vec![(2, Some(1))]);
+    }
+
+    #[test]
+    fn stay_seated_into_next_trip_of_block() {
+        // the regional train terminates at Ostbahnhof and continues as another trip, there's no time to change trains there
+        let mut builder = line_builder_with_transfer_time(
+            &["Hauptbahnhof", "Ostbahnhof", "Erkner", "Ostkreuz"],
+            Some(chrono::Duration::minutes(3)),
+        );
+        builder.add_route(
+            1,
+            "RE1".to_owned(),
+            RouteType::SuburbanRailway,
+            "#e2001a".to_owned(),
+        );
+        let block_id = Some(StopId::new(7).unwrap());
+        for &(trip_id, block_id) in &[(1, block_id), (2, block_id), (3, None)] {
+            builder
+                .add_trip(TripId::new(trip_id).unwrap(), 1, 1, None, block_id)
+                .unwrap();
+        }
+        for &(trip_id, i, arrival, departure) in &[
+            (1, 0, 1, 1),
+            (1, 1, 5, 5),
+            (2, 1, 6, 6),
+            (2, 2, 10, 10),
+            (3, 1, 6, 6),
+            (3, 3, 9, 9),
+        ] {
+            builder
+                .add_trip_stop(
+                    TripId::new(trip_id).unwrap(),
+                    Time::from_hms(10, arrival, 0),
+                    Time::from_hms(10, departure, 0),
+                    platform(i),
+                )
+                .unwrap();
+        }
+        let data = builder.build().unwrap();
+        assert_eq!(
+            data.trips()
+                .find(|trip| trip.trip_id.get() == 1)
+                .unwrap()
+                .next_in_block,
+            TripId::new(2)
+        );
+
+        let items = search(&data);
+        for expected in &[
+            "board 2 at Ostbahnhof 10:05:00",
+            "2 Ostbahnhof-Erkner",
+            "Erkner 10:10:00",
+        ] {
+            assert!(items.contains(&expected.to_string()), "{:?}", items);
+        }
+        assert!(
+            !items.iter().any(|item| item.contains("Ostkreuz")),
+            "{:?}",
+            items
+        );
+    }
+
+    #[test]
+    fn leg_limits() {
+        // at most one bus leg, the buses from the bus stop at Mexikoplatz can only be caught by arriving there by train
+        let mut builder =
+            line_builder(&["Zehlendorf", "Mexikoplatz", "Krumme Lanke", "Schlachtensee"]);
+        let bus_stop = StopId::new(112).unwrap();
+        builder.add_stop_or_platform(
+            bus_stop,
+            "Mexikoplatz".to_owned(),
+            "Mexikoplatz".to_owned(),
+            geo::Point::new(52.5, 13.41),
+            Some(station(1)),
+        );
+        builder.add_route(1, "X10".to_owned(), RouteType::Bus, "#a5027d".to_owned());
+        builder.add_route(
+            2,
+            "S1".to_owned(),
+            RouteType::SuburbanRailway,
+            "#dd6ca6".to_owned(),
+        );
+        add_trip(&mut builder, 1, 1, &[(0, 0, 0), (1, 2, 2)]);
+        add_trip(&mut builder, 3, 2, &[(0, 1, 1), (1, 4, 4), (2, 9, 9)]);
+        for &(trip_id, departure, to, arrival) in &[(2, 5, 2, 8), (4, 3, 3, 6)] {
+            let trip_id = TripId::new(trip_id).unwrap();
+            builder.add_trip(trip_id, 1, 1, None, None).unwrap();
+            for &(stop_id, time) in &[(bus_stop, departure), (platform(to), arrival)] {
+                let time = Time::from_hms(10, time, 0);
+                builder.add_trip_stop(trip_id, time, time, stop_id).unwrap();
+            }
+        }
+        let data = builder.build().unwrap();
+
+        let items = search_with(&data, |plotter| plotter.add_route_type(RouteType::Bus));
+        assert!(
+            items.contains(&"Schlachtensee 10:06:00".to_owned()),
+            "{:?}",
+            items
+        );
+
+        let items = search_with(&data, |plotter| {
+            plotter.add_route_type(RouteType::Bus);
+            plotter.limit_legs(&[RouteType::Bus, RouteType::BusService], 1);
+        });
+        assert!(
+            items.contains(&"board 2 at Mexikoplatz 10:04:00".to_owned()),
+            "{:?}",
+            items
+        );
+        assert!(
+            items.contains(&"Krumme Lanke 10:08:00".to_owned()),
+            "{:?}",
+            items
+        );
+        assert!(
+            !items.iter().any(|item| item.contains("Schlachtensee")),
+            "{:?}",
+            items
+        );
+    }
+
+    #[test]
+    fn leg_limits_of_each_mode() {
+        // at most two bus and two tram legs, Tierpark is reached first after two buses and a tram and after a bus and two
+        // trams, the slow tram arriving later after a bus and a tram is overtaken by the fast one but is the only way on
+        // by bus and then tram to Wuhletal
+        let names = [
+            "Lichtenberg",
+            "Herzbergstr.",
+            "Sewanstr.",
+            "Zobtener Str.",
+            "Tierpark",
+            "Friedrichsfelde Ost",
+            "Wuhletal",
+            "Rummelsburg",
+        ];
+        let mut builder = line_builder(&names);
+        // the trams call at their own platforms, so they can be changed to and from the buses
+        let tram_stop = |i: usize| StopId::new(102 + i as u32 * 10).unwrap();
+        for (i, name) in names.iter().enumerate() {
+            builder.add_stop_or_platform(
+                tram_stop(i),
+                name.to_string(),
+                name.to_string(),
+                geo::Point::new(52.5, 13.4 + 0.01 * i as f64),
+                Some(station(i)),
+            );
+        }
+        builder.add_route(1, "240".to_owned(), RouteType::Bus, "#a5027d".to_owned());
+        builder.add_route(
+            2,
+            "M17".to_owned(),
+            RouteType::TramService,
+            "#be1414".to_owned(),
+        );
+        // a bus and the slow tram
+        add_trip(&mut builder, 1, 1, &[(0, 1, 1), (2, 4, 4)]);
+        // a bus, a tram and a bus
+        add_trip(&mut builder, 3, 1, &[(0, 1, 1), (1, 2, 2)]);
+        add_trip(&mut builder, 5, 1, &[(7, 9, 9), (4, 14, 14)]);
+        // a tram, a bus and the fast tram
+        add_trip(&mut builder, 7, 1, &[(1, 5, 5), (3, 8, 8)]);
+        // on from Tierpark
+        add_trip(&mut builder, 9, 1, &[(4, 19, 19), (5, 21, 21)]);
+        for (trip_id, calls) in [
+            (2, vec![(2, 6), (3, 10), (4, 16)]),
+            (4, vec![(1, 4), (7, 7)]),
+            (6, vec![(0, 1), (1, 3)]),
+            (8, vec![(2, 3), (3, 11), (4, 14)]),
+            (10, vec![(5, 24), (6, 26)]),
+        ] {
+            let trip_id = TripId::new(trip_id).unwrap();
+            builder.add_trip(trip_id, 2, 1, None, None).unwrap();
+            for (i, time) in calls {
+                let time = Time::from_hms(10, time, 0);
+                builder
+                    .add_trip_stop(trip_id, time, time, tram_stop(i))
+                    .unwrap();
+            }
+        }
+        let data = builder.build().unwrap();
+
+        let items = search_with(&data, |plotter| {
+            plotter.add_route_type(RouteType::Bus);
+            plotter.add_route_type(RouteType::TramService);
+            plotter.limit_legs(&[RouteType::Bus], 2);
+            plotter.limit_legs(&[RouteType::TramService], 2);
+        });
+        assert!(
+            items.contains(&"Tierpark 10:14:00".to_owned()),
+            "{:?}",
+            items
+        );
+        assert!(
+            items.contains(&"Wuhletal 10:26:00".to_owned()),
+            "{:?}",
+            items
+        );
+
+        let items = search_with(&data, |plotter| {
+            plotter.add_route_type(RouteType::Bus);
+            plotter.add_route_type(RouteType::TramService);
+            plotter.limit_legs(&[RouteType::Bus], 1);
+            plotter.limit_legs(&[RouteType::TramService], 2);
+        });
+        assert!(
+            !items.iter().any(|item| item.contains("Wuhletal")),
+            "{:?}",
+            items
+        );
+    }
+
+    #[test]
+    fn origin_grace() {
+        // the train which left a minute before the search is boarded as if it were a minute late, the one which left
+        // three minutes before is gone
+        let mut builder = line_builder(&["Alexanderplatz", "Jannowitzbrücke"]);
+        builder.add_route(
+            1,
+            "S5".to_owned(),
+            RouteType::SuburbanRailway,
+            "#ff5900".to_owned(),
+        );
+        for &(trip_id, departure, arrival) in &[(1, 59, 3), (2, 57, 1)] {
+            let trip_id = TripId::new(trip_id).unwrap();
+            builder.add_trip(trip_id, 1, 1, None, None).unwrap();
+            let departure = Time::from_hms(9, departure, 0);
+            builder
+                .add_trip_stop(trip_id, departure, departure, platform(0))
+                .unwrap();
+            let arrival = Time::from_hms(10, arrival, 0);
+            builder
+                .add_trip_stop(trip_id, arrival, arrival, platform(1))
+                .unwrap();
+        }
+        let data = builder.build().unwrap();
+
+        assert_eq!(search(&data), vec!["Alexanderplatz 10:00:00"]);
+
+        let items = search_with(&data, |plotter| {
+            plotter.set_origin_grace(chrono::Duration::minutes(2))
+        });
+        assert_eq!(
+            items,
+            vec![
+                "Alexanderplatz 10:00:00",
+                "board 1 at Alexanderplatz 10:00:00",
+                "Jannowitzbrücke 10:04:00",
+                "1 Alexanderplatz-Jannowitzbrücke",
+            ],
+        );
+    }
+
+    #[test]
+    fn origin_grace_trip_is_overtaken() {
+        // the train which left two minutes before the search is boarded as if it were two minutes late, so the next one on
+        // the same pattern, which it is still ahead of at Jannowitzbrücke, gets to Ostbahnhof first
+        let mut builder = line_builder(&["Alexanderplatz", "Jannowitzbrücke", "Ostbahnhof"]);
+        builder.add_route(
+            1,
+            "S5".to_owned(),
+            RouteType::SuburbanRailway,
+            "#ff5900".to_owned(),
+        );
+        for &(trip_id, calls) in &[
+            (1, [(9, 58), (10, 0), (10, 5)]),
+            (2, [(10, 1), (10, 3), (10, 6)]),
+        ] {
+            let trip_id = TripId::new(trip_id).unwrap();
+            builder.add_trip(trip_id, 1, 1, None, None).unwrap();
+            for (i, &(hour, minute)) in calls.iter().enumerate() {
+                let time = Time::from_hms(hour, minute, 0);
+                builder
+                    .add_trip_stop(trip_id, time, time, platform(i))
+                    .unwrap();
+            }
+        }
+        let data = builder.build().unwrap();
+
+        let items = search_with(&data, |plotter| {
+            plotter.set_origin_grace(chrono::Duration::minutes(2))
+        });
+        for expected in &["Jannowitzbrücke 10:02:00", "Ostbahnhof 10:06:00"] {
+            assert!(items.contains(&expected.to_string()), "{:?}", items);
+        }
+    }
+
+    #[test]
+    fn walk_from_origin_entrance() {
+        // from the street the 10:01 is missed, unless the entrance's pathway is quick enough
+        let builder = || {
+            let mut builder = line_builder(&["Alexanderplatz", "Jannowitzbrücke"]);
+            builder.add_entrance_or_exit(
+                StopId::new(109).unwrap(),
+                "Alexanderplatz".to_owned(),
+                "Alexanderplatz".to_owned(),
+                geo::Point::new(52.5, 13.4),
+                station(0),
+            );
+            builder.add_route(
+                1,
+                "S5".to_owned(),
+                RouteType::SuburbanRailway,
+                "#ff5900".to_owned(),
+            );
+            add_trip(&mut builder, 1, 1, &[(0, 1, 1), (1, 4, 4)]);
+            add_trip(&mut builder, 2, 1, &[(0, 5, 5), (1, 8, 8)]);
+            builder
+        };
+        let boarded = |data: &GTFSData| {
+            let mut plotter = Plotter::new(
+                monday(),
+                Period::between(Time::from_hms(10, 0, 0), Time::from_hms(10, 30, 0)),
+                data,
+            );
+            plotter.add_origin_station(data.get_stop(StopId::new(109).unwrap()).unwrap());
+            plotter.add_route_type(RouteType::SuburbanRailway);
+            plotter
+                .filter_map(|item| match item {
+                    Item::ConnectionToTrip { trip_id, .. } => Some(trip_id.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(boarded(&builder().build().unwrap()), vec!["2"]);
+
+        let mut builder = builder();
+        builder
+            .add_transfer(
+                StopId::new(109).unwrap(),
+                platform(0),
+                Some(chrono::Duration::minutes(1)),
+            )
+            .unwrap();
+        assert_eq!(boarded(&builder.build().unwrap()), vec!["1"]);
+    }
+
+    #[test]
+    fn transfers_so_far() {
+        // Ostkreuz is reached on the S5 and Rummelsburg by changing there to the S3 on the other platform
+        let mut builder = line_builder(&["Warschauer Str.", "Ostkreuz", "Rummelsburg"]);
+        let other_platform = StopId::new(112).unwrap();
+        builder.add_stop_or_platform(
+            other_platform,
+            "Ostkreuz".to_owned(),
+            "Ostkreuz".to_owned(),
+            geo::Point::new(52.5, 13.41),
+            Some(station(1)),
+        );
+        for &(route_id, name) in &[(1, "S5"), (2, "S3")] {
+            builder.add_route(
+                route_id,
+                name.to_owned(),
+                RouteType::SuburbanRailway,
+                "#ff5900".to_owned(),
+            );
+        }
+        add_trip(&mut builder, 1, 1, &[(0, 1, 1), (1, 3, 3)]);
+        let trip_id = TripId::new(2).unwrap();
+        builder.add_trip(trip_id, 2, 1, None, None).unwrap();
+        for &(stop_id, time) in &[(other_platform, 6), (platform(2), 8)] {
+            let time = Time::from_hms(10, time, 0);
+            builder.add_trip_stop(trip_id, time, time, stop_id).unwrap();
+        }
+        let data = builder.build().unwrap();
+
+        let mut plotter = Plotter::new(
+            monday(),
+            Period::between(Time::from_hms(10, 0, 0), Time::from_hms(10, 30, 0)),
+            &data,
+        );
+        plotter.add_origin_station(data.get_stop(station(0)).unwrap());
+        plotter.add_route_type(RouteType::SuburbanRailway);
+        let stations: Vec<_> = plotter
+            .filter_map(|item| match item {
+                Item::Station {
+                    stop,
+                    legs,
+                    transfers_so_far,
+                    ..
+                } => Some((stop.short_stop_name().to_string(), legs, transfers_so_far)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            stations,
+            vec![
+                ("Warschauer Str.".to_owned(), 0, 0),
+                ("Ostkreuz".to_owned(), 1, 0),
+                ("Rummelsburg".to_owned(), 2, 1),
+            ]
+        );
+    }
+}
@@ -1,3 +1,19 @@
+//! The search of the departures from a station and the data it searches.
+//!
+//! Without the default `std` feature only [`time`], [`types`] and the increments of [`sync`] are built, with `alloc`, for displays which read the sync payload.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod departures;
+#[cfg(feature = "std")]
 pub mod journey_graph;
+#[cfg(feature = "std")]
 pub mod search_data;
+#[cfg(feature = "std")]
+pub mod size;
+pub mod sync;
 pub mod time;
+pub mod types;
@@ -1,59 +1,16 @@
-use chrono::Duration;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::default::Default;
 use std::fmt;
-use std::num::NonZeroU32;
+use std::sync::Arc;
 
+use crate::departures::Departures;
+use crate::no_heap_size;
+use crate::size::EstimateSize;
 use crate::time::{Period, Time};
 
-pub type AgencyId = u16;
-pub type RouteId = u32;
-pub type TripId = NonZeroU32; // 27bits
-pub type StopId = NonZeroU32; // intern key
-pub type ShapeId = u16;
-// type BlockId = String;
-pub type ServiceId = u16;
-// type ZoneId = String;
-
-/// Refers to a specific stop of a specific trip (an arrival / departure)
-pub type TripStopRef = (TripId, u8); // usize refers to the index of the stop in the trip, should probably instead use stop sequence
-
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
-pub enum Day {
-    Monday,
-    Tuesday,
-    Wednesday,
-    Thursday,
-    Friday,
-    Saturday,
-    Sunday,
-}
-
-impl std::fmt::Display for Day {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(match self {
-            Self::Monday => "mon",
-            Self::Tuesday => "tue",
-            Self::Wednesday => "wed",
-            Self::Thursday => "thu",
-            Self::Friday => "fri",
-            Self::Saturday => "sat",
-            Self::Sunday => "sun",
-        })
-    }
-}
-
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
-pub enum RouteType {
-    Rail,                  // 2
-    Bus,                   // 3
-    RailwayService,        // 100
-    SuburbanRailway,       // 109
-    UrbanRailway,          // 400
-    BusService,            // 700
-    TramService,           // 900
-    WaterTransportService, // 1000
-}
+pub use crate::types::*;
 
 /// Parsed and indexed GTFS data
 /// * efficient lookups for searching
@@ -75,10 +32,30 @@ pub struct GTFSData {
 
     // all synced initially
     pub(crate) services_by_day: HashMap<Day, HashSet<ServiceId>>,
+    /// The services added or removed on particular dates, which overrule the day of the week
+    pub(crate) service_exceptions: HashMap<NaiveDate, Vec<(ServiceId, ServiceException)>>,
     pub(crate) timetable_start_date: String,
+    pub(crate) names: StopNames,
+}
+
+/// The names of the stops, each kept once however many stops have it, as the platforms and entrances of a station mostly have the station's name
+#[derive(Default)]
+pub(crate) struct StopNames(HashSet<Arc<str>>);
+
+impl StopNames {
+    fn intern(&mut self, name: String) -> Arc<str> {
+        if let Some(interned) = self.0.get(name.as_str()) {
+            Arc::clone(interned)
+        } else {
+            let interned: Arc<str> = name.into();
+            self.0.insert(Arc::clone(&interned));
+            interned
+        }
+    }
 }
 
 impl GTFSData {
+    /// Starts building data from the services running on each day of the week, see [`Builder`]
     pub fn builder(
         services_by_day: HashMap<Day, HashSet<ServiceId>>,
         timetable_start_date: String,
@@ -86,9 +63,11 @@ impl GTFSData {
         Builder {
             data: Self {
                 services_by_day,
+                service_exceptions: HashMap::new(),
                 timetable_start_date,
                 stops: HashMap::new(),
                 trips: HashMap::new(),
+                names: StopNames::default(),
             },
             stop_children: HashMap::new(),
             routes: HashMap::new(),
@@ -101,6 +80,7 @@ impl GTFSData {
         RequiredDataBuilder {
             new_data: RequiredData {
                 services_by_day: self.services_by_day.clone(),
+                service_exceptions: self.service_exceptions.clone(),
                 timetable_start_date: self.timetable_start_date.clone(),
                 trips: HashSet::new(),
                 stops: HashSet::new(),
@@ -108,6 +88,28 @@ impl GTFSData {
         }
     }
 
+    /// Opens the data up to add more to it, the children of stations, walks, patterns and blocks are worked out again when it is built
+    pub(crate) fn into_builder(mut self) -> Builder {
+        let mut stop_children = HashMap::new();
+        for stop in self.stops.values_mut() {
+            if let StopStereoType::Station { stops_or_platforms } = &mut stop.stereotype {
+                stop_children.insert(stop.stop_id, std::mem::take(stops_or_platforms));
+            }
+        }
+        let routes = self
+            .trips
+            .values()
+            .map(|trip| (trip.route.route_id, trip.route.clone()))
+            .collect();
+        Builder {
+            data: self,
+            stop_children,
+            routes,
+            departure_count: 0,
+            assert_last_trip: None,
+        }
+    }
+
     /// Start date of the timetable based upon the calendar records
     pub fn timetable_start_date(&self) -> &str {
         &self.timetable_start_date
@@ -126,6 +128,18 @@ impl GTFSData {
         self.services_by_day.get(&day).cloned().unwrap_or_default()
     }
 
+    /// Get all the services which run on a date, those of it's day of the week with the exceptions on the date
+    pub fn services_on(&self, date: NaiveDate) -> HashSet<ServiceId> {
+        let mut services = self.services_of_day(Day::from(date.weekday()));
+        for &(service_id, exception) in self.service_exceptions.get(&date).into_iter().flatten() {
+            match exception {
+                ServiceException::Added => services.insert(service_id),
+                ServiceException::Removed => services.remove(&service_id),
+            };
+        }
+        services
+    }
+
     /// finds all trips leaving a stop within a time period, using the provided services, includes the stop time for that stop and all following stops
     pub fn trips_from(
         &self,
@@ -152,6 +166,15 @@ impl GTFSData {
         self.stops.get(&id)
     }
 
+    /// The fare zone of a stop, or of it's station if it doesn't have one itself
+    pub fn zone_of<'s>(&'s self, stop: &'s Stop) -> Option<&'s str> {
+        stop.zone_id.as_deref().or_else(|| {
+            stop.parent_station()
+                .and_then(|station_id| self.get_stop(station_id))
+                .and_then(|station| station.zone_id.as_deref())
+        })
+    }
+
     /// Get all stops of the trip folling the departure referenced
     fn stop_times(&self, &(trip_id, idx): &TripStopRef) -> impl Iterator<Item = &StopTime> {
         self.trips
@@ -175,7 +198,7 @@ pub enum StopStereoType {
     // station is actually optional for stop or platform, but i think it is always present in vbbland
     StopOrPlatform {
         station: Option<StopId>,
-        departures: BTreeMap<Time, Vec<TripStopRef>>,
+        departures: Departures,
     },
     Station {
         stops_or_platforms: Vec<StopId>,
@@ -198,12 +221,15 @@ impl StopStereoType {
 
 pub struct Stop {
     pub stop_id: StopId,
-    pub full_stop_name: String,
-    pub short_stop_name: String,
+    full_stop_name: Arc<str>,
+    short_stop_name: Arc<str>,
     pub location: geo::Point<f64>,
     /// Type of the location
     pub stereotype: StopStereoType,
     pub transfers: Vec<Transfer>,
+    /// The stops that the transfers lead to, including the children of stations transferred to, worked out when the data is built
+    pub walks: Vec<Walk>,
+    pub zone_id: Option<ZoneId>,
 }
 
 impl fmt::Debug for Stop {
@@ -239,16 +265,22 @@ impl Ord for Stop {
 }
 
 impl Stop {
+    pub fn full_stop_name(&self) -> &str {
+        &self.full_stop_name
+    }
+
+    /// The name shortened to be drawn, such as `Mahlsdorf` for `S Mahlsdorf (Berlin)`
+    pub fn short_stop_name(&self) -> &str {
+        &self.short_stop_name
+    }
+
     /// finds all trips leaving the stop within a time period, using the provided services, includes the stop time for that stop and all following stops
     pub fn departures(&self, period: Period) -> Vec<&TripStopRef> {
         match self.stereotype {
             StopStereoType::StopOrPlatform {
                 station: _,
                 ref departures,
-            } => departures
-                .range(period)
-                .flat_map(|(_time, trip_stop_refs)| trip_stop_refs)
-                .collect(),
+            } => departures.range(period).collect(),
             _ => vec![],
         }
     }
@@ -304,22 +336,15 @@ impl Stop {
         }
     }
 
-    pub fn importance(&self, data: &GTFSData) -> usize {
-        self.transfers.len()
-            + match &self.stereotype {
-                StopStereoType::StopOrPlatform {
-                    station: _,
-                    departures,
-                } => departures.len(),
-                StopStereoType::Station { stops_or_platforms } => stops_or_platforms
-                    .iter()
-                    .map(|&stop_id| {
-                        data.get_stop(stop_id)
-                            .map_or(0, |stop| stop.importance(data))
-                    })
-                    .sum(),
-                StopStereoType::EntranceExit { station: _ } => 0,
-            }
+    /// The number of departures from a stop or platform, stations and entrances have none of their own
+    pub fn departure_count(&self) -> usize {
+        match &self.stereotype {
+            StopStereoType::StopOrPlatform {
+                station: _,
+                departures,
+            } => departures.num_times(),
+            _ => 0,
+        }
     }
 }
 
@@ -360,21 +385,16 @@ pub struct Trip {
     pub service_id: ServiceId,
     /// Identifies a trip.
     pub trip_id: TripId,
+    /// Trips with the same pattern call at the same stops in the same order
+    pub pattern_id: PatternId,
+    /// The direction of travel, if the feed has it
+    pub direction_id: Option<DirectionId>,
+    pub block_id: Option<BlockId>,
+    /// The trip that the vehicle continues as from the last stop of this one, riders can stay seated
+    pub next_in_block: Option<TripId>,
     pub stop_times: Vec<StopTime>,
 }
 
-#[derive(Debug, Clone)]
-pub struct StopTime {
-    /// Arrival time at a specific stop for a specific trip on a route. If there are not separate times for arrival and departure at a stop, enter the same value for arrival_time and departure_time. For times occurring after midnight on the service day, enter the time as a value greater than 24:00:00 in HH:MM:SS local time for the day on which the trip schedule begins.
-    /// Scheduled stops where the vehicle strictly adheres to the specified arrival and departure times are timepoints. If this stop is not a timepoint, it is recommended to provide an estimated or interpolated time. If this is not available, arrival_time can be left empty. Further, indicate that interpolated times are provided with timepoint=0. If interpolated times are indicated with timepoint=0, then time points must be indicated with timepoint=1. Provide arrival times for all stops that are time points. An arrival time must be specified for the first and the last stop in a trip.
-    pub arrival_time: Time, // ~ 17bits
-    /// Departure time from a specific stop for a specific trip on a route. For times occurring after midnight on the service day, enter the time as a value greater than 24:00:00 in HH:MM:SS local time for the day on which the trip schedule begins. If there are not separate times for arrival and departure at a stop, enter the same value for arrival_time and departure_time. See the arrival_time description for more details about using timepoints correctly.
-    /// The departure_time field should specify time values whenever possible, including non-binding estimated or interpolated times between timepoints.
-    pub departure_time: Time, // maybe 10 bits
-    /// Identifies the serviced stop. All stops serviced during a trip must have a record in stop_times.txt. Referenced locations must be stops, not stations or station entrances. A stop may be serviced multiple times in the same trip, and multiple trips and routes may service the same stop.
-    pub stop_id: StopId, // ~27bits needed
-}
-
 #[derive(Debug, Clone)]
 pub struct Transfer {
     /// Identifies a stop or station where a connection between routes ends. If this field refers to a station, the transfer rule applies to all child stops.
@@ -385,12 +405,22 @@ pub struct Transfer {
     pub min_transfer_time: Option<Duration>,
 }
 
+/// A stop reached by a transfer from another stop
+#[derive(Debug, Clone, Copy)]
+pub struct Walk {
+    /// The stop of the transfer, this is the parent when walking to the child of a station
+    pub via_stop_id: StopId,
+    pub to_stop_id: StopId,
+    pub duration: Duration,
+}
+
 pub struct RequiredData {
     pub trips: HashSet<TripId>,
     pub stops: HashSet<StopId>,
 
     // all synced initially
     pub services_by_day: HashMap<Day, HashSet<ServiceId>>,
+    pub service_exceptions: HashMap<NaiveDate, Vec<(ServiceId, ServiceException)>>,
     pub timetable_start_date: String,
 }
 
@@ -413,6 +443,38 @@ impl RequiredDataBuilder {
     }
 }
 
+/// Builds the search data from stops, routes, trips and their stop times, from a GTFS feed or any other source.
+///
+/// Anything referred to has to be added first: the stops before the transfers from them and the stop times at them, and the routes before their trips. The parent station of a stop can be added after it, as stations are only linked to their stops when the data is built.
+/// Adding something which refers to what hasn't been added, or a stop time at a station rather than at one of it's stops, is a [`BuildError`] and leaves the data as it was.
+///
+/// ```rust
+/// use radar_search::search_data::*;
+/// use radar_search::time::Time;
+///
+/// let stop_id = |id| StopId::new(id).unwrap();
+/// let mut services_by_day = std::collections::HashMap::new();
+/// services_by_day.insert(Day::Monday, [1].iter().copied().collect());
+/// let mut builder = GTFSData::builder(services_by_day, "20240101".to_owned());
+/// for (id, name) in [(1, "Ostkreuz"), (2, "Warschauer Str.")] {
+///     let location = geo::Point::new(52.5, 13.45);
+///     builder.add_station(stop_id(id), name.to_owned(), name.to_owned(), location);
+///     builder.add_stop_or_platform(stop_id(id * 10), name.to_owned(), name.to_owned(), location, Some(stop_id(id)));
+/// }
+/// builder.add_route(1, "S3".to_owned(), RouteType::SuburbanRailway, String::new());
+/// let trip_id = TripId::new(1).unwrap();
+/// builder.add_trip(trip_id, 1, 1, None, None)?;
+/// builder.add_trip_stop(trip_id, Time::from_hms(10, 0, 0), Time::from_hms(10, 0, 0), stop_id(10))?;
+/// builder.add_trip_stop(trip_id, Time::from_hms(10, 3, 0), Time::from_hms(10, 3, 0), stop_id(20))?;
+/// // trips stop at the platforms of stations
+/// assert_eq!(
+///     builder.add_trip_stop(trip_id, Time::from_hms(10, 5, 0), Time::from_hms(10, 5, 0), stop_id(1)),
+///     Err(BuildError::NotAStopOrPlatform(stop_id(1)))
+/// );
+/// let data = builder.build()?;
+/// assert_eq!(data.get_stop(stop_id(1)).unwrap().children().count(), 1);
+/// # Ok::<(), BuildError>(())
+/// ```
 pub struct Builder {
     data: GTFSData,
     stop_children: HashMap<StopId, Vec<StopId>>,
@@ -422,6 +484,28 @@ pub struct Builder {
 }
 
 impl Builder {
+    pub fn has_stop(&self, stop_id: StopId) -> bool {
+        self.data.stops.contains_key(&stop_id)
+    }
+
+    pub fn has_trip(&self, trip_id: TripId) -> bool {
+        self.data.trips.contains_key(&trip_id)
+    }
+
+    /// Adds a service to the services of a date, or removes it, whichever day of the week it is
+    pub fn add_service_exception(
+        &mut self,
+        date: NaiveDate,
+        service_id: ServiceId,
+        exception: ServiceException,
+    ) {
+        self.data
+            .service_exceptions
+            .entry(date)
+            .or_default()
+            .push((service_id, exception));
+    }
+
     pub fn add_station(
         &mut self,
         stop_id: StopId,
@@ -433,13 +517,15 @@ impl Builder {
             stop_id,
             Stop {
                 stop_id,
-                full_stop_name,
-                short_stop_name,
+                full_stop_name: self.data.names.intern(full_stop_name),
+                short_stop_name: self.data.names.intern(short_stop_name),
                 location,
                 stereotype: StopStereoType::Station {
                     stops_or_platforms: Vec::<StopId>::default(),
                 },
                 transfers: Vec::<Transfer>::default(),
+                walks: Vec::<Walk>::default(),
+                zone_id: None,
             },
         );
     }
@@ -456,14 +542,16 @@ impl Builder {
             stop_id,
             Stop {
                 stop_id,
-                full_stop_name,
-                short_stop_name,
+                full_stop_name: self.data.names.intern(full_stop_name),
+                short_stop_name: self.data.names.intern(short_stop_name),
                 location,
                 stereotype: StopStereoType::StopOrPlatform {
                     station,
-                    departures: BTreeMap::<Time, Vec<TripStopRef>>::default(),
+                    departures: Departures::default(),
                 },
                 transfers: Vec::<Transfer>::default(),
+                walks: Vec::<Walk>::default(),
+                zone_id: None,
             },
         );
         if let Some(station) = station {
@@ -483,35 +571,49 @@ impl Builder {
             stop_id,
             Stop {
                 stop_id,
-                full_stop_name,
-                short_stop_name,
+                full_stop_name: self.data.names.intern(full_stop_name),
+                short_stop_name: self.data.names.intern(short_stop_name),
                 location,
                 stereotype: StopStereoType::EntranceExit { station },
                 transfers: std::vec::Vec::<Transfer>::default(),
+                walks: Vec::<Walk>::default(),
+                zone_id: None,
             },
         );
         self.stop_children.entry(station).or_default().push(stop_id);
     }
 
+    /// Sets the fare zone of a stop which has already been added
+    pub fn set_zone(&mut self, stop_id: StopId, zone_id: ZoneId) -> Result<(), BuildError> {
+        let stop = self
+            .data
+            .stops
+            .get_mut(&stop_id)
+            .ok_or(BuildError::UnknownStop(stop_id))?;
+        stop.zone_id = Some(zone_id);
+        Ok(())
+    }
+
+    /// Adds a transfer from a stop which has already been added, the stop transferred to needn't be added, as data for a search can leave it out
     pub fn add_transfer(
         &mut self,
         from_stop_id: StopId,
         to_stop_id: StopId,
         min_transfer_time: Option<Duration>,
-    ) {
-        if let Some(stop) = self.data.stops.get_mut(&from_stop_id) {
-            stop.transfers.push(Transfer {
-                to_stop_id,
-                min_transfer_time,
-            });
-        } else {
-            panic!(
-                "Expected from_stop {:?} for transfer to be loaded",
-                from_stop_id
-            );
-        }
+    ) -> Result<(), BuildError> {
+        let stop = self
+            .data
+            .stops
+            .get_mut(&from_stop_id)
+            .ok_or(BuildError::UnknownStop(from_stop_id))?;
+        stop.transfers.push(Transfer {
+            to_stop_id,
+            min_transfer_time,
+        });
+        Ok(())
     }
 
+    /// Adds a route, a missing name falls back to the route id and a missing colour to the default for the route type so that any feed can be drawn
     pub fn add_route(
         &mut self,
         route_id: RouteId,
@@ -519,6 +621,16 @@ impl Builder {
         route_type: RouteType,
         route_color: String,
     ) {
+        let route_short_name = if route_short_name.trim().is_empty() {
+            route_id.to_string()
+        } else {
+            route_short_name
+        };
+        let route_color = if route_color.trim().is_empty() {
+            route_type.default_color().to_owned()
+        } else {
+            route_color
+        };
         self.routes.insert(
             route_id,
             Route {
@@ -530,83 +642,94 @@ impl Builder {
         );
     }
 
-    pub fn add_trip(&mut self, trip_id: TripId, route_id: RouteId, service_id: ServiceId) {
-        let route: &Route = self
+    /// Adds a trip of a route which has already been added, without any stop times
+    pub fn add_trip(
+        &mut self,
+        trip_id: TripId,
+        route_id: RouteId,
+        service_id: ServiceId,
+        direction_id: Option<DirectionId>,
+        block_id: Option<BlockId>,
+    ) -> Result<(), BuildError> {
+        let route: Route = self
             .routes
             .get(&route_id)
-            .expect("trip's route to have been added");
-        let route: Route = (*route).clone();
+            .ok_or(BuildError::UnknownRoute(route_id))?
+            .clone();
         self.data.trips.insert(
             trip_id,
             Trip {
                 trip_id,
                 route,
                 service_id,
+                pattern_id: 0,
+                direction_id,
+                block_id,
+                next_in_block: None,
                 stop_times: Vec::<StopTime>::default(),
             },
         );
+        Ok(())
     }
 
+    /// Adds the next stop time of a trip which has already been added, at a stop or platform which has already been added
     pub fn add_trip_stop(
         &mut self,
         trip_id: TripId,
         arrival_time: Time,
         departure_time: Time,
         stop_id: StopId,
-    ) {
+    ) -> Result<(), BuildError> {
         let trip: &mut Trip = self
             .data
             .trips
             .get_mut(&trip_id)
-            .expect("stop time added to be of added trip");
-        self.assert_last_trip = Some(trip_id);
-
-        let stop_ref = (trip_id, trip.stop_times.len() as u8);
-        trip.stop_times.push(StopTime {
-            arrival_time,
-            departure_time,
-            stop_id,
-        });
+            .ok_or(BuildError::UnknownTrip(trip_id))?;
         let stop = self
             .data
             .stops
             .get_mut(&stop_id)
-            .expect("stop time to be referencing added stop");
-        match &mut stop.stereotype {
-            StopStereoType::Station {
-                stops_or_platforms: _,
-            } => panic!("trip stops at station"),
-            StopStereoType::EntranceExit { station: _ } => panic!("trip stops at station entrance"),
+            .ok_or(BuildError::UnknownStop(stop_id))?;
+        let departures = match &mut stop.stereotype {
             StopStereoType::StopOrPlatform {
                 station: _,
                 ref mut departures,
-            } => departures.entry(departure_time).or_default().push(stop_ref),
+            } => departures,
+            _ => return Err(BuildError::NotAStopOrPlatform(stop_id)),
         };
+        let stop_index = u16::try_from(trip.stop_times.len())
+            .map_err(|_| BuildError::TooManyStopTimes(trip_id))?;
+        self.assert_last_trip = Some(trip_id);
+
+        let stop_ref = (trip_id, stop_index);
+        trip.stop_times.push(StopTime {
+            arrival_time,
+            departure_time,
+            stop_id,
+        });
+        departures.insert(departure_time, stop_ref);
         self.departure_count += 1;
+        Ok(())
     }
 
-    pub fn build(mut self) -> GTFSData {
+    /// Links the stations to their stops and works out the walks, patterns and blocks, it fails if a stop's parent station wasn't added or isn't a station
+    pub fn build(mut self) -> Result<GTFSData, BuildError> {
         for (station_id, children) in self.stop_children {
             let station = self
                 .data
                 .stops
                 .get_mut(&station_id)
-                .expect("parent station to exist");
+                .ok_or(BuildError::UnknownStop(station_id))?;
             match &mut station.stereotype {
                 StopStereoType::Station {
                     ref mut stops_or_platforms,
                 } => *stops_or_platforms = children,
-                StopStereoType::StopOrPlatform {
-                    station: _,
-                    departures: _,
-                } => panic!(
-                    "stop or platform {:?} indicated as a parent station of {:?}",
-                    station, children
-                ),
-                StopStereoType::EntranceExit { station: _ } => panic!(
-                    "entrance or exit {:?} indicated as a parent station of {:?}",
-                    station, children
-                ),
+                _ => {
+                    return Err(BuildError::NotAStation {
+                        stop_id: children[0],
+                        station_id,
+                    })
+                }
             }
         }
 
@@ -617,17 +740,362 @@ impl Builder {
             self.data.stops.len()
         );
 
+        let mut patterns: HashMap<Vec<StopId>, PatternId> = HashMap::new();
         for trip in self.data.trips.values_mut() {
             trip.stop_times.shrink_to_fit();
+            let pattern: Vec<StopId> = trip.stop_times.iter().map(|st| st.stop_id).collect();
+            let next_pattern_id = patterns.len() as PatternId;
+            trip.pattern_id = *patterns.entry(pattern).or_insert(next_pattern_id);
+        }
+        link_blocks(&mut self.data.trips);
+        let data = &self.data;
+        let walks: HashMap<StopId, Vec<Walk>> = data
+            .stops
+            .values()
+            .map(|stop| (stop.stop_id, walks_from(data, stop)))
+            .collect();
+        for (stop_id, walks) in walks {
+            if let Some(stop) = self.data.stops.get_mut(&stop_id) {
+                stop.walks = walks;
+            }
         }
         for stop in self.data.stops.values_mut() {
             if let StopStereoType::StopOrPlatform { departures, .. } = &mut stop.stereotype {
-                for departure_route in departures.values_mut() {
-                    departure_route.shrink_to_fit();
+                departures.shrink_to_fit();
+            }
+        }
+
+        Ok(self.data)
+    }
+}
+
+/// Something added to a [`Builder`] which refers to what hasn't been added or isn't of the right kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    UnknownStop(StopId),
+    UnknownRoute(RouteId),
+    UnknownTrip(TripId),
+    /// A stop time at a station or an entrance, rather than at a stop or platform
+    NotAStopOrPlatform(StopId),
+    /// The parent station of the stop is a stop, platform or entrance
+    NotAStation {
+        stop_id: StopId,
+        station_id: StopId,
+    },
+    /// The trip already has as many stop times as a departure can refer to
+    TooManyStopTimes(TripId),
+    /// A stop of a sync increment refers to a name the increment doesn't have
+    UnknownName(u32),
+    /// A sync increment which isn't the first, so doesn't have the timetable, was built on its own
+    NoTimetable,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::UnknownStop(stop_id) => write!(f, "stop {} wasn't added", stop_id),
+            BuildError::UnknownRoute(route_id) => write!(f, "route {} wasn't added", route_id),
+            BuildError::UnknownTrip(trip_id) => write!(f, "trip {} wasn't added", trip_id),
+            BuildError::NotAStopOrPlatform(stop_id) => write!(
+                f,
+                "stop {} is a station or entrance, which trips don't stop at",
+                stop_id
+            ),
+            BuildError::NotAStation {
+                stop_id,
+                station_id,
+            } => write!(
+                f,
+                "the parent station {} of stop {} isn't a station",
+                station_id, stop_id
+            ),
+            BuildError::TooManyStopTimes(trip_id) => write!(
+                f,
+                "trip {} has more than {} stop times",
+                trip_id,
+                u16::MAX as usize + 1
+            ),
+            BuildError::UnknownName(index) => write!(f, "name {} wasn't sent", index),
+            BuildError::NoTimetable => write!(f, "the increment doesn't have the timetable"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Flattens the transfers from a stop, transfers to a station also lead to all of it's children
+fn walks_from(data: &GTFSData, stop: &Stop) -> Vec<Walk> {
+    let mut walks = vec![];
+    for transfer in &stop.transfers {
+        // we ignore any missing stops in case this is a partial data set
+        if let Some(to_stop) = data.get_stop(transfer.to_stop_id) {
+            let duration = transfer.min_transfer_time.unwrap_or_else(Duration::zero);
+            for &to_stop_id in std::iter::once(&to_stop.stop_id).chain(to_stop.children()) {
+                if data.get_stop(to_stop_id).is_some() {
+                    walks.push(Walk {
+                        via_stop_id: transfer.to_stop_id,
+                        to_stop_id,
+                        duration,
+                    });
                 }
             }
         }
+    }
+    walks.shrink_to_fit();
+    walks
+}
 
-        self.data
+no_heap_size!(
+    Day,
+    NaiveDate,
+    RouteType,
+    ServiceException,
+    StopTime,
+    Transfer,
+    Walk
+);
+
+impl EstimateSize for Route {
+    fn heap_size(&self) -> usize {
+        self.route_short_name.heap_size() + self.route_color.heap_size()
+    }
+}
+
+impl EstimateSize for Trip {
+    fn heap_size(&self) -> usize {
+        self.route.heap_size() + self.stop_times.heap_size()
+    }
+}
+
+impl EstimateSize for StopStereoType {
+    fn heap_size(&self) -> usize {
+        match self {
+            StopStereoType::StopOrPlatform {
+                station: _,
+                departures,
+            } => departures.heap_size(),
+            StopStereoType::Station { stops_or_platforms } => stops_or_platforms.heap_size(),
+            StopStereoType::EntranceExit { station: _ } => 0,
+        }
+    }
+}
+
+impl EstimateSize for Stop {
+    fn heap_size(&self) -> usize {
+        // the names are counted once in the data's names
+        self.stereotype.heap_size()
+            + self.transfers.heap_size()
+            + self.walks.heap_size()
+            + self.zone_id.heap_size()
+    }
+}
+
+/// Estimated bytes used by each part of the search data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Footprint {
+    pub stops: usize,
+    pub departures: usize,
+    pub trips: usize,
+    pub services: usize,
+}
+
+impl Footprint {
+    pub fn total(&self) -> usize {
+        self.stops + self.departures + self.trips + self.services
+    }
+}
+
+impl fmt::Display for Footprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mb = |bytes: usize| bytes as f64 / 1_000_000.;
+        writeln!(f, "stops: {:.1} MB", mb(self.stops))?;
+        writeln!(f, "departures index: {:.1} MB", mb(self.departures))?;
+        writeln!(f, "trips: {:.1} MB", mb(self.trips))?;
+        writeln!(f, "services: {:.1} MB", mb(self.services))?;
+        write!(f, "total: {:.1} MB", mb(self.total()))
+    }
+}
+
+impl GTFSData {
+    /// Estimates the memory used by the stops, the departures index, the trips and the services
+    pub fn footprint(&self) -> Footprint {
+        let departures = self
+            .stops
+            .values()
+            .map(|stop| match &stop.stereotype {
+                StopStereoType::StopOrPlatform { departures, .. } => departures.heap_size(),
+                _ => 0,
+            })
+            .sum();
+        Footprint {
+            stops: self.stops.heap_size() - departures + self.names.0.heap_size(),
+            departures,
+            trips: self.trips.heap_size(),
+            services: self.services_by_day.heap_size()
+                + self.service_exceptions.heap_size()
+                + self.timetable_start_date.heap_size(),
+        }
+    }
+}
+
+/// Links each trip of a block to the trip that follows it on the same service, if that starts where this one ends
+fn link_blocks(trips: &mut HashMap<TripId, Trip>) {
+    let mut blocks: HashMap<(BlockId, ServiceId), Vec<(Time, TripId)>> = HashMap::new();
+    for trip in trips.values() {
+        if let (Some(block_id), Some(first_stop)) = (trip.block_id, trip.stop_times.first()) {
+            blocks
+                .entry((block_id, trip.service_id))
+                .or_default()
+                .push((first_stop.departure_time, trip.trip_id));
+        }
+    }
+    for mut block in blocks.into_values() {
+        block.sort_unstable();
+        for pair in block.windows(2) {
+            if let [(_, trip_id), (next_departure_time, next_trip_id)] = pair {
+                let next_stop_id = trips[next_trip_id].stop_times[0].stop_id;
+                let trip = trips.get_mut(trip_id).unwrap();
+                if let Some(last_stop) = trip.stop_times.last() {
+                    if last_stop.stop_id == next_stop_id
+                        && last_stop.arrival_time <= *next_departure_time
+                    {
+                        trip.next_in_block = Some(*next_trip_id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn zones_fall_back_to_station() {
+        let mut builder = GTFSData::builder(HashMap::new(), "20200101".to_owned());
+        let stop_id = |id| StopId::new(id).unwrap();
+        let location = geo::Point::new(52.5, 13.4);
+        builder.add_station(
+            stop_id(1),
+            "Erkner".to_owned(),
+            "Erkner".to_owned(),
+            location,
+        );
+        for platform in 2..4 {
+            builder.add_stop_or_platform(
+                stop_id(platform),
+                "Erkner".to_owned(),
+                "Erkner".to_owned(),
+                location,
+                Some(stop_id(1)),
+            );
+        }
+        builder.set_zone(stop_id(1), "C".to_owned()).unwrap();
+        builder.set_zone(stop_id(3), "B".to_owned()).unwrap();
+        let data = builder.build().unwrap();
+        let zone = |id| data.zone_of(data.get_stop(stop_id(id)).unwrap());
+        assert_eq!(zone(1), Some("C"));
+        assert_eq!(zone(2), Some("C"));
+        assert_eq!(zone(3), Some("B"));
+    }
+
+    #[test]
+    fn misuse_is_an_error() {
+        let mut builder = GTFSData::builder(HashMap::new(), "20200101".to_owned());
+        let stop_id = |id| StopId::new(id).unwrap();
+        let trip_id = TripId::new(1).unwrap();
+        let location = geo::Point::new(52.5, 13.4);
+        let name = || "Erkner".to_owned();
+        builder.add_station(stop_id(1), name(), name(), location);
+        builder.add_entrance_or_exit(stop_id(2), name(), name(), location, stop_id(1));
+        builder.add_stop_or_platform(stop_id(3), name(), name(), location, Some(stop_id(2)));
+        assert_eq!(
+            builder.set_zone(stop_id(9), "C".to_owned()),
+            Err(BuildError::UnknownStop(stop_id(9)))
+        );
+        assert_eq!(
+            builder.add_trip(trip_id, 1, 1, None, None),
+            Err(BuildError::UnknownRoute(1))
+        );
+        let ten = Time::from_hms(10, 0, 0);
+        assert_eq!(
+            builder.add_trip_stop(trip_id, ten, ten, stop_id(3)),
+            Err(BuildError::UnknownTrip(trip_id))
+        );
+        builder.add_route(
+            1,
+            "S3".to_owned(),
+            RouteType::SuburbanRailway,
+            String::new(),
+        );
+        builder.add_trip(trip_id, 1, 1, None, None).unwrap();
+        assert_eq!(
+            builder.add_trip_stop(trip_id, ten, ten, stop_id(2)),
+            Err(BuildError::NotAStopOrPlatform(stop_id(2)))
+        );
+        // the stop time which failed wasn't added
+        builder
+            .add_trip_stop(trip_id, ten, ten, stop_id(3))
+            .unwrap();
+        assert_eq!(builder.data.trips[&trip_id].stop_times.len(), 1);
+        assert_eq!(
+            builder.build().err(),
+            Some(BuildError::NotAStation {
+                stop_id: stop_id(3),
+                station_id: stop_id(2)
+            })
+        );
+    }
+
+    #[test]
+    fn walks_include_station_children() {
+        let mut builder = GTFSData::builder(HashMap::new(), "20200101".to_owned());
+        let stop_id = |id| StopId::new(id).unwrap();
+        let location = geo::Point::new(52.5, 13.4);
+        for station in &[1, 4] {
+            builder.add_station(
+                stop_id(*station),
+                "Erkner".to_owned(),
+                "Erkner".to_owned(),
+                location,
+            );
+            builder.add_stop_or_platform(
+                stop_id(station + 1),
+                "Erkner".to_owned(),
+                "Erkner".to_owned(),
+                location,
+                Some(stop_id(*station)),
+            );
+        }
+        builder
+            .add_transfer(stop_id(1), stop_id(4), Some(Duration::minutes(3)))
+            .unwrap();
+        builder.add_transfer(stop_id(1), stop_id(2), None).unwrap();
+        builder.add_transfer(stop_id(1), stop_id(9), None).unwrap();
+        let data = builder.build().unwrap();
+        let walks: Vec<_> = data
+            .get_stop(stop_id(1))
+            .unwrap()
+            .walks
+            .iter()
+            .map(|walk| {
+                (
+                    walk.via_stop_id.get(),
+                    walk.to_stop_id.get(),
+                    walk.duration.num_minutes(),
+                )
+            })
+            .collect();
+        assert_eq!(walks, vec![(4, 4, 3), (4, 5, 3), (2, 2, 0)]);
+    }
+
+    #[test]
+    fn route_type_codes() {
+        for route_type in &RouteType::ALL {
+            assert_eq!(RouteType::from_code(route_type.code()), Some(*route_type));
+        }
+        assert_eq!(RouteType::from_code(109), Some(RouteType::SuburbanRailway));
+        assert_eq!(RouteType::from_code(1), None);
     }
 }
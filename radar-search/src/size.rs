@@ -0,0 +1,103 @@
+//! Rough estimates of the memory used by the search data, so that the day filter and feed subsets can be tuned.
+//!
+//! The estimates count the capacity of collections and the heap data they own, they don't know about allocator overhead or the exact layout of hash tables.
+
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::sync::Arc;
+
+pub trait EstimateSize {
+    /// Bytes owned on the heap, not including the size of the value itself
+    fn heap_size(&self) -> usize;
+
+    fn total_size(&self) -> usize
+    where
+        Self: Sized,
+    {
+        size_of::<Self>() + self.heap_size()
+    }
+}
+
+/// Implements `EstimateSize` for types that don't own any heap memory
+#[macro_export]
+macro_rules! no_heap_size {
+    ($($t:ty),*) => {
+        $(impl $crate::size::EstimateSize for $t {
+            fn heap_size(&self) -> usize {
+                0
+            }
+        })*
+    };
+}
+
+no_heap_size!(
+    u8,
+    u16,
+    u32,
+    u64,
+    usize,
+    std::num::NonZeroU32,
+    crate::time::Time
+);
+
+impl<A: EstimateSize, B: EstimateSize> EstimateSize for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+impl<T: EstimateSize> EstimateSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, EstimateSize::heap_size)
+    }
+}
+
+impl EstimateSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+/// The whole of the shared string, with it's reference counts, however many others share it
+impl EstimateSize for Arc<str> {
+    fn heap_size(&self) -> usize {
+        2 * size_of::<usize>() + self.len()
+    }
+}
+
+impl<T: EstimateSize> EstimateSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+impl<K: EstimateSize, V: EstimateSize, S> EstimateSize for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        // a control byte per bucket
+        self.capacity() * (size_of::<K>() + size_of::<V>() + 1)
+            + self
+                .iter()
+                .map(|(k, v)| k.heap_size() + v.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl<T: EstimateSize, S> EstimateSize for HashSet<T, S> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * (size_of::<T>() + 1) + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+#[test]
+fn collection_sizes() {
+    let string = String::with_capacity(10);
+    assert_eq!(string.heap_size(), 10);
+    let mut strings = Vec::with_capacity(2);
+    strings.push(string);
+    assert_eq!(strings.heap_size(), 2 * size_of::<String>() + 10);
+    assert_eq!(Some(5u32).heap_size(), 0);
+    assert_eq!(
+        Some(String::from("abc")).total_size(),
+        size_of::<Option<String>>() + 3
+    );
+}
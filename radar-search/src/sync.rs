@@ -0,0 +1,1026 @@
+//! Sending a client the part of the search data it needs for its own searches, as increments on what it already holds.
+//!
+//! The server runs the search to find the [`RequiredData`], the client sends a [`Manifest`] of the stops and trips it holds and gets back a [`GTFSSyncIncrement`] with the rest of them.
+//! Departures, walks and stopping patterns aren't sent, they are worked out again on the client when the increment is applied.
+//!
+//! A client can instead sync through a session kept by the server in [`SyncSessions`], sending just the [`SyncVersion`] it holds rather than a manifest of it.
+//!
+//! Encoded increments are sent [`seal`]ed with a header and checksum, so that a client can tell corrupted or incompatible data from a bug.
+//!
+//! The increment itself only needs `alloc`, the rest needs `std`.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{
+    BlockId, Day, DirectionId, RouteId, ServiceException, ServiceId, StopId, StopTime, TripId,
+    ZoneId,
+};
+#[cfg(feature = "std")]
+use crate::{
+    search_data::{BuildError, Builder, GTFSData, RequiredData, RouteType, StopStereoType},
+    time::Time,
+};
+#[cfg(feature = "std")]
+use chrono::{Duration, NaiveDate};
+#[cfg(feature = "std")]
+use std::collections::{hash_map::RandomState, HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hasher};
+
+#[cfg(feature = "std")]
+const GTFS_DATE_FORMAT: &str = "%Y%m%d";
+
+/// The stops and trips a client already holds
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub stops: HashSet<StopId>,
+    pub trips: HashSet<TripId>,
+}
+
+#[cfg(feature = "std")]
+impl Manifest {
+    pub fn of(data: &GTFSData) -> Self {
+        Self {
+            stops: data.stops().map(|stop| stop.stop_id).collect(),
+            trips: data.trips().map(|trip| trip.trip_id).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stops.is_empty() && self.trips.is_empty()
+    }
+}
+
+/// Stops and trips to add to the data a client holds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GTFSSyncIncrement {
+    /// Only sent to a client which doesn't hold anything yet
+    pub timetable: Option<SyncTimetable>,
+    /// The names of the stops, each sent once however many of the stops have it
+    pub names: Vec<String>,
+    /// In order of their ids, which are sent as the difference from the id of the stop before
+    #[serde(with = "stop_id_deltas")]
+    pub stops: Vec<SyncStop>,
+    pub trips: Vec<SyncTrip>,
+}
+
+/// The data a client holds, as the session it syncs in and the number of increments it has applied in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncVersion {
+    pub session: u64,
+    pub version: u32,
+}
+
+/// An increment and the version the client holds once it has applied it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionedIncrement {
+    pub version: SyncVersion,
+    pub increment: GTFSSyncIncrement,
+}
+
+/// Written at the start of a sealed increment
+pub const MAGIC: &[u8; 4] = b"TRSI";
+/// Increased whenever the layout of the increment changes, a client opens sealed increments of the version it was built with only
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Why a sealed increment couldn't be opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SealError {
+    /// It doesn't start with the magic header
+    NotAnIncrement,
+    /// It was cut short or altered on the way, so it's checksum doesn't match
+    Corrupted,
+    /// It's of another version of the schema
+    Version(u32),
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SealError::NotAnIncrement => write!(f, "not a sync increment"),
+            SealError::Corrupted => write!(f, "the sync increment is corrupted"),
+            SealError::Version(version) => write!(
+                f,
+                "the sync increment is of schema version {} but version {} is read",
+                version, SCHEMA_VERSION
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SealError {}
+
+/// An encoded increment after the magic header and the schema version, followed by the CRC-32 of all of them, little endian
+pub fn seal(encoded: &[u8]) -> Vec<u8> {
+    let mut sealed = Vec::with_capacity(MAGIC.len() + 4 + encoded.len() + 4);
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+    sealed.extend_from_slice(encoded);
+    let crc = crc32fast::hash(&sealed);
+    sealed.extend_from_slice(&crc.to_le_bytes());
+    sealed
+}
+
+/// The encoded increment in a sealed one, once it's header and checksum are checked
+pub fn unseal(sealed: &[u8]) -> Result<&[u8], SealError> {
+    if !sealed.starts_with(MAGIC) {
+        return Err(SealError::NotAnIncrement);
+    }
+    let header = MAGIC.len() + 4;
+    if sealed.len() < header + 4 {
+        return Err(SealError::Corrupted);
+    }
+    let (checked, crc) = sealed.split_at(sealed.len() - 4);
+    if crc32fast::hash(checked).to_le_bytes() != crc {
+        return Err(SealError::Corrupted);
+    }
+    let mut version = [0; 4];
+    version.copy_from_slice(&checked[MAGIC.len()..header]);
+    match u32::from_le_bytes(version) {
+        SCHEMA_VERSION => Ok(&checked[header..]),
+        version => Err(SealError::Version(version)),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncTimetable {
+    pub services_by_day: Vec<(Day, Vec<ServiceId>)>,
+    /// The services added or removed on each date, which is written as in GTFS, `YYYYMMDD`
+    pub service_exceptions: Vec<(String, Vec<(ServiceId, ServiceException)>)>,
+    pub timetable_start_date: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStopKind {
+    Station,
+    StopOrPlatform { station: Option<StopId> },
+    EntranceExit { station: StopId },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncStop {
+    pub stop_id: StopId,
+    /// Index of the name in the increment's names
+    pub full_stop_name: u32,
+    pub short_stop_name: u32,
+    pub lat: f64,
+    pub lon: f64,
+    pub kind: SyncStopKind,
+    /// Stops transferred to and the minimum transfer time in seconds
+    pub transfers: Vec<(StopId, Option<i64>)>,
+    pub zone_id: Option<ZoneId>,
+}
+
+/// Sends the stops with the difference from the id of the stop before as their id, a smaller number to encode than the id
+mod stop_id_deltas {
+    use super::*;
+    use serde::{de, ser, Deserializer, Serializer};
+
+    /// A [`SyncStop`] as it's sent
+    #[derive(Serialize)]
+    struct DeltaStop<'s> {
+        stop_id: StopId,
+        full_stop_name: u32,
+        short_stop_name: u32,
+        lat: f64,
+        lon: f64,
+        kind: SyncStopKind,
+        transfers: &'s [(StopId, Option<i64>)],
+        zone_id: &'s Option<ZoneId>,
+    }
+
+    pub fn serialize<S: Serializer>(stops: &[SyncStop], serializer: S) -> Result<S::Ok, S::Error> {
+        if stops
+            .windows(2)
+            .any(|pair| pair[0].stop_id >= pair[1].stop_id)
+        {
+            return Err(ser::Error::custom("stops aren't in order of their ids"));
+        }
+        let mut previous = 0;
+        serializer.collect_seq(stops.iter().map(|stop| {
+            let delta = StopId::new(stop.stop_id.get() - previous).expect("stops to be in order");
+            previous = stop.stop_id.get();
+            DeltaStop {
+                stop_id: delta,
+                full_stop_name: stop.full_stop_name,
+                short_stop_name: stop.short_stop_name,
+                lat: stop.lat,
+                lon: stop.lon,
+                kind: stop.kind,
+                transfers: &stop.transfers,
+                zone_id: &stop.zone_id,
+            }
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<SyncStop>, D::Error> {
+        let mut stops = Vec::<SyncStop>::deserialize(deserializer)?;
+        let mut previous: u32 = 0;
+        for stop in &mut stops {
+            stop.stop_id = previous
+                .checked_add(stop.stop_id.get())
+                .and_then(StopId::new)
+                .ok_or_else(|| de::Error::custom("stop id is out of range"))?;
+            previous = stop.stop_id.get();
+        }
+        Ok(stops)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncTrip {
+    pub trip_id: TripId,
+    pub route_id: RouteId,
+    pub route_short_name: String,
+    /// GTFS route type code
+    pub route_type: u16,
+    pub route_color: String,
+    pub service_id: ServiceId,
+    pub direction_id: Option<DirectionId>,
+    pub block_id: Option<BlockId>,
+    pub stop_times: Vec<StopTime>,
+}
+
+#[cfg(feature = "std")]
+impl GTFSData {
+    /// The stops and trips of a search which the client doesn't hold yet.
+    /// All the stops called at by the trips and the parent stations of the stops are included so that the increment can always be applied.
+    pub fn sync_increment(&self, required: &RequiredData, held: &Manifest) -> GTFSSyncIncrement {
+        let mut trip_ids: Vec<TripId> = required
+            .trips
+            .iter()
+            .copied()
+            .filter(|trip_id| !held.trips.contains(trip_id))
+            .collect();
+        trip_ids.sort();
+        let trips: Vec<SyncTrip> = trip_ids
+            .into_iter()
+            .filter_map(|trip_id| self.trips.get(&trip_id))
+            .map(|trip| SyncTrip {
+                trip_id: trip.trip_id,
+                route_id: trip.route.route_id,
+                route_short_name: trip.route.route_short_name.clone(),
+                route_type: trip.route.route_type.code(),
+                route_color: trip.route.route_color.clone(),
+                service_id: trip.service_id,
+                direction_id: trip.direction_id,
+                block_id: trip.block_id,
+                stop_times: trip.stop_times.clone(),
+            })
+            .collect();
+
+        let mut stop_ids: HashSet<StopId> = required.stops.clone();
+        stop_ids.extend(
+            trips
+                .iter()
+                .flat_map(|trip| trip.stop_times.iter().map(|stop_time| stop_time.stop_id)),
+        );
+        let parents: Vec<StopId> = stop_ids
+            .iter()
+            .filter_map(|stop_id| self.get_stop(*stop_id)?.parent_station())
+            .collect();
+        stop_ids.extend(parents);
+        let mut stop_ids: Vec<StopId> = stop_ids
+            .into_iter()
+            .filter(|stop_id| !held.stops.contains(stop_id))
+            .collect();
+        stop_ids.sort();
+        let mut names = vec![];
+        let mut name_indices: HashMap<&str, u32> = HashMap::new();
+        let mut name_index = |name| {
+            *name_indices.entry(name).or_insert_with(|| {
+                names.push(String::from(name));
+                names.len() as u32 - 1
+            })
+        };
+        let stops = stop_ids
+            .into_iter()
+            .filter_map(|stop_id| self.get_stop(stop_id))
+            .map(|stop| SyncStop {
+                stop_id: stop.stop_id,
+                full_stop_name: name_index(stop.full_stop_name()),
+                short_stop_name: name_index(stop.short_stop_name()),
+                lat: stop.location.x(),
+                lon: stop.location.y(),
+                kind: match stop.stereotype {
+                    StopStereoType::Station { .. } => SyncStopKind::Station,
+                    StopStereoType::StopOrPlatform { station, .. } => {
+                        SyncStopKind::StopOrPlatform { station }
+                    }
+                    StopStereoType::EntranceExit { station } => {
+                        SyncStopKind::EntranceExit { station }
+                    }
+                },
+                transfers: stop
+                    .transfers
+                    .iter()
+                    .map(|transfer| {
+                        (
+                            transfer.to_stop_id,
+                            transfer
+                                .min_transfer_time
+                                .map(|duration| duration.num_seconds()),
+                        )
+                    })
+                    .collect(),
+                zone_id: stop.zone_id.clone(),
+            })
+            .collect();
+
+        let timetable = held.is_empty().then(|| {
+            let mut services_by_day: Vec<(Day, Vec<ServiceId>)> = required
+                .services_by_day
+                .iter()
+                .map(|(day, services)| {
+                    let mut services: Vec<ServiceId> = services.iter().copied().collect();
+                    services.sort_unstable();
+                    (*day, services)
+                })
+                .collect();
+            services_by_day.sort();
+            let mut service_exceptions: Vec<(String, Vec<(ServiceId, ServiceException)>)> =
+                required
+                    .service_exceptions
+                    .iter()
+                    .map(|(date, exceptions)| {
+                        let mut exceptions = exceptions.clone();
+                        exceptions.sort_unstable();
+                        (date.format(GTFS_DATE_FORMAT).to_string(), exceptions)
+                    })
+                    .collect();
+            service_exceptions.sort();
+            SyncTimetable {
+                services_by_day,
+                service_exceptions,
+                timetable_start_date: required.timetable_start_date.clone(),
+            }
+        });
+
+        GTFSSyncIncrement {
+            timetable,
+            names,
+            stops,
+            trips,
+        }
+    }
+
+    /// Copies just the stops and trips needed for a search into new data, which can be searched in the same way
+    pub fn materialise(&self, required: &RequiredData) -> GTFSData {
+        self.sync_increment(required, &Manifest::default())
+            .into_data()
+            .expect("an increment of the data's own stops and trips to build")
+    }
+
+    /// Copy of the data a client holds without the trips which have arrived at their last stop before `time` and the stops only they called at.
+    /// The increments accumulate on the client, so this is meant to be run after each refresh at the expiry of a search.
+    pub fn without_past_trips(&self, time: Time) -> GTFSData {
+        let mut still_running = self.build_from();
+        for trip in self.trips() {
+            if trip
+                .stop_times
+                .last()
+                .map(|last_stop| last_stop.arrival_time >= time)
+                .unwrap_or(false)
+            {
+                still_running.keep_trip(trip.trip_id);
+            }
+        }
+        self.materialise(&still_running.build())
+    }
+}
+
+/// The manifests of the clients syncing through sessions, the least recently synced are dropped beyond the limit
+#[cfg(feature = "std")]
+pub struct SyncSessions {
+    max_sessions: usize,
+    sessions: HashMap<u64, SyncSession>,
+    /// Counts the syncs, to find the least recently synced session
+    syncs: u64,
+    random: RandomState,
+}
+
+#[cfg(feature = "std")]
+struct SyncSession {
+    version: u32,
+    held: Manifest,
+    last_synced: u64,
+}
+
+#[cfg(feature = "std")]
+impl SyncSessions {
+    pub fn new(max_sessions: usize) -> Self {
+        SyncSessions {
+            max_sessions,
+            sessions: HashMap::new(),
+            syncs: 0,
+            random: RandomState::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Drops all the sessions, as when the data they were synced from is replaced
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+    }
+
+    /// The increment on the data held at the client's version, with the version it will hold.
+    /// When the version isn't the latest of a session, such as when the client missed an increment or the session was dropped, the client starts again in a new session with everything it needs.
+    /// A client which evicts past trips still holds them in the session, though it won't need them again for later searches.
+    pub fn sync(
+        &mut self,
+        data: &GTFSData,
+        required: &RequiredData,
+        held: Option<SyncVersion>,
+    ) -> VersionedIncrement {
+        self.syncs += 1;
+        let session_id = match held {
+            Some(held)
+                if self
+                    .sessions
+                    .get(&held.session)
+                    .is_some_and(|session| session.version == held.version) =>
+            {
+                held.session
+            }
+            _ => self.start_session(),
+        };
+        let session = self
+            .sessions
+            .get_mut(&session_id)
+            .expect("session to be started");
+        let increment = data.sync_increment(required, &session.held);
+        session
+            .held
+            .stops
+            .extend(increment.stops.iter().map(|stop| stop.stop_id));
+        session
+            .held
+            .trips
+            .extend(increment.trips.iter().map(|trip| trip.trip_id));
+        session.version += 1;
+        session.last_synced = self.syncs;
+        VersionedIncrement {
+            version: SyncVersion {
+                session: session_id,
+                version: session.version,
+            },
+            increment,
+        }
+    }
+
+    /// A new session with nothing held, which has an id that isn't guessed from the ids of other sessions
+    fn start_session(&mut self) -> u64 {
+        if self.sessions.len() >= self.max_sessions {
+            let least_recent = self
+                .sessions
+                .iter()
+                .min_by_key(|(_, session)| session.last_synced)
+                .map(|(session_id, _)| *session_id);
+            if let Some(least_recent) = least_recent {
+                self.sessions.remove(&least_recent);
+            }
+        }
+        let mut session_id;
+        loop {
+            let mut hasher = self.random.build_hasher();
+            hasher.write_u64(self.syncs);
+            hasher.write_usize(self.sessions.len());
+            session_id = hasher.finish();
+            if !self.sessions.contains_key(&session_id) {
+                break;
+            }
+            self.syncs += 1;
+        }
+        self.sessions.insert(
+            session_id,
+            SyncSession {
+                version: 0,
+                held: Manifest::default(),
+                last_synced: self.syncs,
+            },
+        );
+        session_id
+    }
+}
+
+#[cfg(feature = "std")]
+impl GTFSSyncIncrement {
+    /// Builds new data from the first increment sent to a client, which fails if the increment isn't the first, so doesn't have the timetable, or refers to stops or trips it doesn't have
+    pub fn into_data(mut self) -> Result<GTFSData, BuildError> {
+        let timetable = self.timetable.take().ok_or(BuildError::NoTimetable)?;
+        let services_by_day: HashMap<Day, HashSet<ServiceId>> = timetable
+            .services_by_day
+            .into_iter()
+            .map(|(day, services)| (day, services.into_iter().collect()))
+            .collect();
+        let mut builder = GTFSData::builder(services_by_day, timetable.timetable_start_date);
+        for (date, exceptions) in timetable.service_exceptions {
+            if let Ok(date) = NaiveDate::parse_from_str(&date, GTFS_DATE_FORMAT) {
+                for (service_id, exception) in exceptions {
+                    builder.add_service_exception(date, service_id, exception);
+                }
+            }
+        }
+        self.add_to(&mut builder)?;
+        builder.build()
+    }
+
+    /// Adds the increment to the data the client holds, anything it already held is left as it was
+    pub fn apply(self, data: GTFSData) -> Result<GTFSData, BuildError> {
+        let mut builder = data.into_builder();
+        self.add_to(&mut builder)?;
+        builder.build()
+    }
+
+    fn add_to(self, builder: &mut Builder) -> Result<(), BuildError> {
+        let mut new_stops = vec![];
+        let names = self.names;
+        for stop in self.stops {
+            if builder.has_stop(stop.stop_id) {
+                continue;
+            }
+            let location = geo::Point::new(stop.lat, stop.lon);
+            let name = |index: u32| {
+                names
+                    .get(index as usize)
+                    .cloned()
+                    .ok_or(BuildError::UnknownName(index))
+            };
+            let (full_stop_name, short_stop_name) =
+                (name(stop.full_stop_name)?, name(stop.short_stop_name)?);
+            match stop.kind {
+                SyncStopKind::Station => {
+                    builder.add_station(stop.stop_id, full_stop_name, short_stop_name, location)
+                }
+                SyncStopKind::StopOrPlatform { station } => builder.add_stop_or_platform(
+                    stop.stop_id,
+                    full_stop_name,
+                    short_stop_name,
+                    location,
+                    station,
+                ),
+                SyncStopKind::EntranceExit { station } => builder.add_entrance_or_exit(
+                    stop.stop_id,
+                    full_stop_name,
+                    short_stop_name,
+                    location,
+                    station,
+                ),
+            }
+            if let Some(zone_id) = stop.zone_id {
+                builder.set_zone(stop.stop_id, zone_id)?;
+            }
+            new_stops.push((stop.stop_id, stop.transfers));
+        }
+        // transfers are added once all the stops are, as they may lead to a stop later in the increment
+        for (stop_id, transfers) in new_stops {
+            for (to_stop_id, min_transfer_time) in transfers {
+                builder.add_transfer(
+                    stop_id,
+                    to_stop_id,
+                    min_transfer_time.map(Duration::seconds),
+                )?;
+            }
+        }
+        for trip in self.trips {
+            if builder.has_trip(trip.trip_id) {
+                continue;
+            }
+            let route_type = if let Some(route_type) = RouteType::from_code(trip.route_type) {
+                route_type
+            } else {
+                eprintln!(
+                    "Skipping synced trip {} with unknown route type {}",
+                    trip.trip_id, trip.route_type
+                );
+                continue;
+            };
+            builder.add_route(
+                trip.route_id,
+                trip.route_short_name,
+                route_type,
+                trip.route_color,
+            );
+            builder.add_trip(
+                trip.trip_id,
+                trip.route_id,
+                trip.service_id,
+                trip.direction_id,
+                trip.block_id,
+            )?;
+            for stop_time in trip.stop_times {
+                builder.add_trip_stop(
+                    trip.trip_id,
+                    stop_time.arrival_time,
+                    stop_time.departure_time,
+                    stop_time.stop_id,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::journey_graph::Plotter;
+    use crate::time::Period;
+
+    fn stop_id(id: u32) -> StopId {
+        StopId::new(id).unwrap()
+    }
+
+    /// An S-Bahn line through stations 10, 20, 30 and a bus from station 10 to station 40, running every 10 minutes from 10:00
+    fn network() -> GTFSData {
+        let mut services_by_day = HashMap::new();
+        services_by_day.insert(Day::Monday, [1].iter().copied().collect());
+        let mut builder = GTFSData::builder(services_by_day, "20200101".to_owned());
+        // and on new year's eve, a Tuesday
+        builder.add_service_exception(
+            NaiveDate::from_ymd_opt(2019, 12, 31).unwrap(),
+            1,
+            ServiceException::Added,
+        );
+        for (i, name) in ["Ostkreuz", "Rummelsburg", "Karlshorst", "Blockdammweg"]
+            .iter()
+            .enumerate()
+        {
+            let station = (i as u32 + 1) * 10;
+            let location = geo::Point::new(52.5, 13.4 + 0.01 * i as f64);
+            builder.add_station(
+                stop_id(station),
+                name.to_string(),
+                name.to_string(),
+                location,
+            );
+            builder.add_stop_or_platform(
+                stop_id(station + 1),
+                name.to_string(),
+                name.to_string(),
+                location,
+                Some(stop_id(station)),
+            );
+            builder
+                .add_transfer(stop_id(station), stop_id(station), None)
+                .unwrap();
+        }
+        builder.add_route(
+            1,
+            "S3".to_owned(),
+            RouteType::SuburbanRailway,
+            "#026597".to_owned(),
+        );
+        builder.add_route(2, "194".to_owned(), RouteType::BusService, String::new());
+        for departure in 0..6 {
+            let time = |minutes| Time::from_hms(10, departure * 10 + minutes, 0);
+            let s_bahn = TripId::new(100 + departure).unwrap();
+            builder.add_trip(s_bahn, 1, 1, Some(0), None).unwrap();
+            for (stop, minutes) in [(11, 0), (21, 3), (31, 6)] {
+                builder
+                    .add_trip_stop(s_bahn, time(minutes), time(minutes), stop_id(stop))
+                    .unwrap();
+            }
+            let bus = TripId::new(200 + departure).unwrap();
+            builder.add_trip(bus, 2, 1, Some(0), None).unwrap();
+            for (stop, minutes) in [(11, 1), (41, 8)] {
+                builder
+                    .add_trip_stop(bus, time(minutes), time(minutes), stop_id(stop))
+                    .unwrap();
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    fn required(data: &GTFSData, from_minute: u32, route_types: &[RouteType]) -> RequiredData {
+        let mut plotter = Plotter::new(
+            NaiveDate::from_ymd_opt(2020, 1, 6).unwrap(),
+            Period::between(
+                Time::from_hms(10, from_minute, 0),
+                Time::from_hms(10, from_minute + 20, 0),
+            ),
+            data,
+        );
+        plotter.add_origin_station(data.get_stop(stop_id(10)).unwrap());
+        for route_type in route_types {
+            plotter.add_route_type(*route_type);
+        }
+        plotter.filtered_data()
+    }
+
+    fn sorted_trips(data: &GTFSData) -> Vec<u32> {
+        let mut trips: Vec<u32> = data.trips().map(|trip| trip.trip_id.get()).collect();
+        trips.sort_unstable();
+        trips
+    }
+
+    #[test]
+    fn materialised_data_has_the_trips_of_the_search() {
+        let data = network();
+        let search = required(&data, 0, &[RouteType::SuburbanRailway]);
+        let materialised = data.materialise(&search);
+        // the later trains don't get anywhere first, so aren't needed
+        assert_eq!(sorted_trips(&materialised), vec![100]);
+        // the parent stations come along with the platforms the trips call at
+        for station in [10, 20, 30] {
+            assert!(materialised
+                .get_stop(stop_id(station))
+                .unwrap()
+                .is_station());
+        }
+        assert!(materialised.get_stop(stop_id(41)).is_none());
+        assert_eq!(materialised.services_of_day(Day::Monday).len(), 1);
+        assert_eq!(
+            materialised
+                .services_on(NaiveDate::from_ymd_opt(2019, 12, 31).unwrap())
+                .len(),
+            1
+        );
+        // and the materialised data gets the same search result
+        assert_eq!(
+            required(&materialised, 0, &[RouteType::SuburbanRailway]).trips,
+            search.trips
+        );
+    }
+
+    #[test]
+    fn names_are_sent_and_kept_once() {
+        let data = network();
+        let increment = data.sync_increment(
+            &required(&data, 0, &[RouteType::SuburbanRailway]),
+            &Manifest::default(),
+        );
+        // the stations and their platforms have the same names
+        assert_eq!(increment.stops.len(), 6);
+        assert_eq!(
+            increment.names,
+            vec!["Ostkreuz", "Rummelsburg", "Karlshorst"]
+        );
+        let client = increment.into_data().unwrap();
+        let station = client.get_stop(stop_id(20)).unwrap();
+        let platform = client.get_stop(stop_id(21)).unwrap();
+        assert_eq!(platform.short_stop_name(), "Rummelsburg");
+        assert!(std::ptr::eq(
+            station.full_stop_name(),
+            platform.short_stop_name()
+        ));
+    }
+
+    #[test]
+    fn unknown_names_are_an_error() {
+        let data = network();
+        let mut increment = data.sync_increment(
+            &required(&data, 0, &[RouteType::SuburbanRailway]),
+            &Manifest::default(),
+        );
+        increment.stops[0].short_stop_name = 3;
+        assert_eq!(
+            increment.into_data().err(),
+            Some(BuildError::UnknownName(3))
+        );
+    }
+
+    #[test]
+    fn overlapping_windows_only_send_the_new_trips() {
+        let data = network();
+        let first = data.sync_increment(
+            &required(&data, 0, &[RouteType::SuburbanRailway]),
+            &Manifest::default(),
+        );
+        let client = first.into_data().unwrap();
+
+        let held = Manifest::of(&client);
+        let second =
+            data.sync_increment(&required(&data, 10, &[RouteType::SuburbanRailway]), &held);
+        assert!(second.timetable.is_none());
+        assert!(second.stops.is_empty());
+        let sent: Vec<u32> = second.trips.iter().map(|trip| trip.trip_id.get()).collect();
+        assert_eq!(sent, vec![101]);
+
+        let client = second.apply(client).unwrap();
+        assert_eq!(sorted_trips(&client), vec![100, 101]);
+        // searching the same window again needs nothing more
+        let again = data.sync_increment(
+            &required(&data, 10, &[RouteType::SuburbanRailway]),
+            &Manifest::of(&client),
+        );
+        assert!(again.stops.is_empty() && again.trips.is_empty());
+        // and a later increment can't be built on its own
+        assert_eq!(again.into_data().err(), Some(BuildError::NoTimetable));
+        // departures were indexed for the newly added trip
+        let platform = client.get_stop(stop_id(11)).unwrap();
+        assert_eq!(
+            platform
+                .departures(Period::between(
+                    Time::from_hms(10, 0, 0),
+                    Time::from_hms(10, 30, 0)
+                ))
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn mode_change_sends_the_new_stops() {
+        let data = network();
+        let client = data
+            .sync_increment(
+                &required(&data, 0, &[RouteType::SuburbanRailway]),
+                &Manifest::default(),
+            )
+            .into_data()
+            .unwrap();
+
+        let increment = data.sync_increment(
+            &required(
+                &data,
+                0,
+                &[RouteType::SuburbanRailway, RouteType::BusService],
+            ),
+            &Manifest::of(&client),
+        );
+        let sent: Vec<u32> = increment
+            .trips
+            .iter()
+            .map(|trip| trip.trip_id.get())
+            .collect();
+        assert_eq!(sent, vec![200]);
+        let stops: Vec<u32> = increment
+            .stops
+            .iter()
+            .map(|stop| stop.stop_id.get())
+            .collect();
+        assert_eq!(stops, vec![40, 41]);
+
+        // it survives being sent as JSON, with the stop ids after the first as the difference from the one before
+        let json = serde_json::to_value(&increment).unwrap();
+        let sent: Vec<&serde_json::Value> = json["stops"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|stop| &stop["stop_id"])
+            .collect();
+        assert_eq!(sent, vec![40, 1]);
+        let increment: GTFSSyncIncrement = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            increment
+                .stops
+                .iter()
+                .map(|stop| stop.stop_id.get())
+                .collect::<Vec<_>>(),
+            stops
+        );
+        let client = increment.apply(client).unwrap();
+        assert_eq!(sorted_trips(&client), vec![100, 200]);
+        let bus = client
+            .trips()
+            .find(|trip| trip.trip_id.get() == 200)
+            .unwrap();
+        assert_eq!(bus.route.route_type, RouteType::BusService);
+        assert_eq!(bus.route.route_color, RouteType::BusService.default_color());
+    }
+
+    #[test]
+    fn stop_id_deltas_are_checked() {
+        let data = network();
+        let mut increment = data.sync_increment(
+            &required(&data, 0, &[RouteType::SuburbanRailway]),
+            &Manifest::default(),
+        );
+        // a difference which takes the id beyond the largest isn't read
+        let mut json = serde_json::to_value(&increment).unwrap();
+        json["stops"][1]["stop_id"] = u32::MAX.into();
+        assert!(serde_json::from_value::<GTFSSyncIncrement>(json).is_err());
+        // and stops out of order aren't sent
+        increment.stops.reverse();
+        assert!(serde_json::to_string(&increment).is_err());
+    }
+
+    #[test]
+    fn sealed_increments_are_checked() {
+        let data = network();
+        let increment = data.sync_increment(
+            &required(&data, 0, &[RouteType::SuburbanRailway]),
+            &Manifest::default(),
+        );
+        let encoded = serde_json::to_vec(&increment).unwrap();
+        let sealed = seal(&encoded);
+        assert!(sealed.starts_with(MAGIC));
+        let opened: GTFSSyncIncrement = serde_json::from_slice(unseal(&sealed).unwrap()).unwrap();
+        assert_eq!(sorted_trips(&opened.into_data().unwrap()), vec![100]);
+
+        // any byte altered, or the end cut off, is caught by the checksum
+        for i in MAGIC.len()..sealed.len() {
+            let mut corrupted = sealed.clone();
+            corrupted[i] ^= 0x10;
+            assert_eq!(unseal(&corrupted), Err(SealError::Corrupted), "{}", i);
+        }
+        assert_eq!(
+            unseal(&sealed[..sealed.len() - 1]),
+            Err(SealError::Corrupted)
+        );
+        assert_eq!(unseal(&sealed[..MAGIC.len()]), Err(SealError::Corrupted));
+        assert_eq!(unseal(&encoded), Err(SealError::NotAnIncrement));
+        assert_eq!(unseal(b""), Err(SealError::NotAnIncrement));
+
+        // an increment of another schema isn't read, though it's intact
+        let mut other_version = MAGIC.to_vec();
+        other_version.extend_from_slice(&(SCHEMA_VERSION + 1).to_le_bytes());
+        other_version.extend_from_slice(&encoded);
+        let crc = crc32fast::hash(&other_version);
+        other_version.extend_from_slice(&crc.to_le_bytes());
+        assert_eq!(
+            unseal(&other_version),
+            Err(SealError::Version(SCHEMA_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn sessions_send_what_the_version_doesnt_hold() {
+        let data = network();
+        let s_bahn = |from_minute| required(&data, from_minute, &[RouteType::SuburbanRailway]);
+        let mut sessions = SyncSessions::new(2);
+
+        let first = sessions.sync(&data, &s_bahn(0), None);
+        assert_eq!(first.version.version, 1);
+        assert!(first.increment.timetable.is_some());
+        let client = first.increment.into_data().unwrap();
+
+        let second = sessions.sync(&data, &s_bahn(10), Some(first.version));
+        assert_eq!(second.version.session, first.version.session);
+        assert_eq!(second.version.version, 2);
+        assert!(second.increment.timetable.is_none() && second.increment.stops.is_empty());
+        let sent: Vec<u32> = second
+            .increment
+            .trips
+            .iter()
+            .map(|trip| trip.trip_id.get())
+            .collect();
+        assert_eq!(sent, vec![101]);
+        let client = second.increment.apply(client).unwrap();
+        assert_eq!(sorted_trips(&client), vec![100, 101]);
+
+        // a client which missed the second increment starts again
+        let again = sessions.sync(&data, &s_bahn(10), Some(first.version));
+        assert_ne!(again.version.session, first.version.session);
+        assert_eq!(again.version.version, 1);
+        assert!(again.increment.timetable.is_some());
+        assert_eq!(
+            sorted_trips(&again.increment.into_data().unwrap()),
+            vec![101]
+        );
+
+        // and the least recently synced session is dropped for a third
+        assert_eq!(sessions.len(), 2);
+        sessions.sync(&data, &s_bahn(0), None);
+        assert_eq!(sessions.len(), 2);
+        let dropped = sessions.sync(&data, &s_bahn(20), Some(second.version));
+        assert!(dropped.increment.timetable.is_some());
+    }
+
+    #[test]
+    fn past_trips_and_their_stops_are_evicted() {
+        let data = network();
+        let client = data
+            .sync_increment(
+                &required(
+                    &data,
+                    0,
+                    &[RouteType::SuburbanRailway, RouteType::BusService],
+                ),
+                &Manifest::default(),
+            )
+            .into_data()
+            .unwrap();
+        let client = data
+            .sync_increment(
+                &required(&data, 10, &[RouteType::SuburbanRailway]),
+                &Manifest::of(&client),
+            )
+            .apply(client)
+            .unwrap();
+        assert_eq!(sorted_trips(&client), vec![100, 101, 200]);
+
+        // the first train and the bus have arrived by 10:09, the second train is still running
+        let client = client.without_past_trips(Time::from_hms(10, 9, 0));
+        assert_eq!(sorted_trips(&client), vec![101]);
+        assert!(client.get_stop(stop_id(40)).is_none());
+        assert!(client.get_stop(stop_id(41)).is_none());
+        assert!(client.get_stop(stop_id(10)).is_some());
+        assert_eq!(client.services_of_day(Day::Monday).len(), 1);
+    }
+}
@@ -1,6 +1,10 @@
-use std::convert::TryInto;
-use std::fmt;
-use std::ops::{Add, Sub};
+//! Times of day as in GTFS, which can go past midnight, and periods between them.
+//!
+//! These only need `core`, so that they can be used without `std`.
+
+use core::convert::TryInto;
+use core::fmt;
+use core::ops::{Add, Sub};
 
 use chrono::{Duration, NaiveTime, Timelike};
 use serde::{de, ser};
@@ -161,17 +165,21 @@ impl Period {
         self.start
     }
 
+    pub const fn end(self) -> Time {
+        self.end
+    }
+
     pub fn duration(self) -> Duration {
         self.end - self.start
     }
 }
 
-impl std::ops::RangeBounds<Time> for Period {
-    fn start_bound(&self) -> std::ops::Bound<&Time> {
-        std::ops::Bound::Included(&self.start)
+impl core::ops::RangeBounds<Time> for Period {
+    fn start_bound(&self) -> core::ops::Bound<&Time> {
+        core::ops::Bound::Included(&self.start)
     }
-    fn end_bound(&self) -> std::ops::Bound<&Time> {
-        std::ops::Bound::Excluded(&self.end)
+    fn end_bound(&self) -> core::ops::Bound<&Time> {
+        core::ops::Bound::Excluded(&self.end)
     }
 }
 
@@ -189,11 +197,11 @@ impl fmt::Display for Period {
 /// let time: Time = "09:00:00".parse().unwrap();
 /// let time: Time = "23:59:59".parse().unwrap();
 /// let time: Time = "25:00:00".parse().unwrap();
-impl std::str::FromStr for Time {
+impl core::str::FromStr for Time {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use std::str::from_utf8;
+        use core::str::from_utf8;
 
         let s = s.as_bytes();
         let (hh, mm, ss) = if s.len() == 8 {
@@ -225,17 +233,17 @@ impl std::str::FromStr for Time {
 pub enum ParseError {
     InvalidFormat,
     TooManySecondsOrMinutes,
-    ParseIntError(std::num::ParseIntError),
+    ParseIntError(core::num::ParseIntError),
 }
 
-impl From<std::num::ParseIntError> for ParseError {
-    fn from(err: std::num::ParseIntError) -> Self {
+impl From<core::num::ParseIntError> for ParseError {
+    fn from(err: core::num::ParseIntError) -> Self {
         Self::ParseIntError(err)
     }
 }
 
-impl std::convert::From<std::str::Utf8Error> for ParseError {
-    fn from(_err: std::str::Utf8Error) -> Self {
+impl From<core::str::Utf8Error> for ParseError {
+    fn from(_err: core::str::Utf8Error) -> Self {
         Self::InvalidFormat
     }
 }
@@ -250,11 +258,13 @@ impl fmt::Display for ParseError {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for ParseError {}
 
 #[cfg(test)]
 mod test {
     use super::{Duration, Time};
+    use alloc::string::ToString;
 
     #[test]
     fn hms_times() {
@@ -0,0 +1,194 @@
+//! The ids, days, route types and stop times which the search data and the sync payload are made of.
+//!
+//! These only need `alloc`, so that small displays without `std` can read the sync payload.
+
+use alloc::string::String;
+use core::convert::TryFrom;
+use core::fmt;
+use core::num::NonZeroU32;
+use serde::{Deserialize, Serialize};
+
+use crate::time::Time;
+
+pub type AgencyId = u16;
+pub type RouteId = u32;
+pub type TripId = NonZeroU32; // 27bits
+pub type StopId = NonZeroU32; // intern key
+pub type ShapeId = u16;
+/// Trips of a block are run one after the other by the same vehicle
+pub type BlockId = NonZeroU32; // intern key
+/// Which way a trip goes along its route, 0 or 1, what each means is up to the feed
+pub type DirectionId = u8;
+/// Identifies a stopping pattern, the sequence of stops that a trip calls at
+pub type PatternId = u32;
+// type BlockId = String;
+pub type ServiceId = u16;
+/// Fare zone, eg. "A"
+pub type ZoneId = String;
+
+/// Refers to a specific stop of a specific trip (an arrival / departure)
+pub type TripStopRef = (TripId, u16); // u16 refers to the index of the stop in the trip, should probably instead use stop sequence
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum Day {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl fmt::Display for Day {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Monday => "mon",
+            Self::Tuesday => "tue",
+            Self::Wednesday => "wed",
+            Self::Thursday => "thu",
+            Self::Friday => "fri",
+            Self::Saturday => "sat",
+            Self::Sunday => "sun",
+        })
+    }
+}
+
+impl From<chrono::Weekday> for Day {
+    fn from(weekday: chrono::Weekday) -> Self {
+        match weekday {
+            chrono::Weekday::Mon => Self::Monday,
+            chrono::Weekday::Tue => Self::Tuesday,
+            chrono::Weekday::Wed => Self::Wednesday,
+            chrono::Weekday::Thu => Self::Thursday,
+            chrono::Weekday::Fri => Self::Friday,
+            chrono::Weekday::Sat => Self::Saturday,
+            chrono::Weekday::Sun => Self::Sunday,
+        }
+    }
+}
+
+/// Day numbered as by javascript's `Date.getDay()`, 0 is Sunday and 6 is Saturday
+impl TryFrom<u32> for Day {
+    type Error = InvalidDay;
+
+    fn try_from(day: u32) -> Result<Self, Self::Error> {
+        Ok(match day {
+            0 => Self::Sunday,
+            1 => Self::Monday,
+            2 => Self::Tuesday,
+            3 => Self::Wednesday,
+            4 => Self::Thursday,
+            5 => Self::Friday,
+            6 => Self::Saturday,
+            day => return Err(InvalidDay(day)),
+        })
+    }
+}
+
+/// How a date in `calendar_dates.txt` changes the days a service runs on
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum ServiceException {
+    /// The service runs on the date, though it doesn't on that day of the week
+    Added,
+    /// The service doesn't run on the date, such as on a public holiday
+    Removed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidDay(pub u32);
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidDay {}
+
+impl fmt::Display for InvalidDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is not a day of the week, expected 0 (Sunday) to 6",
+            self.0
+        )
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
+pub enum RouteType {
+    Rail,
+    Bus,
+    RailwayService,
+    SuburbanRailway,
+    UrbanRailway,
+    BusService,
+    TramService,
+    WaterTransportService,
+}
+
+impl RouteType {
+    pub const ALL: [RouteType; 8] = [
+        RouteType::Rail,
+        RouteType::Bus,
+        RouteType::RailwayService,
+        RouteType::SuburbanRailway,
+        RouteType::UrbanRailway,
+        RouteType::BusService,
+        RouteType::TramService,
+        RouteType::WaterTransportService,
+    ];
+
+    /// Route type for a GTFS `route_type` code, either a basic type or one of the extended types
+    pub fn from_code(code: u16) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|route_type| route_type.code() == code)
+    }
+
+    /// The GTFS `route_type` code
+    pub fn code(self) -> u16 {
+        match self {
+            RouteType::Rail => 2,
+            RouteType::Bus => 3,
+            RouteType::RailwayService => 100,
+            RouteType::SuburbanRailway => 109,
+            RouteType::UrbanRailway => 400,
+            RouteType::BusService => 700,
+            RouteType::TramService => 900,
+            RouteType::WaterTransportService => 1000,
+        }
+    }
+
+    /// Colour for routes of this type which have no colour of their own
+    pub fn default_color(self) -> &'static str {
+        match self {
+            RouteType::SuburbanRailway => "lightgray",
+            RouteType::UrbanRailway => "lightgray",
+            RouteType::TramService => "lightgray",
+            RouteType::Rail => "#e2001a",
+            RouteType::RailwayService => "#e2001a",
+            RouteType::Bus => "#a01c7d", // not sure if this is bus
+            RouteType::BusService => "#a01c7d",
+            RouteType::WaterTransportService => "#0099d6",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StopTime {
+    /// Arrival time at a specific stop for a specific trip on a route. If there are not separate times for arrival and departure at a stop, enter the same value for arrival_time and departure_time. For times occurring after midnight on the service day, enter the time as a value greater than 24:00:00 in HH:MM:SS local time for the day on which the trip schedule begins.
+    /// Scheduled stops where the vehicle strictly adheres to the specified arrival and departure times are timepoints. If this stop is not a timepoint, it is recommended to provide an estimated or interpolated time. If this is not available, arrival_time can be left empty. Further, indicate that interpolated times are provided with timepoint=0. If interpolated times are indicated with timepoint=0, then time points must be indicated with timepoint=1. Provide arrival times for all stops that are time points. An arrival time must be specified for the first and the last stop in a trip.
+    pub arrival_time: Time, // ~ 17bits
+    /// Departure time from a specific stop for a specific trip on a route. For times occurring after midnight on the service day, enter the time as a value greater than 24:00:00 in HH:MM:SS local time for the day on which the trip schedule begins. If there are not separate times for arrival and departure at a stop, enter the same value for arrival_time and departure_time. See the arrival_time description for more details about using timepoints correctly.
+    /// The departure_time field should specify time values whenever possible, including non-binding estimated or interpolated times between timepoints.
+    pub departure_time: Time, // maybe 10 bits
+    /// Identifies the serviced stop. All stops serviced during a trip must have a record in stop_times.txt. Referenced locations must be stops, not stations or station entrances. A stop may be serviced multiple times in the same trip, and multiple trips and routes may service the same stop.
+    pub stop_id: StopId, // ~27bits needed
+}
+
+#[test]
+fn day_conversions() {
+    assert_eq!(Day::from(chrono::Weekday::Mon), Day::Monday);
+    assert_eq!(Day::from(chrono::Weekday::Sun), Day::Sunday);
+    assert_eq!(Day::try_from(0), Ok(Day::Sunday));
+    assert_eq!(Day::try_from(6), Ok(Day::Saturday));
+    assert_eq!(Day::try_from(7), Err(InvalidDay(7)));
+}
@@ -0,0 +1,81 @@
+//! Times indexing and searching the names of a city's worth of stations, run with `cargo bench -p radar-suggester`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use radar_suggester::Suggester;
+
+const FIRST_WORDS: [&str; 16] = [
+    "Alexander",
+    "Bornholmer",
+    "Friedrich",
+    "Gesundbrunnen",
+    "Hermann",
+    "Kaiser",
+    "Köpenicker",
+    "Lichtenberger",
+    "Mehring",
+    "Nollendorf",
+    "Oranienburger",
+    "Prenzlauer",
+    "Rosenthaler",
+    "Schönhauser",
+    "Warschauer",
+    "Zoologischer",
+];
+const SECOND_WORDS: [&str; 8] = [
+    "Str.", "Platz", "Allee", "Tor", "Damm", "Brücke", "Garten", "Bhf",
+];
+const STATIONS: usize = 10_000;
+
+/// Names like the VBB's, eg. `Berlin, Prenzlauer Allee 17`
+fn names() -> Vec<String> {
+    (0..STATIONS)
+        .map(|station| {
+            format!(
+                "Berlin, {} {} {}",
+                FIRST_WORDS[station % FIRST_WORDS.len()],
+                SECOND_WORDS[station / FIRST_WORDS.len() % SECOND_WORDS.len()],
+                station
+            )
+        })
+        .collect()
+}
+
+fn suggester(names: &[String]) -> Suggester<(u32, usize)> {
+    let mut suggester = Suggester::new();
+    for (id, name) in names.iter().enumerate() {
+        suggester.insert(name, (id as u32, id % 100));
+    }
+    suggester
+}
+
+fn bench(c: &mut Criterion) {
+    let names = names();
+    c.bench_function("insert", |b| b.iter(|| suggester(black_box(&names))));
+
+    let suggester = suggester(&names);
+    c.bench_function("search one word", |b| {
+        b.iter(|| suggester.search(black_box("prenz")).into_iter().count())
+    });
+    c.bench_function("search two words", |b| {
+        b.iter(|| {
+            suggester
+                .search(black_box("prenz allee"))
+                .into_iter()
+                .count()
+        })
+    });
+    c.bench_function("rank", |b| {
+        b.iter(|| {
+            suggester.rank(black_box("schön"), |&(id, importance)| {
+                (std::cmp::Reverse(importance), id)
+            })
+        })
+    });
+    c.bench_function("deserialize", |b| {
+        let json = serde_json::to_string(&suggester).unwrap();
+        b.iter(|| serde_json::from_str::<Suggester<(u32, usize)>>(black_box(&json)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
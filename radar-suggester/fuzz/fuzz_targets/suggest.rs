@@ -0,0 +1,44 @@
+//! Inserts the names of the input and searches them by the last line, checking that each name is found by itself and that a search only finds names with a word starting with each word of the query. Run with `cargo +nightly fuzz run suggest` in `radar-suggester`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use radar_suggester::Suggester;
+
+fuzz_target!(|input: &str| {
+    let mut lines: Vec<&str> = input.lines().collect();
+    let query = lines.pop().unwrap_or_default();
+    let mut suggester = Suggester::new();
+    for (index, name) in lines.iter().enumerate() {
+        suggester.insert(name, index);
+    }
+
+    for (index, name) in lines.iter().enumerate() {
+        assert!(suggester
+            .search(name)
+            .into_iter()
+            .any(|found| found == index));
+    }
+
+    let ranked = suggester.rank(query, |&index| index);
+    assert!(ranked.windows(2).all(|pair| pair[0] < pair[1]));
+    for index in ranked {
+        let name = lines[index];
+        if name == query {
+            continue;
+        }
+        let words: Vec<String> = name
+            .split_whitespace()
+            .filter(|word| word.len() > 2)
+            .map(str::to_lowercase)
+            .collect();
+        for part in query.split_whitespace() {
+            let part = part.to_lowercase();
+            assert!(words.iter().any(|word| word.starts_with(&part)));
+        }
+    }
+
+    let json = serde_json::to_string(&suggester).unwrap();
+    let deserialized: Suggester<usize> = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized.num_words(), suggester.num_words());
+});
@@ -0,0 +1,216 @@
+//! A word search of names mapped to values, such as the names of stations to their ids, used by `transit-radar` to suggest stations as they are typed.
+//!
+//! A [`Suggester`] is filled with [`insert`](Suggester::insert), searched with [`search`](Suggester::search) or [`rank`](Suggester::rank) and serialized as it's names and values.
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::collections::HashSet;
+use std::hash::Hash;
+use tst::TSTMap;
+
+/// Basic text search map.
+///
+/// # Does
+/// * Tokenizes words on whitespace boundaries
+/// * Ignores case
+/// * searches prefixes
+/// * Orders results by a key of the values, such as the importance of stations
+///
+/// # Should do
+/// * Better tokenization of words wrt punctuation
+/// * Fuzzy search (particularly for ¨, ß, etc.)
+/// * Weighting of the results based on closeness of fuzzy search
+/// * Ordering results by closeness of fuzzy search
+///
+/// ```
+/// use radar_suggester::Suggester;
+///
+/// let mut suggester = Suggester::new();
+/// suggester.insert("S+U Alexanderplatz", (1, 700));
+/// suggester.insert("Alexanderstr.", (2, 20));
+/// let most_important_first =
+///     suggester.rank("alex", |&(_id, importance)| std::cmp::Reverse(importance));
+/// assert_eq!(most_important_first, vec![(1, 700), (2, 20)]);
+/// ```
+pub struct Suggester<T> {
+    exact: TSTMap<HashSet<T>>,
+    lowercase_words: TSTMap<HashSet<T>>,
+}
+
+impl<T> Default for Suggester<T> {
+    fn default() -> Self {
+        Suggester {
+            lowercase_words: TSTMap::new(),
+            exact: TSTMap::new(),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Copy> Suggester<T> {
+    pub fn new() -> Suggester<T> {
+        Default::default()
+    }
+
+    pub fn insert(&mut self, key: &str, value: T) {
+        let v = self.exact.entry(key).or_insert_with(HashSet::new);
+        v.insert(value);
+
+        for word in key.split_whitespace() {
+            if word.len() > 2 {
+                let v = self
+                    .lowercase_words
+                    .entry(&word.to_lowercase())
+                    .or_insert_with(HashSet::new);
+                v.insert(value);
+            }
+        }
+    }
+
+    pub fn num_words(&self) -> usize {
+        self.lowercase_words.len()
+    }
+
+    pub fn prefix_iter(&self, prefix: &str) -> impl Iterator<Item = (String, &HashSet<T>)> {
+        self.lowercase_words.prefix_iter(&prefix.to_lowercase())
+    }
+
+    /// The values of the name matching the query exactly, or else of the names with a word starting with each word of the query
+    pub fn search(&self, query: &str) -> impl IntoIterator<Item = T> {
+        if let Some(results) = self.exact.get(query) {
+            return results.clone();
+        }
+        let query: Vec<_> = query.split_whitespace().collect();
+        let mut results: Option<HashSet<T>> = None;
+        for part in query {
+            let filter: Box<dyn Fn(&T) -> bool> = if let Some(results) = results {
+                let previous_results = results;
+                Box::new(move |val| previous_results.contains(val))
+            } else {
+                Box::new(|_| true)
+            };
+            results = Some(
+                self.prefix_iter(part)
+                    .flat_map(|(_, s)| s)
+                    .copied()
+                    .filter(filter)
+                    .collect(),
+            );
+        }
+        results.unwrap_or_default()
+    }
+
+    /// The results of the search ordered by the key, the least first, values of equal keys are in no particular order
+    pub fn rank<K: Ord>(&self, query: &str, key: impl FnMut(&T) -> K) -> Vec<T> {
+        let mut results: Vec<T> = self.search(query).into_iter().collect();
+        results.sort_by_cached_key(key);
+        results
+    }
+
+    /// Bytes owned on the heap, assuming the values own none and a node of the trees for each character of the keys
+    pub fn heap_size(&self) -> usize {
+        /// A ternary search tree node has a character, a value and 3 children
+        const NODE_SIZE: usize = 4 * std::mem::size_of::<usize>() + std::mem::size_of::<char>();
+        let map_size = |map: &TSTMap<HashSet<T>>| -> usize {
+            map.iter()
+                .map(|(key, values)| {
+                    // a control byte per bucket
+                    key.len() * NODE_SIZE
+                        + std::mem::size_of::<HashSet<T>>()
+                        + values.capacity() * (std::mem::size_of::<T>() + 1)
+                })
+                .sum()
+        };
+        map_size(&self.exact) + map_size(&self.lowercase_words)
+    }
+}
+
+/// Serialized as a sequence of each name and it's values, the words are indexed again when it's deserialized
+impl<T: Serialize> Serialize for Suggester<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.exact.iter())
+    }
+}
+
+impl<'de, T: Deserialize<'de> + Hash + Eq + Copy> Deserialize<'de> for Suggester<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<(String, Vec<T>)>::deserialize(deserializer)?;
+        let mut suggester = Suggester::new();
+        for (name, values) in names {
+            for value in values {
+                suggester.insert(&name, value);
+            }
+        }
+        Ok(suggester)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Suggester;
+    use std::collections::HashSet;
+
+    fn suggester() -> Suggester<u32> {
+        let mut suggester = Suggester::new();
+        suggester.insert("Foo Bar", 1);
+        suggester.insert("Foo Baz", 2);
+        suggester.insert("Bar Baz", 3);
+        suggester.insert("bar baz", 4);
+        suggester
+    }
+
+    fn assert_search_results<'i>(query: &str, expected: impl IntoIterator<Item = &'i u32>) {
+        let results: HashSet<_> = suggester().search(query).into_iter().collect();
+        assert_eq!(results, expected.into_iter().copied().collect());
+    }
+
+    #[test]
+    fn word_count() {
+        let suggester = suggester();
+        assert_eq!(suggester.num_words(), 3);
+    }
+
+    #[test]
+    fn exact_match_case_sensitive() {
+        assert_search_results("Bar Baz", &[3]);
+        assert_search_results("bar baz", &[4]);
+    }
+
+    #[test]
+    fn one_word_matchcase() {
+        assert_search_results("Foo", &[1, 2]);
+    }
+
+    #[test]
+    fn two_word_matchcase() {
+        assert_search_results("Foo Bar", &[1]);
+    }
+
+    #[test]
+    fn one_word_offcase() {
+        assert_search_results("foo", &[1, 2]);
+    }
+
+    #[test]
+    fn two_word_offcase() {
+        assert_search_results("foo bar", &[1]);
+    }
+
+    #[test]
+    fn ranked_by_key() {
+        assert_eq!(suggester().rank("ba", |&value| value), vec![1, 2, 3, 4]);
+        assert_eq!(
+            suggester().rank("baz", |&value| std::cmp::Reverse(value)),
+            vec![4, 3, 2]
+        );
+        assert!(suggester().rank("qux", |&value| value).is_empty());
+    }
+
+    #[test]
+    fn serialized_and_indexed_again() {
+        let json = serde_json::to_string(&suggester()).unwrap();
+        let suggester: Suggester<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(suggester.num_words(), 3);
+        assert_eq!(suggester.rank("foo", |&value| value), vec![1, 2]);
+        assert_eq!(suggester.rank("bar baz", |&value| value), vec![4]);
+    }
+}
@@ -0,0 +1,176 @@
+//! Endpoints for operators to check on the running server and to load an updated timetable without restarting it
+
+use std::sync::{Arc, Mutex};
+
+use radar_search::{search_data::Footprint, sync::SyncSessions};
+use rocket::{
+    http::{ContentType, Status},
+    request::{FromRequest, Outcome, Request},
+    State,
+};
+use serde::Serialize;
+use transit_radar::gtfs::ingest::IngestReport;
+
+use crate::radar_cache::RadarCache;
+use crate::scheduler::Prerendered;
+use crate::timetable::{Feed, ReloadError, Timetable, Timetables};
+
+/// The token from `ADMIN_TOKEN`, without one the endpoints which change the server are disabled
+pub struct AdminToken(pub Option<String>);
+
+/// A request with the header `Authorization: Bearer <ADMIN_TOKEN>`
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = request
+            .rocket()
+            .state::<AdminToken>()
+            .and_then(|token| token.0.as_deref());
+        let token = if let Some(token) = token {
+            token
+        } else {
+            return Outcome::Error((Status::NotFound, ()));
+        };
+        let given = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "));
+        match given {
+            Some(given) if same_token(given, token) => Outcome::Success(Admin),
+            _ => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// Compares every byte, so that the time taken doesn't tell how much of a guess was right
+pub fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[derive(Serialize)]
+struct ServerStatus {
+    feeds: Vec<Feed>,
+    timetable_start_date: String,
+    loaded_at: String,
+    load_seconds: f64,
+    /// The rows read from each file of the feeds and the ones skipped
+    ingest: Option<IngestReport>,
+    stations: usize,
+    /// Estimated bytes used by the timetable and the station search
+    memory: MemoryStatus,
+    cached_radars: usize,
+    /// The searches through a day kept for `reachable.json`
+    cached_reachable: usize,
+    /// Clients syncing their data through a session
+    sync_sessions: usize,
+    /// The stations with their stop times read in, when they are read in lazily
+    materialised_stations: Option<usize>,
+    reloading: bool,
+}
+
+#[derive(Serialize)]
+struct MemoryStatus {
+    stops: usize,
+    departures: usize,
+    trips: usize,
+    services: usize,
+    station_search: usize,
+    total: usize,
+}
+
+impl ServerStatus {
+    fn of(
+        timetable: &Timetable,
+        timetables: &Timetables,
+        cache: &RadarCache,
+        sync_sessions: &Mutex<SyncSessions>,
+    ) -> Self {
+        let Footprint {
+            stops,
+            departures,
+            trips,
+            services,
+        } = timetable.data().footprint();
+        let station_search = timetable.suggester.heap_size();
+        ServerStatus {
+            feeds: timetable.feeds.clone(),
+            timetable_start_date: timetable.data().timetable_start_date().to_owned(),
+            loaded_at: timetable.loaded_at.to_rfc3339(),
+            load_seconds: timetable.load_duration.as_secs_f64(),
+            ingest: timetable.ingest.clone(),
+            stations: timetable
+                .data()
+                .stops()
+                .filter(|stop| stop.is_station())
+                .count(),
+            memory: MemoryStatus {
+                stops,
+                departures,
+                trips,
+                services,
+                station_search,
+                total: stops + departures + trips + services + station_search,
+            },
+            cached_radars: cache.len(),
+            cached_reachable: cache.reachable_len(),
+            sync_sessions: sync_sessions.lock().unwrap().len(),
+            materialised_stations: timetable.lazy().map(|lazy| lazy.materialised_stations()),
+            reloading: timetables.is_reloading(),
+        }
+    }
+
+    fn json(&self) -> (ContentType, String) {
+        (ContentType::JSON, serde_json::to_string(self).unwrap())
+    }
+}
+
+/// What's loaded and how much memory it takes
+#[get("/admin/status")]
+pub fn status(
+    timetables: &State<Arc<Timetables>>,
+    cache: &State<Arc<RadarCache>>,
+    sync_sessions: &State<Mutex<SyncSessions>>,
+) -> (ContentType, String) {
+    ServerStatus::of(&timetables.current(), timetables, cache, sync_sessions).json()
+}
+
+/// Loads the timetable again from the feed directories, searches switch to it once it's loaded
+#[post("/admin/reload")]
+pub async fn reload(
+    _admin: Admin,
+    timetables: &State<Arc<Timetables>>,
+    cache: &State<Arc<RadarCache>>,
+    sync_sessions: &State<Mutex<SyncSessions>>,
+    prerendered: &State<Arc<Prerendered>>,
+) -> Result<(ContentType, String), (Status, String)> {
+    let reloading = timetables.inner().clone();
+    let reloaded = rocket::tokio::task::spawn_blocking(move || reloading.reload())
+        .await
+        .map_err(|err| (Status::InternalServerError, err.to_string()))?;
+    match reloaded {
+        Ok(timetable) => {
+            // the cached searches, the data synced to clients and what the jobs rendered are of the replaced timetable, which has other ids
+            cache.clear();
+            sync_sessions.lock().unwrap().clear();
+            prerendered.clear();
+            Ok(ServerStatus::of(&timetable, timetables, cache, sync_sessions).json())
+        }
+        Err(ReloadError::NoSource) => Err((
+            Status::NotImplemented,
+            "this server can't reload it's timetable".to_owned(),
+        )),
+        Err(ReloadError::InProgress) => {
+            Err((Status::Conflict, "a reload is in progress".to_owned()))
+        }
+        Err(ReloadError::Load(err)) => Err((Status::InternalServerError, err)),
+    }
+}
@@ -0,0 +1,172 @@
+//! A search asked for in words, such as `from Alexanderplatz in 25 minutes by tram`, for voice assistants and integrations which don't build radar urls
+//!
+//! The question is parsed by a small grammar rather than understood: `[from] <station> [in|within <minutes> [min|mins|minutes]] [by <mode> [and|or <mode>]...] [at <HH:MM>]`, where the parts after the station can come in any order.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use chrono::{NaiveTime, Utc};
+use rocket::{
+    http::{ContentType, Status},
+    State,
+};
+use transit_radar::draw::{
+    radar::{MaxLegs, RadialScale, TransitMode},
+    render::JsonRenderer,
+};
+use transit_radar::gtfs::db;
+
+use crate::radar_cache::RadarCache;
+use crate::timetable::Timetables;
+use crate::{Overlays, TimeFilter, TransitModes};
+
+/// A search as it was asked for
+#[derive(Debug, PartialEq)]
+struct Question {
+    station: String,
+    minutes: Option<i64>,
+    /// The modes which were named, otherwise the radar's default modes
+    modes: Option<HashSet<TransitMode>>,
+    /// Today in Berlin at this time, otherwise now
+    at: Option<NaiveTime>,
+}
+
+/// The mode a word names, in the singular or plural, by it's key or name
+fn mode_named(word: &str) -> Option<TransitMode> {
+    let mode = match word {
+        "s" | "sbahn" | "s-bahn" => TransitMode::SBahn,
+        "u" | "ubahn" | "u-bahn" | "subway" | "underground" | "metro" => TransitMode::UBahn,
+        "bus" | "buses" => TransitMode::Bus,
+        "tram" | "trams" | "streetcar" | "streetcars" => TransitMode::Tram,
+        "regional" | "train" | "trains" | "rail" => TransitMode::Regional,
+        "boat" | "boats" | "ferry" | "ferries" => TransitMode::Boat,
+        _ => return None,
+    };
+    Some(mode)
+}
+
+/// Each word after the station is a keyword or what follows one, so the question is rejected rather than half understood
+fn parse_question(q: &str) -> Option<Question> {
+    let lowercase = q.to_lowercase();
+    let words: Vec<&str> = lowercase
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let original: Vec<&str> = q
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let is_keyword = |i: usize| match words[i] {
+        // a number has to follow for these to be a keyword rather than part of the station's name
+        "in" | "within" => matches!(words.get(i + 1), Some(n) if n.parse::<u32>().is_ok()),
+        "at" => matches!(words.get(i + 1), Some(t) if parse_time(t).is_some()),
+        "by" => true,
+        _ => false,
+    };
+
+    let mut i = if words.first() == Some(&"from") { 1 } else { 0 };
+    let station_start = i;
+    while i < words.len() && !is_keyword(i) {
+        i += 1;
+    }
+    if i == station_start {
+        return None;
+    }
+    let mut question = Question {
+        station: original[station_start..i].join(" "),
+        minutes: None,
+        modes: None,
+        at: None,
+    };
+    while i < words.len() {
+        match words[i] {
+            "in" | "within" if question.minutes.is_none() => {
+                let minutes: u32 = words.get(i + 1)?.parse().ok()?;
+                if minutes == 0 {
+                    return None;
+                }
+                question.minutes = Some(minutes.into());
+                i += 2;
+                if let Some(&("min" | "mins" | "minutes")) = words.get(i) {
+                    i += 1;
+                }
+            }
+            "by" if question.modes.is_none() => {
+                let mut modes = HashSet::new();
+                modes.insert(mode_named(words.get(i + 1)?)?);
+                i += 2;
+                while let Some(&("and" | "or")) = words.get(i) {
+                    modes.insert(mode_named(words.get(i + 1)?)?);
+                    i += 2;
+                }
+                question.modes = Some(modes);
+            }
+            "at" if question.at.is_none() => {
+                question.at = Some(parse_time(words.get(i + 1)?)?);
+                i += 2;
+            }
+            _ => return None,
+        }
+    }
+    Some(question)
+}
+
+/// `10:05` or `9:30`
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M").ok()
+}
+
+/// The departure tree of the search asked for, in the schema at `/schema/tree.json`, the station is found by it's name as in the station search
+///
+/// A question which doesn't follow the grammar is a bad request
+#[get("/ask?<q>")]
+pub fn ask(
+    q: &str,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+    cache: &State<Arc<RadarCache>>,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let question = parse_question(q).ok_or(Status::BadRequest)?;
+    let timetable = timetables.current();
+    let station_id = crate::find_station(&question.station, &timetable).ok_or(Status::NotFound)?;
+    let mode = question
+        .modes
+        .map_or_else(TransitModes::default, TransitModes);
+    let time = match question.at {
+        Some(at) => TimeFilter::Local(
+            Utc::now()
+                .with_timezone(&chrono_tz::Europe::Berlin)
+                .date_naive()
+                .and_time(at),
+        ),
+        None => TimeFilter::Now,
+    };
+    let data = crate::search_data(&timetable, station_id, question.minutes)?;
+    let (radar, url_search_params) = crate::radar_search(
+        station_id,
+        time,
+        question.minutes,
+        &mode,
+        None,
+        None,
+        None,
+        None,
+        false,
+        RadialScale::Linear,
+        &MaxLegs::default(),
+        None,
+        vec![],
+        &data,
+        &timetable.slugs,
+        &timetable.importance,
+        day_filter,
+        overlays,
+        cache,
+    )?;
+    Ok(crate::render(
+        &JsonRenderer,
+        radar.radar(),
+        url_search_params,
+    ))
+}
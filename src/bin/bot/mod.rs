@@ -0,0 +1,324 @@
+//! Answers chat messages such as `radar Alexanderplatz 40` with a summary of the radar departing now and a link to it, as the webhook of a Telegram bot or a Slack slash command
+//!
+//! The radar is sent as it's PNG card, with a link to it's share page.
+
+use std::sync::Arc;
+
+use rocket::{
+    data::{Data, ToByteUnit},
+    form::Form,
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+    response::content,
+    Build, Rocket, State,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::admin::same_token;
+use crate::radar_cache::RadarCache;
+use crate::site::SiteUrl;
+use crate::timetable::Timetables;
+use crate::{Overlays, TimeFilter, TransitModes};
+use transit_radar::draw::radar::{MaxLegs, RadialScale};
+use transit_radar::gtfs::db;
+
+/// The secrets which webhook requests are checked against, each chat app is only answered when it's secret is set
+pub struct BotTokens {
+    /// `TELEGRAM_SECRET`, given as the `secret_token` when setting the bot's webhook
+    pub telegram_secret: Option<String>,
+    /// `SLACK_TOKEN`, the verification token of the Slack app
+    pub slack_token: Option<String>,
+}
+
+/// Mounts the webhooks on the server
+pub fn mount(rocket: Rocket<Build>, tokens: BotTokens) -> Rocket<Build> {
+    rocket.manage(tokens).mount("/", routes![telegram, slack])
+}
+
+const USAGE: &str = "Send the name of a station and optionally the minutes to search for, such as \"radar Alexanderplatz 40\"";
+
+/// Longer searches take too long to answer a chat message
+const MAX_MINUTES: i64 = 120;
+
+/// A station and how long to search from it for, from a message
+#[derive(Debug, PartialEq)]
+struct Query {
+    station: String,
+    minutes: Option<i64>,
+}
+
+/// The message is the station's name after an optional `radar` command, which can be addressed to the bot as `/radar@bot_name`, and then the minutes
+fn parse_query(message: &str) -> Option<Query> {
+    let mut words: Vec<&str> = message.split_whitespace().collect();
+    let command = words.first().map(|first| {
+        first
+            .trim_start_matches('/')
+            .split('@')
+            .next()
+            .unwrap_or(first)
+    });
+    if matches!(command, Some(command) if command.eq_ignore_ascii_case("radar")) {
+        words.remove(0);
+    }
+    let minutes = words.last().and_then(|last| last.parse::<i64>().ok());
+    if let Some(minutes) = minutes {
+        if !(1..=MAX_MINUTES).contains(&minutes) {
+            return None;
+        }
+        words.pop();
+    }
+    if words.is_empty() {
+        return None;
+    }
+    Some(Query {
+        station: words.join(" "),
+        minutes,
+    })
+}
+
+/// The reply to a message
+struct Answer {
+    text: String,
+    /// The url of the radar's PNG card, when a radar was searched
+    card: Option<String>,
+}
+
+impl Answer {
+    fn text(text: String) -> Self {
+        Answer { text, card: None }
+    }
+}
+
+/// The reply to a message, which is the usage when it isn't understood
+fn answer(
+    message: &str,
+    site_url: &SiteUrl,
+    timetables: &Timetables,
+    day_filter: &db::DayFilter,
+    overlays: &Overlays,
+    cache: &RadarCache,
+) -> Answer {
+    let query = if let Some(query) = parse_query(message) {
+        query
+    } else {
+        return Answer::text(USAGE.to_owned());
+    };
+    let timetable = timetables.current();
+    let station_id = if let Some(station_id) = crate::find_station(&query.station, &timetable) {
+        station_id
+    } else {
+        return Answer::text(format!("There's no station called \"{}\"", query.station));
+    };
+    let mode = TransitModes::default();
+    let searched = crate::search_data(&timetable, station_id, query.minutes).and_then(|data| {
+        let (radar, url_search_params) = crate::radar_search(
+            station_id,
+            TimeFilter::Now,
+            query.minutes,
+            &mode,
+            None,
+            None,
+            None,
+            None,
+            false,
+            RadialScale::Linear,
+            &MaxLegs::default(),
+            None,
+            vec![],
+            &data,
+            &timetable.slugs,
+            &timetable.importance,
+            day_filter,
+            overlays,
+            cache,
+        )?;
+        let radar_url = url_search_params.to_string();
+        let separator = if radar_url.contains('?') { '&' } else { '?' };
+        Ok(Answer {
+            text: format!(
+                "{}\n{}/share{}",
+                radar.radar().summary(),
+                site_url.0,
+                radar_url
+            ),
+            card: Some(format!(
+                "{}{}{}format=png",
+                site_url.0, radar_url, separator
+            )),
+        })
+    });
+    match searched {
+        Ok(answer) => answer,
+        Err(status) if status == Status::NotFound => {
+            Answer::text("There's no timetable for today".to_owned())
+        }
+        Err(_) => Answer::text("The radar couldn't be searched, try again later".to_owned()),
+    }
+}
+
+/// Answers on a blocking thread, as the radar's search would hold up the server's async workers
+async fn answer_blocking(
+    message: String,
+    site_url: SiteUrl,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+    cache: &State<Arc<RadarCache>>,
+) -> Result<Answer, Status> {
+    let (timetables, day_filter, overlays, cache) = (
+        timetables.inner().clone(),
+        *day_filter.inner(),
+        *overlays.inner(),
+        cache.inner().clone(),
+    );
+    rocket::tokio::task::spawn_blocking(move || {
+        answer(
+            &message,
+            &site_url,
+            &timetables,
+            &day_filter,
+            &overlays,
+            &cache,
+        )
+    })
+    .await
+    .map_err(|err| {
+        eprintln!("Answering a message panicked : {}", err);
+        Status::InternalServerError
+    })
+}
+
+/// A request from Telegram, which sends the secret set with the webhook in the `X-Telegram-Bot-Api-Secret-Token` header
+struct FromTelegram;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for FromTelegram {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let secret = request
+            .rocket()
+            .state::<BotTokens>()
+            .and_then(|tokens| tokens.telegram_secret.as_deref());
+        let secret = if let Some(secret) = secret {
+            secret
+        } else {
+            return Outcome::Error((Status::NotFound, ()));
+        };
+        match request.headers().get_one("X-Telegram-Bot-Api-Secret-Token") {
+            Some(given) if same_token(given, secret) => Outcome::Success(FromTelegram),
+            _ => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// The parts of a Telegram update which are answered, other updates are ignored
+#[derive(Deserialize)]
+struct TelegramUpdate {
+    message: Option<TelegramMessage>,
+}
+
+#[derive(Deserialize)]
+struct TelegramMessage {
+    chat: TelegramChat,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TelegramChat {
+    id: i64,
+}
+
+/// Updates are a few kilobytes
+const MAX_UPDATE_KIBIBYTES: u64 = 64;
+
+/// The answer is sent by replying to the webhook with the `sendPhoto` method, or `sendMessage` without a radar, so that the bot doesn't call the Telegram API itself
+#[post("/bot/telegram", data = "<update>")]
+#[allow(clippy::too_many_arguments)]
+async fn telegram(
+    _telegram: FromTelegram,
+    update: Data<'_>,
+    site_url: SiteUrl,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+    cache: &State<Arc<RadarCache>>,
+) -> Result<content::RawJson<String>, Status> {
+    let update = update
+        .open(MAX_UPDATE_KIBIBYTES.kibibytes())
+        .into_string()
+        .await
+        .map_err(|_| Status::BadRequest)?;
+    if !update.is_complete() {
+        return Err(Status::PayloadTooLarge);
+    }
+    let update: TelegramUpdate = serde_json::from_str(&update).map_err(|_| Status::BadRequest)?;
+    let (chat, text) = match update.message {
+        Some(TelegramMessage {
+            chat,
+            text: Some(text),
+        }) => (chat, text),
+        _ => return Ok(content::RawJson("{}".to_owned())),
+    };
+    let answer = answer_blocking(text, site_url, timetables, day_filter, overlays, cache).await?;
+    let reply = match answer.card {
+        Some(card) => json!({
+            "method": "sendPhoto",
+            "chat_id": chat.id,
+            "photo": card,
+            "caption": answer.text,
+        }),
+        None => json!({
+            "method": "sendMessage",
+            "chat_id": chat.id,
+            "text": answer.text,
+        }),
+    };
+    Ok(content::RawJson(reply.to_string()))
+}
+
+/// The fields of a Slack slash command which are used, it has many more
+#[derive(FromForm)]
+struct SlashCommand {
+    token: String,
+    text: String,
+}
+
+/// The answer to a slash command is shown in the channel it was sent from, with the radar's card below it
+#[post("/bot/slack", data = "<command>")]
+async fn slack(
+    command: Form<SlashCommand>,
+    site_url: SiteUrl,
+    tokens: &State<BotTokens>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+    cache: &State<Arc<RadarCache>>,
+) -> Result<content::RawJson<String>, Status> {
+    let slack_token = tokens.slack_token.as_deref().ok_or(Status::NotFound)?;
+    if !same_token(&command.token, slack_token) {
+        return Err(Status::Unauthorized);
+    }
+    let command = command.into_inner();
+    let answer = answer_blocking(
+        command.text,
+        site_url,
+        timetables,
+        day_filter,
+        overlays,
+        cache,
+    )
+    .await?;
+    let mut reply = json!({
+        "response_type": "in_channel",
+        "text": answer.text,
+    });
+    if let Some(card) = answer.card {
+        reply["blocks"] = json!([
+            { "type": "section", "text": { "type": "plain_text", "text": answer.text } },
+            { "type": "image", "image_url": card, "alt_text": answer.text },
+        ]);
+    }
+    Ok(content::RawJson(reply.to_string()))
+}
@@ -1,35 +1,45 @@
+//! `departing_soon [station] [minutes]` lists the trips departing from each stop of a station in the next minutes, 30 by default, from the feed in `GTFS_DIR` loaded with `DAY_FILTER`
+
 use chrono::prelude::*;
 use std::path::Path;
 
-use radar_search::{search_data::*, time::*};
-use transit_radar::gtfs::db;
+use radar_search::time::*;
+use transit_radar::gtfs::{colors::RouteColors, db, ingest};
+
+/// The station the trips are listed from if none is given
+const DEFAULT_STATION_NAME: &str = "U Voltastr. (Berlin)";
 
 fn main() {
     let gtfs_dir = std::env::var("GTFS_DIR").unwrap_or_else(|_| "gtfs".to_owned());
     let gtfs_dir = Path::new(&gtfs_dir);
+    let day_filter = match std::env::var("DAY_FILTER") {
+        Ok(day_filter) => day_filter
+            .parse()
+            .expect("a day filter such as `all` or `sat`"),
+        Err(_) => db::DayFilter::All,
+    };
+    let mut args = std::env::args().skip(1);
+    let station_name = args
+        .next()
+        .unwrap_or_else(|| DEFAULT_STATION_NAME.to_owned());
+    let minutes: i64 = args
+        .next()
+        .map_or(30, |minutes| minutes.parse().expect("a number of minutes"));
 
-    let data = db::load_data(
+    let (data, _report) = db::load_data(
         gtfs_dir,
-        db::DayFilter::All,
-        std::collections::HashMap::new(),
+        day_filter,
+        &RouteColors::default(),
+        ingest::Strictness::Warn,
     )
     .unwrap();
 
     let date_time = chrono::Utc::now().with_timezone(&chrono_tz::Europe::Berlin);
     let now = Time::from_hms(date_time.hour(), date_time.minute(), date_time.second());
-    let day = match date_time.weekday() {
-        Weekday::Mon => Day::Monday,
-        Weekday::Tue => Day::Tuesday,
-        Weekday::Wed => Day::Wednesday,
-        Weekday::Thu => Day::Thursday,
-        Weekday::Fri => Day::Friday,
-        Weekday::Sat => Day::Saturday,
-        Weekday::Sun => Day::Sunday,
-    };
-    let period = Period::between(now, now + chrono::Duration::minutes(30));
-    let station = db::get_station_by_name(&data, "U Voltastr. (Berlin)").unwrap();
+    let period = Period::between(now, now + chrono::Duration::minutes(minutes));
+    let station = db::get_station_by_name(&data, &station_name).unwrap();
 
-    let services = data.services_of_day(day);
+    let services = data.services_on(date_time.date_naive());
     eprintln!("{} services", services.len());
 
     let trips = data.trips_from(station, &services, period);
@@ -0,0 +1,11 @@
+//! `lambda` is the AWS Lambda function serving the radars of the bundle at `BUNDLE`, an `s3://<bucket>/<key>` url or a path in the function's package, default `radar.bundle`
+
+use transit_radar::lambda;
+
+#[tokio::main]
+async fn main() -> Result<(), lambda_runtime::Error> {
+    let location = std::env::var("BUNDLE").unwrap_or_else(|_| "radar.bundle".to_owned());
+    let feed_bundle = lambda::load_bundle(&location).await?;
+    eprintln!("Loaded {}", location);
+    lambda::run(feed_bundle).await
+}
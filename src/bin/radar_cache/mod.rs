@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDate};
+use chrono_tz::Tz;
+use radar_search::search_data::{DirectionId, StopId, TripId};
+use transit_radar::draw::radar::{MaxLegs, Radar, RadialScale, TransitMode};
+
+use crate::timetable::Timetable;
+
+/// How long a search is kept for, it is only shared between requests for the same minute anyway
+const TTL: Duration = Duration::from_secs(60);
+/// The stations reached through a day are the same until the timetable is replaced, they are kept for a while to bound the memory
+const REACHABLE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Identifies a search, searches from now are shared within the minute
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RadarKey {
+    pub origin: StopId,
+    /// Minutes since the epoch of the departure time
+    pub departure_minute: i64,
+    pub max_minutes: i64,
+    /// Whether each of `TransitMode::ALL` is searched
+    pub modes: Vec<bool>,
+    pub direction: Option<DirectionId>,
+    /// The percent of the confidence layer, if it's drawn
+    pub confidence: Option<u8>,
+    /// The distance limit in metres, if there is one
+    pub max_metres: Option<u64>,
+    /// The minutes before the departure time of the missed trips, if they're drawn
+    pub missed_minutes: Option<i64>,
+    /// Whether the routes are counted to be drawn by how often they run
+    pub frequency: bool,
+    pub scale: RadialScale,
+    pub max_legs: MaxLegs,
+    /// The minutes before the departure time of the trips which are boarded late, if any are
+    pub grace_minutes: Option<i64>,
+    /// The trips preferred in a tie, in order
+    pub prefer_trips: Vec<TripId>,
+}
+
+impl RadarKey {
+    pub fn modes(modes: &std::collections::HashSet<TransitMode>) -> Vec<bool> {
+        TransitMode::ALL
+            .iter()
+            .map(|mode| modes.contains(mode))
+            .collect()
+    }
+}
+
+self_cell::self_cell! {
+    /// A radar kept with the timetable it was searched on and borrows
+    struct TimetableRadar {
+        owner: Arc<Timetable>,
+        #[covariant]
+        dependent: Radar,
+    }
+}
+
+/// A search kept with the timetable it was made on
+struct Searched {
+    searched_at: Instant,
+    radar: TimetableRadar,
+}
+
+/// A radar from the cache, which keeps the timetable it was searched on
+pub struct CachedRadar {
+    searched: Arc<Searched>,
+}
+
+impl CachedRadar {
+    pub fn radar(&self) -> &Radar<'_> {
+        self.searched.radar.borrow_dependent()
+    }
+}
+
+/// Identifies the searches through a day, which are shared by any time of the day
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReachableKey {
+    pub origin: StopId,
+    pub day: NaiveDate,
+    pub max_minutes: i64,
+    /// Whether each of `TransitMode::ALL` is searched
+    pub modes: Vec<bool>,
+    pub direction: Option<DirectionId>,
+    /// The distance limit in metres, if there is one
+    pub max_metres: Option<u64>,
+}
+
+/// The number of stations reached departing at each interval through a day
+pub type Reachable = Vec<(DateTime<Tz>, usize)>;
+
+/// The searches through a day kept with the timetable they were made on
+struct Counted {
+    counted_at: Instant,
+    reachable: Arc<Reachable>,
+    timetable: Arc<Timetable>,
+}
+
+/// Recent searches, so that the SVG and JSON of the same query share one search
+#[derive(Default)]
+pub struct RadarCache {
+    radars: Mutex<HashMap<RadarKey, Arc<Searched>>>,
+    reachable: Mutex<HashMap<ReachableKey, Counted>>,
+}
+
+impl RadarCache {
+    /// The search can only borrow the timetable it's given, and what lives as long as the server
+    pub fn get_or_search(
+        &self,
+        key: RadarKey,
+        timetable: &Arc<Timetable>,
+        search: impl for<'a> FnOnce(&'a Timetable) -> Radar<'a>,
+    ) -> CachedRadar {
+        let cached = self
+            .radars
+            .lock()
+            .unwrap()
+            .get(&key)
+            // searches of a timetable which was replaced are of other ids
+            .filter(|searched| Arc::ptr_eq(searched.radar.borrow_owner(), timetable))
+            .cloned();
+        let searched = if let Some(searched) = cached {
+            searched
+        } else {
+            // search without holding the lock, rarely the same search will run twice
+            let searched = Arc::new(Searched {
+                searched_at: Instant::now(),
+                radar: TimetableRadar::new(timetable.clone(), |timetable| search(timetable)),
+            });
+            let mut radars = self.radars.lock().unwrap();
+            radars.retain(|_key, cached| {
+                searched.searched_at.duration_since(cached.searched_at) < TTL
+            });
+            radars.insert(key, searched.clone());
+            searched
+        };
+        CachedRadar { searched }
+    }
+
+    /// The stations reached through the day, unless they were counted on the timetable recently
+    pub fn get_or_count(
+        &self,
+        key: ReachableKey,
+        timetable: &Arc<Timetable>,
+        count: impl FnOnce() -> Reachable,
+    ) -> Arc<Reachable> {
+        let cached = self
+            .reachable
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|counted| Arc::ptr_eq(&counted.timetable, timetable))
+            .map(|counted| counted.reachable.clone());
+        if let Some(reachable) = cached {
+            return reachable;
+        }
+        // counted without holding the lock, as each is a search at every interval through the day
+        let counted = Counted {
+            counted_at: Instant::now(),
+            reachable: Arc::new(count()),
+            timetable: timetable.clone(),
+        };
+        let reachable = counted.reachable.clone();
+        let mut cached = self.reachable.lock().unwrap();
+        cached.retain(|_key, cached| {
+            counted.counted_at.duration_since(cached.counted_at) < REACHABLE_TTL
+        });
+        cached.insert(key, counted);
+        reachable
+    }
+
+    pub fn len(&self) -> usize {
+        self.radars.lock().unwrap().len()
+    }
+
+    pub fn reachable_len(&self) -> usize {
+        self.reachable.lock().unwrap().len()
+    }
+
+    /// Forgets every search, for when the timetable they were made on is replaced
+    pub fn clear(&self) {
+        self.radars.lock().unwrap().clear();
+        self.reachable.lock().unwrap().clear();
+    }
+}
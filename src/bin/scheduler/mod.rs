@@ -0,0 +1,560 @@
+//! Jobs run by the server at times of the day, which render ahead what's slow to render on request and read in the stop times of the busiest stations before they are searched from
+//!
+//! They are scheduled by `SCHEDULE`, such as `sitemap@03:00,previews@*/15,popular@04:30`, each at a time of day in Berlin or every so many minutes from midnight. `/admin/jobs` shows when each job ran and how it went, and an admin runs one straight away with `POST /admin/jobs/<job>`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::{Europe::Berlin, Tz};
+use radar_search::search_data::StopId;
+use rocket::{
+    fairing::AdHoc,
+    http::{ContentType, Header, Status},
+    request::{FromRequest, Outcome, Request},
+    Build, Orbit, Rocket, State,
+};
+use serde::Serialize;
+use transit_radar::draw::{
+    radar::{MaxLegs, RadialScale, DEFAULT_MAX_DURATION_MINS},
+    render::Format,
+};
+use transit_radar::gtfs::db;
+
+use crate::admin::Admin;
+use crate::radar_cache::RadarCache;
+use crate::site::{self, PublicUrl, SiteUrl};
+use crate::station_name_search::stations_by_importance;
+use crate::timetable::Timetables;
+use crate::{Overlays, RenderedRadar, TimeFilter, TransitModes};
+
+/// The number of stations which previews are rendered for, the busiest ones
+const PREVIEW_STATIONS: usize = 100;
+
+/// Previews older than this aren't served, the radar departing now has moved on from them
+const PREVIEW_MAX_AGE_MINS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Job {
+    /// Renders `/sitemap.xml`, which needs `PUBLIC_URL` for it's urls
+    Sitemap,
+    /// Renders the PNG cards of the radars departing now from the busiest stations, which link previews of their share pages show
+    Previews,
+    /// Reads in the stop times around the busiest stations, when they are read in lazily
+    Popular,
+}
+
+impl Job {
+    const ALL: [Job; 3] = [Job::Sitemap, Job::Previews, Job::Popular];
+
+    fn name(self) -> &'static str {
+        match self {
+            Job::Sitemap => "sitemap",
+            Job::Previews => "previews",
+            Job::Popular => "popular",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Job> {
+        Job::ALL.iter().copied().find(|job| job.name() == name)
+    }
+
+    fn index(self) -> usize {
+        Job::ALL
+            .iter()
+            .position(|job| *job == self)
+            .expect("every job to be in ALL")
+    }
+
+    /// What the job did, or why it failed
+    fn run(self, context: &JobContext) -> Result<String, String> {
+        match self {
+            Job::Sitemap => render_sitemap(context),
+            Job::Previews => render_previews(context),
+            Job::Popular => read_in_popular(context),
+        }
+    }
+}
+
+/// When a job runs, in Berlin's time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// `03:00`, each day at the time
+    Daily(NaiveTime),
+    /// `*/15`, every so many minutes from midnight
+    Every(u32),
+}
+
+impl Schedule {
+    /// The first time after `now` the job is due
+    fn next_after(self, now: DateTime<Tz>) -> DateTime<Tz> {
+        let local = now.naive_local();
+        let midnight = local.date().and_time(NaiveTime::MIN);
+        let next = match self {
+            Schedule::Daily(time) => {
+                let today = local.date().and_time(time);
+                if today > local {
+                    today
+                } else {
+                    today + Duration::days(1)
+                }
+            }
+            Schedule::Every(minutes) => {
+                let minutes = i64::from(minutes);
+                let runs_today = (local - midnight).num_minutes() / minutes + 1;
+                (midnight + Duration::minutes(runs_today * minutes))
+                    .min(midnight + Duration::days(1))
+            }
+        };
+        in_berlin(next)
+    }
+
+    fn parse(when: &str) -> Option<Schedule> {
+        if let Some(minutes) = when.strip_prefix("*/") {
+            let minutes: u32 = minutes.parse().ok()?;
+            (1..=24 * 60)
+                .contains(&minutes)
+                .then_some(Schedule::Every(minutes))
+        } else {
+            NaiveTime::parse_from_str(when, "%H:%M")
+                .ok()
+                .map(Schedule::Daily)
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schedule::Daily(time) => write!(f, "{}", time.format("%H:%M")),
+            Schedule::Every(minutes) => write!(f, "*/{}", minutes),
+        }
+    }
+}
+
+/// A time skipped when the clocks go forward is run an hour later, a time which happens twice is run the first time
+fn in_berlin(local: NaiveDateTime) -> DateTime<Tz> {
+    Berlin
+        .from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            Berlin
+                .from_local_datetime(&(local + Duration::hours(1)))
+                .earliest()
+        })
+        .expect("an hour later to be after the clocks went forward")
+}
+
+/// The jobs in `SCHEDULE`, such as `sitemap@03:00,previews@*/15`
+pub fn parse_schedule(schedule: &str) -> Result<Vec<(Job, Schedule)>, String> {
+    schedule
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (job, when) = entry
+                .split_once('@')
+                .ok_or_else(|| format!("{} isn't <job>@<HH:MM> or <job>@*/<minutes>", entry))?;
+            let job = Job::from_name(job).ok_or_else(|| format!("there's no {} job", job))?;
+            let when = Schedule::parse(when).ok_or_else(|| format!("{} isn't a time", when))?;
+            Ok((job, when))
+        })
+        .collect()
+}
+
+/// How a job has run, as shown at `/admin/jobs`
+#[derive(Serialize, Clone)]
+struct JobStatus {
+    job: Job,
+    /// As in `SCHEDULE`, jobs which aren't scheduled are only run by an admin
+    schedule: Option<String>,
+    next_run: Option<String>,
+    running: bool,
+    runs: u32,
+    last_started: Option<String>,
+    last_seconds: Option<f64>,
+    /// What the last run did, or why it failed
+    last_outcome: Option<String>,
+    last_failed: bool,
+}
+
+/// Runs the jobs when they are due and keeps how they went
+pub struct Scheduler {
+    schedule: Vec<(Job, Schedule)>,
+    /// In the order of `Job::ALL`
+    statuses: Mutex<Vec<JobStatus>>,
+}
+
+impl Scheduler {
+    fn new(schedule: Vec<(Job, Schedule)>) -> Self {
+        let statuses = Job::ALL
+            .iter()
+            .map(|&job| JobStatus {
+                job,
+                schedule: Some(
+                    schedule
+                        .iter()
+                        .filter(|(scheduled, _)| *scheduled == job)
+                        .map(|(_, when)| when.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                )
+                .filter(|when| !when.is_empty()),
+                next_run: None,
+                running: false,
+                runs: 0,
+                last_started: None,
+                last_seconds: None,
+                last_outcome: None,
+                last_failed: false,
+            })
+            .collect();
+        Scheduler {
+            schedule,
+            statuses: Mutex::new(statuses),
+        }
+    }
+
+    /// Runs the job and returns how it went, unless it's running already
+    fn run(&self, job: Job, context: &JobContext) -> Option<JobStatus> {
+        {
+            let mut statuses = self.statuses.lock().unwrap();
+            let status = &mut statuses[job.index()];
+            if status.running {
+                return None;
+            }
+            status.running = true;
+            status.last_started = Some(Utc::now().to_rfc3339());
+        }
+        let started = Instant::now();
+        let outcome = job.run(context);
+        let seconds = started.elapsed().as_secs_f64();
+        match &outcome {
+            Ok(done) => eprintln!("The {} job {} in {:.1}s", job.name(), done, seconds),
+            Err(err) => eprintln!("The {} job failed : {}", job.name(), err),
+        }
+        let mut statuses = self.statuses.lock().unwrap();
+        let status = &mut statuses[job.index()];
+        status.running = false;
+        status.runs += 1;
+        status.last_seconds = Some(seconds);
+        status.last_failed = outcome.is_err();
+        status.last_outcome = Some(outcome.unwrap_or_else(|err| err));
+        Some(status.clone())
+    }
+
+    /// The earliest of each job's next runs, a job can be scheduled more than once
+    fn set_next_runs(&self, next_runs: &[(Job, Schedule, DateTime<Tz>)]) {
+        let mut statuses = self.statuses.lock().unwrap();
+        for status in statuses.iter_mut() {
+            status.next_run = next_runs
+                .iter()
+                .filter(|(job, _, _)| *job == status.job)
+                .map(|(_, _, at)| *at)
+                .min()
+                .map(|at| at.to_rfc3339());
+        }
+    }
+
+    /// Runs each job when it's due, one at a time so that they don't slow the searches down more than one job does
+    async fn run_on_schedule(self: Arc<Self>, context: JobContext) {
+        let now = Utc::now().with_timezone(&Berlin);
+        let mut next_runs: Vec<(Job, Schedule, DateTime<Tz>)> = self
+            .schedule
+            .iter()
+            .map(|&(job, when)| (job, when, when.next_after(now)))
+            .collect();
+        loop {
+            self.set_next_runs(&next_runs);
+            let next = if let Some(next) = next_runs.iter().map(|(_, _, at)| *at).min() {
+                next
+            } else {
+                return;
+            };
+            let wait = (next.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or_default();
+            rocket::tokio::time::sleep(wait).await;
+            for (job, when, at) in &mut next_runs {
+                if *at > Utc::now() {
+                    continue;
+                }
+                let (scheduler, context, job) = (self.clone(), context.clone(), *job);
+                if let Err(err) =
+                    rocket::tokio::task::spawn_blocking(move || scheduler.run(job, &context)).await
+                {
+                    eprintln!("The {} job panicked : {}", job.name(), err);
+                }
+                *at = when.next_after(Utc::now().with_timezone(&Berlin));
+            }
+        }
+    }
+}
+
+/// Mounts the jobs' endpoints on the server, and runs the scheduled jobs once it's serving
+pub fn mount(rocket: Rocket<Build>, schedule: Vec<(Job, Schedule)>) -> Rocket<Build> {
+    let scheduler = Arc::new(Scheduler::new(schedule));
+    let scheduled = scheduler.clone();
+    rocket
+        .manage(scheduler)
+        .manage(Arc::new(Prerendered::default()))
+        .mount("/", routes![jobs, run_job])
+        .attach(AdHoc::on_liftoff("Scheduled jobs", |rocket| {
+            Box::pin(async move {
+                if scheduled.schedule.is_empty() {
+                    return;
+                }
+                let context = JobContext::of(rocket).expect("the server's state to be managed");
+                rocket::tokio::spawn(scheduled.run_on_schedule(context));
+            })
+        }))
+}
+
+/// The server's state which jobs use, owned so that they can run outside of a request
+#[derive(Clone)]
+pub struct JobContext {
+    timetables: Arc<Timetables>,
+    day_filter: db::DayFilter,
+    overlays: Overlays,
+    public_url: Option<String>,
+    prerendered: Arc<Prerendered>,
+}
+
+impl JobContext {
+    fn of(rocket: &Rocket<Orbit>) -> Option<Self> {
+        Some(JobContext {
+            timetables: rocket.state::<Arc<Timetables>>()?.clone(),
+            day_filter: *rocket.state::<db::DayFilter>()?,
+            overlays: *rocket.state::<Overlays>()?,
+            public_url: rocket
+                .state::<PublicUrl>()
+                .and_then(|public_url| public_url.0.clone()),
+            prerendered: rocket.state::<Arc<Prerendered>>()?.clone(),
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for JobContext {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match JobContext::of(request.rocket()) {
+            Some(context) => Outcome::Success(context),
+            None => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// What the jobs rendered ahead of it being asked for
+#[derive(Default)]
+pub struct Prerendered {
+    sitemap: RwLock<Option<PrerenderedSitemap>>,
+    previews: RwLock<HashMap<StopId, Preview>>,
+}
+
+struct PrerenderedSitemap {
+    site_url: String,
+    xml: String,
+}
+
+/// The PNG card of the radar departing now from a station
+struct Preview {
+    rendered_at: DateTime<Utc>,
+    expires_at: DateTime<Tz>,
+    card: Vec<u8>,
+}
+
+impl Prerendered {
+    /// The sitemap, when it was rendered for the same site url
+    pub fn sitemap(&self, site_url: &SiteUrl) -> Option<String> {
+        self.sitemap
+            .read()
+            .unwrap()
+            .as_ref()
+            .filter(|sitemap| sitemap.site_url == site_url.0)
+            .map(|sitemap| sitemap.xml.clone())
+    }
+
+    /// The card of the radar departing now from the station, when it was rendered recently
+    pub fn preview(&self, station_id: StopId) -> Option<RenderedRadar> {
+        let previews = self.previews.read().unwrap();
+        let preview = previews.get(&station_id)?;
+        if Utc::now() - preview.rendered_at > Duration::minutes(PREVIEW_MAX_AGE_MINS) {
+            return None;
+        }
+        Some(RenderedRadar {
+            body: (ContentType::PNG, preview.card.clone()),
+            expires: Header::new("X-Radar-Expires", preview.expires_at.to_rfc3339()),
+        })
+    }
+
+    /// Forgets everything rendered, which was of a replaced timetable
+    pub fn clear(&self) {
+        *self.sitemap.write().unwrap() = None;
+        self.previews.write().unwrap().clear();
+    }
+}
+
+fn render_sitemap(context: &JobContext) -> Result<String, String> {
+    let site_url = context
+        .public_url
+        .as_deref()
+        .ok_or("the sitemap's urls need PUBLIC_URL")?;
+    let site_url = SiteUrl(site_url.trim_end_matches('/').to_owned());
+    let xml = site::sitemap_xml(&site_url, &context.timetables.current());
+    let done = format!("rendered {} bytes", xml.len());
+    *context.prerendered.sitemap.write().unwrap() = Some(PrerenderedSitemap {
+        site_url: site_url.0,
+        xml,
+    });
+    Ok(done)
+}
+
+fn render_previews(context: &JobContext) -> Result<String, String> {
+    let timetable = context.timetables.current();
+    // the radars aren't searched again, so they aren't kept in the server's cache
+    let cache = RadarCache::default();
+    let mode = TransitModes::default();
+    let renderer = Format::Png.renderer(false);
+    let stations: Vec<StopId> = stations_by_importance(timetable.data(), &timetable.importance)
+        .take(PREVIEW_STATIONS)
+        .map(|station| station.stop_id)
+        .collect();
+    let mut previews = HashMap::new();
+    for &station_id in &stations {
+        let preview = crate::search_data(&timetable, station_id, None).and_then(|data| {
+            let (radar, url_search_params) = crate::radar_search(
+                station_id,
+                TimeFilter::Now,
+                None,
+                &mode,
+                None,
+                None,
+                None,
+                None,
+                false,
+                RadialScale::Linear,
+                &MaxLegs::default(),
+                None,
+                vec![],
+                &data,
+                &timetable.slugs,
+                &timetable.importance,
+                &context.day_filter,
+                &context.overlays,
+                &cache,
+            )?;
+            Ok(Preview {
+                rendered_at: Utc::now(),
+                expires_at: radar.radar().expires_at(),
+                card: crate::render(&*renderer, radar.radar(), url_search_params).1,
+            })
+        });
+        // a station without a timetable today has no radar to preview
+        if let Ok(preview) = preview {
+            previews.insert(station_id, preview);
+        }
+    }
+    let done = format!("rendered {} of {} stations", previews.len(), stations.len());
+    *context.prerendered.previews.write().unwrap() = previews;
+    Ok(done)
+}
+
+fn read_in_popular(context: &JobContext) -> Result<String, String> {
+    let timetable = context.timetables.current();
+    let lazy = if let Some(lazy) = timetable.lazy() {
+        lazy
+    } else {
+        return Ok("had nothing to do, all the stop times are loaded".to_owned());
+    };
+    let stations: Vec<StopId> = stations_by_importance(timetable.data(), &timetable.importance)
+        .take(lazy.capacity())
+        .map(|station| station.stop_id)
+        .collect();
+    // the busiest last, so that they are the last to make way for other stations
+    for &station_id in stations.iter().rev() {
+        lazy.around(station_id, Duration::minutes(DEFAULT_MAX_DURATION_MINS))
+            .map_err(|err| {
+                format!(
+                    "reading in the stop times around {} failed : {}",
+                    station_id, err
+                )
+            })?;
+    }
+    Ok(format!(
+        "read in the stop times around {} stations",
+        stations.len()
+    ))
+}
+
+/// How each job has run
+#[get("/admin/jobs")]
+pub fn jobs(scheduler: &State<Arc<Scheduler>>) -> (ContentType, String) {
+    let statuses = scheduler.statuses.lock().unwrap();
+    (
+        ContentType::JSON,
+        serde_json::to_string(&*statuses).unwrap(),
+    )
+}
+
+/// Runs the job now and returns how it went, it's a conflict when it's running already
+#[post("/admin/jobs/<job>")]
+pub async fn run_job(
+    _admin: Admin,
+    job: &str,
+    context: JobContext,
+    scheduler: &State<Arc<Scheduler>>,
+) -> Result<(ContentType, String), (Status, String)> {
+    let job = Job::from_name(job).ok_or((Status::NotFound, format!("there's no {} job", job)))?;
+    let scheduler = scheduler.inner().clone();
+    let status = rocket::tokio::task::spawn_blocking(move || scheduler.run(job, &context))
+        .await
+        .map_err(|err| (Status::InternalServerError, err.to_string()))?
+        .ok_or((
+            Status::Conflict,
+            format!("the {} job is running", job.name()),
+        ))?;
+    Ok((ContentType::JSON, serde_json::to_string(&status).unwrap()))
+}
+
+#[test]
+fn schedules_are_parsed() {
+    assert_eq!(
+        parse_schedule("sitemap@03:00, previews@*/15,").unwrap(),
+        vec![
+            (
+                Job::Sitemap,
+                Schedule::Daily(NaiveTime::from_hms_opt(3, 0, 0).unwrap())
+            ),
+            (Job::Previews, Schedule::Every(15)),
+        ]
+    );
+    assert!(parse_schedule("sitemap").is_err());
+    assert!(parse_schedule("backup@03:00").is_err());
+    assert!(parse_schedule("popular@25:00").is_err());
+    assert!(parse_schedule("popular@*/0").is_err());
+}
+
+#[test]
+fn jobs_are_due_after_now() {
+    let at = |day: u32, hour: u32, minute: u32| {
+        Berlin
+            .with_ymd_and_hms(2024, 3, day, hour, minute, 0)
+            .unwrap()
+    };
+    let three = Schedule::Daily(NaiveTime::from_hms_opt(3, 0, 0).unwrap());
+    assert_eq!(three.next_after(at(15, 2, 59)), at(15, 3, 0));
+    assert_eq!(three.next_after(at(15, 3, 0)), at(16, 3, 0));
+    let quarterly = Schedule::Every(15);
+    assert_eq!(quarterly.next_after(at(15, 10, 7)), at(15, 10, 15));
+    assert_eq!(quarterly.next_after(at(15, 10, 15)), at(15, 10, 30));
+    assert_eq!(quarterly.next_after(at(15, 23, 50)), at(16, 0, 0));
+    // every 7 minutes starts again at midnight
+    assert_eq!(Schedule::Every(7).next_after(at(15, 23, 58)), at(16, 0, 0));
+    // 02:30 is skipped on the 31st of March when the clocks go forward
+    let half_two = Schedule::Daily(NaiveTime::from_hms_opt(2, 30, 0).unwrap());
+    assert_eq!(half_two.next_after(at(30, 12, 0)), at(31, 3, 30));
+}
@@ -1,28 +1,86 @@
+const RESULT_LIMIT = 20;
+
+// lower case without accents, so that eg. "schoneberg" finds "Schöneberg"
+function fold(text) {
+    return text.normalize('NFD').replace(/[\u0300-\u036f]/g, '').toLowerCase();
+}
+
+// A linear scan of the station list, each word of the term is the start of a word of the name as with the server's suggester
+function searchStations(stations, term) {
+    const termWords = fold(term).split(/\s+/).filter((word) => word.length > 0);
+    if (termWords.length === 0) {
+        return [];
+    }
+    const results = [];
+    for (const station of stations) {
+        if (termWords.every((termWord) => station.words.some((word) => word.startsWith(termWord)))) {
+            results.push(station);
+            if (results.length === RESULT_LIMIT) {
+                break;
+            }
+        }
+    }
+    return results;
+}
+
+function renderResults(stations) {
+    const main = document.createElement('main');
+    for (const station of stations) {
+        const link = document.createElement('a');
+        link.href = `/depart-from/${station.slug || station.stop_id}/now`;
+        link.textContent = station.name;
+        main.appendChild(link);
+    }
+    return main.outerHTML;
+}
+
 function setupAutosearch() {
     const searchbox = document.getElementsByName('q')[0];
 
+    // the station list is scanned once it's loaded, the server is asked too when that doesn't fill a page of results
+    let stations = null;
+    fetch(new Request('./stations.json')).then((resp) => resp.json()).then((list) => {
+        stations = list.map((station) => ({...station, words: fold(station.name).split(/\s+/)}));
+    }, (reason) => {
+        console.error('station list fetch rejected', reason);
+    });
+
     const main = document.getElementsByTagName('main')[0];
     history.replaceState({term: searchbox.value, results: main.outerHTML}, '');
 
     let isFetching = false;
     let hasPushedState = false;
     
-    searchbox.oninput = (event) => {
-        if (!isFetching) {
+    const showResults = (term, query, results) => {
+        if (hasPushedState) {
+            history.replaceState({term: term, results: results}, '', '.?' + query.toString());
+        } else {
+            history.pushState({term: term, results: results}, '', '.?' + query.toString());
+            hasPushedState = true;
+        }
+        const main = document.getElementsByTagName('main')[0];
+        main.outerHTML = results
+    };
+
+    searchbox.oninput = () => {
+        const term = searchbox.value;
+        const query = new URLSearchParams({'q': term});
+        const localResults = stations ? searchStations(stations, term) : [];
+        if (localResults.length > 0) {
+            showResults(term, query, renderResults(localResults));
+        }
+        // the list hasn't got the stations' aliases, which only the server's suggester finds
+        if (localResults.length < RESULT_LIMIT && !isFetching) {
             isFetching = true;
-            const term = event.target.value;
-            const query = new URLSearchParams({'q': term});
             const req = new Request('./auto?' + query.toString());
             fetch(req).then((resp) => resp.text()).then((results) => {
                 isFetching = false;
-                if (hasPushedState) {
-                    history.replaceState({term: term, results: results}, '', '.?' + query.toString());
+                if (searchbox.value === term) {
+                    showResults(term, query, results);
                 } else {
-                    history.pushState({term: term, results: results}, '', '.?' + query.toString());
-                    hasPushedState = true;
+                    // the term changed while it was fetched, search again for the latest one
+                    searchbox.oninput();
                 }
-                const main = document.getElementsByTagName('main')[0];
-                main.outerHTML = results
             }, (reason) => {
                 isFetching = false;
                 console.error('fetch rejected', reason);
@@ -1,19 +1,22 @@
+//! `search [station] [minutes]` runs the radar's search from a station departing now for the minutes, 30 by default, on the feed in `GTFS_DIR` loaded with `DAY_FILTER`, for profiling the planner
+
 use chrono::prelude::*;
 use std::path::Path;
 
 use radar_search::journey_graph;
 use radar_search::{search_data::*, time::*};
-use transit_radar::gtfs::db;
+use transit_radar::draw::radar::TransitMode;
+use transit_radar::gtfs::{colors::RouteColors, db, ingest};
 
 fn lookup(
     data: &GTFSData,
     station_name: String,
     options: RadarOptions,
-    day: Day,
+    date: NaiveDate,
     period: Period,
 ) -> Result<(), db::SearchError> {
     let station = db::get_station_by_name(data, &station_name)?;
-    produce_tree_json(data, station.stop_id, day, period, &options);
+    produce_tree_json(data, station.stop_id, date, period, &options);
     Ok(())
 }
 
@@ -21,29 +24,25 @@ fn lookup(
 fn produce_tree_json(
     data: &GTFSData,
     station: StopId,
-    day: Day,
+    date: NaiveDate,
     period: Period,
     options: &RadarOptions,
 ) {
-    let mut plotter = journey_graph::Plotter::new(day, period, data);
+    let mut plotter = journey_graph::Plotter::new(date, period, data);
     let origin = data.get_stop(station).unwrap();
     plotter.add_origin_station(origin);
-    if options.ubahn {
-        plotter.add_route_type(RouteType::UrbanRailway);
-    }
-    if options.sbahn {
-        plotter.add_route_type(RouteType::SuburbanRailway);
-    }
-    if options.bus {
-        plotter.add_route_type(RouteType::Bus);
-        plotter.add_route_type(RouteType::BusService);
-    }
-    if options.tram {
-        plotter.add_route_type(RouteType::TramService);
-    }
-    if options.regio {
-        plotter.add_route_type(RouteType::Rail);
-        plotter.add_route_type(RouteType::RailwayService);
+    for (enabled, mode) in &[
+        (options.ubahn, TransitMode::UBahn),
+        (options.sbahn, TransitMode::SBahn),
+        (options.bus, TransitMode::Bus),
+        (options.tram, TransitMode::Tram),
+        (options.regio, TransitMode::Regional),
+    ] {
+        if *enabled {
+            for route_type in mode.route_types() {
+                plotter.add_route_type(*route_type);
+            }
+        }
     }
 
     for item in plotter {
@@ -52,6 +51,8 @@ fn produce_tree_json(
                 stop,
                 earliest_arrival,
                 name_trunk_length,
+                legs,
+                transfers_so_far,
             } => {}
             journey_graph::Item::Transfer {
                 departure_time,
@@ -68,6 +69,7 @@ fn produce_tree_json(
                 route_name,
                 route_type,
                 route_color,
+                direction_id,
             } => {}
             journey_graph::Item::ConnectionToTrip {
                 departure_time,
@@ -78,26 +80,18 @@ fn produce_tree_json(
                 route_name,
                 route_type,
                 route_color,
+                direction_id,
             } => {}
         }
     }
 }
 
-fn search(name: String, options: RadarOptions, data: &GTFSData) {
+fn search(name: String, minutes: i64, options: RadarOptions, data: &GTFSData) {
     let date_time = chrono::Utc::now().with_timezone(&chrono_tz::Europe::Berlin);
     let now = Time::from_hms(date_time.hour(), date_time.minute(), date_time.second());
-    let day = match date_time.weekday() {
-        Weekday::Mon => Day::Monday,
-        Weekday::Tue => Day::Tuesday,
-        Weekday::Wed => Day::Wednesday,
-        Weekday::Thu => Day::Thursday,
-        Weekday::Fri => Day::Friday,
-        Weekday::Sat => Day::Saturday,
-        Weekday::Sun => Day::Sunday,
-    };
-    let period = Period::between(now, now + chrono::Duration::minutes(30));
+    let period = Period::between(now, now + chrono::Duration::minutes(minutes));
 
-    lookup(data, name, options, day, period).unwrap();
+    lookup(data, name, options, date_time.date_naive(), period).unwrap();
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -109,19 +103,37 @@ pub struct RadarOptions {
     pub tram: bool,
 }
 
+/// The station searched from if none is given
+const DEFAULT_STATION_NAME: &str = "U Voltastr. (Berlin)";
+
 fn main() {
     let gtfs_dir = std::env::var("GTFS_DIR").unwrap_or_else(|_| "gtfs".to_owned());
     let gtfs_dir = Path::new(&gtfs_dir);
+    let day_filter = match std::env::var("DAY_FILTER") {
+        Ok(day_filter) => day_filter
+            .parse()
+            .expect("a day filter such as `all` or `sat`"),
+        Err(_) => db::DayFilter::All,
+    };
+    let mut args = std::env::args().skip(1);
+    let station_name = args
+        .next()
+        .unwrap_or_else(|| DEFAULT_STATION_NAME.to_owned());
+    let minutes: i64 = args
+        .next()
+        .map_or(30, |minutes| minutes.parse().expect("a number of minutes"));
 
-    let data = db::load_data(
+    let (data, _report) = db::load_data(
         gtfs_dir,
-        db::DayFilter::All,
-        std::collections::HashMap::new(),
+        day_filter,
+        &RouteColors::default(),
+        ingest::Strictness::Warn,
     )
     .unwrap();
 
     search(
-        "U Voltastr. (Berlin)".to_owned(),
+        station_name,
+        minutes,
         RadarOptions {
             ubahn: true,
             sbahn: false,
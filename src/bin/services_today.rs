@@ -1,33 +1,23 @@
-use chrono::prelude::*;
 use std::path::Path;
 
-use radar_search::search_data::*;
-use transit_radar::gtfs::db;
+use transit_radar::gtfs::{colors::RouteColors, db, ingest};
 
 fn main() {
     let gtfs_dir = std::env::var("GTFS_DIR").unwrap_or_else(|_| "gtfs".to_owned());
     let gtfs_dir = Path::new(&gtfs_dir);
 
-    let data = db::load_data(
+    let (data, _report) = db::load_data(
         gtfs_dir,
         db::DayFilter::All,
-        std::collections::HashMap::new(),
+        &RouteColors::default(),
+        ingest::Strictness::Warn,
     )
     .unwrap();
 
     let date_time = chrono::Utc::now().with_timezone(&chrono_tz::Europe::Berlin);
-    let day = match date_time.weekday() {
-        Weekday::Mon => Day::Monday,
-        Weekday::Tue => Day::Tuesday,
-        Weekday::Wed => Day::Wednesday,
-        Weekday::Thu => Day::Thursday,
-        Weekday::Fri => Day::Friday,
-        Weekday::Sat => Day::Saturday,
-        Weekday::Sun => Day::Sunday,
-    };
     // let station = db::get_station_by_name(&data, &station_name).unwrap();
 
-    let services = data.services_of_day(day);
+    let services = data.services_on(date_time.date_naive());
     eprintln!("{} services", services.len());
 
     let mut trips: Vec<_> = data
@@ -0,0 +1,129 @@
+//! Pages for search engines and for the previews shown where a radar's link is shared
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use radar_search::search_data::Stop;
+use rocket::{
+    http::{ContentType, Status},
+    request::{FromRequest, Outcome, Request},
+    State,
+};
+use transit_radar::{
+    draw::{
+        radar::{UrlSearchParams, CARD_HEIGHT, CARD_WIDTH},
+        xml::Escaped,
+    },
+    write_xml,
+};
+
+use crate::scheduler::Prerendered;
+use crate::station_name_search;
+use crate::timetable::{Timetable, Timetables};
+
+/// The scheme and host the site is served at from `PUBLIC_URL`, such as `https://radar.example`
+pub struct PublicUrl(pub Option<String>);
+
+/// The start of the absolute urls that sitemaps and previews need, without a `PUBLIC_URL` it's taken from the request's host
+pub struct SiteUrl(pub String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SiteUrl {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let public_url = request
+            .rocket()
+            .state::<PublicUrl>()
+            .and_then(|public_url| public_url.0.as_deref());
+        if let Some(public_url) = public_url {
+            Outcome::Success(SiteUrl(public_url.trim_end_matches('/').to_owned()))
+        } else if let Some(host) = request.headers().get_one("Host") {
+            Outcome::Success(SiteUrl(format!("http://{}", host)))
+        } else {
+            Outcome::Error((Status::BadRequest, ()))
+        }
+    }
+}
+
+/// The number of stations in the sitemap, the busiest ones
+const SITEMAP_STATIONS: usize = 1000;
+
+/// Crawlers are kept to the pages, each radar is a search
+#[get("/robots.txt")]
+pub fn robots(site_url: SiteUrl) -> String {
+    format!(
+        "User-agent: *\n\
+        Disallow: /admin/\n\
+        Disallow: /auto\n\
+        Disallow: /data/\n\
+        Disallow: /depart-from/\n\
+        Allow: /\n\
+        \n\
+        Sitemap: {}/sitemap.xml\n",
+        site_url.0
+    )
+}
+
+/// The station search and the share pages of radars from the busiest stations, as the sitemap job rendered it when it has
+#[get("/sitemap.xml")]
+pub fn sitemap(
+    site_url: SiteUrl,
+    timetables: &State<Arc<Timetables>>,
+    prerendered: &State<Arc<Prerendered>>,
+) -> (ContentType, String) {
+    let xml = prerendered
+        .sitemap(&site_url)
+        .unwrap_or_else(|| sitemap_xml(&site_url, &timetables.current()));
+    (ContentType::XML, xml)
+}
+
+/// The sitemap of the timetable, served at `/sitemap.xml`
+pub fn sitemap_xml(site_url: &SiteUrl, timetable: &Timetable) -> String {
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    write_xml!(xml, <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">).unwrap();
+    write_xml!(xml, <url><loc>{format!("{}/", site_url.0)}</loc></url>).unwrap();
+    for station in
+        station_name_search::stations_by_importance(timetable.data(), &timetable.importance)
+            .take(SITEMAP_STATIONS)
+    {
+        let radar = UrlSearchParams::new(station.stop_id, &timetable.slugs);
+        write_xml!(xml, <url><loc>{format!("{}/share{}", site_url.0, radar)}</loc></url>).unwrap();
+    }
+    write_xml!(xml, </urlset>).unwrap();
+    xml
+}
+
+/// A page showing a radar, with the OpenGraph and Twitter card tags that link previews are made from, the preview's image is the radar's PNG card
+pub fn share_page(site_url: &SiteUrl, station: &Stop, radar: &UrlSearchParams) -> String {
+    let title = format!("Transit radar from {}", station.short_stop_name());
+    let departing = if let Some(departure_time) = radar.departure_time {
+        departure_time.format("at %H:%M on %A %-d %B").to_string()
+    } else {
+        "now".to_owned()
+    };
+    let description = format!(
+        "Everywhere you can get to by public transport from {} within {} minutes, departing {}",
+        station.full_stop_name(),
+        radar.max_duration.num_minutes(),
+        departing
+    );
+    let radar_url = radar.to_string();
+    let image = if radar_url.contains('?') {
+        format!("{}{}&format=png", site_url.0, radar_url)
+    } else {
+        format!("{}{}?format=png", site_url.0, radar_url)
+    };
+    format!(
+        include_str!("share.html"),
+        image = Escaped(image),
+        image_width = CARD_WIDTH,
+        image_height = CARD_HEIGHT,
+        title = Escaped(&title),
+        description = Escaped(&description),
+        url = Escaped(format!("{}/share{}", site_url.0, radar)),
+        radar = Escaped(radar),
+        style = include_str!("../style.css"),
+    )
+}
@@ -4,12 +4,16 @@ use std::cmp::Ordering;
 use urlencoding::decode;
 
 use radar_search::search_data::*;
-use transit_radar::Suggester;
+use transit_radar::{
+    gtfs::{db, importance::Importance, slugs::StationSlugs},
+    Suggester,
+};
 
 #[derive(Serialize)]
-struct FEStationLookup<'s> {
+pub struct FEStationLookup<'s> {
     stop_id: StopId,
     name: &'s str,
+    slug: Option<&'s str>,
 }
 
 fn most_important((id1, imp1): &(StopId, usize), (id2, imp2): &(StopId, usize)) -> Ordering {
@@ -24,10 +28,9 @@ pub fn station_search_handler<'d>(
     const RESULT_LIMIT: usize = 20;
     match decode(query) {
         Ok(query) => {
-            let matches = station_search.search(&query);
-            let top_matches = matches
+            let top_matches = station_search
+                .rank(&query, db::by_importance)
                 .into_iter()
-                .sorted_by(most_important)
                 .take(RESULT_LIMIT)
                 .map(move |(stop_id, _importance)| {
                     data.get_stop(stop_id)
@@ -41,3 +44,30 @@ pub fn station_search_handler<'d>(
         }
     }
 }
+
+/// All the stations, most important first, for searching on the client
+pub fn station_list<'s>(
+    data: &'s GTFSData,
+    slugs: &'s StationSlugs,
+    importance: &'s Importance,
+) -> Vec<FEStationLookup<'s>> {
+    stations_by_importance(data, importance)
+        .map(|stop| FEStationLookup {
+            stop_id: stop.stop_id,
+            name: stop.full_stop_name(),
+            slug: slugs.slug(stop.stop_id),
+        })
+        .collect()
+}
+
+/// All the stations, the most important first
+pub fn stations_by_importance<'d>(
+    data: &'d GTFSData,
+    importance: &'d Importance,
+) -> impl Iterator<Item = &'d Stop> {
+    data.stops()
+        .filter(|stop| stop.is_station())
+        .map(move |stop| ((stop.stop_id, importance.score(stop, data)), stop))
+        .sorted_by(|(a, _), (b, _)| most_important(a, b))
+        .map(|(_, stop)| stop)
+}
@@ -0,0 +1,356 @@
+use std::error::Error;
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use chrono::{DateTime, Duration, Utc};
+use radar_search::search_data::StopId;
+use serde::Serialize;
+use transit_radar::{
+    gtfs::{
+        aliases, bundle,
+        colors::RouteColors,
+        db,
+        importance::{Importance, Weights},
+        ingest::{IngestReport, Strictness},
+        lazy::{LazyTimetable, StopTimesAccess},
+        short_names::ShortNames,
+        slugs::StationSlugs,
+    },
+    GTFSData, Suggester,
+};
+
+/// A loaded timetable with the indexes built on it, replaced as a whole when the timetable is reloaded
+pub struct Timetable {
+    data: TimetableData,
+    pub stop_ids: db::StopIds,
+    pub slugs: StationSlugs,
+    /// Ranks the station search and picks the stations labelled larger on the radar
+    pub importance: Importance,
+    pub suggester: Suggester<(StopId, usize)>,
+    /// The body of `/stations.json`, which is the same for each request until the timetable is replaced
+    pub station_list: String,
+    pub feeds: Vec<Feed>,
+    /// The rows read from the feeds and the ones skipped, unless it was loaded from a bundle
+    pub ingest: Option<IngestReport>,
+    pub loaded_at: DateTime<Utc>,
+    pub load_duration: std::time::Duration,
+}
+
+/// The stops, routes and trips of a timetable, with or without it's stop times
+enum TimetableData {
+    Loaded(GTFSData),
+    /// The stop times are read in for each station searched from
+    Lazy(LazyTimetable),
+}
+
+impl TimetableData {
+    fn gtfs(&self) -> &GTFSData {
+        match self {
+            TimetableData::Loaded(data) => data,
+            TimetableData::Lazy(lazy) => lazy.data(),
+        }
+    }
+}
+
+impl Timetable {
+    pub fn new(
+        data: GTFSData,
+        stop_ids: db::StopIds,
+        slugs: StationSlugs,
+        importance: Importance,
+    ) -> Self {
+        Timetable::of(TimetableData::Loaded(data), stop_ids, slugs, importance)
+    }
+
+    /// A timetable which reads in the stop times around each station searched from
+    pub fn new_lazy(
+        lazy: LazyTimetable,
+        stop_ids: db::StopIds,
+        slugs: StationSlugs,
+        importance: Importance,
+    ) -> Self {
+        Timetable::of(TimetableData::Lazy(lazy), stop_ids, slugs, importance)
+    }
+
+    fn of(
+        data: TimetableData,
+        stop_ids: db::StopIds,
+        slugs: StationSlugs,
+        importance: Importance,
+    ) -> Self {
+        let station_list = serde_json::to_string(&crate::station_name_search::station_list(
+            data.gtfs(),
+            &slugs,
+            &importance,
+        ))
+        .unwrap();
+        Timetable {
+            suggester: db::build_station_word_index(data.gtfs(), &importance),
+            station_list,
+            data,
+            stop_ids,
+            slugs,
+            importance,
+            feeds: vec![],
+            ingest: None,
+            loaded_at: Utc::now(),
+            load_duration: std::time::Duration::ZERO,
+        }
+    }
+
+    /// Without departures when the stop times are read in lazily
+    pub fn data(&self) -> &GTFSData {
+        self.data.gtfs()
+    }
+
+    /// Set when the stop times are read in for each station searched from
+    pub fn lazy(&self) -> Option<&LazyTimetable> {
+        match &self.data {
+            TimetableData::Loaded(_) => None,
+            TimetableData::Lazy(lazy) => Some(lazy),
+        }
+    }
+
+    /// The data to search from the station on for up to the duration
+    pub fn search_data(
+        self: &Arc<Self>,
+        station_id: StopId,
+        max_duration: Duration,
+    ) -> io::Result<SearchData> {
+        match self.lazy() {
+            Some(lazy) => lazy
+                .around(station_id, max_duration)
+                .map(SearchData::Materialised),
+            None => Ok(SearchData::Loaded(self.clone())),
+        }
+    }
+}
+
+/// The data a search is made on, either the whole timetable or the part of it read in around the origin
+pub enum SearchData {
+    /// Kept while it's searched, a reload doesn't drop it from under the search
+    Loaded(Arc<Timetable>),
+    Materialised(Arc<GTFSData>),
+}
+
+impl Deref for SearchData {
+    type Target = GTFSData;
+
+    fn deref(&self) -> &GTFSData {
+        match self {
+            SearchData::Loaded(timetable) => timetable.data(),
+            SearchData::Materialised(data) => data,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct Feed {
+    pub dir: String,
+    /// The `feed_version` of the feed's `feed_info.txt`, which is optional
+    pub version: Option<String>,
+}
+
+/// Where the timetable is loaded from, kept so that it can be loaded again after the feed is updated
+pub struct TimetableSource {
+    pub gtfs_dirs: Vec<PathBuf>,
+    pub colors: RouteColors,
+    /// How the names of the stops of each feed are shortened
+    pub short_names: ShortNames,
+    pub day_filter: db::DayFilter,
+    /// What's done with the rows of the feeds which can't be loaded
+    pub malformed_rows: Strictness,
+    pub slug_redirects: Option<PathBuf>,
+    /// Other names the station search finds stations by
+    pub station_aliases: Option<PathBuf>,
+    pub importance_weights: Weights,
+    /// The ridership of stations, which adds to their importance
+    pub ridership: Option<PathBuf>,
+    /// Read the stop times in for each station searched from, keeping this many stations
+    pub lazy_stations: Option<usize>,
+    /// How the stop times are read in lazily
+    pub stop_times_access: StopTimesAccess,
+    /// A bundle compiled from the feeds, which is loaded instead of them
+    pub bundle: Option<PathBuf>,
+}
+
+impl TimetableSource {
+    pub fn load(&self) -> Result<Timetable, Box<dyn Error>> {
+        let started = std::time::Instant::now();
+        let (mut timetable, feeds, ingest) = if let Some(bundle_path) = &self.bundle {
+            if self.lazy_stations.is_some() {
+                return Err("the stop times can't be read in lazily from a bundle".into());
+            }
+            let bundled = bundle::load(bundle_path)?;
+            if bundled.day_filter.to_string() != self.day_filter.to_string() {
+                return Err(format!(
+                    "the bundle was compiled with the day filter {} rather than {}",
+                    bundled.day_filter, self.day_filter
+                )
+                .into());
+            }
+            let slugs = self.slugs(&bundled.data, &bundled.stop_ids)?;
+            let importance = self.importance(&bundled.stop_ids)?;
+            let feeds = bundled
+                .feeds
+                .into_iter()
+                .map(|feed| Feed {
+                    dir: feed.dir,
+                    version: feed.version,
+                })
+                .collect();
+            let timetable = Timetable::new(bundled.data, bundled.stop_ids, slugs, importance);
+            (timetable, feeds, None)
+        } else if let Some(lazy_stations) = self.lazy_stations {
+            let (lazy, stop_ids, ingest) = LazyTimetable::load(
+                &self.gtfs_dirs,
+                self.day_filter,
+                &self.colors,
+                &self.short_names,
+                self.malformed_rows,
+                lazy_stations,
+                self.stop_times_access,
+            )?;
+            let slugs = self.slugs(lazy.data(), &stop_ids)?;
+            let importance = self.importance(&stop_ids)?;
+            let timetable = Timetable::new_lazy(lazy, stop_ids, slugs, importance);
+            (timetable, self.feeds(), Some(ingest))
+        } else {
+            let (data, stop_ids, ingest) = db::load_feeds_reporting(
+                &self.gtfs_dirs,
+                self.day_filter,
+                &self.colors,
+                &self.short_names,
+                self.malformed_rows,
+            )?;
+            let slugs = self.slugs(&data, &stop_ids)?;
+            let importance = self.importance(&stop_ids)?;
+            let timetable = Timetable::new(data, stop_ids, slugs, importance);
+            (timetable, self.feeds(), Some(ingest))
+        };
+        timetable.feeds = feeds;
+        timetable.ingest = ingest;
+        if let Some(station_aliases) = &self.station_aliases {
+            add_aliases(&mut timetable, station_aliases)?;
+        }
+        timetable.load_duration = started.elapsed();
+        Ok(timetable)
+    }
+
+    fn feeds(&self) -> Vec<Feed> {
+        self.gtfs_dirs
+            .iter()
+            .map(|dir| Feed {
+                dir: dir.display().to_string(),
+                version: db::feed_version(dir),
+            })
+            .collect()
+    }
+
+    fn slugs(
+        &self,
+        data: &GTFSData,
+        stop_ids: &db::StopIds,
+    ) -> Result<StationSlugs, Box<dyn Error>> {
+        let mut slugs = StationSlugs::new(data);
+        if let Some(slug_redirects) = &self.slug_redirects {
+            slugs.update_redirects(slug_redirects, data, stop_ids)?;
+        }
+        Ok(slugs)
+    }
+
+    /// Reads the ridership again with each load, so that it can be updated with a reload
+    fn importance(&self, stop_ids: &db::StopIds) -> Result<Importance, Box<dyn Error>> {
+        let mut importance = Importance::new(self.importance_weights);
+        if let Some(ridership) = &self.ridership {
+            let unknown = importance.load_ridership(ridership, stop_ids)?;
+            if !unknown.is_empty() {
+                eprintln!(
+                    "The ridership of {} stops which aren't in the timetable is left out, such as {}",
+                    unknown.len(),
+                    unknown[0]
+                );
+            }
+        }
+        Ok(importance)
+    }
+}
+
+/// Reads the aliases again with each load, so that they can be changed with a reload
+fn add_aliases(timetable: &mut Timetable, path: &Path) -> Result<(), Box<dyn Error>> {
+    let station_aliases = aliases::read_aliases(path)?;
+    let Timetable {
+        data,
+        stop_ids,
+        importance,
+        suggester,
+        ..
+    } = timetable;
+    let unresolved = aliases::add_aliases(
+        suggester,
+        &station_aliases,
+        data.gtfs(),
+        stop_ids,
+        importance,
+    );
+    for alias in unresolved {
+        eprintln!(
+            "The alias {} is of {}, which isn't a station of the timetable",
+            alias.alias, alias.station
+        );
+    }
+    Ok(())
+}
+
+/// The timetable searches use now
+pub struct Timetables {
+    current: RwLock<Arc<Timetable>>,
+    /// Unset when the server can't reload, such as for tests
+    source: Option<TimetableSource>,
+    reloading: Mutex<()>,
+}
+
+#[derive(Debug)]
+pub enum ReloadError {
+    /// The server wasn't started from a source it can load again
+    NoSource,
+    /// Another reload hasn't finished yet
+    InProgress,
+    Load(String),
+}
+
+impl Timetables {
+    pub fn new(timetable: Timetable, source: Option<TimetableSource>) -> Self {
+        Timetables {
+            current: RwLock::new(Arc::new(timetable)),
+            source,
+            reloading: Mutex::new(()),
+        }
+    }
+
+    pub fn current(&self) -> Arc<Timetable> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reloading.try_lock().is_err()
+    }
+
+    /// Loads the timetable again from it's source and replaces the current one, which searches in progress keep using
+    pub fn reload(&self) -> Result<Arc<Timetable>, ReloadError> {
+        let source = self.source.as_ref().ok_or(ReloadError::NoSource)?;
+        let _reloading = self
+            .reloading
+            .try_lock()
+            .map_err(|_| ReloadError::InProgress)?;
+        let timetable = Arc::new(
+            source
+                .load()
+                .map_err(|err| ReloadError::Load(err.to_string()))?,
+        );
+        *self.current.write().unwrap() = timetable.clone();
+        Ok(timetable)
+    }
+}
@@ -0,0 +1,141 @@
+//! `transit-radar compile <bundle>` loads the feeds configured as for the server and writes them to a bundle, which the server loads from `BUNDLE` much faster than the feeds.
+//!
+//! `transit-radar diff <old> <new>` reports the stations, routes and trips per route which changed between two versions of the timetable, each a bundle or a feed directory.
+//!
+//! `transit-radar import-hafas <api> <station> <start> <minutes> <bundle>` imports the trips within the minutes from the start, such as `2024-01-15T10:00`, around a station of a HAFAS style REST API into a bundle for the day, when built with the `hafas` feature.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use radar_search::search_data::GTFSData;
+use transit_radar::gtfs::{
+    bundle, colors::RouteColors, db, diff, ingest::Strictness, short_names::ShortNames,
+};
+#[cfg(feature = "hafas")]
+use transit_radar::hafas;
+
+const USAGE: &str = "usage: transit-radar compile <bundle>
+       transit-radar diff <old bundle or feed> <new bundle or feed>
+       transit-radar import-hafas <api url> <station id> <start> <minutes> <bundle>";
+
+enum Command {
+    Compile(PathBuf),
+    Diff(PathBuf, PathBuf),
+    #[cfg(feature = "hafas")]
+    ImportHafas {
+        api: String,
+        window: hafas::Window,
+        bundle_path: PathBuf,
+    },
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match args.as_slice() {
+        [command, bundle_path] if command == "compile" => {
+            Command::Compile(PathBuf::from(bundle_path))
+        }
+        [command, old, new] if command == "diff" => {
+            Command::Diff(PathBuf::from(old), PathBuf::from(new))
+        }
+        #[cfg(feature = "hafas")]
+        [command, api, station, start, minutes, bundle_path] if command == "import-hafas" => {
+            Command::ImportHafas {
+                api: api.clone(),
+                window: hafas::Window {
+                    station: station.clone(),
+                    start: chrono::NaiveDateTime::parse_from_str(start, "%Y-%m-%dT%H:%M")?,
+                    duration: chrono::Duration::minutes(minutes.parse()?),
+                    max_stations: hafas::DEFAULT_MAX_STATIONS,
+                },
+                bundle_path: PathBuf::from(bundle_path),
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let (colors, short_names, day_filter) = feed_config()?;
+
+    match command {
+        Command::Compile(bundle_path) => {
+            let gtfs_dirs = std::env::var("GTFS_DIR").unwrap_or_else(|_| "gtfs".to_owned());
+            let gtfs_dirs: Vec<PathBuf> = std::env::split_paths(&gtfs_dirs).collect();
+            let started = std::time::Instant::now();
+            bundle::compile(&gtfs_dirs, day_filter, &colors, &short_names, &bundle_path)?;
+            eprintln!(
+                "Compiled {} into {} in {:.1}s",
+                gtfs_dirs
+                    .iter()
+                    .map(|dir| dir.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                bundle_path.display(),
+                started.elapsed().as_secs_f64()
+            );
+        }
+        Command::Diff(old, new) => {
+            let old = load(&old, day_filter, &colors, &short_names)?;
+            let new = load(&new, day_filter, &colors, &short_names)?;
+            print!("{}", diff::diff((&old.0, &old.1), (&new.0, &new.1)));
+        }
+        #[cfg(feature = "hafas")]
+        Command::ImportHafas {
+            api,
+            window,
+            bundle_path,
+        } => {
+            let (data, stop_ids) =
+                hafas::import(&hafas::HttpApi::new(&api)?, &window, &colors, &short_names)?;
+            // the window is of a single day
+            let day_filter = db::DayFilter::Single(chrono::Datelike::weekday(&window.start).into());
+            let feeds = vec![bundle::BundledFeed {
+                dir: api,
+                version: Some(window.start.to_string()),
+            }];
+            bundle::write(&data, &stop_ids, day_filter, feeds, &bundle_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// The colours, short names and day filter of the feeds, configured as for the server
+fn feed_config() -> Result<(RouteColors, ShortNames, db::DayFilter), Box<dyn Error>> {
+    let line_colors_path =
+        std::env::var("LINE_COLORS").unwrap_or_else(|_| "./VBB_Colours.csv".to_owned());
+    let mut colors = RouteColors::new();
+    colors.load_all(std::env::split_paths(&line_colors_path))?;
+    if let Ok(route_type_colors) = std::env::var("ROUTE_TYPE_COLORS") {
+        colors.set_route_type_colors_from_config(&route_type_colors)?;
+    }
+    let mut short_names = ShortNames::new();
+    if let Some(rules_path) = std::env::var_os("SHORT_NAME_RULES") {
+        short_names.load_rules(Path::new(&rules_path))?;
+    }
+    if let Ok(profiles) = std::env::var("SHORT_NAMES") {
+        short_names.set_feed_profiles(&profiles)?;
+    }
+    let day_filter = match std::env::var("DAY_FILTER") {
+        Ok(day_filter) => day_filter.parse()?,
+        Err(_) => db::DayFilter::All,
+    };
+    Ok((colors, short_names, day_filter))
+}
+
+/// A version of the timetable from a feed directory, or otherwise a bundle which keeps the day filter it was compiled with
+fn load(
+    path: &Path,
+    day_filter: db::DayFilter,
+    colors: &RouteColors,
+    short_names: &ShortNames,
+) -> Result<(GTFSData, db::StopIds), Box<dyn Error>> {
+    if path.is_dir() {
+        let (data, stop_ids, _report) =
+            db::load_feeds_reporting(&[path], day_filter, colors, short_names, Strictness::Warn)?;
+        Ok((data, stop_ids))
+    } else {
+        let bundled = bundle::load(path)?;
+        Ok((bundled.data, bundled.stop_ids))
+    }
+}
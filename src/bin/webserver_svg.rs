@@ -1,21 +1,67 @@
-use std::{borrow::Cow, collections::HashSet, fmt, io, num::NonZeroU32, path::Path, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fmt, io,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use admin::AdminToken;
+use radar_cache::{CachedRadar, RadarCache, RadarKey, ReachableKey};
+use scheduler::Prerendered;
+use site::{PublicUrl, SiteUrl};
+use timetable::{SearchData, Timetable, TimetableSource, Timetables};
 
 use chrono::{Duration, NaiveDateTime, TimeZone};
-use radar_search::search_data::{Stop, StopId};
+use radar_search::{
+    search_data::{DirectionId, Stop, StopId, TripId},
+    sync::{seal, Manifest, SyncSessions, SyncVersion},
+};
 use rocket::{
+    data::{Data, ToByteUnit},
     form::FromFormField,
-    http::{ContentType, Status},
+    http::{Accept, ContentType, Header, Status},
     request::FromParam,
-    response::content,
+    response::{content, stream::TextStream, Redirect},
     State,
 };
+use serde::Serialize;
 use transit_radar::{
-    draw::radar::{search, SearchParams, TransitMode, UrlSearchParams},
-    gtfs::db,
+    districts::{load_districts, District},
+    draw::{
+        isochrone::{feature_collection, isochrones},
+        radar::{
+            day_time, earliest_arrivals, fitted_duration, reachable_through_the_day, required_data,
+            search, JourneyUrl, MaxLegs, Palette, Radar, RadialScale, SearchParams, TransitMode,
+            UrlSearchParams, DEFAULT_MAX_DURATION_MINS, MAX_FIT_MINS,
+        },
+        render::{Format, JsonRenderer, RadarRenderer},
+        stream::{discoveries, explained_discoveries},
+        tree::TREE_SCHEMA,
+        weekly::{week_of, write_weekly_svg_to, WeekdayRadar},
+        xml::Escaped,
+    },
+    gtfs::{
+        colors::RouteColors, db, demo, importance::Importance, lazy::StopTimesAccess,
+        short_names::ShortNames, slugs::StationSlugs,
+    },
+    poi::{load_pois, Poi},
     write_xml, GTFSData, Suggester,
 };
 
+mod admin;
+mod ask;
+#[cfg(feature = "bot")]
+mod bot;
+mod radar_cache;
+mod scheduler;
+mod site;
 mod station_name_search;
+mod timetable;
+#[cfg(test)]
+#[path = "../draw/well_formed.rs"]
+mod well_formed;
 
 #[macro_use]
 extern crate rocket;
@@ -41,28 +87,17 @@ impl<'v> FromFormField<'v> for TransitModes {
         let modes: HashSet<_> = field
             .value
             .split(',')
-            .map(|mode| match mode {
-                "ubahn" => Ok(TransitMode::UBahn),
-                "sbahn" => Ok(TransitMode::SBahn),
-                "bus" => Ok(TransitMode::Bus),
-                "tram" => Ok(TransitMode::Tram),
-                "regional" => Ok(TransitMode::Regional),
-                "boat" => Ok(TransitMode::Boat),
-                other => Err(rocket::form::Errors::from(
-                    rocket::form::prelude::ErrorKind::InvalidChoice {
-                        choices: vec![
-                            "ubahn".into(),
-                            "sbahn".into(),
-                            "tram".into(),
-                            "tram".into(),
-                            "regional".into(),
-                            "boat".into(),
-                        ]
-                        .into(),
-                    },
-                )
-                .with_name(field.name)
-                .with_value(other)),
+            .map(|mode| {
+                TransitMode::from_key(mode).ok_or_else(|| {
+                    rocket::form::Errors::from(rocket::form::prelude::ErrorKind::InvalidChoice {
+                        choices: TransitMode::ALL
+                            .iter()
+                            .map(|mode| mode.key().into())
+                            .collect(),
+                    })
+                    .with_name(field.name)
+                    .with_value(mode)
+                })
             })
             .collect::<Result<_, _>>()?;
         Ok(TransitModes(modes))
@@ -73,53 +108,1271 @@ impl<'v> FromFormField<'v> for TransitModes {
     }
 }
 
-#[get("/depart-from/<station_id>/<time>?<minutes>&<refresh>&<mode>")]
+/// How long to search for, `auto` fits it to the station
+#[derive(Clone, Copy)]
+enum Minutes {
+    Given(i64),
+    Auto,
+}
+
+impl Minutes {
+    /// The minutes given, which are left to be fitted when `auto`
+    fn given(minutes: Option<Minutes>) -> Option<i64> {
+        match minutes {
+            Some(Minutes::Given(minutes)) => Some(minutes),
+            Some(Minutes::Auto) | None => None,
+        }
+    }
+}
+
+impl<'v> FromFormField<'v> for Minutes {
+    fn from_value(field: rocket::form::ValueField<'v>) -> rocket::form::Result<'v, Self> {
+        if field.value == "auto" {
+            return Ok(Minutes::Auto);
+        }
+        i64::from_value(field).map(Minutes::Given)
+    }
+}
+
+/// The radar with when it becomes stale, for clients to schedule a refresh
+#[derive(Responder)]
+struct RenderedRadar {
+    body: (ContentType, Vec<u8>),
+    expires: Header<'static>,
+}
+
+#[derive(Responder)]
+enum RadarResponse {
+    Rendered(RenderedRadar),
+    /// The station was given some other way than by it's id on the radar
+    Canonical(Redirect),
+}
+
+/// The station is the slug used on the radar's own links, a slug or id from an earlier timetable, the radar's id, a GTFS or IFOPT stop id, or a station name
+///
+/// The radar is SVG unless another format is asked for by `format` or the `Accept` header
+///
+/// The trips in `prefer` are drawn rather than others arriving at the same time, the refreshing radar sends the trips it drew which won such a tie so that it's redrawn the same
+#[get("/depart-from/<station>/<time>?<minutes>&<refresh>&<mode>&<direction>&<confidence>&<max_km>&<missed>&<frequency>&<scale>&<legs>&<grace>&<palette>&<format>&<prefer>")]
+#[allow(clippy::too_many_arguments)]
 fn index(
-    station_id: NonZeroU32,
+    station: &str,
     time: TimeFilter,
-    minutes: Option<i64>,
+    minutes: Option<Minutes>,
     refresh: Option<bool>,
     mode: TransitModes,
-    data: &State<Arc<GTFSData>>,
-) -> (ContentType, String) {
-    let origin = data.get_stop(station_id).unwrap();
-    assert!(origin.is_station(), "Origin must be a station");
+    direction: Option<DirectionId>,
+    confidence: Option<u8>,
+    max_km: Option<f64>,
+    missed: Option<i64>,
+    frequency: Option<bool>,
+    scale: Option<&str>,
+    legs: Option<&str>,
+    grace: Option<i64>,
+    palette: Option<&str>,
+    format: Option<&str>,
+    prefer: Option<&str>,
+    accept: Option<&Accept>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+    cache: &State<Arc<RadarCache>>,
+    prerendered: &State<Arc<Prerendered>>,
+) -> Result<RadarResponse, Status> {
+    let refresh_given = refresh;
+    let refresh = refresh.unwrap_or(false) && matches!(time, TimeFilter::Now);
+    let requested_format = requested_format(format, accept)?;
+    let renderer = requested_format.renderer(refresh);
+    let fitted = matches!(minutes, Some(Minutes::Auto));
+    let minutes = Minutes::given(minutes);
+    let palette = requested_palette(palette)?;
+    let frequency = frequency.unwrap_or(false);
+    let scale = requested_scale(scale)?;
+    let max_legs = requested_max_legs(legs)?;
+    let prefer_trips = requested_trips(prefer)?;
+    let timetable = timetables.current();
+    let slugs = &timetable.slugs;
+    let station_id = find_station(station, &timetable).ok_or(Status::NotFound)?;
+    let requested = UrlSearchParams {
+        palette,
+        fitted,
+        ..search_url(
+            station_id, slugs, time, minutes, &mode, direction, confidence, max_km, missed,
+            frequency, scale, &max_legs, grace,
+        )
+    }
+    .to_string();
+    if station != station_path(station_id, slugs) {
+        // the params which aren't part of the search are carried over as they were given
+        let unsearched: Vec<String> = [
+            ("refresh", refresh_given.map(|refresh| refresh.to_string())),
+            ("format", format.map(str::to_owned)),
+            ("prefer", prefer.map(str::to_owned)),
+        ]
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .as_ref()
+                .map(|value| format!("{}={}", name, urlencoding::encode(value)))
+        })
+        .collect();
+        let canonical = if unsearched.is_empty() {
+            requested
+        } else {
+            let separator = if requested.contains('?') { '&' } else { '?' };
+            format!("{}{}{}", requested, separator, unsearched.join("&"))
+        };
+        return Ok(RadarResponse::Canonical(Redirect::to(canonical)));
+    }
+    // link previews of share pages show the card of the radar departing now with the default search
+    if requested_format == Format::Png
+        && requested == UrlSearchParams::new(station_id, slugs).to_string()
+    {
+        if let Some(preview) = prerendered.preview(station_id) {
+            return Ok(RadarResponse::Rendered(preview));
+        }
+    }
+    let data = search_data(&timetable, station_id, fit_minutes(minutes, fitted))?;
+    let minutes = if fitted {
+        Some(fitted_minutes(
+            station_id, time, &mode, direction, &max_legs, &data, day_filter, overlays,
+        )?)
+    } else {
+        minutes
+    };
+    let (radar, url_search_params) = radar_search(
+        station_id,
+        time,
+        minutes,
+        &mode,
+        direction,
+        confidence,
+        max_km,
+        missed,
+        frequency,
+        scale,
+        &max_legs,
+        grace,
+        prefer_trips,
+        &data,
+        slugs,
+        &timetable.importance,
+        day_filter,
+        overlays,
+        cache,
+    )?;
+    let url_search_params = UrlSearchParams {
+        palette,
+        fitted,
+        ..url_search_params
+    };
+    Ok(RadarResponse::Rendered(RenderedRadar {
+        body: render(&*renderer, radar.radar(), url_search_params),
+        expires: Header::new("X-Radar-Expires", radar.radar().expires_at().to_rfc3339()),
+    }))
+}
+
+/// The format named by `format`, or else the most preferred in the `Accept` header, SVG when any will do
+fn requested_format(format: Option<&str>, accept: Option<&Accept>) -> Result<Format, Status> {
+    if let Some(format) = format {
+        return Format::from_name(format).ok_or(Status::BadRequest);
+    }
+    let accept = if let Some(accept) = accept {
+        accept
+    } else {
+        return Ok(Format::Svg);
+    };
+    let mut media_types: Vec<_> = accept
+        .iter()
+        .filter(|media_type| media_type.weight_or(1.) > 0.)
+        .collect();
+    // stable, so that equally preferred types stay in the order they were given
+    media_types.sort_by(|a, b| b.weight_or(1.).total_cmp(&a.weight_or(1.)));
+    for media_type in media_types {
+        let (top, sub) = (media_type.top(), media_type.sub());
+        if top == "*" || (top == "image" && sub == "*") {
+            return Ok(Format::Svg);
+        }
+        if let Some(format) = Format::from_media_type(&format!("{}/{}", top, sub)) {
+            return Ok(format);
+        }
+    }
+    Err(Status::NotAcceptable)
+}
+
+/// The minutes to read in the stop times for, the longest a radar is fitted to when it's fitted
+fn fit_minutes(minutes: Option<i64>, fitted: bool) -> Option<i64> {
+    if fitted {
+        Some(MAX_FIT_MINS)
+    } else {
+        minutes
+    }
+}
+
+/// The minutes a radar from the station is fitted to, see `fitted_duration`
+#[allow(clippy::too_many_arguments)]
+fn fitted_minutes(
+    station_id: StopId,
+    time: TimeFilter,
+    mode: &TransitModes,
+    direction: Option<DirectionId>,
+    max_legs: &MaxLegs,
+    data: &GTFSData,
+    day_filter: &db::DayFilter,
+    overlays: &Overlays,
+) -> Result<i64, Status> {
+    let search_params = SearchParams {
+        max_legs: max_legs.clone(),
+        ..search_params(
+            station_id, time, None, mode, direction, data, day_filter, overlays,
+        )?
+    };
+    Ok(fitted_duration(data, search_params).num_minutes())
+}
+
+/// The palette named by `palette`, the routes' own colours if it isn't given
+fn requested_palette(palette: Option<&str>) -> Result<Palette, Status> {
+    palette.map_or(Ok(Palette::Route), |palette| {
+        Palette::from_key(palette).ok_or(Status::BadRequest)
+    })
+}
+
+/// The radial scale named by `scale`, linear if it isn't given
+fn requested_scale(scale: Option<&str>) -> Result<RadialScale, Status> {
+    scale.map_or(Ok(RadialScale::Linear), |scale| {
+        RadialScale::from_key(scale).ok_or(Status::BadRequest)
+    })
+}
+
+/// The most legs by each mode named by `legs`, such as `bus:1`, any number if it isn't given
+fn requested_max_legs(legs: Option<&str>) -> Result<MaxLegs, Status> {
+    legs.map_or(Ok(MaxLegs::default()), |legs| {
+        MaxLegs::from_key(legs).ok_or(Status::BadRequest)
+    })
+}
+
+/// The trip ids separated by commas, those of a radar with no trips are empty
+fn requested_trips(trips: Option<&str>) -> Result<Vec<TripId>, Status> {
+    let mut trips = trips
+        .into_iter()
+        .flat_map(|trips| trips.split(','))
+        .filter(|trip_id| !trip_id.is_empty())
+        .map(|trip_id| trip_id.parse().map_err(|_| Status::BadRequest))
+        .collect::<Result<Vec<TripId>, Status>>()?;
+    // in order, so that the same trips make the same search
+    trips.sort();
+    trips.dedup();
+    Ok(trips)
+}
+
+/// Renders the radar, with the content type of the renderer
+fn render<'s>(
+    renderer: &dyn RadarRenderer,
+    radar: &Radar<'s>,
+    search_params: UrlSearchParams<'s>,
+) -> (ContentType, Vec<u8>) {
+    let mut body = Vec::new();
+    renderer
+        .render(&mut io::Cursor::new(&mut body), radar, search_params)
+        .unwrap();
+    let content_type =
+        ContentType::parse_flexible(renderer.media_type()).expect("a valid media type");
+    (content_type, body)
+}
+
+/// Small radars from the same station at the same time on each day of the week the time is in, the station is found as for the radar
+#[get("/weekly/depart-from/<station>/<time>?<minutes>&<mode>&<direction>&<confidence>&<max_km>&<missed>&<frequency>&<scale>&<legs>&<grace>&<palette>")]
+#[allow(clippy::too_many_arguments)]
+fn weekly(
+    station: &str,
+    time: TimeFilter,
+    minutes: Option<Minutes>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    confidence: Option<u8>,
+    max_km: Option<f64>,
+    missed: Option<i64>,
+    frequency: Option<bool>,
+    scale: Option<&str>,
+    legs: Option<&str>,
+    grace: Option<i64>,
+    palette: Option<&str>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+    cache: &State<Arc<RadarCache>>,
+) -> Result<RadarResponse, Status> {
+    let fitted = matches!(minutes, Some(Minutes::Auto));
+    let minutes = Minutes::given(minutes);
+    let palette = requested_palette(palette)?;
+    let frequency = frequency.unwrap_or(false);
+    let scale = requested_scale(scale)?;
+    let max_legs = requested_max_legs(legs)?;
+    let timetable = timetables.current();
+    let slugs = &timetable.slugs;
+    let station_id = find_station(station, &timetable).ok_or(Status::NotFound)?;
+    if station != station_path(station_id, slugs) {
+        return Ok(RadarResponse::Canonical(Redirect::to(format!(
+            "/weekly{}",
+            UrlSearchParams {
+                palette,
+                fitted,
+                ..search_url(
+                    station_id, slugs, time, minutes, &mode, direction, confidence, max_km, missed,
+                    frequency, scale, &max_legs, grace,
+                )
+            }
+        ))));
+    }
+    let origin = timetable
+        .data()
+        .get_stop(station_id)
+        .ok_or(Status::NotFound)?;
+    let data = search_data(&timetable, station_id, fit_minutes(minutes, fitted))?;
     let departure_time = match time {
-        TimeFilter::Now => None,
-        TimeFilter::Local(dt) => Some(chrono_tz::Europe::Berlin.from_local_datetime(&dt).unwrap()),
+        TimeFilter::Now => chrono::Utc::now()
+            .with_timezone(&chrono_tz::Europe::Berlin)
+            .naive_local(),
+        TimeFilter::Local(dt) => dt,
+    };
+    // each day is fitted the same, so that they can be compared
+    let minutes = if fitted {
+        Some(fitted_minutes(
+            station_id,
+            TimeFilter::Local(departure_time),
+            &mode,
+            direction,
+            &max_legs,
+            &data,
+            day_filter,
+            overlays,
+        )?)
+    } else {
+        minutes
+    };
+    let mut radars = vec![];
+    for day in week_of(departure_time) {
+        let radar = radar_search(
+            station_id,
+            TimeFilter::Local(day),
+            minutes,
+            &mode,
+            direction,
+            confidence,
+            max_km,
+            missed,
+            frequency,
+            scale,
+            &max_legs,
+            grace,
+            vec![],
+            &data,
+            slugs,
+            &timetable.importance,
+            day_filter,
+            overlays,
+            cache,
+        );
+        let radar = match radar {
+            Ok((radar, _url_search_params)) => Some(radar),
+            // the server only has the timetable of some days
+            Err(status) if status == Status::NotFound => None,
+            Err(status) => return Err(status),
+        };
+        radars.push((day, radar));
+    }
+    let mut days = vec![];
+    for (day, radar) in &radars {
+        let search_params = UrlSearchParams {
+            palette,
+            fitted,
+            ..search_url(
+                station_id,
+                slugs,
+                TimeFilter::Local(*day),
+                minutes,
+                &mode,
+                direction,
+                confidence,
+                max_km,
+                missed,
+                frequency,
+                scale,
+                &max_legs,
+                grace,
+            )
+        };
+        days.push(WeekdayRadar {
+            departure_time: search_params.departure_time.ok_or(Status::NotFound)?,
+            radar: radar.as_ref().map(SearchedRadar::radar),
+            search_params,
+        });
+    }
+    let mut svg = Vec::new();
+    write_weekly_svg_to(&mut io::Cursor::new(&mut svg), origin, &days).unwrap();
+    let expires_at = radars
+        .iter()
+        .filter_map(|(_day, radar)| radar.as_ref())
+        .map(|radar| radar.radar().expires_at())
+        .min()
+        .ok_or(Status::NotFound)?;
+    Ok(RadarResponse::Rendered(RenderedRadar {
+        body: (ContentType::SVG, svg),
+        expires: Header::new("X-Radar-Expires", expires_at.to_rfc3339()),
+    }))
+}
+
+/// The station as the radar's own urls have it
+fn station_path(station_id: StopId, slugs: &StationSlugs) -> Cow<'_, str> {
+    slugs
+        .slug(station_id)
+        .map(Cow::Borrowed)
+        .unwrap_or_else(|| station_id.to_string().into())
+}
+
+/// The url of the radar of a search, before it's searched
+#[allow(clippy::too_many_arguments)]
+fn search_url<'s>(
+    station_id: StopId,
+    slugs: &'s StationSlugs,
+    time: TimeFilter,
+    minutes: Option<i64>,
+    mode: &'s TransitModes,
+    direction: Option<DirectionId>,
+    confidence: Option<u8>,
+    max_km: Option<f64>,
+    missed: Option<i64>,
+    frequency: bool,
+    scale: RadialScale,
+    max_legs: &MaxLegs,
+    grace: Option<i64>,
+) -> UrlSearchParams<'s> {
+    UrlSearchParams {
+        station_id,
+        slugs,
+        departure_time: match time {
+            TimeFilter::Now => None,
+            TimeFilter::Local(dt) => chrono_tz::Europe::Berlin
+                .from_local_datetime(&dt)
+                .earliest(),
+        },
+        max_duration: Duration::minutes(minutes.unwrap_or(DEFAULT_MAX_DURATION_MINS)),
+        modes: Cow::Borrowed(&mode.0),
+        direction,
+        confidence,
+        max_km,
+        missed,
+        palette: Palette::Route,
+        frequency,
+        scale,
+        fitted: false,
+        max_legs: max_legs.clone(),
+        grace,
+    }
+}
+
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+enum ShareResponse {
+    Page(content::RawHtml<String>),
+    Canonical(Redirect),
+}
+
+/// A page of the radar for sharing, which link previews can be made from, the station is found as for the radar
+#[get("/share/depart-from/<station>/<time>?<minutes>&<mode>&<direction>&<confidence>&<max_km>&<missed>&<frequency>&<scale>&<legs>&<grace>&<palette>")]
+#[allow(clippy::too_many_arguments)]
+fn share(
+    station: &str,
+    time: TimeFilter,
+    minutes: Option<Minutes>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    confidence: Option<u8>,
+    max_km: Option<f64>,
+    missed: Option<i64>,
+    frequency: Option<bool>,
+    scale: Option<&str>,
+    legs: Option<&str>,
+    grace: Option<i64>,
+    palette: Option<&str>,
+    site_url: SiteUrl,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+) -> Result<ShareResponse, Status> {
+    let fitted = matches!(minutes, Some(Minutes::Auto));
+    let minutes = Minutes::given(minutes);
+    let frequency = frequency.unwrap_or(false);
+    let scale = requested_scale(scale)?;
+    let max_legs = requested_max_legs(legs)?;
+    let timetable = timetables.current();
+    let station_id = find_station(station, &timetable).ok_or(Status::NotFound)?;
+    let mut radar = UrlSearchParams {
+        palette: requested_palette(palette)?,
+        fitted,
+        ..search_url(
+            station_id,
+            &timetable.slugs,
+            time,
+            minutes,
+            &mode,
+            direction,
+            confidence,
+            max_km,
+            missed,
+            frequency,
+            scale,
+            &max_legs,
+            grace,
+        )
     };
-    let max_duration = Duration::minutes(minutes.unwrap_or(30));
+    if station != station_path(station_id, &timetable.slugs) {
+        return Ok(ShareResponse::Canonical(Redirect::to(format!(
+            "/share{}",
+            radar
+        ))));
+    }
+    if fitted {
+        // the page describes the radar as it's drawn
+        let data = search_data(&timetable, station_id, fit_minutes(minutes, fitted))?;
+        radar.max_duration = Duration::minutes(fitted_minutes(
+            station_id, time, &mode, direction, &max_legs, &data, day_filter, overlays,
+        )?);
+    }
+    let station = timetable
+        .data()
+        .get_stop(station_id)
+        .ok_or(Status::NotFound)?;
+    Ok(ShareResponse::Page(content::RawHtml(site::share_page(
+        &site_url, station, &radar,
+    ))))
+}
+
+/// Finds the station a search is from, the slugs and ids are tried before the name
+fn find_station(station: &str, timetable: &Timetable) -> Option<StopId> {
+    let Timetable {
+        stop_ids,
+        slugs,
+        suggester,
+        ..
+    } = timetable;
+    let data = timetable.data();
+    let by_id = |stop_id| data.get_stop(stop_id).map(Stop::station_id);
+    if let Some(station_id) = slugs.station(station).or_else(|| slugs.redirect(station)) {
+        return Some(station_id);
+    }
+    station
+        .parse()
+        .ok()
+        .and_then(|stop_id| data.get_stop(stop_id))
+        .filter(|stop| stop.is_station())
+        .map(|stop| stop.stop_id)
+        .or_else(|| stop_ids.get(station).and_then(by_id))
+        .or_else(|| stop_ids.get_ifopt(station).and_then(by_id))
+        .or_else(|| db::best_match(station, data, suggester).map(|stop| stop.stop_id))
+}
+
+/// The departure tree of the same search as the radar, in the schema at `/schema/tree.json`
+#[get("/depart-from/<station_id>/<time>/tree.json?<minutes>&<mode>&<direction>&<max_km>")]
+#[allow(clippy::too_many_arguments)]
+fn tree(
+    station_id: NonZeroU32,
+    time: TimeFilter,
+    minutes: Option<i64>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    max_km: Option<f64>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+    cache: &State<Arc<RadarCache>>,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let timetable = timetables.current();
+    let data = search_data(&timetable, station_id, minutes)?;
+    let (radar, url_search_params) = radar_search(
+        station_id,
+        time,
+        minutes,
+        &mode,
+        direction,
+        None,
+        max_km,
+        None,
+        false,
+        RadialScale::Linear,
+        &MaxLegs::default(),
+        None,
+        vec![],
+        &data,
+        &timetable.slugs,
+        &timetable.importance,
+        day_filter,
+        overlays,
+        cache,
+    )?;
+    Ok(render(&JsonRenderer, radar.radar(), url_search_params))
+}
+
+/// Minutes between the searches through the day, small enough for a sparkline to show the gaps in service
+const REACHABLE_INTERVAL_MINS: i64 = 15;
+/// Each request is a search at every interval of the day, so they are searched for no longer than the bot's
+const MAX_REACHABLE_MINS: i64 = 120;
+
+#[derive(Serialize)]
+struct ReachableThroughTheDay {
+    interval_minutes: i64,
+    max_minutes: i64,
+    samples: Vec<ReachableSample>,
+}
+
+#[derive(Serialize)]
+struct ReachableSample {
+    /// RFC 3339
+    departure_time: String,
+    /// Not counting the origin
+    stations: usize,
+}
+
+/// The number of stations reached by the same search departing every 15 minutes through the day of the time, from midnight
+#[get("/depart-from/<station_id>/<time>/reachable.json?<minutes>&<mode>&<direction>&<max_km>")]
+#[allow(clippy::too_many_arguments)]
+fn reachable(
+    station_id: NonZeroU32,
+    time: TimeFilter,
+    minutes: Option<i64>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    max_km: Option<f64>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+    cache: &State<Arc<RadarCache>>,
+) -> Result<(ContentType, String), Status> {
+    let minutes = minutes.map(|minutes| minutes.min(MAX_REACHABLE_MINS));
+    let timetable = timetables.current();
+    let data = search_data(&timetable, station_id, minutes)?;
+    check_max_km(max_km)?;
     let search_params = SearchParams {
+        max_km,
+        ..search_params(
+            station_id, time, minutes, &mode, direction, &data, day_filter, overlays,
+        )?
+    };
+    let max_minutes = search_params.max_duration.num_minutes();
+    let key = ReachableKey {
+        origin: station_id,
+        day: search_params
+            .departure_time
+            .expect("the departure time to be set")
+            .date_naive(),
+        max_minutes,
+        modes: RadarKey::modes(&mode.0),
+        direction,
+        max_metres: max_km.map(|max_km| (max_km * 1000.).round() as u64),
+    };
+    let samples = cache.get_or_count(key, &timetable, || {
+        reachable_through_the_day(
+            &data,
+            search_params,
+            Duration::minutes(REACHABLE_INTERVAL_MINS),
+        )
+    });
+    let reachable = ReachableThroughTheDay {
+        interval_minutes: REACHABLE_INTERVAL_MINS,
+        max_minutes,
+        samples: samples
+            .iter()
+            .map(|(departure_time, stations)| ReachableSample {
+                departure_time: departure_time.to_rfc3339(),
+                stations: *stations,
+            })
+            .collect(),
+    };
+    Ok((
+        ContentType::JSON,
+        serde_json::to_string(&reachable).unwrap(),
+    ))
+}
+
+/// The seconds to each station reached by the search, by it's stop id, departing now unless the time is given as in the radar url
+///
+/// This is the same search as the radar without the trips, so it's much smaller than the departure tree
+#[get("/reach/<station>?<time>&<minutes>&<mode>&<direction>&<max_km>")]
+#[allow(clippy::too_many_arguments)]
+fn reach(
+    station: &str,
+    time: Option<&str>,
+    minutes: Option<i64>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    max_km: Option<f64>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+) -> Result<(ContentType, String), Status> {
+    let time = match time {
+        Some(time) => TimeFilter::from_param(time).map_err(|_| Status::BadRequest)?,
+        None => TimeFilter::Now,
+    };
+    let timetable = timetables.current();
+    let station_id = find_station(station, &timetable).ok_or(Status::NotFound)?;
+    let data = search_data(&timetable, station_id, minutes)?;
+    check_max_km(max_km)?;
+    let search_params = SearchParams {
+        max_km,
+        ..search_params(
+            station_id, time, minutes, &mode, direction, &data, day_filter, overlays,
+        )?
+    };
+    let seconds: BTreeMap<StopId, i64> = earliest_arrivals(&data, search_params)
+        .into_iter()
+        .map(|(station_id, travel_time)| (station_id, travel_time.num_seconds()))
+        .collect();
+    Ok((ContentType::JSON, serde_json::to_string(&seconds).unwrap()))
+}
+
+/// The areas reached by the search within 5, 10, 15 and 30 minutes and it's own minutes, as GeoJSON polygons, departing now unless the time is given as in the radar url
+#[get("/isochrone/<station>?<time>&<minutes>&<mode>&<direction>&<max_km>")]
+#[allow(clippy::too_many_arguments)]
+fn isochrone(
+    station: &str,
+    time: Option<&str>,
+    minutes: Option<i64>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    max_km: Option<f64>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+) -> Result<(ContentType, String), Status> {
+    let time = match time {
+        Some(time) => TimeFilter::from_param(time).map_err(|_| Status::BadRequest)?,
+        None => TimeFilter::Now,
+    };
+    let timetable = timetables.current();
+    let station_id = find_station(station, &timetable).ok_or(Status::NotFound)?;
+    let data = search_data(&timetable, station_id, minutes)?;
+    check_max_km(max_km)?;
+    let search_params = SearchParams {
+        max_km,
+        ..search_params(
+            station_id, time, minutes, &mode, direction, &data, day_filter, overlays,
+        )?
+    };
+    let isochrones = isochrones(&data, search_params);
+    Ok((
+        ContentType::parse_flexible(Format::GeoJson.media_type()).expect("a valid media type"),
+        feature_collection(&isochrones).to_string(),
+    ))
+}
+
+/// The stations and trips of the radar's search as newline delimited JSON, each line written as soon as the search reaches it, departing now unless the time is given as in the radar url
+///
+/// The search runs as the response is written, so clients of very large searches can draw it as it goes. With `explain=true` each line says why the planner made it, and the walks are written too, for reports of the planner's bugs
+#[get("/stream/from/<station>?<time>&<minutes>&<mode>&<direction>&<explain>")]
+#[allow(clippy::too_many_arguments)]
+fn stream(
+    station: &str,
+    time: Option<&str>,
+    minutes: Option<i64>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    explain: Option<bool>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+) -> Result<(ContentType, TextStream![String]), Status> {
+    let explain = explain.unwrap_or(false);
+    let time = match time {
+        Some(time) => TimeFilter::from_param(time).map_err(|_| Status::BadRequest)?,
+        None => TimeFilter::Now,
+    };
+    let timetable = timetables.current();
+    let station_id = find_station(station, &timetable).ok_or(Status::NotFound)?;
+    let data = search_data(&timetable, station_id, minutes)?;
+    let (day_filter, overlays) = (*day_filter.inner(), *overlays.inner());
+    // checked before the response starts, then searched again from the same time as it's written
+    let departure_time = search_params(
+        station_id,
+        time,
+        minutes,
+        &mode,
+        direction,
+        &data,
+        &day_filter,
+        &overlays,
+    )?
+    .departure_time;
+    let time = departure_time.map_or(time, |departure_time| {
+        TimeFilter::Local(departure_time.naive_local())
+    });
+    Ok((
+        ContentType::new("application", "x-ndjson"),
+        TextStream! {
+            let search_params = search_params(
+                station_id, time, minutes, &mode, direction, &data, &day_filter, &overlays,
+            );
+            if let Ok(search_params) = search_params {
+                if explain {
+                    for explained in explained_discoveries(&data, search_params) {
+                        yield format!("{}\n", serde_json::to_string(&explained).unwrap());
+                    }
+                } else {
+                    for discovery in discoveries(&data, search_params) {
+                        yield format!("{}\n", serde_json::to_string(&discovery).unwrap());
+                    }
+                }
+            }
+        },
+    ))
+}
+
+/// Ids of the stops and trips needed to draw a search, without the stops and trips themselves
+#[derive(Serialize)]
+struct RequiredIds {
+    stops: Vec<StopId>,
+    trips: Vec<TripId>,
+}
+
+/// The stops and trips the radar of the same search needs, so clients can fetch just the ones they don't have
+#[get("/data/required/<station_id>/<time>?<minutes>&<mode>&<direction>")]
+#[allow(clippy::too_many_arguments)]
+fn required(
+    station_id: NonZeroU32,
+    time: TimeFilter,
+    minutes: Option<i64>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+) -> Result<(ContentType, String), Status> {
+    let data = search_data(&timetables.current(), station_id, minutes)?;
+    let search_params = search_params(
+        station_id, time, minutes, &mode, direction, &data, day_filter, overlays,
+    )?;
+    let required = required_data(&data, search_params);
+    let mut stops: Vec<_> = required.stops.into_iter().collect();
+    stops.sort();
+    let mut trips: Vec<_> = required.trips.into_iter().collect();
+    trips.sort();
+    Ok((
+        ContentType::JSON,
+        serde_json::to_string(&RequiredIds { stops, trips }).unwrap(),
+    ))
+}
+
+/// The stops and trips needed for a search which the client doesn't hold yet, the body is the JSON manifest of what it holds
+#[post(
+    "/data/sync/<station_id>/<time>?<minutes>&<mode>&<direction>",
+    data = "<manifest>"
+)]
+#[allow(clippy::too_many_arguments)]
+async fn sync(
+    station_id: NonZeroU32,
+    time: TimeFilter,
+    minutes: Option<i64>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    manifest: Data<'_>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+) -> Result<(ContentType, String), Status> {
+    let data = search_data(&timetables.current(), station_id, minutes)?;
+    let manifest = manifest
+        .open(MAX_MANIFEST_MEBIBYTES.mebibytes())
+        .into_string()
+        .await
+        .map_err(|_| Status::BadRequest)?;
+    if !manifest.is_complete() {
+        return Err(Status::PayloadTooLarge);
+    }
+    let manifest: Manifest = serde_json::from_str(&manifest).map_err(|_| Status::BadRequest)?;
+    let search_params = search_params(
+        station_id, time, minutes, &mode, direction, &data, day_filter, overlays,
+    )?;
+    let required = required_data(&data, search_params);
+    Ok((
+        ContentType::JSON,
+        serde_json::to_string(&data.sync_increment(&required, &manifest)).unwrap(),
+    ))
+}
+
+/// A manifest of every stop and trip is a few megabytes
+const MAX_MANIFEST_MEBIBYTES: u64 = 8;
+
+/// Each session holds the manifest of a client, which is a few kilobytes after a few searches
+const MAX_SYNC_SESSIONS: usize = 1000;
+
+/// The same increment as `POST /data/sync` as MessagePack sealed with a header and checksum, on what the client holds at the version of it's session rather than a manifest, along with the version it holds once it's applied
+///
+/// Without a version, or with one of a session which has been dropped, the client starts again in a new session
+#[get("/data/sync/<station_id>/<time>?<minutes>&<mode>&<direction>&<session>&<version>")]
+#[allow(clippy::too_many_arguments)]
+fn sync_session(
+    station_id: NonZeroU32,
+    time: TimeFilter,
+    minutes: Option<i64>,
+    mode: TransitModes,
+    direction: Option<DirectionId>,
+    session: Option<u64>,
+    version: Option<u32>,
+    timetables: &State<Arc<Timetables>>,
+    day_filter: &State<db::DayFilter>,
+    overlays: &State<Overlays>,
+    sync_sessions: &State<Mutex<SyncSessions>>,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let held = match (session, version) {
+        (Some(session), Some(version)) => Some(SyncVersion { session, version }),
+        (None, None) => None,
+        _ => return Err(Status::BadRequest),
+    };
+    let data = search_data(&timetables.current(), station_id, minutes)?;
+    let search_params = search_params(
+        station_id, time, minutes, &mode, direction, &data, day_filter, overlays,
+    )?;
+    let required = required_data(&data, search_params);
+    let increment = sync_sessions.lock().unwrap().sync(&data, &required, held);
+    Ok((
+        ContentType::Binary,
+        seal(&rmp_serde::to_vec_named(&increment).unwrap()),
+    ))
+}
+
+#[get("/schema/tree.json")]
+fn tree_schema() -> (ContentType, &'static str) {
+    (ContentType::JSON, TREE_SCHEMA)
+}
+
+/// Points of interest and districts drawn on the radar, loaded once for the life of the server
+#[derive(Clone, Copy)]
+struct Overlays {
+    pois: &'static [Poi],
+    districts: &'static [District],
+    /// Where the trips on the radar link to
+    journey_url: Option<&'static JourneyUrl>,
+}
+
+/// The data to search from the station on, which is read in first when the stop times are read in lazily
+fn search_data(
+    timetable: &Arc<Timetable>,
+    station_id: StopId,
+    minutes: Option<i64>,
+) -> Result<SearchData, Status> {
+    let max_duration = Duration::minutes(minutes.unwrap_or(DEFAULT_MAX_DURATION_MINS));
+    timetable
+        .search_data(station_id, max_duration)
+        .map_err(|err| {
+            eprintln!(
+                "Reading in the stop times around {} failed : {}",
+                station_id, err
+            );
+            Status::InternalServerError
+        })
+}
+
+/// A radar shared through the cache, or searched on stop times read in for it's origin, which aren't cached
+enum SearchedRadar<'d> {
+    Cached(CachedRadar),
+    Uncached(Box<Radar<'d>>),
+}
+
+impl<'d> SearchedRadar<'d> {
+    fn radar(&self) -> &Radar<'_> {
+        match self {
+            SearchedRadar::Cached(radar) => radar.radar(),
+            SearchedRadar::Uncached(radar) => radar,
+        }
+    }
+}
+
+/// Searches, or reuses a recent search for the same minute
+#[allow(clippy::too_many_arguments)]
+fn radar_search<'s, 'd>(
+    station_id: NonZeroU32,
+    time: TimeFilter,
+    minutes: Option<i64>,
+    mode: &'s TransitModes,
+    direction: Option<DirectionId>,
+    confidence: Option<u8>,
+    max_km: Option<f64>,
+    missed: Option<i64>,
+    frequency: bool,
+    scale: RadialScale,
+    max_legs: &MaxLegs,
+    grace: Option<i64>,
+    prefer_trips: Vec<TripId>,
+    data: &'d SearchData,
+    slugs: &'s StationSlugs,
+    importance: &'d Importance,
+    day_filter: &db::DayFilter,
+    overlays: &Overlays,
+    cache: &RadarCache,
+) -> Result<(SearchedRadar<'d>, UrlSearchParams<'s>), Status> {
+    match data {
+        SearchData::Loaded(timetable) => searched(
+            station_id,
+            time,
+            minutes,
+            mode,
+            direction,
+            confidence,
+            max_km,
+            missed,
+            frequency,
+            scale,
+            max_legs,
+            grace,
+            prefer_trips,
+            timetable.data(),
+            slugs,
+            importance,
+            day_filter,
+            overlays,
+            |key, search_params| {
+                let overlays = *overlays;
+                SearchedRadar::Cached(cache.get_or_search(key, timetable, |timetable| {
+                    search(
+                        timetable.data(),
+                        searching(timetable, overlays, search_params),
+                    )
+                }))
+            },
+        ),
+        SearchData::Materialised(data) => searched(
+            station_id,
+            time,
+            minutes,
+            mode,
+            direction,
+            confidence,
+            max_km,
+            missed,
+            frequency,
+            scale,
+            max_legs,
+            grace,
+            prefer_trips,
+            data,
+            slugs,
+            importance,
+            day_filter,
+            overlays,
+            |_key, search_params| SearchedRadar::Uncached(Box::new(search(data, search_params))),
+        ),
+    }
+}
+
+/// The parameters of a search, borrowing from the timetable it's searched on and the overlays rather than what they were checked with
+fn searching<'t>(
+    timetable: &'t Timetable,
+    overlays: Overlays,
+    search_params: SearchParams,
+) -> SearchParams<'t> {
+    let SearchParams {
         origin,
         departure_time,
         max_duration,
-        modes: Cow::Borrowed(&mode.0),
+        modes,
+        direction,
+        pois: _,
+        districts: _,
+        confidence,
+        max_km,
+        missed,
+        journey_url: _,
+        frequency,
+        scale,
+        max_legs,
+        grace,
+        importance,
+        prefer_trips,
+    } = search_params;
+    SearchParams {
+        origin: timetable
+            .data()
+            .get_stop(origin.stop_id)
+            .expect("origin to be in the timetable it was checked with"),
+        departure_time,
+        max_duration,
+        modes: Cow::Owned(modes.into_owned()),
+        direction,
+        pois: overlays.pois,
+        districts: overlays.districts,
+        confidence,
+        max_km,
+        missed,
+        journey_url: overlays.journey_url,
+        frequency,
+        scale,
+        max_legs,
+        grace,
+        importance: importance.map(|_| &timetable.importance),
+        prefer_trips,
+    }
+}
+
+/// Trips which left longer ago than this can't be caught by running late
+const MAX_GRACE_MINS: i64 = 10;
+
+/// Checks the parameters of a radar search and runs it
+#[allow(clippy::too_many_arguments)]
+fn searched<'s, 'd, 'r>(
+    station_id: NonZeroU32,
+    time: TimeFilter,
+    minutes: Option<i64>,
+    mode: &'s TransitModes,
+    direction: Option<DirectionId>,
+    confidence: Option<u8>,
+    max_km: Option<f64>,
+    missed: Option<i64>,
+    frequency: bool,
+    scale: RadialScale,
+    max_legs: &MaxLegs,
+    grace: Option<i64>,
+    prefer_trips: Vec<TripId>,
+    data: &'d GTFSData,
+    slugs: &'s StationSlugs,
+    importance: &'d Importance,
+    day_filter: &db::DayFilter,
+    overlays: &Overlays,
+    run: impl FnOnce(RadarKey, SearchParams<'d>) -> SearchedRadar<'r>,
+) -> Result<(SearchedRadar<'r>, UrlSearchParams<'s>), Status> {
+    if matches!(confidence, Some(confidence) if confidence == 0 || confidence > 100) {
+        return Err(Status::BadRequest);
+    }
+    check_max_km(max_km)?;
+    if matches!(missed, Some(missed) if !(1..=60).contains(&missed)) {
+        return Err(Status::BadRequest);
+    }
+    if matches!(grace, Some(grace) if !(1..=MAX_GRACE_MINS).contains(&grace)) {
+        return Err(Status::BadRequest);
+    }
+    let search_params = SearchParams {
+        confidence,
+        max_km,
+        missed: missed.map(Duration::minutes),
+        frequency,
+        scale,
+        max_legs: max_legs.clone(),
+        grace: grace.map(Duration::minutes),
+        importance: Some(importance),
+        prefer_trips,
+        ..search_params(
+            station_id, time, minutes, mode, direction, data, day_filter, overlays,
+        )?
+    };
+    let effective_departure_time = search_params
+        .departure_time
+        .expect("departure time to be set");
+    let key = RadarKey {
+        origin: station_id,
+        departure_minute: effective_departure_time.timestamp() / 60,
+        max_minutes: search_params.max_duration.num_minutes(),
+        modes: RadarKey::modes(&mode.0),
+        direction,
+        confidence,
+        max_metres: max_km.map(|max_km| (max_km * 1000.).round() as u64),
+        missed_minutes: missed,
+        frequency,
+        scale,
+        max_legs: max_legs.clone(),
+        grace_minutes: grace,
+        prefer_trips: search_params.prefer_trips.clone(),
     };
     let url_search_params = UrlSearchParams {
         station_id,
-        departure_time,
-        max_duration,
+        slugs,
+        departure_time: match time {
+            TimeFilter::Now => None,
+            TimeFilter::Local(_) => Some(effective_departure_time),
+        },
+        max_duration: search_params.max_duration,
         modes: Cow::Borrowed(&mode.0),
+        direction,
+        confidence,
+        max_km,
+        missed,
+        palette: Palette::Route,
+        frequency,
+        scale,
+        fitted: false,
+        max_legs: max_legs.clone(),
+        grace,
     };
-    let radar = search(data, search_params);
-    let refresh = refresh.unwrap_or(false) && matches!(time, TimeFilter::Now);
-    let mut svg = Vec::new();
-    radar
-        .write_svg_to(&mut io::Cursor::new(&mut svg), url_search_params, refresh)
-        .unwrap();
-    (ContentType::SVG, String::from_utf8(svg).unwrap())
+    Ok((run(key, search_params), url_search_params))
+}
+
+/// Checks the parameters of a search, the departure time is always set, to now if it isn't given
+#[allow(clippy::too_many_arguments)]
+fn search_params<'d>(
+    station_id: NonZeroU32,
+    time: TimeFilter,
+    minutes: Option<i64>,
+    mode: &TransitModes,
+    direction: Option<DirectionId>,
+    data: &'d GTFSData,
+    day_filter: &db::DayFilter,
+    overlays: &Overlays,
+) -> Result<SearchParams<'d>, Status> {
+    let origin = data
+        .get_stop(station_id)
+        .filter(|stop| stop.is_station())
+        .ok_or(Status::NotFound)?;
+    let departure_time = match time {
+        TimeFilter::Now => None,
+        TimeFilter::Local(dt) => Some(chrono_tz::Europe::Berlin.from_local_datetime(&dt).unwrap()),
+    };
+    let effective_departure_time = departure_time
+        .unwrap_or_else(|| chrono::Utc::now().with_timezone(&chrono_tz::Europe::Berlin));
+    let (day, _time) = day_time(effective_departure_time);
+    if matches!(direction, Some(direction) if direction > 1) {
+        return Err(Status::BadRequest);
+    }
+    if !day_filter.includes(day) {
+        // this server only has the timetable for one day
+        return Err(Status::NotFound);
+    }
+    let max_duration = Duration::minutes(minutes.unwrap_or(DEFAULT_MAX_DURATION_MINS));
+    Ok(SearchParams {
+        origin,
+        departure_time: Some(effective_departure_time),
+        max_duration,
+        modes: Cow::Owned(mode.0.clone()),
+        direction,
+        pois: overlays.pois,
+        districts: overlays.districts,
+        confidence: None,
+        max_km: None,
+        missed: None,
+        journey_url: overlays.journey_url,
+        frequency: false,
+        scale: RadialScale::Linear,
+        max_legs: MaxLegs::default(),
+        grace: None,
+        importance: None,
+        prefer_trips: vec![],
+    })
+}
+
+/// A distance limit is a positive number of kilometres
+fn check_max_km(max_km: Option<f64>) -> Result<(), Status> {
+    match max_km {
+        Some(max_km) if !(max_km.is_finite() && max_km > 0.) => Err(Status::BadRequest),
+        _ => Ok(()),
+    }
 }
 
 #[get("/?<q>")]
 fn station_search(
     q: Option<&str>,
-    data: &State<Arc<GTFSData>>,
-    suggester: &State<Suggester<(StopId, usize)>>,
+    timetables: &State<Arc<Timetables>>,
 ) -> (Status, content::RawHtml<String>) {
-    let (status, main) = station_search_xml(q, data, suggester);
+    let (status, main) = station_search_xml(q, timetables);
     let input_args: Cow<_> = if let Some(q) = q {
         if !q.is_empty() {
-            format!(r#"value="{}""#, q).into()
+            format!(r#"value="{}""#, Escaped(q)).into()
         } else {
             "".into()
         }
@@ -130,7 +1383,7 @@ fn station_search(
         include_str!("station_search.html"),
         style = include_str!("style.css"),
         script = include_str!("script.js"),
-        date = data.timetable_start_date(),
+        date = timetables.current().data().timetable_start_date(),
         main = main,
         input_args = input_args
     );
@@ -138,15 +1391,14 @@ fn station_search(
 }
 
 #[get("/auto?<q>")]
-fn station_search_xml(
-    q: Option<&str>,
-    data: &State<Arc<GTFSData>>,
-    suggester: &State<Suggester<(StopId, usize)>>,
-) -> (Status, String) {
+fn station_search_xml(q: Option<&str>, timetables: &State<Arc<Timetables>>) -> (Status, String) {
+    let timetable = timetables.current();
     if let Some(q) = q {
-        if let Ok(top_matches) = station_name_search::station_search_handler(q, data, suggester) {
+        if let Ok(top_matches) =
+            station_name_search::station_search_handler(q, timetable.data(), &timetable.suggester)
+        {
             let mut string = String::new();
-            write_results(&mut string, top_matches).unwrap();
+            write_results(&mut string, top_matches, &timetable.slugs).unwrap();
             (Status::Ok, string)
         } else {
             (
@@ -159,15 +1411,37 @@ fn station_search_xml(
     }
 }
 
+/// Every station's name, which the station search scans on the client before asking `/auto`, which also knows the aliases
+#[get("/stations.json")]
+fn station_list(timetables: &State<Arc<Timetables>>) -> (ContentType, String) {
+    (ContentType::JSON, timetables.current().station_list.clone())
+}
+
+/// Estimated memory used by the loaded data, to tune `DAY_FILTER` and the feed
+#[get("/footprint")]
+fn footprint(timetables: &State<Arc<Timetables>>) -> String {
+    let timetable = timetables.current();
+    format_footprint(timetable.data(), &timetable.suggester)
+}
+
+fn format_footprint(data: &GTFSData, suggester: &Suggester<(StopId, usize)>) -> String {
+    format!(
+        "{}\nstation search index: {:.1} MB",
+        data.footprint(),
+        suggester.heap_size() as f64 / 1_000_000.
+    )
+}
+
 fn write_results<'s>(
     w: &mut dyn fmt::Write,
     matches: impl IntoIterator<Item = &'s Stop>,
+    slugs: &StationSlugs,
 ) -> fmt::Result {
     write_xml!(w, <main>)?;
     for stop in matches {
         write_xml!(w,
-            <a href={&format!("/depart-from/{id}/now", id = stop.stop_id)}>
-                {stop.full_stop_name}
+            <a href={UrlSearchParams::new(stop.stop_id, slugs)}>
+                {stop.full_stop_name()}
             </a>
         )?;
     }
@@ -177,21 +1451,232 @@ fn write_results<'s>(
 
 #[launch]
 fn rocket() -> _ {
-    let gtfs_dir = std::env::var("GTFS_DIR").unwrap_or_else(|_| "gtfs".to_owned());
+    let gtfs_dirs = std::env::var("GTFS_DIR").unwrap_or_else(|_| "gtfs".to_owned());
     let line_colors_path =
         std::env::var("LINE_COLORS").unwrap_or_else(|_| "./VBB_Colours.csv".to_owned());
-    let gtfs_dir = Path::new(&gtfs_dir);
+    let gtfs_dirs: Vec<_> = if std::env::args().any(|arg| arg == "--demo") {
+        // the made up feed is written out and loaded like any other, so that nothing needs downloading
+        let demo_dir = std::env::temp_dir().join("transit-radar-demo");
+        std::fs::create_dir_all(&demo_dir).expect("demo feed dir");
+        demo::write_demo_feed(&demo_dir).expect("demo feed to be written");
+        vec![demo_dir]
+    } else {
+        std::env::split_paths(&gtfs_dirs).collect()
+    };
 
-    let colors = db::load_colors(Path::new(&line_colors_path)).expect(&line_colors_path);
-    let data =
-        Arc::new(db::load_data(gtfs_dir, db::DayFilter::All, colors).expect("gtfs data to load"));
+    let mut colors = RouteColors::new();
+    colors
+        .load_all(std::env::split_paths(&line_colors_path))
+        .expect(&line_colors_path);
+    if let Ok(route_type_colors) = std::env::var("ROUTE_TYPE_COLORS") {
+        colors
+            .set_route_type_colors_from_config(&route_type_colors)
+            .expect("ROUTE_TYPE_COLORS");
+    }
+    let mut short_names = ShortNames::new();
+    if let Some(rules_path) = std::env::var_os("SHORT_NAME_RULES") {
+        short_names
+            .load_rules(Path::new(&rules_path))
+            .expect("SHORT_NAME_RULES");
+    }
+    if let Ok(profiles) = std::env::var("SHORT_NAMES") {
+        short_names
+            .set_feed_profiles(&profiles)
+            .expect("SHORT_NAMES");
+    }
+    let day_filter: db::DayFilter = std::env::var("DAY_FILTER")
+        .map(|day_filter| day_filter.parse().expect("DAY_FILTER"))
+        .unwrap_or(db::DayFilter::All);
+    // the overlays live as long as the server, so that searches borrowing them can be cached
+    let source = TimetableSource {
+        gtfs_dirs,
+        colors,
+        short_names,
+        day_filter,
+        malformed_rows: std::env::var("MALFORMED_ROWS")
+            .map(|malformed_rows| malformed_rows.parse().expect("MALFORMED_ROWS"))
+            .unwrap_or_default(),
+        slug_redirects: std::env::var_os("SLUG_REDIRECTS").map(PathBuf::from),
+        station_aliases: std::env::var_os("STATION_ALIASES").map(PathBuf::from),
+        importance_weights: std::env::var("IMPORTANCE_WEIGHTS")
+            .map(|weights| weights.parse().expect("IMPORTANCE_WEIGHTS"))
+            .unwrap_or_default(),
+        ridership: std::env::var_os("RIDERSHIP").map(PathBuf::from),
+        lazy_stations: std::env::var("LAZY_STOP_TIMES").ok().map(|stations| {
+            stations
+                .parse()
+                .expect("LAZY_STOP_TIMES to be a number of stations")
+        }),
+        stop_times_access: stop_times_access(std::env::var_os("MAP_STOP_TIMES").is_some()),
+        bundle: std::env::var_os("BUNDLE").map(PathBuf::from),
+    };
+    let timetable = source.load().expect("gtfs data to load");
+    let timetables = Timetables::new(timetable, Some(source));
+
+    let pois = std::env::var("POIS")
+        .map(|pois_path| load_pois(Path::new(&pois_path)).expect(&pois_path))
+        .unwrap_or_default();
+    let districts = std::env::var("DISTRICTS")
+        .map(|districts_path| load_districts(Path::new(&districts_path)).expect(&districts_path))
+        .unwrap_or_default();
+
+    let journey_url = std::env::var("JOURNEY_URL")
+        .ok()
+        .map(|template| &*Box::leak(Box::new(JourneyUrl::new(template))));
+
+    let overlays = Overlays {
+        pois: Vec::leak(pois),
+        districts: Vec::leak(districts),
+        journey_url,
+    };
+
+    let admin_token = AdminToken(std::env::var("ADMIN_TOKEN").ok());
 
-    let suggester = db::build_station_word_index(&data);
+    let schedule = std::env::var("SCHEDULE")
+        .map(|schedule| scheduler::parse_schedule(&schedule).expect("SCHEDULE"))
+        .unwrap_or_default();
 
-    rocket::build()
-        .manage(data)
-        .manage(suggester)
-        .mount("/", routes![index, station_search, station_search_xml])
+    let rocket = server(timetables, admin_token, day_filter, overlays, schedule)
+        .manage(PublicUrl(std::env::var("PUBLIC_URL").ok()));
+    let rocket = with_bot(
+        rocket,
+        std::env::var("TELEGRAM_SECRET").ok(),
+        std::env::var("SLACK_TOKEN").ok(),
+    );
+    with_tls(
+        rocket,
+        std::env::var_os("TLS_CERTS"),
+        std::env::var_os("TLS_KEY"),
+    )
+}
+
+/// Stop times read in lazily are read through a memory map when it's asked for
+#[cfg(feature = "mmap")]
+fn stop_times_access(mapped: bool) -> StopTimesAccess {
+    if mapped {
+        StopTimesAccess::Mapped
+    } else {
+        StopTimesAccess::Read
+    }
+}
+
+#[cfg(not(feature = "mmap"))]
+fn stop_times_access(mapped: bool) -> StopTimesAccess {
+    if mapped {
+        panic!("MAP_STOP_TIMES needs the server to be built with `--features mmap`");
+    }
+    StopTimesAccess::Read
+}
+
+/// Answers chat messages when the secret of a chat app is given
+#[cfg(feature = "bot")]
+fn with_bot(
+    rocket: rocket::Rocket<rocket::Build>,
+    telegram_secret: Option<String>,
+    slack_token: Option<String>,
+) -> rocket::Rocket<rocket::Build> {
+    bot::mount(
+        rocket,
+        bot::BotTokens {
+            telegram_secret,
+            slack_token,
+        },
+    )
+}
+
+#[cfg(not(feature = "bot"))]
+fn with_bot(
+    rocket: rocket::Rocket<rocket::Build>,
+    telegram_secret: Option<String>,
+    slack_token: Option<String>,
+) -> rocket::Rocket<rocket::Build> {
+    if telegram_secret.is_some() || slack_token.is_some() {
+        panic!("TELEGRAM_SECRET and SLACK_TOKEN need the server to be built with `--features bot`");
+    }
+    rocket
+}
+
+/// Serves HTTPS when the certificate chain and private key are both given, which also lets clients use HTTP/2
+#[cfg(feature = "tls")]
+fn with_tls(
+    rocket: rocket::Rocket<rocket::Build>,
+    certs: Option<std::ffi::OsString>,
+    key: Option<std::ffi::OsString>,
+) -> rocket::Rocket<rocket::Build> {
+    match (certs, key) {
+        (Some(certs), Some(key)) => {
+            let figment = rocket
+                .figment()
+                .clone()
+                .merge(("tls.certs", PathBuf::from(certs)))
+                .merge(("tls.key", PathBuf::from(key)));
+            rocket.configure(figment)
+        }
+        (None, None) => rocket,
+        _ => panic!("TLS_CERTS and TLS_KEY must be set together"),
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn with_tls(
+    rocket: rocket::Rocket<rocket::Build>,
+    certs: Option<std::ffi::OsString>,
+    key: Option<std::ffi::OsString>,
+) -> rocket::Rocket<rocket::Build> {
+    if certs.is_some() || key.is_some() {
+        panic!("TLS_CERTS and TLS_KEY need the server to be built with `--features tls`");
+    }
+    rocket
+}
+
+/// The server for the loaded data, with all the routes mounted
+fn server(
+    timetables: Timetables,
+    admin_token: AdminToken,
+    day_filter: db::DayFilter,
+    overlays: Overlays,
+    schedule: Vec<(scheduler::Job, scheduler::Schedule)>,
+) -> rocket::Rocket<rocket::Build> {
+    let timetable = timetables.current();
+    eprintln!(
+        "{}",
+        format_footprint(timetable.data(), &timetable.suggester)
+    );
+
+    let rocket = rocket::build()
+        .manage(Arc::new(timetables))
+        .manage(admin_token)
+        .manage(day_filter)
+        .manage(overlays)
+        .manage(Arc::new(RadarCache::default()))
+        .manage(Mutex::new(SyncSessions::new(MAX_SYNC_SESSIONS)))
+        .mount(
+            "/",
+            routes![
+                index,
+                tree,
+                tree_schema,
+                reachable,
+                reach,
+                isochrone,
+                stream,
+                required,
+                sync,
+                sync_session,
+                station_search,
+                station_search_xml,
+                station_list,
+                footprint,
+                admin::status,
+                admin::reload,
+                ask::ask,
+                share,
+                weekly,
+                site::robots,
+                site::sitemap
+            ],
+        );
+    scheduler::mount(rocket, schedule)
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
@@ -220,3 +1705,1439 @@ impl std::fmt::Display for TimeFilter {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::well_formed::assert_well_formed;
+    use radar_search::search_data::Day;
+    use rocket::local::blocking::Client;
+    use transit_radar::gtfs::demo::{demo_data, load_demo_feed};
+    use transit_radar::gtfs::{bundle, importance::Weights, ingest::Strictness};
+
+    /// Each server owns it's timetable, as it would replace it on a reload
+    fn demo_timetable() -> Timetable {
+        let (data, stop_ids) = load_demo_feed();
+        let slugs = StationSlugs::new(&data);
+        Timetable::new(data, stop_ids, slugs, Importance::default())
+    }
+
+    const ADMIN_TOKEN: &str = "let me in";
+
+    fn client() -> Client {
+        client_of(Timetables::new(demo_timetable(), None))
+    }
+
+    fn client_of(timetables: Timetables) -> Client {
+        let overlays = Overlays {
+            pois: &[],
+            districts: &[],
+            journey_url: None,
+        };
+        Client::tracked(server(
+            timetables,
+            AdminToken(Some(ADMIN_TOKEN.to_owned())),
+            db::DayFilter::All,
+            overlays,
+            vec![],
+        ))
+        .unwrap()
+    }
+
+    fn station_id(name: &str) -> StopId {
+        demo_data()
+            .stops()
+            .find(|stop| stop.is_station() && stop.full_stop_name() == name)
+            .unwrap()
+            .stop_id
+    }
+
+    const ALEXANDERPLATZ: &str = "/depart-from/alexanderplatz/2024-01-15T10:00:00";
+
+    /// The radar from Alexanderplatz at 10:00 with the query, such as `?missed=5`
+    fn radar(client: &Client, query: &str) -> String {
+        let response = client
+            .get(format!("{}{}", ALEXANDERPLATZ, query))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok, "{}", query);
+        response.into_string().unwrap()
+    }
+
+    /// Fails unless the radar's toggle of a flag links to the radar without any
+    fn assert_toggles_off(svg: &str, toggle: &str) {
+        assert!(
+            svg.contains(&format!(
+                r#"<a href="{}"><text id="{}-toggle""#,
+                ALEXANDERPLATZ, toggle
+            )),
+            "{}",
+            toggle
+        );
+    }
+
+    /// Fails unless the radar is refused with each of the values of the flag
+    fn assert_bad_values(client: &Client, flag: &str, values: &[&str]) {
+        for value in values {
+            let response = client
+                .get(format!("{}?{}={}", ALEXANDERPLATZ, flag, value))
+                .dispatch();
+            assert_eq!(response.status(), Status::BadRequest, "{}={}", flag, value);
+        }
+    }
+
+    #[test]
+    fn station_search_finds_stations() {
+        let client = client();
+        let response = client.get("/auto?q=alex").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let results = response.into_string().unwrap();
+        assert_well_formed(&results);
+        assert!(results.contains(r#"<a href="/depart-from/alexanderplatz/now">Alexanderplatz</a>"#));
+
+        let response = client.get("/?q=alex").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.into_string().unwrap().contains("Alexanderplatz"));
+
+        let response = client.get("/stations.json").dispatch();
+        assert!(response
+            .into_string()
+            .unwrap()
+            .contains(r#""name":"Alexanderplatz","slug":"alexanderplatz""#));
+    }
+
+    #[test]
+    fn search_query_is_escaped() {
+        let client = client();
+        let response = client
+            .get("/?q=%22%3E%3Cscript%3Ealert(1)%3C/script%3E")
+            .dispatch();
+        let page = response.into_string().unwrap();
+        assert!(page.contains(r#"value="&quot;&gt;&lt;script&gt;alert(1)&lt;/script&gt;""#));
+        assert!(!page.contains("<script>alert"));
+    }
+
+    #[test]
+    fn radar_svg() {
+        let client = client();
+        let response = client
+            .get("/depart-from/alexanderplatz/2024-01-15T10:00:00?mode=sbahn,ubahn")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::SVG));
+        let expires = response.headers().get_one("X-Radar-Expires").unwrap();
+        assert!(expires.starts_with("2024-01-15T10:"), "{}", expires);
+        let svg = response.into_string().unwrap();
+        assert_well_formed(&svg);
+        assert!(svg.contains("<title>Alexanderplatz departures: Transit Radar</title>"));
+        assert!(svg.contains(r#"class="Connection "#));
+    }
+
+    #[test]
+    fn tree_json() {
+        let client = client();
+        let response = client
+            .get(format!(
+                "/depart-from/{}/2024-01-15T10:00:00/tree.json",
+                station_id("Alexanderplatz")
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let tree: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(tree["version"], 1);
+        let reached: Vec<&str> = tree["stations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|station| !station["earliest_arrival"].is_null())
+            .map(|station| station["name"].as_str().unwrap())
+            .collect();
+        for station in ["Friedrichstr.", "Ostkreuz", "Stadtmitte", "Senefelderplatz"] {
+            assert!(reached.contains(&station), "{:?}", reached);
+        }
+        // trams and buses aren't searched by default
+        assert!(!reached.contains(&"Nordbahnhof"), "{:?}", reached);
+        let origin = &tree["stations"][tree["origin"].as_u64().unwrap() as usize];
+        assert_eq!(origin["transfers"], 0);
+        assert!(
+            tree["stations"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .all(|station| station["earliest_arrival"].is_null()
+                    == station["transfers"].is_null())
+        );
+    }
+
+    #[test]
+    fn required_and_sync() {
+        let client = client();
+        let url = |endpoint: &str| {
+            format!(
+                "/data/{}/{}/2024-01-15T10:00:00",
+                endpoint,
+                station_id("Hauptbahnhof")
+            )
+        };
+        let response = client.get(url("required")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let required: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert!(!required["trips"].as_array().unwrap().is_empty());
+
+        let response = client
+            .post(url("sync"))
+            .body(r#"{"stops": [], "trips": []}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let increment: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert!(increment["timetable"].is_object());
+        // a client holding nothing gets every trip the search needs
+        assert_eq!(
+            increment["trips"].as_array().unwrap().len(),
+            required["trips"].as_array().unwrap().len()
+        );
+    }
+
+    #[test]
+    fn sync_in_a_session() {
+        use radar_search::sync::{unseal, VersionedIncrement};
+
+        let client = client();
+        let sync = |query: &str| -> VersionedIncrement {
+            let response = client
+                .get(format!(
+                    "/data/sync/{}/2024-01-15T10:00:00{}",
+                    station_id("Hauptbahnhof"),
+                    query
+                ))
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(response.content_type(), Some(ContentType::Binary));
+            let sealed = response.into_bytes().unwrap();
+            rmp_serde::from_slice(unseal(&sealed).unwrap()).unwrap()
+        };
+        let first = sync("");
+        assert_eq!(first.version.version, 1);
+        assert!(first.increment.timetable.is_some());
+        assert!(!first.increment.trips.is_empty());
+
+        // the same search again needs nothing more
+        let second = sync(&format!("?session={}&version=1", first.version.session));
+        assert_eq!(second.version.session, first.version.session);
+        assert_eq!(second.version.version, 2);
+        assert!(second.increment.timetable.is_none());
+        assert!(second.increment.stops.is_empty() && second.increment.trips.is_empty());
+
+        let response = client
+            .get(format!(
+                "/data/sync/{}/2024-01-15T10:00:00?version=2",
+                station_id("Hauptbahnhof")
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn bad_requests() {
+        let client = client();
+        assert_bad_values(&client, "direction", &["2"]);
+        let response = client.get("/depart-from/Atlantis/now").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client
+            .get(format!(
+                "/data/required/{}/now",
+                station_id("Alexanderplatz").get() + 1
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client
+            .post(format!("/data/sync/{}/now", station_id("Alexanderplatz")))
+            .body("not a manifest")
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn radar_formats() {
+        let client = client();
+        let radar = "/depart-from/alexanderplatz/2024-01-15T10:00:00";
+        let response = client.get(format!("{}?format=geojson", radar)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "geo+json"))
+        );
+        let geojson: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let features = geojson["features"].as_array().unwrap();
+        let origin = features
+            .iter()
+            .find(|feature| feature["properties"]["origin"] == true)
+            .unwrap();
+        assert_eq!(origin["properties"]["name"], "Alexanderplatz");
+        assert_eq!(origin["geometry"]["type"], "Point");
+        assert!(features
+            .iter()
+            .any(|feature| feature["geometry"]["type"] == "LineString"));
+
+        let response = client.get(format!("{}?format=dot", radar)).dispatch();
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("text", "vnd.graphviz"))
+        );
+        let dot = response.into_string().unwrap();
+        assert!(dot.starts_with("digraph radar {"), "{}", dot);
+        assert!(
+            dot.contains("[label=\"Alexanderplatz\\n10:00:00\", peripheries=2]"),
+            "{}",
+            dot
+        );
+        assert!(dot.contains(" -> "), "{}", dot);
+
+        let response = client.get(format!("{}?format=text", radar)).dispatch();
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("text", "markdown"))
+        );
+        let text = response.into_string().unwrap();
+        assert!(text.starts_with("# Alexanderplatz departures\n\nWithin 30 min from Alexanderplatz at 10:00 on 15 Jan 2024: "), "{}", text);
+
+        let response = client.get(radar).header(Accept::JSON).dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let tree: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(tree["version"], 1);
+
+        // as browsers ask for a page or an image
+        for accept in [
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            "image/avif,image/webp,*/*",
+            "application/json;q=0.5,image/svg+xml",
+        ] {
+            let response = client
+                .get(radar)
+                .header(Header::new("Accept", accept))
+                .dispatch();
+            assert_eq!(
+                response.content_type(),
+                Some(ContentType::SVG),
+                "{}",
+                accept
+            );
+        }
+
+        let response = client
+            .get(radar)
+            .header(Header::new("Accept", "image/png"))
+            .dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::PNG));
+        let response = client.get(format!("{}?format=png", radar)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let png = response.into_bytes().unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // the width and height in the header
+        assert_eq!(&png[16..24], &[0, 0, 4, 176, 0, 0, 2, 118]);
+        let response = client
+            .get(radar)
+            .header(Header::new("Accept", "image/gif"))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotAcceptable);
+        let response = client.get(format!("{}?format=bmp", radar)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = client
+            .get("/depart-from/Alexanderplatz/2024-01-15T10:00:00?format=geojson")
+            .dispatch();
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("/depart-from/alexanderplatz/2024-01-15T10:00:00?format=geojson")
+        );
+    }
+
+    #[test]
+    fn stations_by_id_and_name_redirect_to_slug() {
+        let client = client();
+        let canonical = "/depart-from/alexanderplatz/2024-01-15T10:00:00?minutes=20";
+        for station in [
+            &station_id("Alexanderplatz").to_string(),
+            // the demo feed's id of the station and of a platform of it
+            "600",
+            "602",
+            "Alexanderplatz",
+            "Alexanderpl",
+        ] {
+            let response = client
+                .get(format!(
+                    "/depart-from/{}/2024-01-15T10:00:00?minutes=20",
+                    station
+                ))
+                .dispatch();
+            assert_eq!(response.status(), Status::SeeOther, "{}", station);
+            assert_eq!(
+                response.headers().get_one("Location"),
+                Some(canonical),
+                "{}",
+                station
+            );
+        }
+
+        let response = client.get(canonical).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .get("/depart-from/Alexanderplatz/now?minutes=20&refresh=true&format=json&prefer=4,7")
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some(
+                "/depart-from/alexanderplatz/now?minutes=20&refresh=true&format=json&prefer=4%2C7"
+            )
+        );
+    }
+
+    fn admin_status(client: &Client) -> serde_json::Value {
+        let response = client.get("/admin/status").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        serde_json::from_str(&response.into_string().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn status() {
+        let client = client();
+        client.get("/depart-from/alexanderplatz/now").dispatch();
+        let status = admin_status(&client);
+        assert_eq!(status["stations"], 14);
+        assert_eq!(status["timetable_start_date"], "20200101");
+        assert_eq!(status["cached_radars"], 1);
+        assert_eq!(status["reloading"], false);
+        assert!(status["memory"]["total"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn reload_needs_token_and_source() {
+        let client = client();
+        let response = client.post("/admin/reload").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .post("/admin/reload")
+            .header(Header::new("Authorization", "Bearer let me out"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .post("/admin/reload")
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", ADMIN_TOKEN),
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotImplemented);
+
+        let timetables = Timetables::new(demo_timetable(), None);
+        let overlays = Overlays {
+            pois: &[],
+            districts: &[],
+            journey_url: None,
+        };
+        let client = Client::tracked(server(
+            timetables,
+            AdminToken(None),
+            db::DayFilter::All,
+            overlays,
+            vec![],
+        ))
+        .unwrap();
+        let response = client
+            .post("/admin/reload")
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", ADMIN_TOKEN),
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn reload_replaces_timetable() {
+        let dir =
+            std::env::temp_dir().join(format!("transit-radar-reload-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        demo::write_demo_feed(&dir).unwrap();
+        let source = TimetableSource {
+            gtfs_dirs: vec![dir.clone()],
+            colors: RouteColors::new(),
+            short_names: ShortNames::new(),
+            day_filter: db::DayFilter::All,
+            malformed_rows: Strictness::Fail,
+            slug_redirects: None,
+            station_aliases: None,
+            importance_weights: Weights::default(),
+            ridership: None,
+            lazy_stations: None,
+            stop_times_access: StopTimesAccess::Read,
+            bundle: None,
+        };
+        let client = client_of(Timetables::new(source.load().unwrap(), Some(source)));
+        let loaded_at = admin_status(&client)["loaded_at"].clone();
+        client.get("/depart-from/alexanderplatz/now").dispatch();
+        let replaced = Arc::downgrade(
+            &client
+                .rocket()
+                .state::<Arc<Timetables>>()
+                .unwrap()
+                .current(),
+        );
+
+        let response = client
+            .post("/admin/reload")
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", ADMIN_TOKEN),
+            ))
+            .dispatch();
+        std::fs::remove_dir_all(dir).unwrap();
+        assert_eq!(response.status(), Status::Ok);
+        let status: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_ne!(status["loaded_at"], loaded_at);
+        assert_eq!(status["feeds"][0]["version"], "demo");
+        assert_eq!(status["ingest"]["files"][0]["file"], "calendar_dates.txt");
+        assert_eq!(status["cached_radars"], 0);
+        // nothing searched keeps the replaced timetable in memory
+        assert!(replaced.upgrade().is_none());
+
+        let response = client.get("/depart-from/alexanderplatz/now").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn lazy_stop_times_search_the_same() {
+        let dir =
+            std::env::temp_dir().join(format!("transit-radar-lazy-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        demo::write_demo_feed(&dir).unwrap();
+        let client = client();
+        let stations: Vec<StopId> = demo_data()
+            .stops()
+            .filter(|stop| stop.is_station())
+            .map(|stop| stop.stop_id)
+            .collect();
+        for &stop_times_access in StopTimesAccess::ALL {
+            let source = TimetableSource {
+                gtfs_dirs: vec![dir.clone()],
+                colors: RouteColors::new(),
+                short_names: ShortNames::new(),
+                day_filter: db::DayFilter::All,
+                malformed_rows: Strictness::Fail,
+                slug_redirects: None,
+                station_aliases: None,
+                importance_weights: Weights::default(),
+                ridership: None,
+                lazy_stations: Some(2),
+                stop_times_access,
+                bundle: None,
+            };
+            let lazy_client = client_of(Timetables::new(source.load().unwrap(), Some(source)));
+            for &station_id in &stations {
+                for minutes in [30, 60] {
+                    let url = format!(
+                        "/depart-from/{}/2024-01-15T10:00:00/tree.json?minutes={}&mode=sbahn,ubahn,bus,tram,regional",
+                        station_id, minutes
+                    );
+                    let tree = |client: &Client| {
+                        let response = client.get(url.clone()).dispatch();
+                        assert_eq!(response.status(), Status::Ok);
+                        serde_json::from_str::<serde_json::Value>(&response.into_string().unwrap())
+                            .unwrap()
+                    };
+                    assert_eq!(
+                        tree(&lazy_client),
+                        tree(&client),
+                        "{} {:?}",
+                        url,
+                        stop_times_access
+                    );
+                }
+            }
+            assert_eq!(admin_status(&lazy_client)["materialised_stations"], 2);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(admin_status(&client)["materialised_stations"].is_null());
+    }
+
+    #[test]
+    fn timetable_from_bundle() {
+        let dir = std::env::temp_dir().join(format!(
+            "transit-radar-bundle-server-test-{}",
+            std::process::id()
+        ));
+        let gtfs_dir = dir.join("gtfs");
+        std::fs::create_dir_all(&gtfs_dir).unwrap();
+        demo::write_demo_feed(&gtfs_dir).unwrap();
+        let bundle_path = dir.join("radar.bundle");
+        bundle::compile(
+            &[&gtfs_dir],
+            db::DayFilter::All,
+            &RouteColors::new(),
+            &ShortNames::new(),
+            &bundle_path,
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&gtfs_dir).unwrap();
+        let source = TimetableSource {
+            gtfs_dirs: vec![],
+            colors: RouteColors::new(),
+            short_names: ShortNames::new(),
+            day_filter: db::DayFilter::All,
+            malformed_rows: Strictness::Fail,
+            slug_redirects: None,
+            station_aliases: None,
+            importance_weights: Weights::default(),
+            ridership: None,
+            lazy_stations: None,
+            stop_times_access: StopTimesAccess::Read,
+            bundle: Some(bundle_path.clone()),
+        };
+        let client = client_of(Timetables::new(source.load().unwrap(), Some(source)));
+        assert_eq!(admin_status(&client)["feeds"][0]["version"], "demo");
+        let response = client
+            .get("/depart-from/alexanderplatz/2024-01-15T10:00:00")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let single_day = TimetableSource {
+            gtfs_dirs: vec![],
+            colors: RouteColors::new(),
+            short_names: ShortNames::new(),
+            day_filter: db::DayFilter::Single(Day::Saturday),
+            malformed_rows: Strictness::Fail,
+            slug_redirects: None,
+            station_aliases: None,
+            importance_weights: Weights::default(),
+            ridership: None,
+            lazy_stations: None,
+            stop_times_access: StopTimesAccess::Read,
+            bundle: Some(bundle_path),
+        };
+        assert!(single_day.load().is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn robots_and_sitemap() {
+        let client = client();
+        let robots = client
+            .get("/robots.txt")
+            .header(Header::new("Host", "radar.example"))
+            .dispatch()
+            .into_string()
+            .unwrap();
+        assert!(robots.contains("Disallow: /depart-from/\n"), "{}", robots);
+        assert!(
+            robots.contains("Sitemap: http://radar.example/sitemap.xml\n"),
+            "{}",
+            robots
+        );
+
+        let response = client
+            .get("/sitemap.xml")
+            .header(Header::new("Host", "radar.example"))
+            .dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::XML));
+        let sitemap = response.into_string().unwrap();
+        assert!(sitemap.starts_with("<?xml"), "{}", sitemap);
+        assert!(sitemap.contains("<loc>http://radar.example/</loc>"));
+        assert!(sitemap
+            .contains("<loc>http://radar.example/share/depart-from/alexanderplatz/now</loc>"));
+        assert_eq!(
+            sitemap.matches("<url>").count(),
+            1 + demo_data().stops().filter(|stop| stop.is_station()).count()
+        );
+
+        let response = client.get("/sitemap.xml").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn jobs_render_ahead() {
+        let overlays = Overlays {
+            pois: &[],
+            districts: &[],
+            journey_url: None,
+        };
+        let client = Client::tracked(
+            server(
+                Timetables::new(demo_timetable(), None),
+                AdminToken(Some(ADMIN_TOKEN.to_owned())),
+                db::DayFilter::All,
+                overlays,
+                scheduler::parse_schedule("sitemap@03:00,sitemap@15:00").unwrap(),
+            )
+            .manage(PublicUrl(Some("https://radar.example/".to_owned()))),
+        )
+        .unwrap();
+        let run = |job: &str| {
+            client
+                .post(format!("/admin/jobs/{}", job))
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", ADMIN_TOKEN),
+                ))
+                .dispatch()
+        };
+
+        let jobs: serde_json::Value =
+            serde_json::from_str(&client.get("/admin/jobs").dispatch().into_string().unwrap())
+                .unwrap();
+        assert_eq!(jobs[0]["job"], "sitemap");
+        assert_eq!(jobs[0]["schedule"], "03:00,15:00");
+        assert_eq!(jobs[1]["schedule"], serde_json::Value::Null);
+        assert_eq!(jobs[1]["runs"], 0);
+
+        assert_eq!(
+            client.post("/admin/jobs/sitemap").dispatch().status(),
+            Status::Unauthorized
+        );
+        assert_eq!(run("backup").status(), Status::NotFound);
+
+        let response = run("sitemap");
+        assert_eq!(response.status(), Status::Ok);
+        let status: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(status["runs"], 1);
+        assert_eq!(status["last_failed"], false);
+        let sitemap = client.get("/sitemap.xml").dispatch().into_string().unwrap();
+        assert!(sitemap
+            .contains("<loc>https://radar.example/share/depart-from/alexanderplatz/now</loc>"));
+
+        let status: serde_json::Value =
+            serde_json::from_str(&run("previews").into_string().unwrap()).unwrap();
+        assert_eq!(status["last_failed"], false);
+        let response = client
+            .get("/depart-from/alexanderplatz/now?format=png")
+            .dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::PNG));
+        assert!(response.headers().get_one("X-Radar-Expires").is_some());
+
+        // the stop times are all loaded, so there's nothing to read in
+        let status: serde_json::Value =
+            serde_json::from_str(&run("popular").into_string().unwrap()).unwrap();
+        assert_eq!(
+            status["last_outcome"],
+            "had nothing to do, all the stop times are loaded"
+        );
+        let jobs: serde_json::Value =
+            serde_json::from_str(&client.get("/admin/jobs").dispatch().into_string().unwrap())
+                .unwrap();
+        assert_eq!(
+            jobs.as_array()
+                .unwrap()
+                .iter()
+                .map(|job| job["runs"].as_u64().unwrap())
+                .collect::<Vec<_>>(),
+            vec![1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn share_page() {
+        let client = client();
+        let page = client
+            .get("/share/depart-from/alexanderplatz/2024-01-15T10:00:00?minutes=20")
+            .header(Header::new("Host", "radar.example"))
+            .dispatch()
+            .into_string()
+            .unwrap();
+        assert!(page
+            .contains(r#"<meta property="og:title" content="Transit radar from Alexanderplatz">"#));
+        assert!(page.contains(r#"<meta property="og:url" content="http://radar.example/share/depart-from/alexanderplatz/2024-01-15T10:00:00?minutes=20">"#));
+        assert!(page.contains("within 20 minutes, departing at 10:00 on Monday 15 January"));
+        assert!(page
+            .contains(r#"<img src="/depart-from/alexanderplatz/2024-01-15T10:00:00?minutes=20""#));
+        assert!(page.contains(r#"<meta property="og:image" content="http://radar.example/depart-from/alexanderplatz/2024-01-15T10:00:00?minutes=20&amp;format=png">"#));
+        assert!(page.contains(r#"<meta name="twitter:card" content="summary_large_image">"#));
+
+        let response = client
+            .get("/share/depart-from/Alexanderplatz/now?minutes=20")
+            .header(Header::new("Host", "radar.example"))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("/share/depart-from/alexanderplatz/now?minutes=20")
+        );
+
+        let response = client
+            .get("/share/depart-from/nowhere-at-all/now")
+            .header(Header::new("Host", "radar.example"))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn weekly_radars() {
+        let client = client();
+        let response = client
+            .get("/weekly/depart-from/alexanderplatz/2024-01-17T10:00:00?minutes=20")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::SVG));
+        let svg = response.into_string().unwrap();
+        assert_well_formed(&svg);
+        assert_eq!(svg.matches(r#"<g class="weekday""#).count(), 7);
+        assert!(svg.contains("Monday 15 Jan"));
+        assert!(svg.contains("Sunday 21 Jan"));
+        assert!(svg
+            .contains(r#"<a href="/depart-from/alexanderplatz/2024-01-21T10:00:00?minutes=20">"#));
+        assert!(!svg.contains("no timetable loaded"));
+
+        let response = client
+            .get("/weekly/depart-from/Alexanderplatz/now")
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("/weekly/depart-from/alexanderplatz/now")
+        );
+    }
+
+    #[test]
+    fn weekly_radars_of_a_single_day_server() {
+        let overlays = Overlays {
+            pois: &[],
+            districts: &[],
+            journey_url: None,
+        };
+        let client = Client::tracked(server(
+            Timetables::new(demo_timetable(), None),
+            AdminToken(None),
+            "mon".parse().unwrap(),
+            overlays,
+            vec![],
+        ))
+        .unwrap();
+        let svg = client
+            .get("/weekly/depart-from/alexanderplatz/2024-01-17T10:00:00")
+            .dispatch()
+            .into_string()
+            .unwrap();
+        assert_eq!(svg.matches("no timetable loaded for this day").count(), 6);
+        assert_eq!(svg.matches("stations reached").count(), 1);
+    }
+
+    #[test]
+    fn confidence_layer() {
+        let client = client();
+        let svg = radar(&client, "?confidence=90");
+        assert!(svg.contains("<g class=\"confidence\">\n<g><title>"));
+        assert_toggles_off(&svg, "confidence");
+        assert_bad_values(&client, "confidence", &["0", "101"]);
+    }
+
+    #[test]
+    fn missed_layer() {
+        let client = client();
+        let svg = radar(&client, "?missed=5");
+        let (_before, missed) = svg.split_once("<g class=\"missed\">").unwrap();
+        let (missed, _after) = missed.split_once("</g>").unwrap();
+        assert!(missed.contains("<path"));
+        assert_toggles_off(&svg, "missed");
+        assert_bad_values(&client, "missed", &["0", "61"]);
+    }
+
+    #[test]
+    fn colour_blind_friendly_palette() {
+        let client = client();
+        let svg = radar(&client, "?palette=cbf");
+        // the mode's colour is drawn over the line's colour in the stylesheet
+        assert!(svg.contains(r##"stroke="#009e73" data-color=""##));
+        assert!(svg.contains(r##"style="--stroke: #009e73; --stroke-dark: #009e73" d="##));
+        assert!(svg.contains(
+            r##"style="--stroke: #0072b2; --stroke-dark: #0072b2; stroke-dasharray: 12 3" d="##
+        ));
+        assert_toggles_off(&svg, "palette");
+        assert!(svg.contains(
+            r#"<a href="/share/depart-from/alexanderplatz/2024-01-15T10:00:00?palette=cbf">"#
+        ));
+
+        let line_colours = radar(&client, "");
+        assert!(!line_colours.contains("stroke-dasharray: 12 3"));
+
+        let card = client
+            .get(format!("{}?palette=cbf&format=png", ALEXANDERPLATZ))
+            .dispatch();
+        assert_eq!(card.status(), Status::Ok);
+        assert_eq!(card.content_type(), Some(ContentType::PNG));
+
+        assert_bad_values(&client, "palette", &["rainbow"]);
+    }
+
+    #[test]
+    fn lines_by_frequency() {
+        let client = client();
+        let svg = radar(&client, "?frequency=true");
+        let widths: std::collections::HashSet<&str> = svg
+            .split("stroke-width: ")
+            .skip(1)
+            .filter_map(|rest| rest.split_once("pt\""))
+            .map(|(width, _)| width)
+            .collect();
+        // the lines through the station don't all run as often
+        assert!(widths.len() > 1, "{:?}", widths);
+        assert_toggles_off(&svg, "frequency");
+
+        let by_mode = radar(&client, "");
+        assert!(!by_mode.contains("; stroke-width: "));
+        assert!(by_mode.contains(
+            r#"<a href="/depart-from/alexanderplatz/2024-01-15T10:00:00?frequency=true"><text id="frequency-toggle""#
+        ));
+    }
+
+    #[test]
+    fn radial_scales() {
+        let client = client();
+        let grid = |svg: &str| {
+            let (_before, grid) = svg.split_once("<g class=\"grid\">").unwrap();
+            grid.split_once("</g>").unwrap().0.to_owned()
+        };
+        let linear = grid(&radar(&client, ""));
+        let svg = radar(&client, "?scale=sqrt");
+        let sqrt = grid(&svg);
+        // the ring at 10 of the 30 minutes moves out from a third of the radius to over half of it
+        assert!(linear.contains(r#"<circle cx="0" cy="0" r="166.66"#));
+        assert!(sqrt.contains(r#"<circle cx="0" cy="0" r="288.67"#));
+        assert_ne!(grid(&radar(&client, "?scale=log")), sqrt);
+        assert_toggles_off(&svg, "scale");
+        assert_bad_values(&client, "scale", &["cubic"]);
+    }
+
+    #[test]
+    fn legs_limited_by_mode() {
+        let client = client();
+        let trips = |svg: &str| svg.matches("<g class=\"trip\"").count();
+        let rail = trips(&radar(&client, ""));
+        let with_buses = trips(&radar(&client, "?mode=sbahn,ubahn,bus"));
+        let one_bus = trips(&radar(&client, "?mode=sbahn,ubahn,bus&legs=bus:1"));
+        let svg = radar(&client, "?mode=sbahn,ubahn,bus&legs=bus:0");
+        assert_eq!(trips(&svg), rail);
+        assert!(rail <= one_bus && one_bus <= with_buses);
+        // the links keep the limits
+        assert!(svg.contains("&amp;scale=sqrt&amp;legs=bus%3A0\""));
+        assert_bad_values(&client, "legs", &["bus"]);
+    }
+
+    #[test]
+    fn preferred_trips_are_drawn_again() {
+        let client = client();
+        let trips = |query: &str| {
+            let svg = radar(&client, query);
+            let mut trips: Vec<String> = svg
+                .split("data-trip=\"")
+                .skip(1)
+                .filter_map(|rest| Some(rest.split_once('"')?.0.to_owned()))
+                .collect();
+            trips.sort();
+            trips
+        };
+        let drawn = trips("");
+        assert!(!drawn.is_empty());
+        let redrawn = trips(&format!("?prefer={}", drawn.join(",")));
+        assert_eq!(redrawn, drawn);
+        assert_eq!(trips("?prefer="), drawn);
+        // the same trips in another order are the same search, which is cached
+        let cache = client.rocket().state::<Arc<RadarCache>>().unwrap();
+        let cached = cache.len();
+        let mut reversed = drawn.clone();
+        reversed.reverse();
+        trips(&format!("?prefer={}", reversed.join(",")));
+        assert_eq!(cache.len(), cached);
+        assert_bad_values(&client, "prefer", &["S5", "0", "1,,x"]);
+    }
+
+    #[test]
+    fn trips_boarded_late() {
+        // the S1 towards Westkreuz left at 10:03, and is boarded as if it left at 10:04
+        let client = client();
+        let svg = |url: &str| client.get(url).dispatch().into_string().unwrap();
+        let boarded_late = "data-departure=\"10:04\"";
+        let on_time = svg("/depart-from/alexanderplatz/2024-01-15T10:04:00");
+        assert!(!on_time.contains(boarded_late));
+        let late = svg("/depart-from/alexanderplatz/2024-01-15T10:04:00?grace=2");
+        assert!(late.contains(boarded_late));
+        // the links keep the grace
+        assert!(late.contains("?scale=sqrt&amp;grace=2\""));
+        assert_bad_values(&client, "grace", &["0", "11"]);
+    }
+
+    #[test]
+    fn minutes_fitted_to_station() {
+        let client = client();
+        let svg = radar(&client, "?minutes=auto");
+        let (_before, lasting) = svg.split_once("and lasting less than ").unwrap();
+        let minutes: i64 = lasting.split_once(' ').unwrap().0.parse().unwrap();
+        assert!((15..=90).contains(&minutes), "{}", minutes);
+        assert_eq!(minutes % 5, 0);
+        // the links fit the radar again rather than keep the minutes
+        assert!(svg.contains("?minutes=auto&amp;scale=sqrt\""));
+        assert!(!svg.contains(&format!("minutes={}", minutes)));
+
+        let share = client
+            .get("/share/depart-from/alexanderplatz/2024-01-15T10:00:00?minutes=auto")
+            .header(Header::new("Host", "radar.example"))
+            .dispatch()
+            .into_string()
+            .unwrap();
+        assert!(share.contains(&format!("within {} minutes", minutes)));
+    }
+
+    #[test]
+    fn trips_link_to_journey_planner() {
+        let svg = client()
+            .get("/depart-from/alexanderplatz/2024-01-15T10:00:00")
+            .dispatch()
+            .into_string()
+            .unwrap();
+        assert!(svg.contains("<g class=\"trip\" data-trip=\""));
+        assert!(!svg.contains("<a href=\"https://example.org"));
+
+        let journey_url =
+            JourneyUrl::new("https://example.org/trip/{trip}?route={route}&time={time}".to_owned());
+        let overlays = Overlays {
+            pois: &[],
+            districts: &[],
+            journey_url: Some(Box::leak(Box::new(journey_url))),
+        };
+        let client = Client::tracked(server(
+            Timetables::new(demo_timetable(), None),
+            AdminToken(None),
+            db::DayFilter::All,
+            overlays,
+            vec![],
+        ))
+        .unwrap();
+        let svg = client
+            .get("/depart-from/alexanderplatz/2024-01-15T10:00:00")
+            .dispatch()
+            .into_string()
+            .unwrap();
+        let (_before, link) = svg
+            .split_once("<a href=\"https://example.org/trip/")
+            .unwrap();
+        let (url, _after) = link.split_once('"').unwrap();
+        let (trip, query) = url.split_once("?route=").unwrap();
+        assert!(trip.parse::<u32>().is_ok());
+        let (route, time) = query.split_once("&amp;time=").unwrap();
+        assert!(route.parse::<u32>().is_ok());
+        assert!(time.starts_with("2024-01-15T10:"), "{}", time);
+    }
+
+    #[test]
+    fn reachable_through_the_day() {
+        let client = client();
+        let response = client
+            .get(format!(
+                "/depart-from/{}/2024-01-15T10:00:00/reachable.json?minutes=20",
+                station_id("Alexanderplatz")
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let reachable: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(reachable["interval_minutes"], 15);
+        assert_eq!(reachable["max_minutes"], 20);
+        let samples = reachable["samples"].as_array().unwrap();
+        assert_eq!(samples.len(), 24 * 4);
+        assert_eq!(samples[0]["departure_time"], "2024-01-15T00:00:00+01:00");
+        assert_eq!(samples[40]["departure_time"], "2024-01-15T10:00:00+01:00");
+        assert!(samples[40]["stations"].as_u64().unwrap() > 0);
+        assert_eq!(admin_status(&client)["cached_reachable"], 1);
+
+        // any time of the day is the same search through it
+        let response = client
+            .get(format!(
+                "/depart-from/{}/2024-01-15T18:30:00/reachable.json?minutes=20",
+                station_id("Alexanderplatz")
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(admin_status(&client)["cached_reachable"], 1);
+
+        let response = client
+            .get(format!(
+                "/depart-from/{}/2024-01-15T10:00:00/reachable.json?minutes=100000",
+                station_id("Alexanderplatz")
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let reachable: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(reachable["max_minutes"], MAX_REACHABLE_MINS);
+    }
+
+    #[test]
+    fn isochrone_bands() {
+        let client = client();
+        let response = client
+            .get("/isochrone/alexanderplatz?time=2024-01-15T10:00:00&minutes=30")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "geo+json"))
+        );
+        let isochrones: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        let features = isochrones["features"].as_array().unwrap();
+        let minutes: Vec<i64> = features
+            .iter()
+            .map(|feature| feature["properties"]["minutes"].as_i64().unwrap())
+            .collect();
+        assert_eq!(minutes, vec![30, 15, 10, 5]);
+        let stations: Vec<u64> = features
+            .iter()
+            .map(|feature| feature["properties"]["stations"].as_u64().unwrap())
+            .collect();
+        assert!(
+            stations.windows(2).all(|pair| pair[0] >= pair[1]),
+            "{:?}",
+            stations
+        );
+        assert!(stations[0] > stations[3], "{:?}", stations);
+        for feature in features {
+            let ring = feature["geometry"]["coordinates"][0].as_array().unwrap();
+            assert_eq!(ring.first(), ring.last());
+            // around Alexanderplatz, as (lon, lat)
+            let lon = ring[0][0].as_f64().unwrap();
+            let lat = ring[0][1].as_f64().unwrap();
+            assert!(
+                (13.0..14.0).contains(&lon) && (52.0..53.0).contains(&lat),
+                "{:?}",
+                ring[0]
+            );
+        }
+
+        let response = client
+            .get("/isochrone/Atlantis?time=2024-01-15T10:00:00")
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn reach_by_stop_id() {
+        let client = client();
+        let response = client
+            .get("/reach/alexanderplatz?time=2024-01-15T10:00:00&minutes=20")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let reach: BTreeMap<String, i64> =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(reach[&station_id("Alexanderplatz").to_string()], 0);
+        assert!(
+            reach.values().all(|&seconds| seconds <= 20 * 60),
+            "{:?}",
+            reach
+        );
+
+        // the same earliest arrivals as the departure tree, which has the stops the stations were reached at
+        let response = client
+            .get(format!(
+                "/depart-from/{}/2024-01-15T10:00:00/tree.json?minutes=20",
+                station_id("Alexanderplatz")
+            ))
+            .dispatch();
+        let tree: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        let ten_am = 10 * 60 * 60;
+        let mut tree_seconds: Vec<i64> = tree["stations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|station| station["earliest_arrival"].as_i64())
+            .map(|arrival| arrival - ten_am)
+            .filter(|&seconds| seconds <= 20 * 60)
+            .collect();
+        tree_seconds.sort_unstable();
+        let mut seconds: Vec<i64> = reach.values().copied().collect();
+        seconds.sort_unstable();
+        assert_eq!(seconds, tree_seconds);
+
+        for (url, status) in [
+            ("/reach/Atlantis?time=2024-01-15T10:00:00", Status::NotFound),
+            ("/reach/alexanderplatz?time=teatime", Status::BadRequest),
+        ] {
+            assert_eq!(client.get(url).dispatch().status(), status, "{}", url);
+        }
+    }
+
+    #[test]
+    fn stream_of_stations_as_they_are_reached() {
+        let client = client();
+        let response = client
+            .get("/stream/from/alexanderplatz?time=2024-01-15T10:00:00&minutes=20")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "x-ndjson"))
+        );
+        let lines: Vec<serde_json::Value> = response
+            .into_string()
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let arrivals: Vec<(u64, i64)> = lines
+            .iter()
+            .filter(|line| line["type"] == "station")
+            .map(|station| {
+                (
+                    station["station_id"].as_u64().unwrap(),
+                    station["earliest_arrival"].as_i64().unwrap(),
+                )
+            })
+            .collect();
+        let ten_am = 10 * 60 * 60;
+        assert_eq!(
+            arrivals[0],
+            (u64::from(station_id("Alexanderplatz").get()), ten_am)
+        );
+        assert!(arrivals.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        assert!(lines.iter().any(|line| line["type"] == "edge"));
+        assert!(lines.iter().all(|line| line.get("why").is_none()));
+
+        let explained: Vec<serde_json::Value> = client
+            .get("/stream/from/alexanderplatz?time=2024-01-15T10:00:00&minutes=20&explain=true")
+            .dispatch()
+            .into_string()
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            explained[0]["why"],
+            "Alexanderplatz is the origin of the search, departing at 10:00:00"
+        );
+        assert!(explained.iter().all(|line| line["why"].is_string()));
+        assert!(explained.len() >= lines.len());
+
+        // each station reached within the minutes is in the reach of the same search
+        let reach: BTreeMap<String, i64> = serde_json::from_str(
+            &client
+                .get("/reach/alexanderplatz?time=2024-01-15T10:00:00&minutes=20")
+                .dispatch()
+                .into_string()
+                .unwrap(),
+        )
+        .unwrap();
+        for (station_id, arrival) in arrivals {
+            if arrival - ten_am <= 20 * 60 {
+                assert!(
+                    reach.contains_key(&station_id.to_string()),
+                    "{}",
+                    station_id
+                );
+            }
+        }
+
+        for (url, status) in [
+            ("/stream/from/Atlantis", Status::NotFound),
+            (
+                "/stream/from/alexanderplatz?time=teatime",
+                Status::BadRequest,
+            ),
+        ] {
+            assert_eq!(client.get(url).dispatch().status(), status, "{}", url);
+        }
+    }
+
+    #[test]
+    fn distance_limit() {
+        let client = client();
+        let tree = |query: &str| -> serde_json::Value {
+            let response = client
+                .get(format!(
+                    "/depart-from/{}/2024-01-15T10:00:00/tree.json{}",
+                    station_id("Alexanderplatz"),
+                    query
+                ))
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            serde_json::from_str(&response.into_string().unwrap()).unwrap()
+        };
+        let reached = |tree: &serde_json::Value| {
+            tree["stations"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|station| !station["earliest_arrival"].is_null())
+                .count()
+        };
+        let everywhere = reached(&tree(""));
+        let nearby = reached(&tree("?max_km=2"));
+        assert!(nearby > 1, "{}", nearby);
+        assert!(nearby < everywhere, "{} of {}", nearby, everywhere);
+
+        let svg = radar(&client, "?max_km=2");
+        assert!(svg.contains("and lasting less than 30 minutes within 2 km</tspan>"));
+        // the radar's links keep the limit
+        assert!(svg.contains(
+            r#"<a href="/depart-from/alexanderplatz/2024-01-15T10:00:00?confidence=90&amp;max_km=2">"#
+        ));
+
+        assert_bad_values(&client, "max_km", &["0", "-1", "inf"]);
+    }
+
+    #[test]
+    fn ask() {
+        let client = client();
+        let ask = |q: &str| {
+            client
+                .get(format!("/ask?q={}", urlencoding::encode(q)))
+                .dispatch()
+        };
+        let response = ask("from Hauptbahnhof in 25 minutes by tram at 10:00");
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let tree: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        let origin = tree["origin"].as_u64().unwrap() as usize;
+        assert_eq!(tree["stations"][origin]["name"], "Hauptbahnhof");
+        let trips = tree["trips"].as_array().unwrap();
+        assert!(!trips.is_empty());
+        assert!(trips.iter().all(|trip| trip["mode"] == "tram"));
+        // within the time, the stations arrived at
+        let latest_arrival = tree["stations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|station| station["earliest_arrival"].as_u64())
+            .max()
+            .unwrap();
+        assert!(latest_arrival <= (10 * 60 + 25) * 60, "{}", latest_arrival);
+
+        let response = ask("Zoologischer Garten by bus or U-Bahn, within 40 at 9:30");
+        assert_eq!(response.status(), Status::Ok);
+        let tree: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        let origin = tree["origin"].as_u64().unwrap() as usize;
+        assert_eq!(tree["stations"][origin]["name"], "Zoologischer Garten");
+        assert!(tree["trips"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|trip| trip["mode"] == "bus" || trip["mode"] == "ubahn"));
+
+        for q in [
+            "",
+            "from",
+            "from Alexanderplatz in 0 minutes",
+            "from Alexanderplatz by rocket",
+            "from Alexanderplatz in 20 minutes please",
+        ] {
+            assert_eq!(ask(q).status(), Status::BadRequest, "{}", q);
+        }
+    }
+
+    #[cfg(feature = "bot")]
+    #[test]
+    fn bot_answers() {
+        let overlays = Overlays {
+            pois: &[],
+            districts: &[],
+            journey_url: None,
+        };
+        let rocket = server(
+            Timetables::new(demo_timetable(), None),
+            AdminToken(None),
+            db::DayFilter::All,
+            overlays,
+            vec![],
+        );
+        let client = Client::tracked(bot::mount(
+            rocket,
+            bot::BotTokens {
+                telegram_secret: Some("telegram secret".to_owned()),
+                slack_token: Some("slack token".to_owned()),
+            },
+        ))
+        .unwrap();
+        let telegram = |secret: &str, text: &str| {
+            let update = serde_json::json!({
+                "update_id": 1,
+                "message": { "message_id": 2, "chat": { "id": 3 }, "text": text },
+            });
+            client
+                .post("/bot/telegram")
+                .header(Header::new("Host", "radar.example"))
+                .header(Header::new(
+                    "X-Telegram-Bot-Api-Secret-Token",
+                    secret.to_owned(),
+                ))
+                .body(update.to_string())
+                .dispatch()
+        };
+
+        let response = telegram("telegram secret", "/radar@radar_bot Alexanderplatz 40");
+        assert_eq!(response.status(), Status::Ok);
+        let reply: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(reply["method"], "sendPhoto");
+        assert_eq!(reply["chat_id"], 3);
+        assert_eq!(
+            reply["photo"],
+            "http://radar.example/depart-from/alexanderplatz/now?minutes=40&format=png"
+        );
+        let text = reply["caption"].as_str().unwrap();
+        assert!(
+            text.starts_with("Within 40 min from Alexanderplatz at "),
+            "{}",
+            text
+        );
+        assert!(
+            text.ends_with(
+                "\nhttp://radar.example/share/depart-from/alexanderplatz/now?minutes=40"
+            ),
+            "{}",
+            text
+        );
+        let reply = telegram("telegram secret", "radar 40")
+            .into_string()
+            .unwrap();
+        assert!(reply.contains("Send the name of a station"), "{}", reply);
+        assert_eq!(
+            telegram("wrong secret", "radar Alexanderplatz").status(),
+            Status::Unauthorized
+        );
+
+        let slack = |token: &str| {
+            client
+                .post("/bot/slack")
+                .header(Header::new("Host", "radar.example"))
+                .header(ContentType::Form)
+                .body(format!(
+                    "token={}&team_id=T1&command=%2Fradar&text=Alexanderplatz",
+                    token
+                ))
+                .dispatch()
+        };
+        let response = slack("slack+token");
+        assert_eq!(response.status(), Status::Ok);
+        let reply: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(reply["response_type"], "in_channel");
+        assert!(reply["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("Within 30 min from Alexanderplatz"));
+        assert_eq!(
+            reply["blocks"][1]["image_url"],
+            "http://radar.example/depart-from/alexanderplatz/now?format=png"
+        );
+        assert_eq!(slack("guess").status(), Status::Unauthorized);
+    }
+}
@@ -0,0 +1,72 @@
+//! Districts loaded from GeoJSON polygons, their names are written around the rim of the radar so that the distorted geometry can be related to the map.
+//!
+//! `Polygon` and `MultiPolygon` features are used, they are named by their `name` property.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use geo::{Centroid, Contains};
+
+use crate::geojson::FeatureCollection;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct District {
+    pub name: String,
+    /// Stored as (lat, lon) like the stop locations
+    pub area: geo::MultiPolygon<f64>,
+}
+
+impl District {
+    pub fn contains(&self, location: geo::Point<f64>) -> bool {
+        self.area.contains(&location)
+    }
+
+    pub fn centroid(&self) -> Option<geo::Point<f64>> {
+        self.area.centroid()
+    }
+}
+
+pub fn load_districts(path: &Path) -> Result<Vec<District>, Box<dyn Error>> {
+    Ok(districts_from_reader(BufReader::new(File::open(path)?))?)
+}
+
+/// Reads the named areas of a GeoJSON feature collection, other features are skipped
+pub fn districts_from_reader(reader: impl Read) -> Result<Vec<District>, serde_json::Error> {
+    let collection: FeatureCollection = serde_json::from_reader(reader)?;
+    Ok(collection
+        .features
+        .into_iter()
+        .filter_map(|feature| {
+            Some(District {
+                area: feature.geometry.as_ref()?.area()?,
+                name: feature.property("name")?,
+            })
+        })
+        .collect())
+}
+
+#[test]
+fn read_geojson_areas() {
+    let geojson = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {"type": "Feature", "geometry": {"type": "Polygon", "coordinates": [[[13.3, 52.5], [13.4, 52.5], [13.4, 52.6], [13.3, 52.5]]]}, "properties": {"name": "Pankow"}},
+            {"type": "Feature", "geometry": {"type": "MultiPolygon", "coordinates": [[[[13.5, 52.4], [13.6, 52.4], [13.6, 52.5], [13.5, 52.4]]]]}, "properties": {"name": "Treptow-Köpenick"}},
+            {"type": "Feature", "geometry": {"type": "Polygon", "coordinates": [[[13.3, 52.5], [13.4, 52.5], [13.4, 52.6], [13.3, 52.5]]]}, "properties": {}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [13.4, 52.5]}, "properties": {"name": "Mitte"}}
+        ]
+    }"#;
+    let districts = districts_from_reader(geojson.as_bytes()).unwrap();
+    assert_eq!(
+        districts
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["Pankow", "Treptow-Köpenick"]
+    );
+    assert!(districts[0].contains(geo::Point::new(52.55, 13.38)));
+    assert!(!districts[0].contains(geo::Point::new(52.45, 13.55)));
+    assert!(districts[1].contains(geo::Point::new(52.45, 13.58)));
+}
@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+use std::fmt;
+
+/// WCAG minimum contrast ratio for graphical objects
+const MIN_CONTRAST: f64 = 3.;
+
+/// The background of the light theme
+pub const LIGHT_BACKGROUND: Rgb = Rgb(0xff, 0xff, 0xff);
+
+/// The background of the dark theme, as in the stylesheet's `prefers-color-scheme: dark` rules
+pub const DARK_BACKGROUND: Rgb = Rgb(0x22, 0x22, 0x22);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Parses hex colours (`#rrggbb`, `#rgb` or GTFS's `rrggbb`) and the named colours used in the colour files
+    pub fn parse(colour: &str) -> Option<Self> {
+        let hex = colour.trim().trim_start_matches('#');
+        let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+        if hex.is_ascii() && hex.len() == 6 {
+            Some(Self(
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+            ))
+        } else if hex.is_ascii() && hex.len() == 3 && colour.trim().starts_with('#') {
+            let short = |s: &str| channel(s).map(|c| c * 0x11);
+            Some(Self(
+                short(&hex[0..1])?,
+                short(&hex[1..2])?,
+                short(&hex[2..3])?,
+            ))
+        } else {
+            match colour.trim().to_ascii_lowercase().as_str() {
+                "black" => Some(Self(0, 0, 0)),
+                "white" => Some(Self(0xff, 0xff, 0xff)),
+                "red" => Some(Self(0xff, 0, 0)),
+                "blue" => Some(Self(0, 0, 0xff)),
+                "green" => Some(Self(0, 0x80, 0)),
+                "yellow" => Some(Self(0xff, 0xff, 0)),
+                "gray" | "grey" => Some(Self(0x80, 0x80, 0x80)),
+                "lightgray" | "lightgrey" => Some(Self(0xd3, 0xd3, 0xd3)),
+                _ => None,
+            }
+        }
+    }
+
+    /// Relative luminance as defined by WCAG
+    pub fn luminance(self) -> f64 {
+        let linear = |channel: u8| {
+            let c = f64::from(channel) / 255.;
+            if c <= 0.039_28 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(self.0) + 0.7152 * linear(self.1) + 0.0722 * linear(self.2)
+    }
+
+    pub fn contrast_with(self, other: Self) -> f64 {
+        let (l1, l2) = (self.luminance(), other.luminance());
+        (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+    }
+
+    /// Mixes in the target colour, by an amount from 0 (none of it) to 1 (only the target)
+    fn towards(self, target: Self, amount: f64) -> Self {
+        let mix = |channel: u8, target: u8| {
+            (f64::from(channel) + (f64::from(target) - f64::from(channel)) * amount).round() as u8
+        };
+        Self(
+            mix(self.0, target.0),
+            mix(self.1, target.1),
+            mix(self.2, target.2),
+        )
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// A colour that is visible on the background, darkened (or lightened on a dark background) from the original if it had too little contrast, unparseable colours are left as they are
+pub fn contrast_safe(colour: &str, background: Rgb) -> Cow<'_, str> {
+    match Rgb::parse(colour) {
+        Some(rgb) if rgb.contrast_with(background) < MIN_CONTRAST => {
+            let target = if background.luminance() > 0.5 {
+                Rgb(0, 0, 0)
+            } else {
+                Rgb(0xff, 0xff, 0xff)
+            };
+            let mut amount: f64 = 0.;
+            let mut safe = rgb;
+            while safe.contrast_with(background) < MIN_CONTRAST && amount < 1. {
+                amount += 0.05;
+                safe = rgb.towards(target, amount.min(1.));
+            }
+            Cow::Owned(safe.to_string())
+        }
+        _ => Cow::Borrowed(colour),
+    }
+}
+
+#[test]
+fn parse_colours() {
+    assert_eq!(Rgb::parse("#eb588f"), Some(Rgb(0xeb, 0x58, 0x8f)));
+    assert_eq!(Rgb::parse("EB588F"), Some(Rgb(0xeb, 0x58, 0x8f)));
+    assert_eq!(Rgb::parse("#fff"), Some(Rgb(0xff, 0xff, 0xff)));
+    assert_eq!(Rgb::parse("lightgray"), Some(Rgb(0xd3, 0xd3, 0xd3)));
+    assert_eq!(Rgb::parse("#ggg"), None);
+    assert_eq!(Rgb::parse("fff"), None);
+    assert_eq!(Rgb::parse("papayawhip"), None);
+}
+
+#[test]
+fn contrast_ratios() {
+    assert!((Rgb(0, 0, 0).contrast_with(LIGHT_BACKGROUND) - 21.).abs() < 0.01);
+    assert!((LIGHT_BACKGROUND.contrast_with(LIGHT_BACKGROUND) - 1.).abs() < 0.01);
+}
+
+#[test]
+fn contrast_safe_colours() {
+    // dark enough colours are kept as they were
+    assert_eq!(contrast_safe("#224f86", LIGHT_BACKGROUND), "#224f86");
+    assert_eq!(contrast_safe("red", LIGHT_BACKGROUND), "red");
+    assert_eq!(contrast_safe("unknown", LIGHT_BACKGROUND), "unknown");
+    // near white colours are darkened until they are visible
+    for colour in &["lightgray", "#f0d722", "#fefefe"] {
+        let safe = contrast_safe(colour, LIGHT_BACKGROUND);
+        assert_ne!(safe, *colour);
+        assert!(Rgb::parse(&safe).unwrap().contrast_with(LIGHT_BACKGROUND) >= MIN_CONTRAST);
+    }
+    // and near black colours are lightened on the dark background, where the light ones are kept
+    assert_eq!(contrast_safe("#f0d722", DARK_BACKGROUND), "#f0d722");
+    for colour in &["black", "#224f86", "#333"] {
+        let safe = Rgb::parse(&contrast_safe(colour, DARK_BACKGROUND)).unwrap();
+        assert!(safe.luminance() > Rgb::parse(colour).unwrap().luminance());
+        assert!(safe.contrast_with(DARK_BACKGROUND) >= MIN_CONTRAST);
+    }
+}
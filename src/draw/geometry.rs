@@ -65,11 +65,68 @@ impl fmt::Display for Pixels {
     }
 }
 
+/// How the time since the origin is spread along the radius, the alternatives to linear spread out the centre of the radar, which is crowded around origins with dense local networks
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum RadialScale {
+    #[default]
+    Linear,
+    SquareRoot,
+    Logarithmic,
+}
+
+/// The logarithmic scale is of `1 + LOG_SCALE_BASE * t`, which puts the first tenth of the time over a third of the way out
+const LOG_SCALE_BASE: f64 = 20.;
+
+impl RadialScale {
+    pub const ALL: &'static [RadialScale] = &[
+        RadialScale::Linear,
+        RadialScale::SquareRoot,
+        RadialScale::Logarithmic,
+    ];
+
+    /// How the scale is named in urls
+    pub fn key(self) -> &'static str {
+        match self {
+            RadialScale::Linear => "linear",
+            RadialScale::SquareRoot => "sqrt",
+            RadialScale::Logarithmic => "log",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        RadialScale::ALL
+            .iter()
+            .copied()
+            .find(|scale| scale.key() == key)
+    }
+
+    /// The fraction of the radius at the fraction of the maximum duration, both are 0 at the origin and 1 at the rim
+    fn radius(self, time: f64) -> f64 {
+        match self {
+            RadialScale::Linear => time,
+            RadialScale::SquareRoot => time.max(0.).sqrt(),
+            RadialScale::Logarithmic => {
+                (LOG_SCALE_BASE * time.max(0.)).ln_1p() / LOG_SCALE_BASE.ln_1p()
+            }
+        }
+    }
+
+    /// The inverse of `radius`
+    fn time(self, radius: f64) -> f64 {
+        match self {
+            RadialScale::Linear => radius,
+            RadialScale::SquareRoot => radius.powi(2),
+            RadialScale::Logarithmic => (radius * LOG_SCALE_BASE.ln_1p()).exp_m1() / LOG_SCALE_BASE,
+        }
+    }
+}
+
 /// A geometry which represents each point as a polar coordinate, a bearing and a time. The geometry has an origin time, the point of the cone.
 pub struct FlattenedTimeCone {
     origin: DateTime<Tz>,
     max_duration: Duration,
     max_points: Pixels, // maybe replace with scale, something like points per minute
+    scale: RadialScale,
 }
 
 impl FlattenedTimeCone {
@@ -77,26 +134,41 @@ impl FlattenedTimeCone {
         origin: DateTime<Tz>,
         max_duration: Duration,
         max_points: Pixels, // maybe replace with scale, something like points per minute
+        scale: RadialScale,
     ) -> Self {
         Self {
             origin,
             max_duration,
             max_points,
+            scale,
         }
     }
 
     pub fn coords(&self, bearing: Bearing, magnitude: DateTime<Tz>) -> (Pixels, Pixels) {
+        let h = *self.radius(magnitude) / self.max_points;
+        let x = h * bearing.as_radians().cos();
+        let y = h * bearing.as_radians().sin();
+        (x * self.max_points, (-y) * self.max_points)
+    }
+
+    /// The distance from the origin of the time
+    pub fn radius(&self, magnitude: DateTime<Tz>) -> Pixels {
         let radius = magnitude - self.origin;
         if radius < Duration::zero() {
-            (Pixels(0.), Pixels(0.))
+            Pixels(0.)
         } else {
-            let h = radius.num_seconds() as f64 / self.max_duration.num_seconds() as f64;
-            let x = h * bearing.as_radians().cos();
-            let y = h * bearing.as_radians().sin();
-            (x * self.max_points, (-y) * self.max_points)
+            let time = radius.num_seconds() as f64 / self.max_duration.num_seconds() as f64;
+            self.scale.radius(time) * self.max_points
         }
     }
 
+    /// The time at the distance from the origin
+    pub fn magnitude(&self, radius: f64) -> DateTime<Tz> {
+        let time = self.scale.time(radius / self.max_points);
+        self.origin
+            + Duration::milliseconds((self.max_duration.num_milliseconds() as f64 * time) as i64)
+    }
+
     pub fn max(&self) -> DateTime<Tz> {
         self.origin + self.max_duration
     }
@@ -186,15 +258,41 @@ fn test_bearing_normalize_around_zero() {
     assert_f64!(Bearing(-8.5 * PI).normalize_around_zero().0, -0.5 * PI);
 }
 
+#[test]
+fn radial_scales() {
+    for &scale in RadialScale::ALL {
+        assert_f64!(scale.radius(0.), 0.);
+        assert_f64!(scale.radius(1.), 1.);
+        for time in [0.1, 0.5, 0.9, 1.2] {
+            assert_f64!(scale.time(scale.radius(time)), time);
+        }
+        assert_eq!(RadialScale::from_key(scale.key()), Some(scale));
+    }
+    // the centre is spread out
+    assert!(RadialScale::SquareRoot.radius(0.25) > RadialScale::Linear.radius(0.25));
+    assert!(RadialScale::Logarithmic.radius(0.1) > RadialScale::SquareRoot.radius(0.1));
+}
+
 impl Geometry for FlattenedTimeCone {
     type Coords = (Bearing, DateTime<Tz>);
 }
 
 pub struct Path<G: Geometry> {
     pub class: String,
+    /// Colours to draw with in each theme, which take precedence over the stylesheet rules for the class
+    pub stroke: Option<Stroke>,
+    /// Inline style, which takes precedence over the stylesheet
+    pub style: Option<String>,
     pub ops: Vec<PathTo<G>>,
 }
 
+/// A path's colour in the light and dark themes, adjusted from the original colour to be visible on each background
+pub struct Stroke {
+    pub light: String,
+    pub dark: String,
+    pub original: String,
+}
+
 pub enum PathTo<G: Geometry> {
     Move(G::Coords),
     Line(G::Coords),
@@ -205,6 +303,8 @@ impl<G: Geometry> Path<G> {
     pub fn begin_path() -> Self {
         Self {
             class: String::new(),
+            stroke: None,
+            style: None,
             ops: vec![],
         }
     }
@@ -213,6 +313,14 @@ impl<G: Geometry> Path<G> {
         self.class = class;
     }
 
+    pub fn set_stroke(&mut self, stroke: Stroke) {
+        self.stroke = Some(stroke);
+    }
+
+    pub fn set_style(&mut self, style: String) {
+        self.style = Some(style);
+    }
+
     pub fn move_to(&mut self, coords: G::Coords) {
         self.ops.push(PathTo::Move(coords));
     }
@@ -280,12 +388,40 @@ impl Path<FlattenedTimeCone> {
         title: &str,
     ) -> io::Result<()> {
         assert!(!self.ops.is_empty());
-        write_xml!(w,
-            <path
-                class={self.class}
-                d={DisplayInGeometry { display: &self.ops, geometry }}>
-                <title>{title}</title>
-            </path>
-        )
+        if let Some(stroke) = &self.stroke {
+            // the stylesheet draws paths with a data-color in these variables, over the class rules and per theme
+            let mut style = format!("--stroke: {}; --stroke-dark: {}", stroke.light, stroke.dark);
+            if let Some(extra) = &self.style {
+                style.push_str("; ");
+                style.push_str(extra);
+            }
+            write_xml!(w,
+                <path
+                    class={self.class}
+                    stroke={stroke.light}
+                    data-color={stroke.original}
+                    style={style}
+                    d={DisplayInGeometry { display: &self.ops, geometry }}>
+                    <title>{title}</title>
+                </path>
+            )
+        } else if let Some(style) = &self.style {
+            write_xml!(w,
+                <path
+                    class={self.class}
+                    style={style}
+                    d={DisplayInGeometry { display: &self.ops, geometry }}>
+                    <title>{title}</title>
+                </path>
+            )
+        } else {
+            write_xml!(w,
+                <path
+                    class={self.class}
+                    d={DisplayInGeometry { display: &self.ops, geometry }}>
+                    <title>{title}</title>
+                </path>
+            )
+        }
     }
 }
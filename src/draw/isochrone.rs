@@ -0,0 +1,191 @@
+//! The areas reached within bands of minutes from a search's origin, as GeoJSON polygons for GIS tools.
+//!
+//! Each isochrone is star shaped around the origin: along each of a fan of rays from the origin it reaches as far as a station reached in time and a walk on from it for the rest of the time, so each band's polygon contains the earlier bands' polygons.
+
+use std::collections::BTreeMap;
+
+use chrono::Duration;
+use radar_search::search_data::{GTFSData, Stop, StopId};
+use serde_json::json;
+
+use super::radar::{earliest_arrivals, SearchParams, MAX_WALK_METRES, WALKING_METRES_PER_SECOND};
+
+/// The bands shorter than the search, which also has a band of it's own length
+pub const BAND_MINUTES: [i64; 4] = [5, 10, 15, 30];
+
+/// Rays from the origin, each vertex of a polygon is on one of them
+const RAYS: usize = 72;
+
+const EARTH_RADIUS_METRES: f64 = 6_371_000.;
+
+/// The area reached within some minutes
+#[derive(Debug)]
+pub struct Isochrone {
+    pub minutes: i64,
+    /// Not counting the origin
+    pub stations: usize,
+    /// The closed exterior ring as (lon, lat), anticlockwise as GeoJSON has it
+    pub ring: Vec<(f64, f64)>,
+}
+
+/// The isochrones of the search's bands, the shortest first
+pub fn isochrones(data: &GTFSData, search_params: SearchParams) -> Vec<Isochrone> {
+    let origin = search_params.origin;
+    let max_minutes = search_params.max_duration.num_minutes();
+    let reached = earliest_arrivals(data, search_params);
+    bands(max_minutes)
+        .into_iter()
+        .map(|minutes| isochrone(data, origin, &reached, minutes))
+        .collect()
+}
+
+fn bands(max_minutes: i64) -> Vec<i64> {
+    BAND_MINUTES
+        .iter()
+        .copied()
+        .filter(|&minutes| minutes < max_minutes)
+        .chain(std::iter::once(max_minutes))
+        .collect()
+}
+
+fn isochrone(
+    data: &GTFSData,
+    origin: &Stop,
+    reached: &BTreeMap<StopId, Duration>,
+    minutes: i64,
+) -> Isochrone {
+    let band = Duration::minutes(minutes);
+    let projection = Projection::around(origin);
+    // each station reached in time is a circle which can be walked to from it
+    let circles: Vec<((f64, f64), f64)> = reached
+        .iter()
+        .filter(|(_, travel_time)| **travel_time <= band)
+        .filter_map(|(station_id, travel_time)| {
+            let station = data.get_stop(*station_id)?;
+            let walk = ((band - *travel_time).num_seconds() as f64 * WALKING_METRES_PER_SECOND)
+                .min(MAX_WALK_METRES);
+            Some((projection.metres(station), walk))
+        })
+        .collect();
+    let mut ring: Vec<(f64, f64)> = (0..RAYS)
+        .map(|ray| {
+            let angle = std::f64::consts::TAU * ray as f64 / RAYS as f64;
+            let reach = circles
+                .iter()
+                .filter_map(|&(centre, radius)| farthest_along(angle, centre, radius))
+                .fold(0., f64::max);
+            projection.lon_lat((reach * angle.cos(), reach * angle.sin()))
+        })
+        .collect();
+    ring.push(ring[0]);
+    Isochrone {
+        minutes,
+        stations: reached
+            .iter()
+            .filter(|(station_id, travel_time)| {
+                **station_id != origin.stop_id && **travel_time <= band
+            })
+            .count(),
+        ring,
+    }
+}
+
+/// How far along the ray from the origin at the angle, anticlockwise from east, it leaves the circle, if it crosses it
+fn farthest_along(angle: f64, (x, y): (f64, f64), radius: f64) -> Option<f64> {
+    let (cos, sin) = (angle.cos(), angle.sin());
+    let along = x * cos + y * sin;
+    let across = x * sin - y * cos;
+    let half_chord_squared = radius * radius - across * across;
+    if half_chord_squared < 0. {
+        return None;
+    }
+    Some(along + half_chord_squared.sqrt()).filter(|&reach| reach >= 0.)
+}
+
+/// Metres east and north of the origin, which is close enough over the distance of a search
+struct Projection {
+    lat: f64,
+    lon: f64,
+    metres_per_degree_lon: f64,
+    metres_per_degree_lat: f64,
+}
+
+impl Projection {
+    fn around(origin: &Stop) -> Self {
+        // locations are stored (lat, lon)
+        let (lat, lon) = (origin.location.x(), origin.location.y());
+        let metres_per_degree_lat = EARTH_RADIUS_METRES.to_radians();
+        Projection {
+            lat,
+            lon,
+            metres_per_degree_lon: metres_per_degree_lat * lat.to_radians().cos(),
+            metres_per_degree_lat,
+        }
+    }
+
+    fn metres(&self, stop: &Stop) -> (f64, f64) {
+        (
+            (stop.location.y() - self.lon) * self.metres_per_degree_lon,
+            (stop.location.x() - self.lat) * self.metres_per_degree_lat,
+        )
+    }
+
+    fn lon_lat(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            self.lon + x / self.metres_per_degree_lon,
+            self.lat + y / self.metres_per_degree_lat,
+        )
+    }
+}
+
+/// The longest band first, so that the shorter ones are drawn over it
+pub fn feature_collection(isochrones: &[Isochrone]) -> serde_json::Value {
+    let features = isochrones.iter().rev().map(|isochrone| {
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [isochrone
+                    .ring
+                    .iter()
+                    .map(|&(lon, lat)| json!([lon, lat]))
+                    .collect::<Vec<_>>()],
+            },
+            "properties": {
+                "minutes": isochrone.minutes,
+                "stations": isochrone.stations,
+            },
+        })
+    });
+    json!({
+        "type": "FeatureCollection",
+        "features": features.collect::<Vec<_>>(),
+    })
+}
+
+#[test]
+fn bands_end_with_the_search() {
+    assert_eq!(bands(30), vec![5, 10, 15, 30]);
+    assert_eq!(bands(20), vec![5, 10, 15, 20]);
+    assert_eq!(bands(60), vec![5, 10, 15, 30, 60]);
+    assert_eq!(bands(4), vec![4]);
+}
+
+#[test]
+fn rays_leave_circles_on_their_far_side() {
+    // a circle around the origin is left at it's radius in every direction
+    for angle in [0., 1., 4.] {
+        assert!((farthest_along(angle, (0., 0.), 100.).unwrap() - 100.).abs() < 1e-9);
+    }
+    // one to the east is crossed by the ray east and missed by the ray north
+    assert!((farthest_along(0., (1000., 0.), 100.).unwrap() - 1100.).abs() < 1e-9);
+    assert_eq!(
+        farthest_along(std::f64::consts::FRAC_PI_2, (1000., 0.), 100.),
+        None
+    );
+    // and the ray west points away from it
+    assert_eq!(
+        farthest_along(std::f64::consts::PI, (1000., 0.), 100.),
+        None
+    );
+}
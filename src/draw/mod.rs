@@ -1,3 +1,12 @@
+mod color;
 mod geometry;
+pub mod isochrone;
 pub mod radar;
-mod xml;
+mod raster;
+pub mod render;
+pub mod stream;
+pub mod tree;
+pub mod weekly;
+#[cfg(test)]
+pub(crate) mod well_formed;
+pub mod xml;
@@ -5,35 +5,118 @@ use radar_search::journey_graph;
 use radar_search::search_data::*;
 use radar_search::time::*;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::f64::consts::PI;
 use std::fmt::Display;
-use std::fmt::Write;
 use std::io;
 
+use crate::districts::District;
+use crate::gtfs::importance::Importance;
+use crate::gtfs::slugs::StationSlugs;
+use crate::poi::Poi;
 use crate::write_xml;
 
+use super::color::{contrast_safe, Rgb, DARK_BACKGROUND, LIGHT_BACKGROUND};
+pub use super::geometry::RadialScale;
 use super::geometry::*;
+use super::raster::{self, Canvas};
+use super::tree::*;
+use super::xml::{Escaped, JoinList};
 
 pub struct Radar<'s> {
     geometry: Geo,
     trips: HashMap<TripId, RadarTrip<'s>>,
     stations: HashMap<StopId, Station<'s, FlattenedTimeCone>>,
+    pois: Vec<RadarPoi<'s>>,
+    rim_labels: Vec<RimLabel<'s>>,
     origin: &'s Stop,
+    /// Number of trip segments which were left out as they couldn't be drawn
+    skipped_segments: usize,
+    /// Set when there is hardly any service from the origin in the searched period
+    service_gap: Option<ServiceGap>,
+    /// When the first trip on the radar departs, after which the radar is stale
+    expires_at: DateTime<Tz>,
+    confidence: Option<ConfidenceLayer>,
+    /// Stations further than this from the origin are left out
+    max_km: Option<f64>,
+    missed: Option<MissedLayer<'s>>,
+    journey_url: Option<&'s JourneyUrl>,
+    /// The trips of each route on the radar which stop at it's stations in the searched period, when routes are drawn by how often they run
+    frequencies: Option<HashMap<RouteId, usize>>,
+    /// The trips drawn which arrived at a stop at the same time as another trip, to prefer when the radar is refreshed
+    tied_trips: Vec<TripId>,
+}
+
+/// The trips which left the origin shortly before the departure time, drawn faintly from when they left, to show what was just missed
+struct MissedLayer<'s> {
+    /// How long before the departure time the trips could have left
+    before: Duration,
+    /// The radar's geometry from the earlier departure time
+    geometry: Geo,
+    trips: Vec<RadarTrip<'s>>,
+}
+
+/// The missed trips the radar's link turns on, from the last few minutes
+pub const DEFAULT_MISSED_MINS: i64 = 5;
+
+/// How long each station takes to reach from most departures soon after the departure time, for when the departure isn't timed to the timetable
+struct ConfidenceLayer {
+    percent: u8,
+    travel_times: HashMap<StopId, Duration>,
+}
+
+/// The confidence layer samples departures every few minutes through the window after the departure time
+const CONFIDENCE_WINDOW_MINS: i64 = 20;
+const CONFIDENCE_SAMPLE_MINS: i64 = 2;
+/// The confidence of the layer the radar's link turns on
+pub const DEFAULT_CONFIDENCE_PERCENT: u8 = 90;
+
+/// Too few departures in the searched period would leave the radar nearly empty, so the next departure is given
+struct ServiceGap {
+    /// At night the gap is expected, just sparse
+    night: bool,
+    /// The next departure after the searched period with the name of its route
+    next_departure: Option<(String, Time)>,
+}
+
+/// Less departures than this from the origin in the searched period is a gap in service
+const SPARSE_SERVICE_DEPARTURES: usize = 3;
+/// How far after the searched period to look for the next departure
+const NEXT_DEPARTURE_LOOKAHEAD_HOURS: i64 = 6;
+
+/// The name of a district in the direction of a bearing, written around the rim
+struct RimLabel<'s> {
+    bearing: Bearing,
+    name: &'s str,
+}
+
+/// A point of interest that can be reached by walking from a station on the radar
+struct RadarPoi<'s> {
+    coords: <FlattenedTimeCone as Geometry>::Coords,
+    poi: &'s Poi,
 }
 
 struct Station<'s, G: Geometry> {
     coords: G::Coords,
     stop: &'s Stop,
     name_trunk_length: usize,
+    /// The changes between trips on the fastest way to the station
+    transfers: u8,
+    /// The fare zone entered at this station, if the trip to it came from another zone
+    entered_zone: Option<&'s str>,
+    /// One of the most important stations on the radar, which is labelled larger
+    major: bool,
 }
 
 #[derive(Debug)]
 struct RadarTrip<'s> {
-    _trip_id: TripId,
+    trip_id: TripId,
+    route_id: RouteId,
     route_name: String,
     route_type: RouteType,
+    route_color: String,
     /// Usually just one of these, each item is a connection into this trip and the segments that follow it
     parts: Vec<(TripSegment<'s>, Vec<TripSegment<'s>>)>,
 }
@@ -120,21 +203,11 @@ impl Geo {
             // the need to negate here is weird, maybe the above atan2 calls are the wrong way around
             (
                 Bearing::radians(-(cp2_y).atan2(cp2_x)),
-                polar.origin()
-                    + Duration::milliseconds(
-                        (polar.max_duration().num_milliseconds() as f64
-                            * ((cp2_x.powi(2) + cp2_y.powi(2)).sqrt() / polar.max_points()))
-                            as i64,
-                    ),
+                polar.magnitude((cp2_x.powi(2) + cp2_y.powi(2)).sqrt()),
             ),
             (
                 Bearing::radians(-(cp3_y).atan2(cp3_x)),
-                polar.origin()
-                    + Duration::milliseconds(
-                        (polar.max_duration().num_milliseconds() as f64
-                            * ((cp3_x.powi(2) + cp3_y.powi(2)).sqrt() / polar.max_points()))
-                            as i64,
-                    ),
+                polar.magnitude((cp3_x.powi(2) + cp3_y.powi(2)).sqrt()),
             ),
         )
     }
@@ -234,9 +307,38 @@ pub enum TransitMode {
 }
 
 impl TransitMode {
-    const DEFAULTS: &'static [TransitMode] = &[TransitMode::SBahn, TransitMode::UBahn];
+    /// The modes searched unless others are asked for
+    pub const DEFAULTS: &'static [TransitMode] = &[TransitMode::SBahn, TransitMode::UBahn];
+    pub const ALL: &'static [TransitMode] = &[
+        TransitMode::SBahn,
+        TransitMode::UBahn,
+        TransitMode::Tram,
+        TransitMode::Bus,
+        TransitMode::Regional,
+        TransitMode::Boat,
+    ];
+
+    pub fn of_route_type(route_type: RouteType) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.route_types().contains(&route_type))
+    }
 
-    fn key(&self) -> &str {
+    /// The route types which are shown for this mode
+    pub fn route_types(self) -> &'static [RouteType] {
+        match self {
+            TransitMode::SBahn => &[RouteType::SuburbanRailway],
+            TransitMode::UBahn => &[RouteType::UrbanRailway],
+            TransitMode::Bus => &[RouteType::BusService, RouteType::Bus],
+            TransitMode::Tram => &[RouteType::TramService],
+            TransitMode::Regional => &[RouteType::RailwayService, RouteType::Rail],
+            TransitMode::Boat => &[RouteType::WaterTransportService],
+        }
+    }
+
+    /// How the mode is named in urls
+    pub fn key(&self) -> &'static str {
         match self {
             TransitMode::SBahn => "sbahn",
             TransitMode::UBahn => "ubahn",
@@ -246,6 +348,13 @@ impl TransitMode {
             TransitMode::Boat => "boat",
         }
     }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        TransitMode::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.key() == key)
+    }
 }
 
 impl Display for TransitMode {
@@ -261,17 +370,162 @@ impl Display for TransitMode {
     }
 }
 
+/// The most legs of a search which can be ridden by each mode, such as a bus only for the last hop after the trains, the other modes can be ridden any number of times
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct MaxLegs(Vec<(TransitMode, u8)>);
+
+impl MaxLegs {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TransitMode, u8)> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// How the limits are written in urls, such as `bus:1,tram:2`, in the order of `TransitMode::ALL`
+    pub fn key(&self) -> String {
+        self.0
+            .iter()
+            .map(|(mode, max_legs)| format!("{}:{}", mode.key(), max_legs))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Reads the limits as they're written in urls, each mode can only be limited once
+    pub fn from_key(key: &str) -> Option<Self> {
+        let mut limits = vec![];
+        for limit in key.split(',').filter(|limit| !limit.is_empty()) {
+            let (mode, max_legs) = limit.split_once(':')?;
+            let mode = TransitMode::from_key(mode)?;
+            if limits.iter().any(|&(limited, _)| limited == mode) {
+                return None;
+            }
+            limits.push((mode, max_legs.parse().ok()?));
+        }
+        limits.sort_by_key(|&(mode, _)| TransitMode::ALL.iter().position(|&m| m == mode));
+        Some(MaxLegs(limits))
+    }
+}
+
+#[test]
+fn max_legs_keys() {
+    let max_legs = MaxLegs::from_key("tram:2,bus:1").unwrap();
+    assert_eq!(
+        max_legs.iter().collect::<Vec<_>>(),
+        vec![(TransitMode::Tram, 2), (TransitMode::Bus, 1)]
+    );
+    assert_eq!(max_legs.key(), "tram:2,bus:1");
+    assert_eq!(MaxLegs::from_key(""), Some(MaxLegs::default()));
+    for invalid in &["bus", "bus:many", "rocket:1", "bus:1,bus:2", "bus:-1"] {
+        assert_eq!(MaxLegs::from_key(invalid), None, "{}", invalid);
+    }
+}
+
+/// The colours trips are drawn in
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Palette {
+    /// Each route in it's own colour, as in the feed and the colour files
+    #[default]
+    Route,
+    /// Each mode in a colour of the Okabe-Ito palette and with it's own dash pattern, so that colour blind people can tell the modes apart
+    ColourBlindFriendly,
+}
+
+impl Palette {
+    pub const ALL: &'static [Palette] = &[Palette::Route, Palette::ColourBlindFriendly];
+
+    /// How the palette is named in urls
+    pub fn key(self) -> &'static str {
+        match self {
+            Palette::Route => "route",
+            Palette::ColourBlindFriendly => "cbf",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Palette::ALL
+            .iter()
+            .copied()
+            .find(|palette| palette.key() == key)
+    }
+
+    /// The colour to draw a trip in on the background, which is adjusted if it's too close to the background to see
+    pub fn stroke<'c>(
+        self,
+        route_type: RouteType,
+        route_color: &'c str,
+        background: Rgb,
+    ) -> Cow<'c, str> {
+        match self {
+            Palette::Route => contrast_safe(route_color, background),
+            Palette::ColourBlindFriendly => {
+                let colour = match TransitMode::of_route_type(route_type) {
+                    Some(TransitMode::SBahn) => "#009e73",
+                    Some(TransitMode::UBahn) => "#0072b2",
+                    Some(TransitMode::Regional) => "#d55e00",
+                    Some(TransitMode::Tram) => "#cc79a7",
+                    Some(TransitMode::Bus) => "#e69f00",
+                    Some(TransitMode::Boat) => "#56b4e9",
+                    None => "#000000",
+                };
+                Cow::Owned(contrast_safe(colour, background).into_owned())
+            }
+        }
+    }
+
+    /// The lengths of the dashes and gaps to draw a trip with, none for a solid line
+    pub fn dash(self, route_type: RouteType) -> Option<&'static [f64]> {
+        match (self, TransitMode::of_route_type(route_type)) {
+            (Palette::Route, _) | (Palette::ColourBlindFriendly, Some(TransitMode::SBahn)) => None,
+            (Palette::ColourBlindFriendly, Some(TransitMode::UBahn)) => Some(&[12., 3.]),
+            (Palette::ColourBlindFriendly, Some(TransitMode::Regional)) => Some(&[10., 3., 2., 3.]),
+            (Palette::ColourBlindFriendly, Some(TransitMode::Tram)) => Some(&[6., 3.]),
+            (Palette::ColourBlindFriendly, Some(TransitMode::Bus)) => Some(&[3., 3.]),
+            (Palette::ColourBlindFriendly, Some(TransitMode::Boat)) => Some(&[1., 3.]),
+            (Palette::ColourBlindFriendly, None) => Some(&[6., 3., 1., 3.]),
+        }
+    }
+}
+
+#[test]
+fn palettes() {
+    assert_eq!(Palette::from_key("cbf"), Some(Palette::ColourBlindFriendly));
+    assert_eq!(Palette::from_key("route"), Some(Palette::Route));
+    assert_eq!(Palette::from_key("rainbow"), None);
+    assert_eq!(
+        Palette::Route.stroke(RouteType::SuburbanRailway, "#047939", LIGHT_BACKGROUND),
+        "#047939"
+    );
+    // the U4's yellow is darkened on the light background, and kept on the dark one
+    assert_ne!(
+        Palette::Route.stroke(RouteType::UrbanRailway, "#f0d722", LIGHT_BACKGROUND),
+        "#f0d722"
+    );
+    assert_eq!(
+        Palette::Route.stroke(RouteType::UrbanRailway, "#f0d722", DARK_BACKGROUND),
+        "#f0d722"
+    );
+    let cbf = Palette::ColourBlindFriendly;
+    // deuteranopes confuse the red and green lines of the feed, S-Bahn and regional trains are told apart by hue and dashes
+    assert_ne!(
+        cbf.stroke(RouteType::SuburbanRailway, "#047939", LIGHT_BACKGROUND),
+        cbf.stroke(RouteType::RailwayService, "#047939", LIGHT_BACKGROUND)
+    );
+    assert_eq!(cbf.dash(RouteType::SuburbanRailway), None);
+    assert!(cbf.dash(RouteType::RailwayService).is_some());
+    for &mode in TransitMode::ALL {
+        let route_type = mode.route_types()[0];
+        for &background in &[LIGHT_BACKGROUND, DARK_BACKGROUND] {
+            let stroke = Rgb::parse(&cbf.stroke(route_type, "white", background)).unwrap();
+            assert!(stroke.contrast_with(background) >= 3.);
+        }
+    }
+}
+
 pub fn day_time<Tz: TimeZone>(date_time: DateTime<Tz>) -> (Day, Time) {
     let now = Time::from_seconds_since_midnight(date_time.num_seconds_from_midnight());
-    let day = match date_time.weekday() {
-        chrono::Weekday::Mon => Day::Monday,
-        chrono::Weekday::Tue => Day::Tuesday,
-        chrono::Weekday::Wed => Day::Wednesday,
-        chrono::Weekday::Thu => Day::Thursday,
-        chrono::Weekday::Fri => Day::Friday,
-        chrono::Weekday::Sat => Day::Saturday,
-        chrono::Weekday::Sun => Day::Sunday,
-    };
+    let day = Day::from(date_time.weekday());
     (day, now)
 }
 pub struct SearchParams<'s> {
@@ -279,32 +533,138 @@ pub struct SearchParams<'s> {
     pub departure_time: Option<DateTime<Tz>>,
     pub max_duration: Duration,
     pub modes: Cow<'s, HashSet<TransitMode>>,
+    /// Only show trips in this direction
+    pub direction: Option<DirectionId>,
+    /// Points of interest to show if they can be reached
+    pub pois: &'s [Poi],
+    /// Districts to label the rim with
+    pub districts: &'s [District],
+    /// Also show when each station is reached from this percent of the departures soon after the departure time
+    pub confidence: Option<u8>,
+    /// Leave out the stations further than this from the origin, as the crow flies
+    pub max_km: Option<f64>,
+    /// Also draw the trips which left the origin in this long before the departure time
+    pub missed: Option<Duration>,
+    /// Link the trips to their details in the operator's journey planner
+    pub journey_url: Option<&'s JourneyUrl>,
+    /// Draw each route as wide as how often it runs in the searched period
+    pub frequency: bool,
+    /// How the time since the departure is spread along the radius
+    pub scale: RadialScale,
+    /// The most legs which can be ridden by each mode
+    pub max_legs: MaxLegs,
+    /// Also board the trips which left the origin in this long before the departure time, as if they were running late
+    pub grace: Option<Duration>,
+    /// Label the most important stations larger
+    pub importance: Option<&'s Importance>,
+    /// Draw these trips rather than others arriving at the same time, such as the trips drawn on the last refresh so that the radar doesn't jump between them
+    pub prefer_trips: Vec<TripId>,
+}
+
+/// The url of a trip's details in the operator's journey planner, `{route}`, `{trip}` and `{time}` in the template are replaced by the trip's route id, it's trip id and the local time it's boarded, ids are as in the first GTFS feed
+#[derive(Debug, Clone)]
+pub struct JourneyUrl {
+    template: String,
+}
+
+impl JourneyUrl {
+    pub fn new(template: String) -> Self {
+        Self { template }
+    }
+
+    fn of_trip(&self, route_id: RouteId, trip_id: TripId, departure: DateTime<Tz>) -> String {
+        self.template
+            .replace("{route}", &route_id.to_string())
+            .replace("{trip}", &trip_id.to_string())
+            .replace("{time}", &departure.format("%Y-%m-%dT%H:%M").to_string())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct UrlSearchParams<'s> {
     pub station_id: StopId,
+    /// The stations are written by their slugs, so that links keep working after timetable updates
+    pub slugs: &'s StationSlugs,
     pub departure_time: Option<DateTime<Tz>>,
     pub max_duration: Duration,
     pub modes: Cow<'s, HashSet<TransitMode>>,
+    pub direction: Option<DirectionId>,
+    pub confidence: Option<u8>,
+    pub max_km: Option<f64>,
+    /// Minutes
+    pub missed: Option<i64>,
+    /// Only changes how the radar is drawn, not what's searched
+    pub palette: Palette,
+    pub frequency: bool,
+    pub scale: RadialScale,
+    /// The max duration was fitted to the origin, so links fit it again rather than keep it
+    pub fitted: bool,
+    pub max_legs: MaxLegs,
+    /// Minutes
+    pub grace: Option<i64>,
+}
+
+impl<'s> UrlSearchParams<'s> {
+    /// The default search from a station, departing now
+    pub fn new(station_id: StopId, slugs: &'s StationSlugs) -> Self {
+        Self {
+            station_id,
+            slugs,
+            departure_time: None,
+            max_duration: Duration::minutes(DEFAULT_MAX_DURATION_MINS),
+            modes: Cow::Owned(TransitMode::DEFAULTS.iter().copied().collect()),
+            direction: None,
+            confidence: None,
+            max_km: None,
+            missed: None,
+            palette: Palette::Route,
+            frequency: false,
+            scale: RadialScale::Linear,
+            fitted: false,
+            max_legs: MaxLegs::default(),
+            grace: None,
+        }
+    }
 }
 
 impl<'s> UrlSearchParams<'s> {
     fn with_station_id(self, station_id: StopId) -> Self {
         Self {
             station_id,
+            slugs: self.slugs,
             departure_time: self.departure_time,
             max_duration: self.max_duration,
             modes: self.modes,
+            direction: self.direction,
+            confidence: self.confidence,
+            max_km: self.max_km,
+            missed: self.missed,
+            palette: self.palette,
+            frequency: self.frequency,
+            scale: self.scale,
+            fitted: self.fitted,
+            max_legs: self.max_legs,
+            grace: self.grace,
         }
     }
 
     fn with_departure_time(self, departure_time: DateTime<Tz>) -> Self {
         Self {
             station_id: self.station_id,
+            slugs: self.slugs,
             departure_time: Some(departure_time),
             max_duration: self.max_duration,
             modes: self.modes,
+            direction: self.direction,
+            confidence: self.confidence,
+            max_km: self.max_km,
+            missed: self.missed,
+            palette: self.palette,
+            frequency: self.frequency,
+            scale: self.scale,
+            fitted: self.fitted,
+            max_legs: self.max_legs,
+            grace: self.grace,
         }
     }
 
@@ -313,9 +673,20 @@ impl<'s> UrlSearchParams<'s> {
         modes.insert(mode);
         Self {
             station_id: self.station_id,
+            slugs: self.slugs,
             departure_time: self.departure_time,
             max_duration: self.max_duration,
             modes: Cow::Owned(modes),
+            direction: self.direction,
+            confidence: self.confidence,
+            max_km: self.max_km,
+            missed: self.missed,
+            palette: self.palette,
+            frequency: self.frequency,
+            scale: self.scale,
+            fitted: self.fitted,
+            max_legs: self.max_legs,
+            grace: self.grace,
         }
     }
 
@@ -324,47 +695,431 @@ impl<'s> UrlSearchParams<'s> {
         modes.remove(&mode);
         Self {
             station_id: self.station_id,
+            slugs: self.slugs,
             departure_time: self.departure_time,
             max_duration: self.max_duration,
             modes: Cow::Owned(modes),
+            direction: self.direction,
+            confidence: self.confidence,
+            max_km: self.max_km,
+            missed: self.missed,
+            palette: self.palette,
+            frequency: self.frequency,
+            scale: self.scale,
+            fitted: self.fitted,
+            max_legs: self.max_legs,
+            grace: self.grace,
+        }
+    }
+
+    fn with_confidence(self, confidence: Option<u8>) -> Self {
+        Self {
+            station_id: self.station_id,
+            slugs: self.slugs,
+            departure_time: self.departure_time,
+            max_duration: self.max_duration,
+            modes: self.modes,
+            direction: self.direction,
+            confidence,
+            max_km: self.max_km,
+            missed: self.missed,
+            palette: self.palette,
+            frequency: self.frequency,
+            scale: self.scale,
+            fitted: self.fitted,
+            max_legs: self.max_legs,
+            grace: self.grace,
+        }
+    }
+
+    fn with_missed(self, missed: Option<i64>) -> Self {
+        Self {
+            station_id: self.station_id,
+            slugs: self.slugs,
+            departure_time: self.departure_time,
+            max_duration: self.max_duration,
+            modes: self.modes,
+            direction: self.direction,
+            confidence: self.confidence,
+            max_km: self.max_km,
+            missed,
+            palette: self.palette,
+            frequency: self.frequency,
+            scale: self.scale,
+            fitted: self.fitted,
+            max_legs: self.max_legs,
+            grace: self.grace,
+        }
+    }
+
+    fn with_palette(self, palette: Palette) -> Self {
+        Self {
+            station_id: self.station_id,
+            slugs: self.slugs,
+            departure_time: self.departure_time,
+            max_duration: self.max_duration,
+            modes: self.modes,
+            direction: self.direction,
+            confidence: self.confidence,
+            max_km: self.max_km,
+            missed: self.missed,
+            palette,
+            frequency: self.frequency,
+            scale: self.scale,
+            fitted: self.fitted,
+            max_legs: self.max_legs,
+            grace: self.grace,
+        }
+    }
+
+    fn with_frequency(self, frequency: bool) -> Self {
+        Self {
+            station_id: self.station_id,
+            slugs: self.slugs,
+            departure_time: self.departure_time,
+            max_duration: self.max_duration,
+            modes: self.modes,
+            direction: self.direction,
+            confidence: self.confidence,
+            max_km: self.max_km,
+            missed: self.missed,
+            palette: self.palette,
+            frequency,
+            scale: self.scale,
+            fitted: self.fitted,
+            max_legs: self.max_legs,
+            grace: self.grace,
+        }
+    }
+
+    fn with_scale(self, scale: RadialScale) -> Self {
+        Self {
+            station_id: self.station_id,
+            slugs: self.slugs,
+            departure_time: self.departure_time,
+            max_duration: self.max_duration,
+            modes: self.modes,
+            direction: self.direction,
+            confidence: self.confidence,
+            max_km: self.max_km,
+            missed: self.missed,
+            palette: self.palette,
+            frequency: self.frequency,
+            scale,
+            fitted: self.fitted,
+            max_legs: self.max_legs,
+            grace: self.grace,
         }
     }
 }
 
 pub const DEFAULT_MAX_DURATION_MINS: i64 = 30;
 
+/// The one url of a search, parameters at their defaults are left out and the query values are percent encoded
 impl<'s> Display for UrlSearchParams<'s> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let station_id = self.station_id.get();
-        write!(f, "/depart-from/{}/", station_id)?;
+        // the path segments are slugs or ids and an ISO time, which don't need encoding
+        if let Some(slug) = self.slugs.slug(self.station_id) {
+            write!(f, "/depart-from/{}/", slug)?;
+        } else {
+            write!(f, "/depart-from/{}/", self.station_id)?;
+        }
         if let Some(time) = self.departure_time {
             write!(f, "{:?}", time.naive_local())?;
         } else {
             f.write_str("now")?;
         }
-        let uses_non_default_minutes = self.max_duration.num_minutes() != DEFAULT_MAX_DURATION_MINS;
-        let uses_non_default_modes = *self.modes != TransitMode::DEFAULTS.iter().copied().collect();
-        if uses_non_default_minutes || uses_non_default_modes {
-            f.write_char('?')?;
+        let mut query = vec![];
+        if self.fitted {
+            query.push("minutes=auto".to_owned());
+        } else if self.max_duration.num_minutes() != DEFAULT_MAX_DURATION_MINS {
+            query.push(format!("minutes={}", self.max_duration.num_minutes()));
+        }
+        if *self.modes != TransitMode::DEFAULTS.iter().copied().collect() {
+            // in a fixed order so that a search has one url
+            let modes: Vec<_> = TransitMode::ALL
+                .iter()
+                .filter(|mode| self.modes.contains(mode))
+                .map(|mode| urlencoding::encode(mode.key()))
+                .collect();
+            query.push(format!("mode={}", modes.join(",")));
         }
-        if uses_non_default_minutes {
-            write!(f, "minutes={}", self.max_duration.num_minutes())?;
+        if let Some(direction) = self.direction {
+            query.push(format!("direction={}", direction));
         }
-        if uses_non_default_minutes && uses_non_default_modes {
-            write!(f, "&amp;")?;
+        if let Some(confidence) = self.confidence {
+            query.push(format!("confidence={}", confidence));
         }
-        if *self.modes != TransitMode::DEFAULTS.iter().copied().collect() {
-            write!(f, "mode=")?;
-            let mut iter = self.modes.iter().peekable();
-            while let Some(mode) = iter.next() {
-                f.write_str(mode.key())?;
-                if iter.peek().is_some() {
-                    write!(f, ",")?;
+        if let Some(max_km) = self.max_km {
+            query.push(format!("max_km={}", max_km));
+        }
+        if let Some(missed) = self.missed {
+            query.push(format!("missed={}", missed));
+        }
+        if self.palette != Palette::Route {
+            query.push(format!("palette={}", self.palette.key()));
+        }
+        if self.frequency {
+            query.push("frequency=true".to_owned());
+        }
+        if self.scale != RadialScale::Linear {
+            query.push(format!("scale={}", self.scale.key()));
+        }
+        if !self.max_legs.is_empty() {
+            query.push(format!(
+                "legs={}",
+                urlencoding::encode(&self.max_legs.key())
+            ));
+        }
+        if let Some(grace) = self.grace {
+            query.push(format!("grace={}", grace));
+        }
+        if !query.is_empty() {
+            write!(f, "?{}", query.join("&"))?;
+        }
+        Ok(())
+    }
+}
+
+/// A url which isn't of a radar search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidUrl(pub String);
+
+impl std::error::Error for InvalidUrl {}
+
+impl Display for InvalidUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is not the url of a radar", self.0)
+    }
+}
+
+impl<'s> UrlSearchParams<'s> {
+    /// Reads a radar url with the station by it's slug or id, the parameters the server ignores are ignored here too
+    pub fn parse(url: &str, slugs: &'s StationSlugs) -> Result<Self, InvalidUrl> {
+        let invalid = || InvalidUrl(url.to_owned());
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let (station, time) = path
+            .strip_prefix("/depart-from/")
+            .and_then(|path| path.split_once('/'))
+            .ok_or_else(invalid)?;
+        let station_id = slugs
+            .station(station)
+            .or_else(|| station.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut params = UrlSearchParams::new(station_id, slugs);
+        if time != "now" {
+            let time: NaiveDateTime = time.parse().map_err(|_| invalid())?;
+            params.departure_time = Some(
+                chrono_tz::Europe::Berlin
+                    .from_local_datetime(&time)
+                    .earliest()
+                    .ok_or_else(invalid)?,
+            );
+        }
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = urlencoding::decode(value).map_err(|_| invalid())?;
+            match key {
+                "minutes" if value == "auto" => params.fitted = true,
+                "minutes" => {
+                    params.max_duration = Duration::minutes(value.parse().map_err(|_| invalid())?)
+                }
+                "mode" if !value.is_empty() => {
+                    params.modes = Cow::Owned(
+                        value
+                            .split(',')
+                            .map(TransitMode::from_key)
+                            .collect::<Option<_>>()
+                            .ok_or_else(invalid)?,
+                    )
                 }
+                "direction" => params.direction = Some(value.parse().map_err(|_| invalid())?),
+                "confidence" => params.confidence = Some(value.parse().map_err(|_| invalid())?),
+                "max_km" => params.max_km = Some(value.parse().map_err(|_| invalid())?),
+                "missed" => params.missed = Some(value.parse().map_err(|_| invalid())?),
+                "palette" => params.palette = Palette::from_key(&value).ok_or_else(invalid)?,
+                "frequency" => params.frequency = value.parse().map_err(|_| invalid())?,
+                "scale" => params.scale = RadialScale::from_key(&value).ok_or_else(invalid)?,
+                "legs" => params.max_legs = MaxLegs::from_key(&value).ok_or_else(invalid)?,
+                "grace" => params.grace = Some(value.parse().map_err(|_| invalid())?),
+                _ => {}
             }
         }
-        Ok(())
+        Ok(params)
+    }
+}
+
+#[test]
+fn urls_round_trip() {
+    let slugs = StationSlugs::default();
+    let station_id = StopId::new(900100003).unwrap();
+    let departure_time = chrono_tz::Europe::Berlin
+        .with_ymd_and_hms(2024, 1, 15, 10, 5, 30)
+        .unwrap();
+    let all_modes: HashSet<_> = TransitMode::ALL.iter().copied().collect();
+    let searches = [
+        UrlSearchParams::new(station_id, &slugs),
+        UrlSearchParams::new(station_id, &slugs).with_departure_time(departure_time),
+        UrlSearchParams {
+            max_duration: Duration::minutes(45),
+            modes: Cow::Owned(all_modes),
+            direction: Some(1),
+            confidence: Some(90),
+            max_km: Some(2.5),
+            missed: Some(5),
+            palette: Palette::ColourBlindFriendly,
+            frequency: true,
+            scale: RadialScale::Logarithmic,
+            ..UrlSearchParams::new(station_id, &slugs).with_departure_time(departure_time)
+        },
+        UrlSearchParams::new(station_id, &slugs).without_mode(TransitMode::SBahn),
+        UrlSearchParams {
+            fitted: true,
+            ..UrlSearchParams::new(station_id, &slugs)
+        },
+        UrlSearchParams {
+            max_legs: MaxLegs::from_key("bus:1,regional:0").unwrap(),
+            ..UrlSearchParams::new(station_id, &slugs).with_mode(TransitMode::Bus)
+        },
+        UrlSearchParams {
+            grace: Some(2),
+            ..UrlSearchParams::new(station_id, &slugs)
+        },
+    ];
+    for search in searches {
+        let url = search.to_string();
+        let parsed = UrlSearchParams::parse(&url, &slugs).unwrap();
+        assert_eq!(parsed.to_string(), url);
+        assert_eq!(parsed.station_id, search.station_id);
+        assert_eq!(parsed.departure_time, search.departure_time);
+        assert_eq!(parsed.max_duration, search.max_duration);
+        assert_eq!(parsed.modes, search.modes);
+        assert_eq!(parsed.direction, search.direction);
+        assert_eq!(parsed.confidence, search.confidence);
+        assert_eq!(parsed.max_km, search.max_km);
+        assert_eq!(parsed.missed, search.missed);
+        assert_eq!(parsed.palette, search.palette);
+        assert_eq!(parsed.frequency, search.frequency);
+        assert_eq!(parsed.scale, search.scale);
+        assert_eq!(parsed.fitted, search.fitted);
+        assert_eq!(parsed.max_legs, search.max_legs);
+        assert_eq!(parsed.grace, search.grace);
+    }
+}
+
+#[test]
+fn urls_are_canonical() {
+    let slugs = StationSlugs::default();
+    let canonical = |url: &str| UrlSearchParams::parse(url, &slugs).unwrap().to_string();
+    assert_eq!(
+        canonical("/depart-from/5/now?minutes=30&mode=ubahn,sbahn&refresh=true"),
+        "/depart-from/5/now"
+    );
+    assert_eq!(
+        canonical("/depart-from/5/2024-01-15T10:00:00?mode=bus%2Ctram&minutes=20"),
+        "/depart-from/5/2024-01-15T10:00:00?minutes=20&mode=tram,bus"
+    );
+    assert_eq!(canonical("/depart-from/5/now?mode="), "/depart-from/5/now");
+}
+
+#[test]
+fn invalid_urls() {
+    let slugs = StationSlugs::default();
+    for url in [
+        "/depart-to/5/now",
+        "/depart-from/0/now",
+        "/depart-from/5",
+        "/depart-from/5/yesterday",
+        "/depart-from/5/now?mode=rocket",
+        "/depart-from/5/now?palette=rainbow",
+        "/depart-from/5/now?scale=cubic",
+        "/depart-from/5/now?legs=bus",
+        "/depart-from/5/now?minutes=%ZZ",
+    ] {
+        assert_eq!(
+            UrlSearchParams::parse(url, &slugs).unwrap_err(),
+            InvalidUrl(url.to_owned())
+        );
+    }
+}
+
+/// Sets up the journey planner for a search
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn plotter<'s>(
+    data: &'s GTFSData,
+    origin: &'s Stop,
+    departure_time: DateTime<Tz>,
+    max_duration: Duration,
+    modes: &HashSet<TransitMode>,
+    direction: Option<DirectionId>,
+    max_legs: &MaxLegs,
+    grace: Option<Duration>,
+    prefer_trips: &[TripId],
+) -> journey_graph::Plotter<'s> {
+    let (_day, start_time) = day_time(departure_time);
+    let end_time = start_time + max_duration;
+    let max_extra_search = Duration::minutes(0);
+    let mut plotter = journey_graph::Plotter::new(
+        departure_time.date_naive(),
+        Period::between(start_time, end_time + max_extra_search),
+        data,
+    );
+    plotter.add_origin_station(origin);
+    for mode in modes.iter() {
+        for route_type in mode.route_types() {
+            plotter.add_route_type(*route_type);
+        }
+    }
+    if let Some(direction) = direction {
+        plotter.set_direction(direction);
     }
+    for (mode, max_legs) in max_legs.iter() {
+        plotter.limit_legs(mode.route_types(), max_legs);
+    }
+    if let Some(grace) = grace {
+        plotter.set_origin_grace(grace);
+    }
+    plotter.prefer_trips(prefer_trips.iter().copied());
+    plotter
+}
+
+/// Runs the search only to find which stops and trips it needs, for clients which already have some of the data
+pub fn required_data<'s>(
+    data: &'s GTFSData,
+    SearchParams {
+        origin,
+        departure_time,
+        max_duration,
+        modes,
+        direction,
+        pois: _,
+        districts: _,
+        confidence: _,
+        max_km: _,
+        missed: _,
+        journey_url: _,
+        frequency: _,
+        scale: _,
+        max_legs,
+        grace,
+        importance: _,
+        prefer_trips,
+    }: SearchParams<'s>,
+) -> RequiredData {
+    let departure_time =
+        departure_time.unwrap_or_else(|| Utc::now().with_timezone(&chrono_tz::Europe::Berlin));
+    plotter(
+        data,
+        origin,
+        departure_time,
+        max_duration,
+        &modes,
+        direction,
+        &max_legs,
+        grace,
+        &prefer_trips,
+    )
+    .filtered_data()
 }
 
 pub fn search<'s>(
@@ -374,54 +1129,60 @@ pub fn search<'s>(
         departure_time,
         max_duration,
         modes,
+        direction,
+        pois,
+        districts,
+        confidence,
+        max_km,
+        missed,
+        journey_url,
+        frequency,
+        scale,
+        max_legs,
+        grace,
+        importance,
+        prefer_trips,
     }: SearchParams<'s>,
 ) -> Radar<'s> {
     let departure_time =
         departure_time.unwrap_or_else(|| Utc::now().with_timezone(&chrono_tz::Europe::Berlin));
-    let (day, start_time) = day_time(departure_time);
+    let (_day, start_time) = day_time(departure_time);
+    let date = departure_time.date_naive();
     let end_time = start_time + max_duration;
-    let max_extra_search = Duration::minutes(0);
-    let mut plotter = journey_graph::Plotter::new(
-        day,
-        Period::between(start_time, end_time + max_extra_search),
+    let mut plotter = plotter(
         data,
+        origin,
+        departure_time,
+        max_duration,
+        &modes,
+        direction,
+        &max_legs,
+        grace,
+        &prefer_trips,
     );
-    plotter.add_origin_station(origin);
-    if modes.contains(&TransitMode::SBahn) {
-        plotter.add_route_type(RouteType::SuburbanRailway);
-    }
-    if modes.contains(&TransitMode::UBahn) {
-        plotter.add_route_type(RouteType::UrbanRailway);
-    }
-    if modes.contains(&TransitMode::Bus) {
-        plotter.add_route_type(RouteType::BusService);
-        plotter.add_route_type(RouteType::Bus);
-    }
-    if modes.contains(&TransitMode::Tram) {
-        plotter.add_route_type(RouteType::TramService);
-    }
-    if modes.contains(&TransitMode::Regional) {
-        plotter.add_route_type(RouteType::RailwayService);
-        plotter.add_route_type(RouteType::Rail);
-    }
-    if modes.contains(&TransitMode::Boat) {
-        plotter.add_route_type(RouteType::WaterTransportService);
-    }
     let mut expires_time = end_time;
     let mut trips: HashMap<TripId, RadarTrip> = HashMap::new();
+    let mut skipped_segments = 0;
 
     let mut stations: HashMap<StopId, Station<FlattenedTimeCone>> = HashMap::new();
     let geometry = Geo {
-        time_cone_geometry: FlattenedTimeCone::new(departure_time, max_duration, Pixels::new(500.)),
+        time_cone_geometry: FlattenedTimeCone::new(
+            departure_time,
+            max_duration,
+            Pixels::new(500.),
+            scale,
+        ),
         geographic_origin: origin.location,
     };
 
-    for item in plotter {
+    for item in &mut plotter {
         match item {
             journey_graph::Item::Station {
                 stop,
                 earliest_arrival,
                 name_trunk_length,
+                transfers_so_far,
+                ..
             } => {
                 if earliest_arrival > end_time + (expires_time - start_time) {
                     break;
@@ -429,12 +1190,12 @@ pub fn search<'s>(
                 let station = Station {
                     coords: (stop.location, earliest_arrival),
                     stop,
-                    name_trunk_length: if name_trunk_length == stop.short_stop_name.len() {
+                    name_trunk_length: if name_trunk_length == stop.short_stop_name().len() {
                         continue;
                     } else if name_trunk_length > 10 {
                         // last space before the common chars end
                         let trunk_division = stop
-                            .short_stop_name
+                            .short_stop_name()
                             .chars()
                             .enumerate()
                             .filter_map(|(i, c)| {
@@ -446,6 +1207,9 @@ pub fn search<'s>(
                     } else {
                         0
                     },
+                    transfers: transfers_so_far,
+                    entered_zone: None,
+                    major: false,
                 };
                 assert!(stations
                     .insert(stop.station_id(), station.into_polar(&geometry))
@@ -468,28 +1232,61 @@ pub fn search<'s>(
                 route_name: _,
                 route_type: _,
                 route_color: _,
+                direction_id: _,
             } => {
                 expires_time = expires_time.min(departure_time);
-                let trip = trips
-                    .get_mut(&trip_id)
-                    .expect("trip to have been connected to");
+                if let (Some(from_zone), Some(to_zone)) =
+                    (data.zone_of(from_stop), data.zone_of(to_stop))
+                {
+                    if from_zone != to_zone {
+                        if let Some(station) = stations.get_mut(&to_stop.station_id()) {
+                            station.entered_zone.get_or_insert(to_zone);
+                        }
+                    }
+                }
+                let trip = if let Some(trip) = trips.get_mut(&trip_id) {
+                    trip
+                } else {
+                    eprintln!(
+                        "Skipping segment of trip {} from {:?} to {:?} as the trip wasn't connected to",
+                        trip_id, from_stop, to_stop
+                    );
+                    skipped_segments += 1;
+                    continue;
+                };
+                if to_stop.location == origin.location {
+                    // the segment has no bearing to be drawn with
+                    eprintln!(
+                        "Skipping segment of trip {} from {:?} to {:?} as it goes to the origin",
+                        trip_id, from_stop, to_stop
+                    );
+                    skipped_segments += 1;
+                    continue;
+                }
                 let segment = TripSegment {
                     from: from_stop,
                     to: to_stop,
                     departure_time,
                     arrival_time,
                 };
-                if let Some(pre) = trip.parts.last().unwrap().1.last() {
-                    assert!(
-                        !(pre.to != segment.from && pre.arrival_time != segment.departure_time),
-                        "shouldn't have a gap in the route : from ({:?} {}) to ({:?} {})",
-                        pre.to,
-                        pre.arrival_time,
-                        segment.from,
-                        segment.departure_time,
-                    );
+                let (_connection, segments) = if let Some(part) = trip.parts.last_mut() {
+                    part
+                } else {
+                    skipped_segments += 1;
+                    continue;
+                };
+                if let Some(pre) = segments.last() {
+                    if pre.to != segment.from && pre.arrival_time != segment.departure_time {
+                        // a segment in the middle of the trip is missing, such as from partially synced data
+                        eprintln!(
+                            "Skipping segment of trip {} from {:?} to {:?} as there is a gap in the route from {:?}",
+                            trip_id, from_stop, to_stop, pre.to
+                        );
+                        skipped_segments += 1;
+                        continue;
+                    }
                 }
-                trip.parts.last_mut().unwrap().1.push(segment);
+                segments.push(segment);
             }
             journey_graph::Item::ConnectionToTrip {
                 departure_time,
@@ -499,24 +1296,17 @@ pub fn search<'s>(
                 trip_id,
                 route_name,
                 route_type,
-                route_color: _,
+                route_color,
+                direction_id: _,
             } => {
-                let adjusted_departure_time = stations
-                    .get(&from_stop.station_id())
-                    .map(|station| station.coords.1.time().into())
-                    .unwrap_or(departure_time);
-                if adjusted_departure_time != departure_time {
-                    eprintln!("Had to adjust departure time of connection to {}({}) from {:?} as the departure time was {} but {:?} is reached earliest at {}",
-                        route_name, trip_id, from_stop, departure_time, from_stop, adjusted_departure_time,
-                    );
-                }
-
                 trips
                     .entry(trip_id)
                     .or_insert_with(|| RadarTrip {
-                        _trip_id: trip_id,
+                        trip_id,
+                        route_id: data.get_route_for_trip(&trip_id).route_id,
                         route_name: route_name.to_string(),
                         route_type,
+                        route_color: route_color.to_string(),
                         parts: Vec::with_capacity(1),
                     })
                     .parts
@@ -524,7 +1314,7 @@ pub fn search<'s>(
                         TripSegment {
                             from: from_stop,
                             to: to_stop,
-                            departure_time: adjusted_departure_time,
+                            departure_time,
                             arrival_time,
                         },
                         vec![],
@@ -533,43 +1323,727 @@ pub fn search<'s>(
         }
     }
 
+    if let Some(max_km) = max_km {
+        leave_out_beyond(max_km * 1000., origin, &mut stations, &mut trips);
+    }
+    if let Some(importance) = importance {
+        mark_major_stations(data, importance, &mut stations);
+    }
+    let pois = reachable_pois(pois, &stations, &geometry);
+    let rim_labels = rim_labels(districts, origin, &geometry);
+    let service_gap = service_gap(
+        data,
+        origin,
+        date,
+        Period::between(start_time, end_time),
+        &modes,
+        direction,
+    );
+
+    let frequencies = frequency.then(|| {
+        route_frequencies(
+            data,
+            &stations,
+            &trips,
+            date,
+            Period::between(start_time, end_time),
+        )
+    });
+
+    let confidence = confidence.map(|percent| ConfidenceLayer {
+        percent,
+        travel_times: confident_travel_times(
+            data,
+            origin,
+            departure_time,
+            max_duration,
+            &modes,
+            direction,
+            &max_legs,
+            percent,
+        ),
+    });
+
+    let missed = missed.map(|before| {
+        let earlier = search(
+            data,
+            SearchParams {
+                origin,
+                departure_time: Some(departure_time - before),
+                max_duration,
+                modes: modes.clone(),
+                direction,
+                pois: &[],
+                districts: &[],
+                confidence: None,
+                max_km,
+                missed: None,
+                journey_url: None,
+                frequency: false,
+                scale,
+                max_legs: max_legs.clone(),
+                grace: None,
+                importance: None,
+                prefer_trips: prefer_trips.clone(),
+            },
+        );
+        let mut trips: Vec<RadarTrip> = earlier
+            .trips
+            .into_values()
+            .filter(|trip| {
+                // the connection from the origin ends when the trip departs
+                trip.parts.first().is_some_and(|(connection, _)| {
+                    connection.from.station_id() == origin.station_id()
+                        && connection.arrival_time < start_time
+                })
+            })
+            .collect();
+        trips.sort_by_key(|trip| trip.trip_id);
+        MissedLayer {
+            before,
+            geometry: earlier.geometry,
+            trips,
+        }
+    });
+
+    let mut tied_trips: Vec<TripId> = plotter
+        .tied_trips()
+        .iter()
+        .copied()
+        .filter(|trip_id| trips.contains_key(trip_id))
+        .collect();
+    tied_trips.sort();
+
     Radar {
+        expires_at: departure_time + (expires_time - start_time),
         origin,
         geometry,
         trips,
         stations,
+        pois,
+        rim_labels,
+        skipped_segments,
+        service_gap,
+        confidence,
+        max_km,
+        missed,
+        journey_url,
+        frequencies,
+        tied_trips,
     }
 }
 
-impl<'s> RadarTrip<'s> {
-    pub(crate) fn write_svg_fragment_to(
-        &self,
-        w: &mut dyn std::io::Write,
-        geometry: &Geo,
-    ) -> io::Result<()> {
-        let RadarTrip {
-            _trip_id: _,
-            route_name,
-            route_type,
-            parts,
-        } = self;
-        let time_to_datetime = |time: Time| {
-            geometry
+/// One in this many of the stations on a radar, the most important ones, are labelled larger
+const STATIONS_PER_MAJOR_STATION: usize = 10;
+
+/// Marks the most important of the stations to be labelled larger, a radar of fewer than 10 stations has none
+fn mark_major_stations(
+    data: &GTFSData,
+    importance: &Importance,
+    stations: &mut HashMap<StopId, Station<FlattenedTimeCone>>,
+) {
+    let mut by_importance: Vec<(usize, StopId)> = stations
+        .keys()
+        .filter_map(|&station_id| data.get_stop(station_id))
+        .map(|station| (importance.score(station, data), station.stop_id))
+        .collect();
+    by_importance.sort_by_key(|&(score, station_id)| (std::cmp::Reverse(score), station_id));
+    let majors = stations.len() / STATIONS_PER_MAJOR_STATION;
+    for (_, station_id) in by_importance.into_iter().take(majors) {
+        if let Some(station) = stations.get_mut(&station_id) {
+            station.major = true;
+        }
+    }
+}
+
+/// Counts the trips of each route on the radar which stop at one of the radar's stations in the period, which is how often the route runs where it's drawn
+fn route_frequencies(
+    data: &GTFSData,
+    stations: &HashMap<StopId, Station<FlattenedTimeCone>>,
+    trips: &HashMap<TripId, RadarTrip>,
+    date: NaiveDate,
+    period: Period,
+) -> HashMap<RouteId, usize> {
+    let routes: HashSet<RouteId> = trips.values().map(|trip| trip.route_id).collect();
+    let services = data.services_on(date);
+    // a trip stops at many of the stations, and at several platforms of each
+    let mut trips_of_routes: HashSet<(RouteId, TripId)> = HashSet::new();
+    for station in stations.values() {
+        let stops = std::iter::once(station.stop).chain(
+            station
+                .stop
+                .children()
+                .filter_map(|&stop_id| data.get_stop(stop_id)),
+        );
+        for stop in stops {
+            for (trip, _stop_times) in data.trips_from(stop, &services, period) {
+                if routes.contains(&trip.route.route_id) {
+                    trips_of_routes.insert((trip.route.route_id, trip.trip_id));
+                }
+            }
+        }
+    }
+    let mut frequencies = HashMap::new();
+    for (route_id, _trip_id) in trips_of_routes {
+        *frequencies.entry(route_id).or_insert(0) += 1;
+    }
+    frequencies
+}
+
+/// A route running this many trips an hour is drawn as wide as the rail lines are without frequencies
+const FREQUENT_TRIPS_PER_HOUR: f64 = 6.;
+const FREQUENT_WIDTH_POINTS: f64 = 2.;
+
+/// The width in points to draw a route with which runs this many trips in the period, by area so that twice as many trips don't look four times as busy
+fn frequency_width(trips: usize, period: Duration) -> f64 {
+    let trips_per_hour = trips as f64 * 60. / period.num_minutes().max(1) as f64;
+    (FREQUENT_WIDTH_POINTS * (trips_per_hour / FREQUENT_TRIPS_PER_HOUR).sqrt()).clamp(0.5, 6.)
+}
+
+#[test]
+fn frequency_widths() {
+    assert_eq!(frequency_width(3, Duration::minutes(30)), 2.);
+    assert_eq!(frequency_width(12, Duration::minutes(30)), 4.);
+    assert_eq!(frequency_width(0, Duration::minutes(30)), 0.5);
+    assert_eq!(frequency_width(100, Duration::minutes(30)), 6.);
+    assert!(frequency_width(1, Duration::minutes(30)) < frequency_width(2, Duration::minutes(30)));
+}
+
+/// Metres between two stops as the crow flies
+fn metres_between(a: &Stop, b: &Stop) -> f64 {
+    // locations are stored (lat, lon) but haversine distance wants (lon, lat)
+    let lon_lat = |point: geo::Point<f64>| geo::Point::new(point.y(), point.x());
+    geo::algorithm::haversine_distance::HaversineDistance::haversine_distance(
+        &lon_lat(a.location),
+        &lon_lat(b.location),
+    )
+}
+
+/// Leaves out the stations further than the distance from the origin, and the trips from where they leave the distance
+fn leave_out_beyond(
+    max_metres: f64,
+    origin: &Stop,
+    stations: &mut HashMap<StopId, Station<FlattenedTimeCone>>,
+    trips: &mut HashMap<TripId, RadarTrip>,
+) {
+    let within = |stop: &Stop| metres_between(origin, stop) <= max_metres;
+    stations.retain(|_, station| within(station.stop));
+    for trip in trips.values_mut() {
+        trip.parts.retain(|(connection, _)| within(connection.to));
+        for (_connection, segments) in &mut trip.parts {
+            if let Some(leaving) = segments.iter().position(|segment| !within(segment.to)) {
+                segments.truncate(leaving);
+            }
+        }
+    }
+    trips.retain(|_, trip| !trip.parts.is_empty());
+}
+
+/// The travel time to each station reached within the duration, for searches which are only counted rather than drawn
+fn travel_times(
+    data: &GTFSData,
+    origin: &Stop,
+    departure_time: DateTime<Tz>,
+    max_duration: Duration,
+    modes: &HashSet<TransitMode>,
+    direction: Option<DirectionId>,
+    max_legs: &MaxLegs,
+) -> HashMap<StopId, Duration> {
+    let (_day, start_time) = day_time(departure_time);
+    plotter(
+        data,
+        origin,
+        departure_time,
+        max_duration,
+        modes,
+        direction,
+        max_legs,
+        None,
+        &[],
+    )
+    .filter_map(|item| match item {
+        journey_graph::Item::Station {
+            stop,
+            earliest_arrival,
+            ..
+        } => Some((stop.station_id(), earliest_arrival - start_time)),
+        _ => None,
+    })
+    .filter(|(_, travel_time)| *travel_time <= max_duration)
+    .collect()
+}
+
+/// A radar fitted to it's origin is long enough to reach this many stations
+pub const FIT_STATIONS: usize = 40;
+/// The fitted duration is a whole number of these steps, so that searches around the same time fit the same
+const FIT_STEP_MINS: i64 = 5;
+const MIN_FIT_MINS: i64 = 15;
+/// The longest a radar is fitted to, which is searched to fit it
+pub const MAX_FIT_MINS: i64 = 90;
+
+/// How long the search takes to reach enough stations to be worth drawing, so that radars from peripheral stations aren't nearly empty and those from the centre aren't crowded, the max duration of the search is ignored
+pub fn fitted_duration(
+    data: &GTFSData,
+    SearchParams {
+        origin,
+        departure_time,
+        modes,
+        direction,
+        max_legs,
+        ..
+    }: SearchParams,
+) -> Duration {
+    let departure_time =
+        departure_time.unwrap_or_else(|| Utc::now().with_timezone(&chrono_tz::Europe::Berlin));
+    let max_duration = Duration::minutes(MAX_FIT_MINS);
+    let mut reached: Vec<Duration> = travel_times(
+        data,
+        origin,
+        departure_time,
+        max_duration,
+        &modes,
+        direction,
+        &max_legs,
+    )
+    .into_iter()
+    .filter(|(station_id, _)| *station_id != origin.station_id())
+    .map(|(_, travel_time)| travel_time)
+    .collect();
+    reached.sort();
+    let enough = reached
+        .get(FIT_STATIONS - 1)
+        .copied()
+        .unwrap_or(max_duration);
+    let steps = (enough.num_seconds() as f64 / (FIT_STEP_MINS * 60) as f64).ceil() as i64;
+    Duration::minutes((steps * FIT_STEP_MINS).clamp(MIN_FIT_MINS, MAX_FIT_MINS))
+}
+
+/// The number of stations reached from the origin by departures through the day of the departure time, every interval from midnight
+pub fn reachable_through_the_day(
+    data: &GTFSData,
+    SearchParams {
+        origin,
+        departure_time,
+        max_duration,
+        modes,
+        direction,
+        max_km,
+        max_legs,
+        ..
+    }: SearchParams,
+    interval: Duration,
+) -> Vec<(DateTime<Tz>, usize)> {
+    let departure_time =
+        departure_time.unwrap_or_else(|| Utc::now().with_timezone(&chrono_tz::Europe::Berlin));
+    let date = departure_time.date_naive();
+    let midnight = |date: NaiveDate| {
+        chrono_tz::Europe::Berlin
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+            .expect("midnight to exist")
+    };
+    let end = midnight(date + Duration::days(1));
+    let mut samples = vec![];
+    let mut sample_departure_time = midnight(date);
+    while sample_departure_time < end {
+        let reached = travel_times(
+            data,
+            origin,
+            sample_departure_time,
+            max_duration,
+            &modes,
+            direction,
+            &max_legs,
+        )
+        .into_keys()
+        .filter(|&station_id| station_id != origin.stop_id)
+        .filter(|&station_id| match (max_km, data.get_stop(station_id)) {
+            (Some(max_km), Some(station)) => metres_between(origin, station) <= max_km * 1000.,
+            _ => true,
+        })
+        .count();
+        samples.push((sample_departure_time, reached));
+        sample_departure_time += interval;
+    }
+    samples
+}
+
+/// The travel time to each station the search reaches, including the origin, which are the planner's earliest arrivals without the trips to them
+pub fn earliest_arrivals(
+    data: &GTFSData,
+    SearchParams {
+        origin,
+        departure_time,
+        max_duration,
+        modes,
+        direction,
+        max_km,
+        max_legs,
+        ..
+    }: SearchParams,
+) -> BTreeMap<StopId, Duration> {
+    let departure_time =
+        departure_time.unwrap_or_else(|| Utc::now().with_timezone(&chrono_tz::Europe::Berlin));
+    travel_times(
+        data,
+        origin,
+        departure_time,
+        max_duration,
+        &modes,
+        direction,
+        &max_legs,
+    )
+    .into_iter()
+    .filter(
+        |&(station_id, _)| match (max_km, data.get_stop(station_id)) {
+            (Some(max_km), Some(station)) => metres_between(origin, station) <= max_km * 1000.,
+            _ => true,
+        },
+    )
+    .collect()
+}
+
+/// The travel time to each station which the percent of departures sampled through the window after the departure time are at least as fast as.
+///
+/// A station which too many of the departures don't reach within the duration is left out.
+#[allow(clippy::too_many_arguments)]
+fn confident_travel_times(
+    data: &GTFSData,
+    origin: &Stop,
+    departure_time: DateTime<Tz>,
+    max_duration: Duration,
+    modes: &HashSet<TransitMode>,
+    direction: Option<DirectionId>,
+    max_legs: &MaxLegs,
+    percent: u8,
+) -> HashMap<StopId, Duration> {
+    let samples: Vec<HashMap<StopId, Duration>> = (0..CONFIDENCE_WINDOW_MINS)
+        .step_by(CONFIDENCE_SAMPLE_MINS as usize)
+        .map(|offset| {
+            travel_times(
+                data,
+                origin,
+                departure_time + Duration::minutes(offset),
+                max_duration,
+                modes,
+                direction,
+                max_legs,
+            )
+        })
+        .collect();
+    // the number of samples which have to be at least as fast
+    let needed = (samples.len() * usize::from(percent)).div_ceil(100).max(1);
+    let stations: HashSet<StopId> = samples
+        .iter()
+        .flat_map(|sample| sample.keys().copied())
+        .collect();
+    stations
+        .into_iter()
+        .filter_map(|station_id| {
+            let mut travel_times: Vec<Duration> = samples
+                .iter()
+                .filter_map(|sample| sample.get(&station_id).copied())
+                .collect();
+            travel_times.sort();
+            Some((station_id, *travel_times.get(needed - 1)?))
+        })
+        .collect()
+}
+
+/// Checks whether there are enough departures from the origin within the period for the search to show much
+fn service_gap(
+    data: &GTFSData,
+    origin: &Stop,
+    date: NaiveDate,
+    period: Period,
+    modes: &HashSet<TransitMode>,
+    direction: Option<DirectionId>,
+) -> Option<ServiceGap> {
+    let services = data.services_on(date);
+    let stops: Vec<&Stop> = std::iter::once(origin)
+        .chain(
+            origin
+                .children()
+                .filter_map(|&stop_id| data.get_stop(stop_id)),
+        )
+        .collect();
+    // departures by the enabled modes in the searched direction, in the order they leave
+    let departures = |period: Period| {
+        let mut departures: Vec<(Time, &Trip)> = stops
+            .iter()
+            .flat_map(|stop| {
+                data.trips_from(stop, &services, period)
+                    .into_iter()
+                    .filter_map(|(trip, mut stop_times)| {
+                        Some((stop_times.next()?.departure_time, trip))
+                    })
+            })
+            .filter(|(_, trip)| {
+                TransitMode::of_route_type(trip.route.route_type)
+                    .map(|mode| modes.contains(&mode))
+                    .unwrap_or(false)
+                    && (direction.is_none() || trip.direction_id == direction)
+            })
+            .collect();
+        departures.sort_by_key(|(departure_time, _)| *departure_time);
+        departures
+    };
+    if departures(period).len() >= SPARSE_SERVICE_DEPARTURES {
+        return None;
+    }
+    let lookahead = Period::between(
+        period.end(),
+        period.end() + Duration::hours(NEXT_DEPARTURE_LOOKAHEAD_HOURS),
+    );
+    let next_departure = departures(lookahead)
+        .first()
+        .map(|(departure_time, trip)| (trip.route.route_short_name.clone(), *departure_time));
+    Some(ServiceGap {
+        night: is_night(period.start()),
+        next_departure,
+    })
+}
+
+/// Between 0:00 and 5:00 most lines don't run and the night network takes over
+fn is_night(time: Time) -> bool {
+    time.hour() % 24 < 5
+}
+
+impl Display for ServiceGap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.night {
+            f.write_str("limited night service")?;
+        } else {
+            f.write_str("limited service")?;
+        }
+        match &self.next_departure {
+            Some((route_name, departure_time)) => write!(
+                f,
+                " \u{2014} next {} {}:{:02}",
+                route_name,
+                departure_time.hour() % 24,
+                departure_time.minute()
+            ),
+            None => write!(
+                f,
+                " \u{2014} no departures in the next {} hours",
+                NEXT_DEPARTURE_LOOKAHEAD_HOURS
+            ),
+        }
+    }
+}
+
+#[test]
+fn service_gap_note() {
+    let gap = ServiceGap {
+        night: true,
+        next_departure: Some(("S1".to_string(), Time::from_hms(28, 37, 0))),
+    };
+    assert_eq!(
+        gap.to_string(),
+        "limited night service \u{2014} next S1 4:37"
+    );
+    let gap = ServiceGap {
+        night: false,
+        next_departure: None,
+    };
+    assert_eq!(
+        gap.to_string(),
+        "limited service \u{2014} no departures in the next 6 hours"
+    );
+    assert!(is_night(Time::from_hms(2, 0, 0)));
+    assert!(is_night(Time::from_hms(26, 0, 0)));
+    assert!(!is_night(Time::from_hms(14, 0, 0)));
+}
+
+/// Districts further than this aren't used to label the rim
+const MAX_DISTRICT_METRES: f64 = 20_000.;
+/// The rim is divided into this many sectors, each labelled with the closest district in it
+const RIM_SECTORS: f64 = 16.;
+
+/// Labels the rim with the districts surrounding the origin, the origin's own district is left out as it has no direction
+fn rim_labels<'s>(districts: &'s [District], origin: &Stop, geometry: &Geo) -> Vec<RimLabel<'s>> {
+    // locations are stored (lat, lon) but haversine distance wants (lon, lat)
+    let lon_lat = |point: geo::Point<f64>| geo::Point::new(point.y(), point.x());
+    let mut closest_in_sector: HashMap<i64, (f64, RimLabel<'s>)> = HashMap::new();
+    for district in districts {
+        if district.contains(origin.location) {
+            continue;
+        }
+        let centroid = if let Some(centroid) = district.centroid() {
+            centroid
+        } else {
+            continue;
+        };
+        let distance = geo::algorithm::haversine_distance::HaversineDistance::haversine_distance(
+            &lon_lat(origin.location),
+            &lon_lat(centroid),
+        );
+        if distance > MAX_DISTRICT_METRES {
+            continue;
+        }
+        let bearing = if let Some(bearing) = geometry.bearing(centroid) {
+            bearing
+        } else {
+            continue;
+        };
+        let sector = (bearing.normalize_around_zero().as_radians() / (2. * PI) * RIM_SECTORS)
+            .round() as i64
+            % RIM_SECTORS as i64;
+        let label = RimLabel {
+            bearing,
+            name: &district.name,
+        };
+        match closest_in_sector.get(&sector) {
+            Some((closest, _)) if *closest <= distance => {}
+            _ => {
+                closest_in_sector.insert(sector, (distance, label));
+            }
+        }
+    }
+    closest_in_sector
+        .into_values()
+        .map(|(_distance, label)| label)
+        .collect()
+}
+
+/// Furthest to walk from a station to a point of interest
+pub(crate) const MAX_WALK_METRES: f64 = 800.;
+pub(crate) const WALKING_METRES_PER_SECOND: f64 = 1.3;
+
+/// Points of interest within walking distance of a station, placed at the time they can be walked to from the nearest station
+fn reachable_pois<'s>(
+    pois: &'s [Poi],
+    stations: &HashMap<StopId, Station<'s, FlattenedTimeCone>>,
+    geometry: &Geo,
+) -> Vec<RadarPoi<'s>> {
+    // locations are stored (lat, lon) but haversine distance wants (lon, lat)
+    let lon_lat = |point: geo::Point<f64>| geo::Point::new(point.y(), point.x());
+    pois.iter()
+        .filter_map(|poi| {
+            let (distance, station) = stations
+                .values()
+                .map(|station| {
+                    let distance =
+                        geo::algorithm::haversine_distance::HaversineDistance::haversine_distance(
+                            &lon_lat(station.stop.location),
+                            &lon_lat(poi.location),
+                        );
+                    (distance, station)
+                })
+                .filter(|(distance, _station)| *distance <= MAX_WALK_METRES)
+                .min_by(|(d1, _), (d2, _)| d1.total_cmp(d2))?;
+            let arrival =
+                station.coords.1 + Duration::seconds((distance / WALKING_METRES_PER_SECOND) as i64);
+            (arrival <= geometry.time_cone_geometry.max()).then(|| RadarPoi {
+                coords: (geometry.bearing(poi.location).unwrap_or_default(), arrival),
+                poi,
+            })
+        })
+        .collect()
+}
+
+/// Reloads the radar every few seconds while it's shown, unless motion is reduced, preferring the trips it drew which won a tie
+const REFRESH_SCRIPT: &str = r#"
+                const refreshNotice = document.getElementById('refresh-notice')
+                refreshNotice.setAttribute('visibility', 'visible');
+                const refresh = () => {
+                    // trips arriving at the same time as the ones drawn now would otherwise swap with them
+                    const tied = refreshNotice.getAttribute('data-prefer');
+                    const url = new URL(location.href);
+                    if (tied) {
+                        url.searchParams.set('prefer', tied);
+                    } else {
+                        url.searchParams.delete('prefer');
+                    }
+                    location.replace(url);
+                };
+                if (window.matchMedia('(prefers-reduced-motion: reduce)').matches) {
+                    // the radar redrawing every few seconds is a lot of motion, so only refresh on request
+                    refreshNotice.textContent = 'paused for reduced motion [refresh]';
+                    refreshNotice.onclick = refresh;
+                } else {
+                    const refreshTimeout = setTimeout(refresh, 5000);
+                    refreshNotice.onclick = () => {
+                        clearTimeout(refreshTimeout);
+                        refreshNotice.setAttribute('visibility', 'hidden');
+                    }
+                }
+                "#;
+
+/// How long until a departure, for reading the radar as a departure board
+fn countdown(until_departure: Duration) -> String {
+    match until_departure.num_minutes() {
+        minutes if minutes < 1 => "now".to_string(),
+        minutes => format!("in {} min", minutes),
+    }
+}
+
+#[test]
+fn countdown_minutes() {
+    assert_eq!(countdown(Duration::seconds(30)), "now");
+    assert_eq!(countdown(Duration::seconds(4 * 60 + 50)), "in 4 min");
+    assert_eq!(countdown(Duration::minutes(25)), "in 25 min");
+}
+
+impl<'s> RadarTrip<'s> {
+    pub(crate) fn write_svg_fragment_to(
+        &self,
+        w: &mut dyn std::io::Write,
+        geometry: &Geo,
+        origin: &Stop,
+        journey_url: Option<&JourneyUrl>,
+        palette: Palette,
+        width: Option<f64>,
+    ) -> io::Result<()> {
+        let RadarTrip {
+            trip_id,
+            route_id,
+            route_name,
+            route_type,
+            route_color,
+            parts,
+        } = self;
+        // the route colour may be too close to a theme's background to see, the original is kept as metadata on the path
+        let stroke = || Stroke {
+            light: palette
+                .stroke(*route_type, route_color, LIGHT_BACKGROUND)
+                .into_owned(),
+            dark: palette
+                .stroke(*route_type, route_color, DARK_BACKGROUND)
+                .into_owned(),
+            original: route_color.clone(),
+        };
+        // connections keep the stylesheet's dashes and width
+        let mut style = vec![];
+        if let Some(dash) = palette.dash(*route_type) {
+            let dash: Vec<_> = dash.iter().map(ToString::to_string).collect();
+            style.push(format!("stroke-dasharray: {}", dash.join(" ")));
+        }
+        if let Some(width) = width {
+            style.push(format!("stroke-width: {:.1}pt", width));
+        }
+        let style = (!style.is_empty()).then(|| style.join("; "));
+        let time_to_datetime = |time: Time| {
+            geometry
                 .time_cone_geometry
                 .origin()
                 .date()
                 .and_time(time.into())
                 .unwrap()
         };
+        // the trip departs where it's boarded when the connection to it ends
+        let departure = parts
+            .first()
+            .map(|(connection, _)| time_to_datetime(connection.arrival_time))
+            .unwrap_or_else(|| geometry.time_cone_geometry.origin());
+        write_xml!(w,
+            <g class="trip" data-trip={trip_id} data-route={route_id} data-departure={departure.format("%H:%M")}>
+        )?;
+        if let Some(journey_url) = journey_url {
+            write_xml!(w, <a href={journey_url.of_trip(*route_id, *trip_id, departure)}>)?;
+        }
         for (connection, segments) in parts {
-            // At Wannsee, bus 118 leaves Wannsee and arrives at Wannsee 2 minutes later according to my data, remove any of these
-            let mut segments = &segments[..];
-            for i in 0..segments.len() {
-                if segments[i].to.location != geometry.geographic_origin {
-                    segments = &segments[i..];
-                    break;
-                }
-            }
             {
                 let TripSegment {
                     from,
@@ -579,25 +2053,44 @@ impl<'s> RadarTrip<'s> {
                 } = connection;
                 let mut path = Path::begin_path();
                 path.set_class(format!("Connection {:?} {}", route_type, route_name));
+                path.set_stroke(stroke());
 
-                let mut to = to;
-                if to.location == geometry.geographic_origin {
-                    // connection is on origin meaning no natural bearing for it, we use the bearing to the next stop
-                    to = &segments.first().expect("at least one segment in a trip").to;
+                // connection is on origin meaning no natural bearing for it, we use the bearing to the next stop
+                let to_bearing = geometry.bearing(to.location).or_else(|| {
+                    segments
+                        .first()
+                        .and_then(|segment| geometry.bearing(segment.to.location))
+                });
+                if let Some(to_bearing) = to_bearing {
+                    path.move_to((
+                        geometry.bearing(from.location).unwrap_or_default(),
+                        time_to_datetime(*departure_time),
+                    ));
+                    path.line_to((to_bearing, time_to_datetime(*arrival_time)));
+                    path.write_svg_fragment_to(w, &geometry.time_cone_geometry, route_name)?;
+                    if from.station_id() == origin.station_id() {
+                        // first leg, so the radar also shows when to be at the platform, the connection ends when the trip departs
+                        let departure = time_to_datetime(*arrival_time);
+                        let (x, y) = geometry.time_cone_geometry.coords(to_bearing, departure);
+                        write_xml!(w,
+                            <text class="countdown" x={*x + 4.} y={*y - 4.}>
+                                {countdown(departure - geometry.time_cone_geometry.origin())}
+                            </text>
+                        )?;
+                    }
                 }
-                path.move_to((
-                    geometry.bearing(from.location).unwrap_or_default(),
-                    time_to_datetime(*departure_time),
-                ));
-                path.line_to((
-                    geometry.bearing(to.location).unwrap(),
-                    time_to_datetime(*arrival_time),
-                ));
-                path.write_svg_fragment_to(w, &geometry.time_cone_geometry, route_name)?;
+            }
+            if segments.is_empty() {
+                // the trip was boarded but none of it's segments could be drawn
+                continue;
             }
 
             let mut path = Path::begin_path();
             path.set_class(format!("{:?} {}", route_type, route_name));
+            path.set_stroke(stroke());
+            if let Some(style) = &style {
+                path.set_style(style.clone());
+            }
             match segments.len().cmp(&1) {
                 std::cmp::Ordering::Greater => {
                     let mut next_control_point = {
@@ -639,15 +2132,11 @@ impl<'s> RadarTrip<'s> {
                     // all the connecting segments
                     for window in segments.windows(3) {
                         if let [pre, segment, post] = window {
-                            assert!(
-                                !(pre.to != segment.from
-                                    && pre.arrival_time != segment.departure_time),
-                                "shouldn't have a gap in the route : from ({:?} {}) to ({:?} {})",
-                                pre.to,
-                                pre.arrival_time,
-                                segment.from,
-                                segment.departure_time,
-                            );
+                            if pre.to != segment.from && pre.arrival_time != segment.departure_time
+                            {
+                                // segments after a gap were skipped and counted in `skipped_segments` when the radar was searched
+                                continue;
+                            }
 
                             let to_bearing = geometry.bearing(segment.to.location).unwrap();
                             let from_bearing = geometry
@@ -705,17 +2194,68 @@ impl<'s> RadarTrip<'s> {
                         (to_bearing, time_to_datetime(segment.arrival_time)),
                     );
                 }
-                std::cmp::Ordering::Less => {
-                    panic!("path is empty - ignore");
-                }
+                std::cmp::Ordering::Less => unreachable!("empty trips are skipped"),
             }
             assert!(!path.ops.is_empty());
             path.write_svg_fragment_to(w, &geometry.time_cone_geometry, route_name)?;
         }
+        if journey_url.is_some() {
+            write_xml!(w, </a>)?;
+        }
+        write_xml!(w, </g>)
+    }
+
+    /// The waits at the stops the trip is boarded at, from when the stop is reached until the trip departs, drawn along the radius as it's time
+    fn write_waits_svg_fragment_to(
+        &self,
+        w: &mut dyn io::Write,
+        geometry: &Geo,
+        origin: &Stop,
+    ) -> io::Result<()> {
+        let departure = geometry.time_cone_geometry.origin();
+        let departure_time = Time::from(departure.time());
+        for (connection, _segments) in &self.parts {
+            // the wait at the origin is counted down to instead
+            if connection.from.station_id() == origin.station_id() {
+                continue;
+            }
+            let wait = connection.arrival_time - connection.departure_time;
+            if wait < Duration::minutes(MIN_WAIT_MINS) {
+                continue;
+            }
+            let bearing = if let Some(bearing) = geometry.bearing(connection.to.location) {
+                bearing
+            } else {
+                continue;
+            };
+            let mut path = Path::begin_path();
+            path.set_class("wait".to_owned());
+            path.move_to((
+                bearing,
+                departure + (connection.departure_time - departure_time),
+            ));
+            path.line_to((
+                bearing,
+                departure + (connection.arrival_time - departure_time),
+            ));
+            path.write_svg_fragment_to(
+                w,
+                &geometry.time_cone_geometry,
+                &format!(
+                    "{} min wait at {} for {}",
+                    wait.num_minutes(),
+                    connection.to.short_stop_name(),
+                    self.route_name
+                ),
+            )?;
+        }
         Ok(())
     }
 }
 
+/// Shorter waits at a transfer aren't drawn, they're hardly longer than the line's caps
+const MIN_WAIT_MINS: i64 = 1;
+
 impl Geo {
     fn write_svg_fragment_to(&self, w: &mut dyn io::Write) -> io::Result<()> {
         let (origin_x, origin_y) = (0., 0.);
@@ -727,14 +2267,18 @@ impl Geo {
         } else {
             Duration::minutes(10)
         };
-        let pixel_interval: f64 = PIXEL_RADIUS * duration_interval.num_seconds() as f64
-            / max_duration.num_seconds() as f64;
+        let origin = self.time_cone_geometry.origin();
 
         write_xml!(w,
             <g class="grid">)?;
 
+        // the rings are as far apart as the radial scale puts them
         for radius in (1..)
-            .map(|x| pixel_interval * x as f64)
+            .map(|x| {
+                *self
+                    .time_cone_geometry
+                    .radius(origin + duration_interval * x)
+            })
             .take_while(|p: &f64| p <= &PIXEL_RADIUS)
         {
             write_xml!(w, <circle cx={origin_x} cy={origin_y} r={radius} />)?;
@@ -745,7 +2289,349 @@ impl Geo {
     }
 }
 
+/// The size of link previews, such as OpenGraph images
+pub const CARD_WIDTH: usize = 1200;
+pub const CARD_HEIGHT: usize = 630;
+/// The radar takes the left square of the card
+const CARD_RADAR_RADIUS: f64 = 290.;
+
+/// How many of the farthest stations to list for each mode in the summary
+const FARTHEST_STATIONS_PER_MODE: usize = 10;
+
+/// A station reached by the search, with the lines taken to get there
+struct ReachedStation<'r> {
+    name: &'r str,
+    arrival: DateTime<Tz>,
+    /// Metres from the origin
+    distance: f64,
+    lines: Vec<&'r str>,
+}
+
 impl<'s> Radar<'s> {
+    /// The stations furthest from the origin for each mode, by the mode of the last line taken to get there
+    fn farthest_stations(&self, per_mode: usize) -> Vec<(TransitMode, Vec<ReachedStation<'_>>)> {
+        // the trip which first arrives at each station and the station it was boarded at
+        let mut arrived_by: HashMap<StopId, (&RadarTrip, StopId)> = HashMap::new();
+        for trip in self.trips_in_order() {
+            for (connection, segments) in &trip.parts {
+                for segment in segments {
+                    let to_station = segment.to.station_id();
+                    let earliest_arrival = self
+                        .stations
+                        .get(&to_station)
+                        .map(|station| Time::from(station.coords.1.time()));
+                    let is_earliest = earliest_arrival == Some(segment.arrival_time);
+                    if is_earliest {
+                        arrived_by
+                            .entry(to_station)
+                            .or_insert((trip, connection.from.station_id()));
+                    }
+                }
+            }
+        }
+
+        // locations are stored (lat, lon) but haversine distance wants (lon, lat)
+        let lon_lat = |point: geo::Point<f64>| geo::Point::new(point.y(), point.x());
+        let mut by_mode: HashMap<TransitMode, Vec<ReachedStation>> = HashMap::new();
+        for (station_id, station) in &self.stations {
+            let mut lines = vec![];
+            let mut at = *station_id;
+            let mut mode = None;
+            while let Some((trip, boarded_at)) = arrived_by.get(&at) {
+                // a station can't be reached by more lines than there are stations
+                if lines.len() > self.stations.len() {
+                    break;
+                }
+                mode = mode.or_else(|| TransitMode::of_route_type(trip.route_type));
+                lines.push(trip.route_name.as_str());
+                at = *boarded_at;
+            }
+            let mode = if let Some(mode) = mode {
+                mode
+            } else {
+                continue;
+            };
+            lines.reverse();
+            by_mode.entry(mode).or_default().push(ReachedStation {
+                name: station.stop.short_stop_name(),
+                arrival: station.coords.1,
+                distance: geo::algorithm::haversine_distance::HaversineDistance::haversine_distance(
+                    &lon_lat(self.origin.location),
+                    &lon_lat(station.stop.location),
+                ),
+                lines,
+            });
+        }
+        TransitMode::ALL
+            .iter()
+            .filter_map(|mode| {
+                let mut reached = by_mode.remove(mode)?;
+                reached.sort_by(|a, b| b.distance.total_cmp(&a.distance));
+                reached.truncate(per_mode);
+                Some((*mode, reached))
+            })
+            .collect()
+    }
+
+    /// When the radar becomes stale, as the first trip on it has departed
+    pub fn expires_at(&self) -> DateTime<Tz> {
+        self.expires_at
+    }
+
+    /// Trips ordered by their id, rather than in the arbitrary order they are stored in
+    fn trips_in_order(&self) -> Vec<&RadarTrip<'s>> {
+        let mut trips: Vec<&RadarTrip> = self.trips.values().collect();
+        trips.sort_by_key(|trip| trip.trip_id);
+        trips
+    }
+
+    /// The departure tree with stable indices, to be serialised as JSON
+    pub fn tree(&self) -> Tree {
+        let trips = self.trips_in_order();
+        let mut stations: Vec<&Stop> = trips
+            .iter()
+            .flat_map(|trip| &trip.parts)
+            .flat_map(|(connection, segments)| std::iter::once(connection).chain(segments))
+            .flat_map(|segment| [segment.from, segment.to])
+            .chain(self.stations.values().map(|station| station.stop))
+            .chain(std::iter::once(self.origin))
+            .map(|stop| self.station_of(stop))
+            .collect();
+        stations.sort_by_key(|stop| stop.stop_id);
+        stations.dedup_by_key(|stop| stop.stop_id);
+        let index_of = |stop: &Stop| {
+            let station_id = self.station_of(stop).stop_id;
+            stations
+                .binary_search_by_key(&station_id, |station| station.stop_id)
+                .expect("all stations to be indexed")
+        };
+
+        let mut edges = vec![];
+        let tree_trips = trips
+            .iter()
+            .enumerate()
+            .map(|(trip_index, trip)| {
+                let mut segments = vec![];
+                for (connection, trip_segments) in &trip.parts {
+                    let connection = (EdgeKind::Connection, connection);
+                    let trip_segments = trip_segments
+                        .iter()
+                        .map(|segment| (EdgeKind::Segment, segment));
+                    for (kind, segment) in std::iter::once(connection).chain(trip_segments) {
+                        segments.push(edges.len());
+                        edges.push(TreeEdge {
+                            kind,
+                            from: index_of(segment.from),
+                            to: index_of(segment.to),
+                            departure_time: segment.departure_time,
+                            arrival_time: segment.arrival_time,
+                            trip: trip_index,
+                        });
+                    }
+                }
+                TreeTrip {
+                    trip_id: trip.trip_id,
+                    route_name: trip.route_name.clone(),
+                    route_type: trip.route_type.code(),
+                    mode: TransitMode::of_route_type(trip.route_type).map(|mode| mode.key()),
+                    route_color: trip.route_color.clone(),
+                    segments,
+                }
+            })
+            .collect();
+
+        Tree {
+            version: TREE_SCHEMA_VERSION,
+            expires_at: self.expires_at.to_rfc3339(),
+            origin: index_of(self.origin),
+            stations: stations
+                .iter()
+                .map(|stop| TreeStation {
+                    stop_id: stop.stop_id,
+                    name: stop.short_stop_name().to_string(),
+                    lat: stop.location.x(),
+                    lon: stop.location.y(),
+                    earliest_arrival: self
+                        .stations
+                        .get(&stop.station_id())
+                        .map(|station| station.coords.1.time().into()),
+                    transfers: self
+                        .stations
+                        .get(&stop.station_id())
+                        .map(|station| station.transfers),
+                })
+                .collect(),
+            edges,
+            trips: tree_trips,
+        }
+    }
+
+    /// A few sentences on how far the radar reaches, for where it can't be seen such as it's `<desc>`
+    pub fn summary(&self) -> String {
+        let time_cone_geometry = &self.geometry.time_cone_geometry;
+        let departure = time_cone_geometry.origin();
+        let mut summary = format!(
+            "Within {} min from {} {}: {} stations.",
+            time_cone_geometry.max_duration().num_minutes(),
+            self.origin.short_stop_name(),
+            departure.format("at %-H:%M on %-d %b %Y"),
+            self.reached_stations()
+        );
+        if let Some(service_gap) = &self.service_gap {
+            summary.push_str(&format!(" There is {}.", service_gap));
+        }
+        for (mode, reached) in self.farthest_stations(1) {
+            for station in reached {
+                summary.push_str(&format!(
+                    "\nFarthest by {} to {} ({:.1} km) in {} min via {}.",
+                    mode,
+                    station.name,
+                    station.distance / 1000.,
+                    (station.arrival - departure).num_minutes(),
+                    station.lines.join(", ")
+                ));
+            }
+        }
+        summary
+    }
+
+    /// The summary with the farthest stations by each mode as Markdown, which reads as plain text too
+    pub fn write_summary_to(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        writeln!(w, "# {} departures\n", self.origin.short_stop_name())?;
+        writeln!(w, "{}", self.summary().replace('\n', "  \n"))?;
+        for (mode, reached) in self.farthest_stations(FARTHEST_STATIONS_PER_MODE) {
+            writeln!(w, "\n## Farthest by {}\n", mode)?;
+            for station in reached {
+                writeln!(
+                    w,
+                    "- {} at {} ({:.1} km) via {}",
+                    station.name,
+                    station.arrival.format("%-H:%M"),
+                    station.distance / 1000.,
+                    station.lines.join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A PNG of the size link previews are shown at, the trips drawn straight between their stops on the left in the palette's colours and the summary on the right
+    pub fn write_card_png_to(&self, w: &mut dyn io::Write, palette: Palette) -> io::Result<()> {
+        let grey = Rgb(0x99, 0x99, 0x99);
+        let dark = Rgb(0x33, 0x33, 0x33);
+        let mut card = Canvas::new(CARD_WIDTH, CARD_HEIGHT, LIGHT_BACKGROUND);
+        let time_cone_geometry = &self.geometry.time_cone_geometry;
+        let departure = time_cone_geometry.origin();
+        let departure_time = Time::from(departure.time());
+        let centre = (CARD_HEIGHT as f64 / 2., CARD_HEIGHT as f64 / 2.);
+        let scale = CARD_RADAR_RADIUS / *time_cone_geometry.max_points();
+        let point = |bearing: Bearing, time: DateTime<Tz>| {
+            let (x, y) = time_cone_geometry.coords(bearing, time);
+            (centre.0 + *x * scale, centre.1 + *y * scale)
+        };
+
+        let rings = time_cone_geometry.max_duration().num_minutes() / 10;
+        for ring in 1..=rings {
+            let radius =
+                *time_cone_geometry.radius(departure + Duration::minutes(ring * 10)) * scale;
+            card.ring(centre, radius, 1., Rgb(0xdd, 0xdd, 0xdd));
+        }
+        for trip in self.trips_in_order() {
+            let colour =
+                Rgb::parse(&palette.stroke(trip.route_type, &trip.route_color, LIGHT_BACKGROUND))
+                    .unwrap_or(dark);
+            // the card's lines are half as wide again as the radar's, and so are the dashes
+            let width = self.frequency_width(trip).map_or(3., |width| width * 1.5);
+            let dash: Option<Vec<f64>> = palette
+                .dash(trip.route_type)
+                .map(|dash| dash.iter().map(|length| length * 1.5).collect());
+            for (connection, segments) in &trip.parts {
+                for (segment, is_connection) in std::iter::once((connection, true))
+                    .chain(segments.iter().map(|segment| (segment, false)))
+                {
+                    // the origin has no bearing of it's own, so it's taken from where the trip goes next
+                    let to_bearing = self.geometry.bearing(segment.to.location).or_else(|| {
+                        segments
+                            .first()
+                            .and_then(|next| self.geometry.bearing(next.to.location))
+                    });
+                    let to_bearing = if let Some(to_bearing) = to_bearing {
+                        to_bearing
+                    } else {
+                        continue;
+                    };
+                    let from_bearing = self
+                        .geometry
+                        .bearing(segment.from.location)
+                        .unwrap_or(to_bearing);
+                    let from = point(
+                        from_bearing,
+                        departure + (segment.departure_time - departure_time),
+                    );
+                    let to = point(
+                        to_bearing,
+                        departure + (segment.arrival_time - departure_time),
+                    );
+                    if is_connection {
+                        card.line(from, to, 1.5, grey);
+                    } else if let Some(dash) = &dash {
+                        card.dashed_line(from, to, width, colour, dash);
+                    } else {
+                        card.line(from, to, width, colour);
+                    }
+                }
+            }
+        }
+        for station in self.stations.values() {
+            card.disc(point(station.coords.0, station.coords.1), 2.5, dark);
+        }
+        card.disc(centre, 6., Rgb(0, 0, 0));
+
+        // the text is fitted into the right of the card, shrunk to fit long station names
+        let left = CARD_HEIGHT as f64 + 20.;
+        let text_width = CARD_WIDTH as f64 - left - 40.;
+        let fitted = |text: &str, largest: f64| {
+            (text_width / raster::text_width(text, 1.))
+                .floor()
+                .clamp(2., largest)
+        };
+        card.text((left, 50.), 4., "Transit radar", grey);
+        let name = self.origin.short_stop_name();
+        let name_scale = fitted(name, 10.);
+        card.text((left, 110.), name_scale, name, Rgb(0, 0, 0));
+        let mut y = 110. + name_scale * 7. + 40.;
+        let within = format!(
+            "within {} min from {}",
+            time_cone_geometry.max_duration().num_minutes(),
+            departure.format("%-H:%M")
+        );
+        card.text((left, y), fitted(&within, 5.), &within, dark);
+        y += 80.;
+        let stations = format!("{} stations", self.reached_stations());
+        card.text((left, y), fitted(&stations, 8.), &stations, dark);
+        y += 100.;
+        for (mode, reached) in self.farthest_stations(1).into_iter().take(3) {
+            for station in reached {
+                let farthest = format!(
+                    "{} {} {:.1} km",
+                    mode,
+                    station.name,
+                    station.distance / 1000.
+                );
+                card.text((left, y), fitted(&farthest, 4.), &farthest, grey);
+                y += 45.;
+            }
+        }
+        card.write_png(w)
+    }
+
+    /// The parent station of a stop if it is on the radar, otherwise the stop itself
+    fn station_of(&self, stop: &'s Stop) -> &'s Stop {
+        self.stations
+            .get(&stop.station_id())
+            .map_or(stop, |station| station.stop)
+    }
+
     pub fn write_svg_to(
         &self,
         w: &mut dyn io::Write,
@@ -754,41 +2640,122 @@ impl<'s> Radar<'s> {
     ) -> io::Result<()> {
         let Self {
             geometry,
-            stations,
-            trips,
             origin,
+            skipped_segments,
+            service_gap,
+            confidence,
+            max_km,
+            missed,
+            tied_trips,
+            ..
         } = self;
+        let missed_toggle = if let Some(missed) = missed {
+            format!(
+                "hide trips missed in the last {} minutes",
+                missed.before.num_minutes()
+            )
+        } else {
+            format!(
+                "show trips missed in the last {} minutes",
+                DEFAULT_MISSED_MINS
+            )
+        };
+        let within = max_km
+            .map(|max_km| format!(" within {} km", max_km))
+            .unwrap_or_default();
+        let (other_palette, palette_toggle) = match search_params.palette {
+            Palette::Route => (
+                Palette::ColourBlindFriendly,
+                "use colour blind friendly colours",
+            ),
+            Palette::ColourBlindFriendly => (Palette::Route, "use the lines' own colours"),
+        };
+        let frequency_toggle = if search_params.frequency {
+            "draw lines by mode"
+        } else {
+            "draw lines as wide as they are frequent"
+        };
+        let scale_toggle = if search_params.scale == RadialScale::Linear {
+            "spread out the centre"
+        } else {
+            "draw time to scale"
+        };
+        let confidence_toggle = if let Some(confidence) = confidence {
+            format!("hide arrivals with {}% confidence", confidence.percent)
+        } else {
+            format!(
+                "show arrivals with {}% confidence",
+                DEFAULT_CONFIDENCE_PERCENT
+            )
+        };
 
         writeln!(
             w,
             r#"<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
 <svg version="1.1" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100%" height="100%" viewBox="-512 -512 1024 1024">
     <title>{} departures: Transit Radar</title>
-    <desc>Departure tree.</desc>
-         "#,
-            origin.short_stop_name
+    <desc>{}"#,
+            Escaped(origin.short_stop_name()),
+            Escaped(&self.summary())
         )?;
+        for (mode, reached) in self.farthest_stations(FARTHEST_STATIONS_PER_MODE) {
+            writeln!(w, "\nFarthest by {}:", mode)?;
+            for station in reached {
+                writeln!(
+                    w,
+                    "{} at {} ({:.1} km) via {}",
+                    Escaped(station.name),
+                    station.arrival.format("%k:%M"),
+                    station.distance / 1000.,
+                    Escaped(JoinList {
+                        list: &station.lines,
+                        join: ", "
+                    }),
+                )?;
+            }
+        }
+        writeln!(w, "</desc>")?;
 
-        write_xml!(w, <style>{include_str!("Radar.css")}</style>)?;
+        // the style sheet and script are our own, so they are written as they are rather than escaped
+        writeln!(w, "<style>{}</style>", include_str!("Radar.css"))?;
 
         write_xml!(w,
             <g id="header" transform="translate(-506, -506)">
-                <text y="20" style="font-size: 20pt;">{origin.short_stop_name}{" departures"}</text>
+                <text y="20" style="font-size: 20pt;">{origin.short_stop_name()}{" departures"}</text>
                 <a href={search_params.clone().with_departure_time(geometry.time_cone_geometry.origin())} rel="self"><text y="50" style="font-size: 10pt; font-style: oblique;">
                     "All trips starting "{geometry.time_cone_geometry.origin().format("at %k:%M on %e %b %Y")}
-                    <tspan x="0" dy="1.4em">{"and lasting less than "}{geometry.time_cone_geometry.max_duration().num_minutes()}{" minutes"}</tspan>
+                    <tspan x="0" dy="1.4em">{"and lasting less than "}{geometry.time_cone_geometry.max_duration().num_minutes()}{" minutes"}{within}</tspan>
                 </text></a>
-                <text id="refresh-notice" y="90" visibility="hidden">"refreshing every 5 seconds [disable]"</text>
+                <text id="refresh-notice" y="90" visibility="hidden" data-prefer=[tied_trips,]>"refreshing every 5 seconds [disable]"</text>
+                <text id="partial-data" y="250" visibility={if *skipped_segments > 0 { "visible" } else { "hidden" }}>
+                    "partial data: "{skipped_segments}" segments couldn't be drawn"
+                </text>
+                <text id="service-gap" y="270" visibility={if service_gap.is_some() { "visible" } else { "hidden" }}>
+                    {service_gap.as_ref().map(ToString::to_string).unwrap_or_default()}
+                </text>
+                <a href={search_params.clone().with_confidence(if confidence.is_some() { None } else { Some(DEFAULT_CONFIDENCE_PERCENT) })}>
+                    <text id="confidence-toggle" y="290">{confidence_toggle}</text>
+                </a>
+                <a href={search_params.clone().with_missed(if missed.is_some() { None } else { Some(DEFAULT_MISSED_MINS) })}>
+                    <text id="missed-toggle" y="310">{missed_toggle}</text>
+                </a>
+                <a href={format!("/share{}", search_params)}>
+                    <text id="share" y="330">"share this radar"</text>
+                </a>
+        )?;
+        write_xml!(w,
+                <a href={search_params.clone().with_palette(other_palette)}>
+                    <text id="palette-toggle" y="350">{palette_toggle}</text>
+                </a>
+                <a href={search_params.clone().with_frequency(!search_params.frequency)}>
+                    <text id="frequency-toggle" y="370">{frequency_toggle}</text>
+                </a>
+                <a href={search_params.clone().with_scale(if search_params.scale == RadialScale::Linear { RadialScale::SquareRoot } else { RadialScale::Linear })}>
+                    <text id="scale-toggle" y="390">{scale_toggle}</text>
+                </a>
                 <text y="110" id="transport-types">
         )?;
-        for &mode in &[
-            TransitMode::SBahn,
-            TransitMode::UBahn,
-            TransitMode::Tram,
-            TransitMode::Bus,
-            TransitMode::Regional,
-            TransitMode::Boat,
-        ] {
+        for &mode in TransitMode::ALL {
             let mode_enabled = search_params.modes.contains(&mode);
             write_xml!(w,
                 <tspan x="0" dy="1.5em" class={ if mode_enabled { "" } else {"disabled"}}>
@@ -806,30 +2773,107 @@ impl<'s> Radar<'s> {
             </g>
         )?;
 
+        self.write_svg_drawing_to(w, &search_params)?;
+
+        if refresh {
+            writeln!(w, "<script>{}</script>", REFRESH_SCRIPT)?;
+        }
+
+        writeln!(w, "</svg>")
+    }
+
+    /// The radar itself, without the header, in the coordinates of the view box `-512 -512 1024 1024`
+    pub(crate) fn write_svg_drawing_to(
+        &self,
+        w: &mut dyn io::Write,
+        search_params: &UrlSearchParams<'s>,
+    ) -> io::Result<()> {
+        let Self {
+            geometry,
+            stations,
+            pois,
+            rim_labels,
+            origin,
+            confidence,
+            missed,
+            journey_url,
+            ..
+        } = self;
+
         geometry.write_svg_fragment_to(w)?;
-        for trip in trips.values() {
-            trip.write_svg_fragment_to(w, geometry)?;
+        write_xml!(w, <g class="districts">)?;
+        for label in rim_labels {
+            label.write_svg_fragment_to(w, &geometry.time_cone_geometry)?;
+        }
+        write_xml!(w, </g>)?;
+        if let Some(missed) = missed {
+            // under the trips which can still be taken
+            write_xml!(w,
+                <g class="missed">
+                    <title>"Trips which left in the "{missed.before.num_minutes()}" minutes before, as if they had been caught"</title>
+            )?;
+            for trip in &missed.trips {
+                trip.write_svg_fragment_to(
+                    w,
+                    &missed.geometry,
+                    origin,
+                    *journey_url,
+                    search_params.palette,
+                    self.frequency_width(trip),
+                )?;
+            }
+            write_xml!(w, </g>)?;
+        }
+        // under the trips, which they're between
+        write_xml!(w, <g class="waits">)?;
+        for trip in self.trips_in_order() {
+            trip.write_waits_svg_fragment_to(w, geometry, origin)?;
+        }
+        write_xml!(w, </g>)?;
+        for trip in self.trips_in_order() {
+            trip.write_svg_fragment_to(
+                w,
+                geometry,
+                origin,
+                *journey_url,
+                search_params.palette,
+                self.frequency_width(trip),
+            )?;
+        }
+        // in a stable order, so that the same search draws the same document
+        let mut stations: Vec<_> = stations.iter().collect();
+        stations.sort_by_key(|(station_id, _)| **station_id);
+        if let Some(confidence) = confidence {
+            confidence.write_svg_fragment_to(w, &stations, &geometry.time_cone_geometry)?;
         }
         write_xml!(w, <g class="s">)?;
-        for station in stations.values() {
-            station.write_svg_fragment_to(w, &geometry.time_cone_geometry, &search_params)?;
+        for (_, station) in stations {
+            station.write_svg_fragment_to(w, &geometry.time_cone_geometry, search_params)?;
         }
         write_xml!(w, </g>)?;
-
-        if refresh {
-            write_xml!(w,
-                <script>{r#"
-                const refreshTimeout = setTimeout(() => location.reload(), 5000);
-                const refreshNotice = document.getElementById('refresh-notice')
-                refreshNotice.setAttribute('visibility', 'visible');
-                refreshNotice.onclick = () => {
-                    clearTimeout(refreshTimeout);
-                    refreshNotice.setAttribute('visibility', 'hidden');
-                }
-                "#}</script>)?;
+        write_xml!(w, <g class="poi">)?;
+        for poi in pois {
+            poi.write_svg_fragment_to(w, &geometry.time_cone_geometry)?;
         }
+        write_xml!(w, </g>)
+    }
 
-        writeln!(w, "</svg>")
+    /// The width in points to draw the trip's route with, when routes are drawn by how often they run
+    fn frequency_width(&self, trip: &RadarTrip) -> Option<f64> {
+        self.frequencies.as_ref().map(|frequencies| {
+            frequency_width(
+                frequencies.get(&trip.route_id).copied().unwrap_or(1),
+                self.geometry.time_cone_geometry.max_duration(),
+            )
+        })
+    }
+
+    /// The number of stations on the radar, not counting the origin
+    pub fn reached_stations(&self) -> usize {
+        self.stations
+            .keys()
+            .filter(|&&station_id| station_id != self.origin.stop_id)
+            .count()
     }
 }
 
@@ -848,6 +2892,9 @@ impl<'s> Station<'s, Geo> {
             ),
             stop: self.stop,
             name_trunk_length: self.name_trunk_length,
+            transfers: self.transfers,
+            entered_zone: self.entered_zone,
+            major: self.major,
         }
     }
 }
@@ -866,20 +2913,345 @@ impl<'s> Station<'s, FlattenedTimeCone> {
         }
         let (cx, cy) = geometry.coords(bearing, magnitude);
         let name: std::borrow::Cow<_> = if self.name_trunk_length == 0 {
-            (*self.stop.short_stop_name).into()
+            self.stop.short_stop_name().into()
         } else {
             format!(
                 "...{}",
-                &self.stop.short_stop_name[self.name_trunk_length..]
+                &self.stop.short_stop_name()[self.name_trunk_length..]
             )
             .into()
         };
+        if let Some(zone) = self.entered_zone {
+            write_xml!(w,
+                <circle class="zone-change" cx={*cx} cy={*cy} r={STOP_RADIUS * 2.}><title>"Fare zone "{zone}</title></circle>
+            )?;
+        }
+        let href = search_params
+            .clone()
+            .with_station_id(self.stop.station_id());
+        if self.major {
+            write_xml!(w,
+                <a href={href}>
+                <circle cx={*cx} cy={*cy} r={STOP_RADIUS} />
+                    <text class="major" x={*cx + STOP_RADIUS + 6.} y={*cy + 4.}>{name}</text>
+                </a>
+            )?;
+        } else {
+            write_xml!(w,
+                <a href={href}>
+                <circle cx={*cx} cy={*cy} r={STOP_RADIUS} />
+                    <text x={*cx + STOP_RADIUS + 6.} y={*cy + 4.}>{name}</text>
+                </a>
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl ConfidenceLayer {
+    /// A whisker from each station out to when it's reached with the confidence
+    fn write_svg_fragment_to(
+        &self,
+        w: &mut dyn io::Write,
+        stations: &[(&StopId, &Station<FlattenedTimeCone>)],
+        geometry: &FlattenedTimeCone,
+    ) -> io::Result<()> {
+        const RADIUS: f64 = 2.;
+        write_xml!(w, <g class="confidence">)?;
+        for (station_id, station) in stations {
+            let (bearing, earliest_arrival) = station.coords;
+            let travel_time = if let Some(travel_time) = self.travel_times.get(station_id) {
+                *travel_time
+            } else {
+                continue;
+            };
+            let confident_arrival = geometry.origin() + travel_time;
+            if earliest_arrival > geometry.max() || confident_arrival <= earliest_arrival {
+                continue;
+            }
+            let (x1, y1) = geometry.coords(bearing, earliest_arrival);
+            let (x2, y2) = geometry.coords(bearing, confident_arrival);
+            write_xml!(w,
+                <g>
+                    <title>{station.stop.short_stop_name()}" in "{travel_time.num_minutes()}" minutes from "{self.percent}"% of departures in the next "{CONFIDENCE_WINDOW_MINS}" minutes"</title>
+                    <line x1={*x1} y1={*y1} x2={*x2} y2={*y2} />
+                    <circle cx={*x2} cy={*y2} r={RADIUS} />
+                </g>
+            )?;
+        }
+        write_xml!(w, </g>)
+    }
+}
+
+impl<'s> RadarPoi<'s> {
+    fn write_svg_fragment_to(
+        &self,
+        w: &mut dyn io::Write,
+        geometry: &FlattenedTimeCone,
+    ) -> io::Result<()> {
+        const HALF_SIZE: f64 = 2.5;
+        let (bearing, magnitude) = self.coords;
+        let (cx, cy) = geometry.coords(bearing, magnitude);
+        let category = self.poi.category.as_deref().unwrap_or_default();
+        write_xml!(w,
+            <g>
+                <title>{self.poi.name}" "{category}</title>
+                <rect x={*cx - HALF_SIZE} y={*cy - HALF_SIZE} width={HALF_SIZE * 2.} height={HALF_SIZE * 2.} />
+                <text x={*cx + HALF_SIZE + 6.} y={*cy + 4.}>{self.poi.name}</text>
+            </g>
+        )?;
+        Ok(())
+    }
+}
+
+impl<'s> RimLabel<'s> {
+    fn write_svg_fragment_to(
+        &self,
+        w: &mut dyn io::Write,
+        geometry: &FlattenedTimeCone,
+    ) -> io::Result<()> {
+        /// Just inside the outer ring
+        const RIM_SCALE: f64 = 0.96;
+        let (x, y) = geometry.coords(self.bearing, geometry.max());
         write_xml!(w,
-            <a href={search_params.clone().with_station_id(self.stop.station_id())}>
-            <circle cx={*cx} cy={*cy} r={STOP_RADIUS} />
-                <text x={*cx + STOP_RADIUS + 6.} y={*cy + 4.}>{name}</text>
-            </a>
+            <text x={*x * RIM_SCALE} y={*y * RIM_SCALE}>{self.name}</text>
         )?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod snapshot_test {
+    use super::*;
+    use crate::draw::well_formed::assert_well_formed;
+    use crate::gtfs::demo::demo_data;
+    use std::path::Path;
+
+    /// Rounds the numbers in the document, so that floating point noise doesn't show up as a difference
+    fn normalise_numbers(svg: &str) -> String {
+        let number = regex::Regex::new(r"-?\d+\.\d+").unwrap();
+        number
+            .replace_all(svg, |captures: &regex::Captures| {
+                match format!("{:.1}", captures[0].parse::<f64>().unwrap()).as_str() {
+                    "-0.0" => "0.0".to_owned(),
+                    rounded => rounded.to_owned(),
+                }
+            })
+            .replace(r#"="-0""#, r#"="0""#)
+    }
+
+    /// Draws the radar from the station on a Monday morning, checking that it's well formed
+    fn render(
+        data: &GTFSData,
+        station: &str,
+        mins: i64,
+        modes: &[TransitMode],
+        confidence: Option<u8>,
+    ) -> String {
+        let origin = data
+            .stops()
+            .find(|stop| stop.is_station() && stop.full_stop_name() == station)
+            .unwrap();
+        let departure_time = chrono_tz::Europe::Berlin
+            .with_ymd_and_hms(2024, 1, 15, 10, 0, 0)
+            .unwrap();
+        let modes: HashSet<TransitMode> = modes.iter().copied().collect();
+        let importance = Importance::default();
+        let radar = search(
+            data,
+            SearchParams {
+                origin,
+                departure_time: Some(departure_time),
+                max_duration: Duration::minutes(mins),
+                modes: Cow::Borrowed(&modes),
+                direction: None,
+                pois: &[],
+                districts: &[],
+                confidence,
+                max_km: None,
+                missed: None,
+                journey_url: None,
+                frequency: false,
+                scale: RadialScale::Linear,
+                max_legs: MaxLegs::default(),
+                grace: None,
+                importance: Some(&importance),
+                prefer_trips: vec![],
+            },
+        );
+        let slugs = StationSlugs::new(data);
+        let mut svg = vec![];
+        radar
+            .write_svg_to(
+                &mut svg,
+                UrlSearchParams {
+                    station_id: origin.stop_id,
+                    slugs: &slugs,
+                    departure_time: Some(departure_time),
+                    max_duration: Duration::minutes(mins),
+                    modes: Cow::Borrowed(&modes),
+                    direction: None,
+                    confidence,
+                    max_km: None,
+                    missed: None,
+                    palette: Palette::Route,
+                    frequency: false,
+                    scale: RadialScale::Linear,
+                    fitted: false,
+                    max_legs: MaxLegs::default(),
+                    grace: None,
+                },
+                false,
+            )
+            .unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert_well_formed(&svg);
+        svg
+    }
+
+    /// Compares the radar with the snapshot in `src/draw/snapshots`, run with `UPDATE_SNAPSHOTS=1` to write new snapshots after an intended change
+    fn assert_snapshot(
+        name: &str,
+        station: &str,
+        mins: i64,
+        modes: &[TransitMode],
+        confidence: Option<u8>,
+    ) {
+        let svg = normalise_numbers(&render(demo_data(), station, mins, modes, confidence));
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/draw/snapshots")
+            .join(format!("{}.svg", name));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, svg).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+            panic!("{}: {}, run with UPDATE_SNAPSHOTS=1", path.display(), err)
+        });
+        if svg != expected {
+            let new_path = path.with_extension("new.svg");
+            std::fs::write(&new_path, svg).unwrap();
+            panic!(
+                "{} differs from the snapshot, compare it with {} and run with UPDATE_SNAPSHOTS=1 if the change is intended",
+                path.display(),
+                new_path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn alexanderplatz_rapid_transit() {
+        assert_snapshot(
+            "alexanderplatz_rapid_transit",
+            "Alexanderplatz",
+            30,
+            &[TransitMode::SBahn, TransitMode::UBahn],
+            None,
+        );
+    }
+
+    #[test]
+    fn hauptbahnhof_all_modes() {
+        assert_snapshot(
+            "hauptbahnhof_all_modes",
+            "Hauptbahnhof",
+            20,
+            TransitMode::ALL,
+            None,
+        );
+    }
+
+    #[test]
+    fn alexanderplatz_with_confidence() {
+        assert_snapshot(
+            "alexanderplatz_with_confidence",
+            "Alexanderplatz",
+            30,
+            TransitMode::ALL,
+            Some(DEFAULT_CONFIDENCE_PERCENT),
+        );
+    }
+
+    /// Two stations with a platform each, and one U-Bahn trip on the route from the first at 10:02 to the second at 10:08 on Mondays
+    fn one_trip_data(
+        stations: [(&str, f64, f64); 2],
+        route_name: &str,
+        route_color: &str,
+    ) -> GTFSData {
+        use radar_search::search_data::{Day, RouteType};
+        use radar_search::time::Time;
+
+        let stop_id = |id| StopId::new(id).unwrap();
+        let mut builder = GTFSData::builder(
+            HashMap::from([(Day::Monday, HashSet::from([1]))]),
+            "20240101".to_owned(),
+        );
+        for ((name, lat, lon), id) in stations.iter().copied().zip([1, 3].iter().copied()) {
+            let location = geo::Point::new(lat, lon);
+            builder.add_station(stop_id(id), name.to_owned(), name.to_owned(), location);
+            builder.add_stop_or_platform(
+                stop_id(id + 1),
+                name.to_owned(),
+                name.to_owned(),
+                location,
+                Some(stop_id(id)),
+            );
+        }
+        builder.add_route(
+            1,
+            route_name.to_owned(),
+            RouteType::UrbanRailway,
+            route_color.to_owned(),
+        );
+        let trip_id = TripId::new(1).unwrap();
+        builder.add_trip(trip_id, 1, 1, None, None).unwrap();
+        for (time, stop) in [(Time::from_hms(10, 2, 0), 2), (Time::from_hms(10, 8, 0), 4)] {
+            builder
+                .add_trip_stop(trip_id, time, time, stop_id(stop))
+                .unwrap();
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn hostile_stop_names_are_escaped() {
+        let stations = [
+            ("Tom & Jerry's <Bahnhof>", 52.52, 13.41),
+            (r#""/><script>alert(1)</script>"#, 52.53, 13.42),
+        ];
+        let data = one_trip_data(stations, "U<1>", "");
+
+        let svg = render(&data, stations[0].0, 20, TransitMode::ALL, None);
+        assert!(svg.contains("Tom &amp; Jerry&apos;s &lt;Bahnhof&gt;"));
+        assert!(svg.contains("&quot;/&gt;&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(svg.contains("U&lt;1&gt;"));
+        assert!(!svg.contains("<script>alert"));
+    }
+
+    #[test]
+    fn light_route_colours_are_visible_in_each_theme() {
+        let stations = [
+            ("Kurfürstendamm", 52.50, 13.33),
+            ("Uhlandstr.", 52.50, 13.32),
+        ];
+        let data = one_trip_data(stations, "U4", "#f0d722");
+
+        let svg = render(&data, stations[0].0, 20, TransitMode::ALL, None);
+        let path = regex::Regex::new(
+            r#"<path class="UrbanRailway U4" stroke="[^"]*" data-color="([^"]*)" style="--stroke: ([^;]*); --stroke-dark: ([^;"]*)""#,
+        )
+        .unwrap();
+        let captures = path.captures(&svg).expect("U4 segment is drawn");
+        assert_eq!(&captures[1], "#f0d722");
+        // the yellow is darkened on the light background, and kept as it is on the dark one
+        let light = Rgb::parse(&captures[2]).unwrap();
+        assert_ne!(&captures[2], "#f0d722");
+        assert!(light.contrast_with(LIGHT_BACKGROUND) >= 3.);
+        assert_eq!(&captures[3], "#f0d722");
+        // the variables are what the stroke is drawn with, over the stylesheet's colour for the U4
+        let rule = |selector: &str| svg.find(selector).unwrap_or_else(|| panic!("{}", selector));
+        assert!(rule("path[data-color] {\n  stroke: var(--stroke);") > rule(".U4 {"));
+        assert!(svg.contains("path[data-color] { stroke: var(--stroke-dark); }"));
+    }
+}
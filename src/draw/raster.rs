@@ -0,0 +1,290 @@
+//! Drawing into pixels for the PNG share card, as link previews don't show SVG.
+//!
+//! It draws only what the card needs: antialiased lines, solid or dashed, discs and rings, and text in a blocky 5×7 font of capitals and digits, which is encoded by the `png` crate.
+
+use std::io;
+
+use super::color::Rgb;
+
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    /// Mixes the colour into the pixel by how much of it is covered, from 0 to 1
+    fn blend(&mut self, x: i64, y: i64, colour: Rgb, coverage: f64) {
+        if coverage <= 0. || x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let coverage = coverage.min(1.);
+        let pixel = &mut self.pixels[y as usize * self.width + x as usize];
+        let mix = |under: u8, over: u8| {
+            (f64::from(under) * (1. - coverage) + f64::from(over) * coverage).round() as u8
+        };
+        *pixel = Rgb(
+            mix(pixel.0, colour.0),
+            mix(pixel.1, colour.1),
+            mix(pixel.2, colour.2),
+        );
+    }
+
+    /// Covers the pixels by how far their centres are inside the shape, given each pixel's distance from it's middle
+    fn shape(
+        &mut self,
+        (min_x, min_y, max_x, max_y): (f64, f64, f64, f64),
+        half_width: f64,
+        colour: Rgb,
+        distance: impl Fn(f64, f64) -> f64,
+    ) {
+        let (min_x, min_y) = (
+            (min_x - half_width - 1.).floor() as i64,
+            (min_y - half_width - 1.).floor() as i64,
+        );
+        let (max_x, max_y) = (
+            (max_x + half_width + 1.).ceil() as i64,
+            (max_y + half_width + 1.).ceil() as i64,
+        );
+        for y in min_y.max(0)..=max_y.min(self.height as i64 - 1) {
+            for x in min_x.max(0)..=max_x.min(self.width as i64 - 1) {
+                let d = distance(x as f64 + 0.5, y as f64 + 0.5);
+                self.blend(x, y, colour, half_width + 0.5 - d);
+            }
+        }
+    }
+
+    pub fn line(&mut self, (x0, y0): (f64, f64), (x1, y1): (f64, f64), width: f64, colour: Rgb) {
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length_squared = dx * dx + dy * dy;
+        let bounds = (x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1));
+        self.shape(bounds, width / 2., colour, |x, y| {
+            // distance to the nearest point of the segment
+            let t = if length_squared == 0. {
+                0.
+            } else {
+                (((x - x0) * dx + (y - y0) * dy) / length_squared).clamp(0., 1.)
+            };
+            ((x - x0 - t * dx).powi(2) + (y - y0 - t * dy).powi(2)).sqrt()
+        });
+    }
+
+    /// A line of dashes of the lengths in the pattern, with gaps of the lengths after them, the pattern starting again at the start of each line
+    pub fn dashed_line(
+        &mut self,
+        (x0, y0): (f64, f64),
+        (x1, y1): (f64, f64),
+        width: f64,
+        colour: Rgb,
+        pattern: &[f64],
+    ) {
+        let length = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt();
+        if length == 0. || pattern.iter().sum::<f64>() <= 0. {
+            return self.line((x0, y0), (x1, y1), width, colour);
+        }
+        let at = |d: f64| (x0 + (x1 - x0) * d / length, y0 + (y1 - y0) * d / length);
+        let mut start = 0.;
+        for (i, &dash) in pattern.iter().cycle().enumerate() {
+            if start >= length {
+                break;
+            }
+            let end = (start + dash).min(length);
+            if i % 2 == 0 {
+                self.line(at(start), at(end), width, colour);
+            }
+            start = end;
+        }
+    }
+
+    pub fn disc(&mut self, centre: (f64, f64), radius: f64, colour: Rgb) {
+        self.line(centre, centre, radius * 2., colour);
+    }
+
+    pub fn ring(&mut self, (cx, cy): (f64, f64), radius: f64, width: f64, colour: Rgb) {
+        let bounds = (cx - radius, cy - radius, cx + radius, cy + radius);
+        self.shape(bounds, width / 2., colour, |x, y| {
+            (((x - cx).powi(2) + (y - cy).powi(2)).sqrt() - radius).abs()
+        });
+    }
+
+    /// Writes the text with it's top left at the point, each dot of the font a square of `scale` pixels, returning the width written
+    pub fn text(&mut self, (x, y): (f64, f64), scale: f64, text: &str, colour: Rgb) -> f64 {
+        let mut left = x;
+        for glyph in glyphs(text) {
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        let (dot_x, dot_y) = (left + column as f64 * scale, y + row as f64 * scale);
+                        for py in dot_y.round() as i64..(dot_y + scale).round() as i64 {
+                            for px in dot_x.round() as i64..(dot_x + scale).round() as i64 {
+                                self.blend(px, py, colour, 1.);
+                            }
+                        }
+                    }
+                }
+            }
+            left += (GLYPH_WIDTH + 1) as f64 * scale;
+        }
+        left - x
+    }
+
+    /// 8 bit RGB
+    pub fn write_png(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        let data: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|pixel| [pixel.0, pixel.1, pixel.2])
+            .collect();
+        writer.write_image_data(&data).map_err(png_error)?;
+        writer.finish().map_err(png_error)
+    }
+}
+
+fn png_error(err: png::EncodingError) -> io::Error {
+    match err {
+        png::EncodingError::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidInput, err),
+    }
+}
+
+const GLYPH_WIDTH: usize = 5;
+
+/// The rows of each glyph from the top, the leftmost dot in the highest of 5 bits
+type Glyph = [u8; 7];
+
+/// The glyphs of the text, in capitals and with accents dropped, characters without a glyph are `?`
+fn glyphs(text: &str) -> impl Iterator<Item = Glyph> + '_ {
+    text.chars().flat_map(|c| {
+        let plain: &[char] = match c.to_ascii_uppercase() {
+            'ä' | 'Ä' | 'á' | 'à' | 'â' => &['A'],
+            'ö' | 'Ö' | 'ó' | 'ò' | 'ô' => &['O'],
+            'ü' | 'Ü' | 'ú' | 'ù' | 'û' => &['U'],
+            'é' | 'è' | 'ê' | 'É' => &['E'],
+            'ß' => &['S', 'S'],
+            _ => &[],
+        };
+        let upper = c.to_ascii_uppercase();
+        let chars: Vec<char> = if plain.is_empty() {
+            vec![upper]
+        } else {
+            plain.to_vec()
+        };
+        chars.into_iter().map(glyph)
+    })
+}
+
+fn glyph(c: char) -> Glyph {
+    match c {
+        'A' => [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1e],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        ' ' => [0; 7],
+        '.' => [0, 0, 0, 0, 0, 0x0c, 0x0c],
+        ',' => [0, 0, 0, 0, 0x0c, 0x04, 0x08],
+        ':' => [0, 0x0c, 0x0c, 0, 0x0c, 0x0c, 0],
+        '-' => [0, 0, 0, 0x1f, 0, 0, 0],
+        '+' => [0, 0x04, 0x04, 0x1f, 0x04, 0x04, 0],
+        '/' => [0, 0x01, 0x02, 0x04, 0x08, 0x10, 0],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '\'' => [0x04, 0x04, 0x08, 0, 0, 0, 0],
+        '&' => [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        _ => [0x0e, 0x11, 0x01, 0x02, 0x04, 0, 0x04],
+    }
+}
+
+/// The width of the text as written at a scale
+pub fn text_width(text: &str, scale: f64) -> f64 {
+    glyphs(text).count() as f64 * (GLYPH_WIDTH + 1) as f64 * scale
+}
+
+#[test]
+fn dashes() {
+    let background = Rgb(0xff, 0xff, 0xff);
+    let mut canvas = Canvas::new(40, 5, background);
+    canvas.dashed_line((0., 2.), (40., 2.), 1., Rgb(0, 0, 0), &[5., 5.]);
+    assert_ne!(canvas.pixels[2 * 40 + 2], background);
+    assert_eq!(canvas.pixels[2 * 40 + 8], background);
+    assert_ne!(canvas.pixels[2 * 40 + 12], background);
+}
+
+#[test]
+fn png_decodes_to_the_pixels() {
+    let mut canvas = Canvas::new(40, 20, Rgb(0xff, 0xff, 0xff));
+    canvas.disc((10., 10.), 4., Rgb(0, 0, 0));
+    canvas.line((20., 5.), (38., 5.), 2., Rgb(0xe2, 0, 0x1a));
+    let mut png = Vec::new();
+    canvas.write_png(&mut png).unwrap();
+
+    let mut reader = png::Decoder::new(&png[..]).read_info().unwrap();
+    let mut decoded = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut decoded).unwrap();
+    assert_eq!((info.width, info.height), (40, 20));
+    assert_eq!(
+        (info.color_type, info.bit_depth),
+        (png::ColorType::Rgb, png::BitDepth::Eight)
+    );
+    let decoded: Vec<Rgb> = decoded[..info.buffer_size()]
+        .chunks(3)
+        .map(|pixel| Rgb(pixel[0], pixel[1], pixel[2]))
+        .collect();
+    assert_eq!(decoded, canvas.pixels);
+    assert_eq!(decoded[10 * 40 + 10], Rgb(0, 0, 0));
+    assert_eq!(decoded[5 * 40 + 30], Rgb(0xe2, 0, 0x1a));
+    assert_eq!(decoded[15 * 40 + 30], Rgb(0xff, 0xff, 0xff));
+}
+
+#[test]
+fn text_is_capitals_without_accents() {
+    assert_eq!(
+        glyphs("Straße").collect::<Vec<_>>(),
+        glyphs("STRASSE").collect::<Vec<_>>()
+    );
+    assert_eq!(text_width("Zoo", 2.), 36.);
+}
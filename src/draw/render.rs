@@ -0,0 +1,319 @@
+//! The output formats of a radar, endpoints choose one by the `format` query parameter or the `Accept` header.
+//!
+//! Each format has a [`RadarRenderer`] which writes the searched radar, to add a format add a renderer and a [`Format`] for it.
+
+use serde_json::json;
+use std::io;
+
+use super::radar::{Radar, UrlSearchParams};
+use super::tree::{EdgeKind, Tree};
+
+/// Writes a radar in one format
+pub trait RadarRenderer {
+    /// The media type of what is written, for the `Content-Type` header
+    fn media_type(&self) -> &'static str;
+
+    fn render<'s>(
+        &self,
+        w: &mut dyn io::Write,
+        radar: &Radar<'s>,
+        search_params: UrlSearchParams<'s>,
+    ) -> io::Result<()>;
+}
+
+/// The radar as drawn for browsers, with links to the other searches
+pub struct SvgRenderer {
+    /// Reload the radar when the first trip on it departs
+    pub refresh: bool,
+}
+
+impl RadarRenderer for SvgRenderer {
+    fn media_type(&self) -> &'static str {
+        Format::Svg.media_type()
+    }
+
+    fn render<'s>(
+        &self,
+        w: &mut dyn io::Write,
+        radar: &Radar<'s>,
+        search_params: UrlSearchParams<'s>,
+    ) -> io::Result<()> {
+        radar.write_svg_to(w, search_params, self.refresh)
+    }
+}
+
+/// The departure tree, as documented in `tree.schema.json`
+pub struct JsonRenderer;
+
+impl RadarRenderer for JsonRenderer {
+    fn media_type(&self) -> &'static str {
+        Format::Json.media_type()
+    }
+
+    fn render<'s>(
+        &self,
+        w: &mut dyn io::Write,
+        radar: &Radar<'s>,
+        _search_params: UrlSearchParams<'s>,
+    ) -> io::Result<()> {
+        serde_json::to_writer(w, &radar.tree()).map_err(io::Error::from)
+    }
+}
+
+/// The stations as points and the trips as lines on a map, with the properties of the departure tree
+pub struct GeoJsonRenderer;
+
+impl RadarRenderer for GeoJsonRenderer {
+    fn media_type(&self) -> &'static str {
+        Format::GeoJson.media_type()
+    }
+
+    fn render<'s>(
+        &self,
+        w: &mut dyn io::Write,
+        radar: &Radar<'s>,
+        _search_params: UrlSearchParams<'s>,
+    ) -> io::Result<()> {
+        serde_json::to_writer(w, &feature_collection(&radar.tree())).map_err(io::Error::from)
+    }
+}
+
+/// GeoJSON positions are (lon, lat)
+fn feature_collection(tree: &Tree) -> serde_json::Value {
+    let position = |station: usize| {
+        let station = &tree.stations[station];
+        json!([station.lon, station.lat])
+    };
+    let stations = tree.stations.iter().enumerate().map(|(index, station)| {
+        json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": position(index) },
+            "properties": {
+                "stop_id": station.stop_id,
+                "name": station.name,
+                "earliest_arrival": station.earliest_arrival,
+                "transfers": station.transfers,
+                "origin": index == tree.origin,
+            },
+        })
+    });
+    let trips = tree.trips.iter().map(|trip| {
+        // the connections wait at a station, so only the segments go anywhere
+        let mut stops = vec![];
+        for edge in trip.segments.iter().map(|&edge| &tree.edges[edge]) {
+            if edge.kind == EdgeKind::Segment {
+                if stops.last() != Some(&edge.from) {
+                    stops.push(edge.from);
+                }
+                stops.push(edge.to);
+            }
+        }
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": stops.into_iter().map(position).collect::<Vec<_>>(),
+            },
+            "properties": {
+                "trip_id": trip.trip_id,
+                "route_name": trip.route_name,
+                "route_type": trip.route_type,
+                "mode": trip.mode,
+                "route_color": trip.route_color,
+            },
+        })
+    });
+    json!({
+        "type": "FeatureCollection",
+        "features": stations.chain(trips).collect::<Vec<_>>(),
+    })
+}
+
+/// A card of the radar and it's summary for link previews, which don't show SVG
+pub struct PngRenderer;
+
+impl RadarRenderer for PngRenderer {
+    fn media_type(&self) -> &'static str {
+        Format::Png.media_type()
+    }
+
+    fn render<'s>(
+        &self,
+        w: &mut dyn io::Write,
+        radar: &Radar<'s>,
+        search_params: UrlSearchParams<'s>,
+    ) -> io::Result<()> {
+        radar.write_card_png_to(w, search_params.palette)
+    }
+}
+
+/// A summary of how far the radar reaches, for chat and screen readers
+pub struct TextRenderer;
+
+impl RadarRenderer for TextRenderer {
+    fn media_type(&self) -> &'static str {
+        Format::Text.media_type()
+    }
+
+    fn render<'s>(
+        &self,
+        w: &mut dyn io::Write,
+        radar: &Radar<'s>,
+        _search_params: UrlSearchParams<'s>,
+    ) -> io::Result<()> {
+        radar.write_summary_to(w)
+    }
+}
+
+/// The departure tree as a Graphviz digraph, for looking at what the search did on small feeds
+pub struct DotRenderer;
+
+impl RadarRenderer for DotRenderer {
+    fn media_type(&self) -> &'static str {
+        Format::Dot.media_type()
+    }
+
+    fn render<'s>(
+        &self,
+        w: &mut dyn io::Write,
+        radar: &Radar<'s>,
+        _search_params: UrlSearchParams<'s>,
+    ) -> io::Result<()> {
+        write_dot(w, &radar.tree())
+    }
+}
+
+/// Stations are labelled with their earliest arrival, and each edge with it's line, the waits to board are dashed
+fn write_dot(w: &mut dyn io::Write, tree: &Tree) -> io::Result<()> {
+    writeln!(w, "digraph radar {{")?;
+    writeln!(w, "  node [shape=box];")?;
+    for (index, station) in tree.stations.iter().enumerate() {
+        let arrival = station
+            .earliest_arrival
+            .map_or_else(String::new, |arrival| format!("\\n{}", arrival));
+        writeln!(
+            w,
+            "  s{} [label=\"{}{}\"{}];",
+            index,
+            dot_escape(&station.name),
+            arrival,
+            if index == tree.origin {
+                ", peripheries=2"
+            } else {
+                ""
+            }
+        )?;
+    }
+    for edge in &tree.edges {
+        let trip = &tree.trips[edge.trip];
+        writeln!(
+            w,
+            "  s{} -> s{} [label=\"{} {}\", color=\"{}\"{}];",
+            edge.from,
+            edge.to,
+            dot_escape(&trip.route_name),
+            edge.departure_time,
+            dot_escape(&trip.route_color),
+            if edge.kind == EdgeKind::Connection {
+                ", style=dashed"
+            } else {
+                ""
+            }
+        )?;
+    }
+    writeln!(w, "}}")
+}
+
+/// The contents of a quoted DOT string
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// A format the radar can be asked for in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Svg,
+    Json,
+    GeoJson,
+    Dot,
+    /// Markdown
+    Text,
+    /// A card for link previews, which don't show SVG
+    Png,
+}
+
+impl Format {
+    pub const ALL: [Format; 6] = [
+        Format::Svg,
+        Format::Json,
+        Format::GeoJson,
+        Format::Dot,
+        Format::Text,
+        Format::Png,
+    ];
+
+    /// The name in the `format` query parameter
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Svg => "svg",
+            Format::Json => "json",
+            Format::GeoJson => "geojson",
+            Format::Dot => "dot",
+            Format::Text => "text",
+            Format::Png => "png",
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Format::Svg => "image/svg+xml",
+            Format::Json => "application/json",
+            Format::GeoJson => "application/geo+json",
+            Format::Dot => "text/vnd.graphviz",
+            Format::Text => "text/markdown",
+            Format::Png => "image/png",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        Format::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The format of a media type without parameters, such as `image/svg+xml`
+    pub fn from_media_type(media_type: &str) -> Option<Format> {
+        Format::ALL
+            .iter()
+            .copied()
+            .find(|format| format.media_type().eq_ignore_ascii_case(media_type))
+    }
+
+    pub fn renderer(self, refresh: bool) -> Box<dyn RadarRenderer> {
+        match self {
+            Format::Svg => Box::new(SvgRenderer { refresh }),
+            Format::Json => Box::new(JsonRenderer),
+            Format::GeoJson => Box::new(GeoJsonRenderer),
+            Format::Dot => Box::new(DotRenderer),
+            Format::Text => Box::new(TextRenderer),
+            Format::Png => Box::new(PngRenderer),
+        }
+    }
+}
+
+#[test]
+fn formats_by_name_and_media_type() {
+    for format in Format::ALL.iter().copied() {
+        assert_eq!(Format::from_name(format.name()), Some(format));
+        assert_eq!(Format::from_media_type(format.media_type()), Some(format));
+        assert_eq!(format.renderer(false).media_type(), format.media_type());
+    }
+    assert_eq!(Format::from_name("GeoJSON"), Some(Format::GeoJson));
+    assert_eq!(Format::from_name("bmp"), None);
+}
+
+#[test]
+fn test_dot_escape() {
+    assert_eq!(dot_escape(r#"Zoo "Ost" \ Süd"#), r#"Zoo \"Ost\" \\ Süd"#);
+}
@@ -0,0 +1,287 @@
+//! The stations and trips of a search as it reaches them, for clients which draw a slow search as it goes rather than wait for the radar.
+//!
+//! The journey planner searches only as far as the next arrival each time it's iterated, so each [`Discovery`] is available as soon as it's station or edge is reached, in order of arrival. They are written as newline delimited JSON, such as
+//! `{"type":"station","stop_id":2,"station_id":1,"name":"Alexanderplatz","lat":52.52,"lon":13.41,"earliest_arrival":36000,"transfers":0}`
+//! and `{"type":"edge","kind":"segment","from":2,"to":3,"departure_time":36060,"arrival_time":36180,"trip_id":7,"route_name":"S5","route_type":109,"mode":"sbahn","route_color":"#f0f"}`,
+//! times are seconds since midnight. Walks between stations aren't written, as they aren't in the departure tree either.
+//!
+//! To debug the planner, [`explained_discoveries`] adds why it made each discovery to it's line, as `"why"`, and writes the walks as well.
+
+use chrono::Utc;
+use radar_search::journey_graph::{self, Item};
+use radar_search::search_data::*;
+use radar_search::time::Time;
+use serde::Serialize;
+
+use super::radar::{plotter, SearchParams, TransitMode};
+use super::tree::EdgeKind;
+
+/// A station or an edge of the departure tree, when it's reached
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Discovery<'s> {
+    Station {
+        /// The stop which is reached, a platform or the station itself
+        stop_id: StopId,
+        station_id: StopId,
+        name: &'s str,
+        lat: f64,
+        lon: f64,
+        earliest_arrival: Time,
+        /// Changes between trips on the way
+        transfers: u8,
+    },
+    Edge {
+        kind: EdgeKind,
+        from: StopId,
+        to: StopId,
+        departure_time: Time,
+        arrival_time: Time,
+        trip_id: TripId,
+        route_name: &'s str,
+        /// GTFS route type code
+        route_type: u16,
+        /// Key of the transit mode, as used in the `mode` query parameter
+        mode: Option<&'static str>,
+        route_color: &'s str,
+    },
+    /// Only written when explaining
+    Walk {
+        from: StopId,
+        to: StopId,
+        departure_time: Time,
+        arrival_time: Time,
+    },
+}
+
+/// A discovery with a human readable explanation of why the planner made it
+#[derive(Debug, Serialize)]
+pub struct Explained<'s> {
+    #[serde(flatten)]
+    pub discovery: Discovery<'s>,
+    pub why: String,
+}
+
+/// Searches as the radar does, lazily, the departure time is now if it isn't set
+pub fn discoveries<'s>(
+    data: &'s GTFSData,
+    search_params: SearchParams<'s>,
+) -> impl Iterator<Item = Discovery<'s>> + 's {
+    search_plotter(data, search_params)
+        .map(discovery)
+        .filter(|discovery| !matches!(discovery, Discovery::Walk { .. }))
+}
+
+/// Searches as [`discoveries`] does, explaining each discovery, which is slower
+pub fn explained_discoveries<'s>(
+    data: &'s GTFSData,
+    search_params: SearchParams<'s>,
+) -> impl Iterator<Item = Explained<'s>> + 's {
+    search_plotter(data, search_params).explained().map(
+        |journey_graph::Explained { item, explanation }| Explained {
+            discovery: discovery(item),
+            why: explanation,
+        },
+    )
+}
+
+fn search_plotter<'s>(
+    data: &'s GTFSData,
+    SearchParams {
+        origin,
+        departure_time,
+        max_duration,
+        modes,
+        direction,
+        max_legs,
+        grace,
+        prefer_trips,
+        ..
+    }: SearchParams<'s>,
+) -> journey_graph::Plotter<'s> {
+    let departure_time =
+        departure_time.unwrap_or_else(|| Utc::now().with_timezone(&chrono_tz::Europe::Berlin));
+    plotter(
+        data,
+        origin,
+        departure_time,
+        max_duration,
+        &modes,
+        direction,
+        &max_legs,
+        grace,
+        &prefer_trips,
+    )
+}
+
+fn discovery(item: Item) -> Discovery {
+    match item {
+        Item::Station {
+            stop,
+            earliest_arrival,
+            transfers_so_far,
+            ..
+        } => Discovery::Station {
+            stop_id: stop.stop_id,
+            station_id: stop.station_id(),
+            name: stop.short_stop_name(),
+            lat: stop.location.x(),
+            lon: stop.location.y(),
+            earliest_arrival,
+            transfers: transfers_so_far,
+        },
+        Item::ConnectionToTrip {
+            departure_time,
+            arrival_time,
+            from_stop,
+            to_stop,
+            route_name,
+            route_type,
+            route_color,
+            trip_id,
+            ..
+        } => Discovery::Edge {
+            kind: EdgeKind::Connection,
+            from: from_stop.stop_id,
+            to: to_stop.stop_id,
+            departure_time,
+            arrival_time,
+            trip_id,
+            route_name,
+            route_type: route_type.code(),
+            mode: TransitMode::of_route_type(route_type).map(|mode| mode.key()),
+            route_color,
+        },
+        Item::SegmentOfTrip {
+            departure_time,
+            arrival_time,
+            from_stop,
+            to_stop,
+            trip_id,
+            route_name,
+            route_type,
+            route_color,
+            ..
+        } => Discovery::Edge {
+            kind: EdgeKind::Segment,
+            from: from_stop.stop_id,
+            to: to_stop.stop_id,
+            departure_time,
+            arrival_time,
+            trip_id,
+            route_name,
+            route_type: route_type.code(),
+            mode: TransitMode::of_route_type(route_type).map(|mode| mode.key()),
+            route_color,
+        },
+        Item::Transfer {
+            departure_time,
+            arrival_time,
+            from_stop,
+            to_stop,
+        } => Discovery::Walk {
+            from: from_stop.stop_id,
+            to: to_stop.stop_id,
+            departure_time,
+            arrival_time,
+        },
+    }
+}
+
+#[test]
+fn stations_in_order_of_arrival() {
+    use super::radar::{MaxLegs, RadialScale};
+    use crate::gtfs::{colors::RouteColors, db, demo, ingest};
+    use chrono::{Duration, TimeZone};
+    use std::borrow::Cow;
+    use std::collections::HashSet;
+
+    let dir = std::env::temp_dir().join(format!("transit-radar-stream-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    demo::write_demo_feed(&dir).unwrap();
+    let (data, _report) = db::load_data(
+        &dir,
+        db::DayFilter::All,
+        &RouteColors::new(),
+        ingest::Strictness::Fail,
+    )
+    .unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    let origin = data
+        .stops()
+        .find(|stop| stop.is_station() && stop.full_stop_name() == "Alexanderplatz")
+        .unwrap();
+    let modes: HashSet<TransitMode> = TransitMode::DEFAULTS.iter().copied().collect();
+    let search_params = || SearchParams {
+        origin,
+        departure_time: Some(
+            chrono_tz::Europe::Berlin
+                .with_ymd_and_hms(2024, 1, 15, 10, 0, 0)
+                .unwrap(),
+        ),
+        max_duration: Duration::minutes(30),
+        modes: Cow::Borrowed(&modes),
+        direction: None,
+        pois: &[],
+        districts: &[],
+        confidence: None,
+        max_km: None,
+        missed: None,
+        journey_url: None,
+        frequency: false,
+        scale: RadialScale::Linear,
+        max_legs: MaxLegs::default(),
+        grace: None,
+        importance: None,
+        prefer_trips: vec![],
+    };
+    let discovered: Vec<_> = discoveries(&data, search_params()).collect();
+
+    let arrivals: Vec<_> = discovered
+        .iter()
+        .filter_map(|discovery| match discovery {
+            Discovery::Station {
+                name,
+                earliest_arrival,
+                ..
+            } => Some((*name, *earliest_arrival)),
+            Discovery::Edge { .. } | Discovery::Walk { .. } => None,
+        })
+        .collect();
+    assert_eq!(arrivals[0], ("Alexanderplatz", Time::from_hms(10, 0, 0)));
+    assert!(arrivals.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    assert!(arrivals.iter().any(|&(name, _)| name == "Friedrichstr."));
+    assert!(discovered.iter().any(|discovery| matches!(
+        discovery,
+        Discovery::Edge {
+            kind: EdgeKind::Segment,
+            mode: Some("sbahn"),
+            ..
+        }
+    )));
+    let line = serde_json::to_string(&discovered[0]).unwrap();
+    assert!(
+        line.starts_with(r#"{"type":"station","stop_id":"#),
+        "{}",
+        line
+    );
+
+    // explaining writes the walks too, and otherwise the same discoveries
+    let explained: Vec<_> = explained_discoveries(&data, search_params()).collect();
+    assert_eq!(
+        explained
+            .iter()
+            .filter(|explained| !matches!(explained.discovery, Discovery::Walk { .. }))
+            .count(),
+        discovered.len()
+    );
+    let line = serde_json::to_string(&explained[0]).unwrap();
+    assert!(
+        line.starts_with(r#"{"type":"station","stop_id":"#)
+            && line.ends_with(
+                r#","why":"Alexanderplatz is the origin of the search, departing at 10:00:00"}"#
+            ),
+        "{}",
+        line
+    );
+}
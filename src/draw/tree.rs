@@ -0,0 +1,81 @@
+//! The departure tree of a radar as JSON, for clients which draw it themselves.
+//!
+//! The schema is versioned by [`TREE_SCHEMA_VERSION`] and documented in `tree.schema.json`, fields may be added within a version but not removed or changed.
+//! Stations and trips are ordered by their ids and edges by their trip so that indices are stable for the same search on the same data.
+
+use radar_search::search_data::{StopId, TripId};
+use radar_search::time::Time;
+use serde::Serialize;
+
+pub const TREE_SCHEMA_VERSION: u32 = 1;
+
+/// JSON schema of the tree
+pub const TREE_SCHEMA: &str = include_str!("tree.schema.json");
+
+#[derive(Debug, Serialize)]
+pub struct Tree {
+    pub version: u32,
+    /// RFC 3339 time when the first trip departs and the tree should be fetched again
+    pub expires_at: String,
+    /// Index of the origin in `stations`
+    pub origin: usize,
+    pub stations: Vec<TreeStation>,
+    pub edges: Vec<TreeEdge>,
+    pub trips: Vec<TreeTrip>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TreeStation {
+    pub stop_id: StopId,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// Earliest arrival, only set for stations which are reached within the search
+    pub earliest_arrival: Option<Time>,
+    /// Changes between trips on the way to the earliest arrival, set along with it
+    pub transfers: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// Waiting at a station to board a trip
+    Connection,
+    /// Riding a trip from one stop to the next
+    Segment,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TreeEdge {
+    pub kind: EdgeKind,
+    /// Index in `stations`
+    pub from: usize,
+    /// Index in `stations`
+    pub to: usize,
+    pub departure_time: Time,
+    pub arrival_time: Time,
+    /// Index in `trips`
+    pub trip: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TreeTrip {
+    pub trip_id: TripId,
+    pub route_name: String,
+    /// GTFS route type code
+    pub route_type: u16,
+    /// Key of the transit mode, as used in the `mode` query parameter
+    pub mode: Option<&'static str>,
+    pub route_color: String,
+    /// Indices in `edges`, in the order they are travelled
+    pub segments: Vec<usize>,
+}
+
+#[test]
+fn schema_matches_version() {
+    let schema: serde_json::Value = serde_json::from_str(TREE_SCHEMA).unwrap();
+    assert_eq!(
+        schema["properties"]["version"]["const"],
+        serde_json::json!(TREE_SCHEMA_VERSION)
+    );
+}
@@ -0,0 +1,104 @@
+//! Radars from the same station at the same time on each day of a week, side by side, so that the service on different days can be compared at a glance.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime};
+use chrono_tz::Tz;
+use radar_search::search_data::Stop;
+use std::io;
+
+use crate::write_xml;
+
+use super::radar::{Radar, UrlSearchParams};
+
+/// Width and height of each day's radar, as in the view box of a single radar
+const RADAR_SIZE: i32 = 1024;
+/// Height of the label above each day's radar
+const LABEL_HEIGHT: i32 = 80;
+const COLUMNS: i32 = 4;
+
+/// The same time on each day of the week it's in, Monday first
+pub fn week_of(departure_time: NaiveDateTime) -> [NaiveDateTime; 7] {
+    let monday =
+        departure_time - Duration::days(departure_time.weekday().num_days_from_monday().into());
+    std::array::from_fn(|day| monday + Duration::days(day as i64))
+}
+
+/// One day of the week, without a radar when the server doesn't have the timetable of the day
+pub struct WeekdayRadar<'r, 's> {
+    pub departure_time: DateTime<Tz>,
+    pub radar: Option<&'r Radar<'s>>,
+    /// The search of the day's own radar, which it links to
+    pub search_params: UrlSearchParams<'s>,
+}
+
+/// Draws the days' radars in a grid of four columns, with the title in the last cell
+pub fn write_weekly_svg_to(
+    w: &mut dyn io::Write,
+    origin: &Stop,
+    days: &[WeekdayRadar],
+) -> io::Result<()> {
+    let cell_height = RADAR_SIZE + LABEL_HEIGHT;
+    let rows = (days.len() as i32 + 1 + COLUMNS - 1) / COLUMNS;
+    writeln!(
+        w,
+        r#"<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
+<svg version="1.1" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100%" height="100%" viewBox="0 0 {} {}">"#,
+        COLUMNS * RADAR_SIZE,
+        rows * cell_height,
+    )?;
+    write_xml!(w, <title>{origin.short_stop_name()}" departures through the week: Transit Radar"</title>)?;
+    // the style sheet is our own, so it's written as it is rather than escaped
+    writeln!(w, "<style>{}</style>", include_str!("Radar.css"))?;
+
+    for (index, day) in days.iter().enumerate() {
+        let (x, y) = (
+            index as i32 % COLUMNS * RADAR_SIZE,
+            index as i32 / COLUMNS * cell_height,
+        );
+        let summary = if let Some(radar) = day.radar {
+            format!("{} stations reached", radar.reached_stations())
+        } else {
+            "no timetable loaded for this day".to_owned()
+        };
+        write_xml!(w,
+            <g class="weekday" transform={format!("translate({}, {})", x, y)}>
+                <a href={day.search_params}>
+                    <text x="12" y="40" style="font-size: 24pt;">{day.departure_time.format("%A %e %b")}</text>
+                </a>
+                <text x="12" y="70" style="font-size: 12pt;">{summary}</text>
+        )?;
+        if let Some(radar) = day.radar {
+            write_xml!(w,
+                <svg y={LABEL_HEIGHT} width={RADAR_SIZE} height={RADAR_SIZE} viewBox="-512 -512 1024 1024">
+            )?;
+            radar.write_svg_drawing_to(w, &day.search_params)?;
+            write_xml!(w, </svg>)?;
+        }
+        write_xml!(w, </g>)?;
+    }
+
+    if let Some(first) = days.first() {
+        let index = days.len() as i32;
+        let (x, y) = (index % COLUMNS * RADAR_SIZE, index / COLUMNS * cell_height);
+        write_xml!(w,
+            <g id="header" transform={format!("translate({}, {})", x + 12, y + 40)}>
+                <text style="font-size: 24pt;">{origin.short_stop_name()}" departures"</text>
+                <text y="40" style="font-size: 12pt; font-style: oblique;">
+                    "Trips starting at "{first.departure_time.format("%k:%M")}" each day"
+                    <tspan x="0" dy="1.4em">{"and lasting less than "}{first.search_params.max_duration.num_minutes()}{" minutes"}</tspan>
+                </text>
+                <text id="credit" y="120"><a href="https://radar.njk.onl">"from transit radar"</a></text>
+            </g>
+        )?;
+    }
+
+    writeln!(w, "</svg>")
+}
+
+#[test]
+fn week_starts_on_monday() {
+    let sunday: NaiveDateTime = "2024-01-21T10:00:00".parse().unwrap();
+    let week = week_of(sunday);
+    assert_eq!(week[0], "2024-01-15T10:00:00".parse().unwrap());
+    assert_eq!(week[6], sunday);
+    assert_eq!(week_of(week[0]), week);
+}
@@ -0,0 +1,18 @@
+//! Checking drawn documents in tests, shared with the server's tests which include this file by it's path as they can't reach the library's test code.
+
+/// Fails unless the document is well formed XML
+pub fn assert_well_formed(xml: &str) {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        match reader.read_event() {
+            Ok(quick_xml::events::Event::Eof) => break,
+            Ok(_) => {}
+            Err(err) => panic!(
+                "not well formed at {}: {}\n{}",
+                reader.buffer_position(),
+                err,
+                xml
+            ),
+        }
+    }
+}
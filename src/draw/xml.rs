@@ -19,11 +19,11 @@ macro_rules! xml_format_args {
 
     // an expression, evaluating to an iterable as a comma-separated attribute value
     (@attr(=[$avalue:expr,] $($attrs:tt)*) -> ($($pattern:expr),*), ($($args:expr),*)) => {
-        $crate::xml_format_args!(@inner($($attrs)*) -> ($($pattern),*, "=\"{}\""), ($($args,)* $crate::draw::xml::JoinList { list: $avalue, join: "," }))
+        $crate::xml_format_args!(@inner($($attrs)*) -> ($($pattern),*, "=\"{}\""), ($($args,)* $crate::draw::xml::Escaped($crate::draw::xml::JoinList { list: $avalue, join: "," })))
     };
     // an expression as an attribute value
     (@attr(={$avalue:expr} $($attrs:tt)*) -> ($($pattern:expr),*), ($($args:expr),*)) => {
-        $crate::xml_format_args!(@inner($($attrs)*) -> ($($pattern),*, "=\"{}\""), ($($args,)* $avalue))
+        $crate::xml_format_args!(@inner($($attrs)*) -> ($($pattern),*, "=\"{}\""), ($($args,)* $crate::draw::xml::Escaped(&$avalue)))
     };
     // a literal as an attribute value
     (@attr(=$avalue:literal $($attrs:tt)*) -> ($($pattern:expr),*), ($($args:expr),*)) => {
@@ -40,7 +40,7 @@ macro_rules! xml_format_args {
     };
     // matches a text expression
     (@outer({$text:expr} $($attrs:tt)*) -> ($($pattern:expr),*), ($($args:expr),*)) => {
-        $crate::xml_format_args!(@outer($($attrs)*) -> ($($pattern),*, "{}"), ($($args,)* $text))
+        $crate::xml_format_args!(@outer($($attrs)*) -> ($($pattern),*, "{}"), ($($args,)* $crate::draw::xml::Escaped(&$text)))
     };
     // matches a text literal
     (@outer($text:literal $($attrs:tt)*) -> ($($pattern:expr),*), ($($args:expr),*)) => {
@@ -57,7 +57,7 @@ macro_rules! xml_format_args {
     };
 }
 
-/// Write XML
+/// Write XML, the values of attributes and text expressions are escaped
 #[macro_export]
 macro_rules! write_xml {
     ($dst:expr, $($attrs:tt)*) => {
@@ -102,6 +102,36 @@ where
     }
 }
 
+/// Displays a value with the characters which are special in XML escaped, so that it can't end the text or attribute it's written in
+pub struct Escaped<D: std::fmt::Display>(pub D);
+
+impl<D: std::fmt::Display> std::fmt::Display for Escaped<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use std::fmt::Write;
+        write!(EscapingWriter(f), "{}", self.0)
+    }
+}
+
+struct EscapingWriter<'a, 'f>(&'a mut std::fmt::Formatter<'f>);
+
+impl<'a, 'f> std::fmt::Write for EscapingWriter<'a, 'f> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        let mut rest = s;
+        while let Some(special) = rest.find(['&', '<', '>', '"', '\'']) {
+            self.0.write_str(&rest[..special])?;
+            self.0.write_str(match rest.as_bytes()[special] {
+                b'&' => "&amp;",
+                b'<' => "&lt;",
+                b'>' => "&gt;",
+                b'"' => "&quot;",
+                _ => "&apos;",
+            })?;
+            rest = &rest[special + 1..];
+        }
+        self.0.write_str(rest)
+    }
+}
+
 #[test]
 fn self_closing() {
     assert_eq!(format_xml!(<tag />).trim_end(), r#"<tag />"#);
@@ -181,3 +211,19 @@ fn element_containing() {
         r#"<tag><inner /></tag>"#
     );
 }
+
+#[test]
+fn text_is_escaped() {
+    assert_eq!(
+        format_xml!(<tag>{"Tom & Jerry's <b>\"café\"</b>"}</tag>).trim_end(),
+        r#"<tag>Tom &amp; Jerry&apos;s &lt;b&gt;&quot;café&quot;&lt;/b&gt;</tag>"#
+    );
+}
+
+#[test]
+fn attribute_is_escaped() {
+    assert_eq!(
+        format_xml!(<a href={"/?a=1&b=\"><script>"} list=[&["<", ">"],] />).trim_end(),
+        r#"<a href="/?a=1&amp;b=&quot;&gt;&lt;script&gt;" list="&lt;,&gt;" />"#
+    );
+}
@@ -0,0 +1,248 @@
+//! Drawing a radar in one call, for apps which embed it rather than run the server. A [`FeedBundle`] is loaded once, such as when the app starts, and [`render_svg`] draws the radar from a station found by it's name.
+//!
+//! ```no_run
+//! use transit_radar::{render_svg, FeedBundle, RenderOptions};
+//!
+//! let bundle = FeedBundle::load("radar.bundle".as_ref()).unwrap();
+//! let svg = render_svg(&bundle, "Alexanderplatz", &RenderOptions::default()).unwrap();
+//! ```
+//!
+//! The stations on the radar link to the server's own `/depart-from/<station>/<time>` urls, which the app can route to `render_svg` with the station's slug.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use chrono::{DateTime, Duration};
+use chrono_tz::Tz;
+use radar_search::search_data::*;
+
+use crate::draw::radar::{
+    day_time, search, MaxLegs, RadialScale, SearchParams, TransitMode, UrlSearchParams,
+    DEFAULT_MAX_DURATION_MINS,
+};
+use crate::gtfs::{bundle, colors::RouteColors, db, importance::Importance, slugs::StationSlugs};
+use crate::Suggester;
+
+/// A timetable with what's needed to find stations and draw radars from them
+pub struct FeedBundle {
+    data: GTFSData,
+    stop_ids: db::StopIds,
+    day_filter: db::DayFilter,
+    slugs: StationSlugs,
+    importance: Importance,
+    suggester: Suggester<(StopId, usize)>,
+}
+
+impl FeedBundle {
+    /// Loads a bundle compiled by `transit-radar compile`
+    pub fn load(bundle_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let bundled = bundle::load(bundle_path)?;
+        Ok(Self::new(
+            bundled.data,
+            bundled.stop_ids,
+            bundled.day_filter,
+        ))
+    }
+
+    /// Loads GTFS feeds as the server does by default, which is much slower than loading a bundle of them
+    pub fn from_feeds<P: AsRef<Path>>(
+        gtfs_dirs: &[P],
+        day_filter: db::DayFilter,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (data, stop_ids) = db::load_feeds_with_ids(gtfs_dirs, day_filter, &RouteColors::new())?;
+        Ok(Self::new(data, stop_ids, day_filter))
+    }
+
+    fn new(data: GTFSData, stop_ids: db::StopIds, day_filter: db::DayFilter) -> Self {
+        let importance = Importance::default();
+        FeedBundle {
+            slugs: StationSlugs::new(&data),
+            suggester: db::build_station_word_index(&data, &importance),
+            data,
+            stop_ids,
+            day_filter,
+            importance,
+        }
+    }
+
+    pub fn data(&self) -> &GTFSData {
+        &self.data
+    }
+
+    /// The station by it's slug, such as `alexanderplatz`, it's id or otherwise the station a name most likely means, as the server finds them
+    pub fn find_station(&self, query: &str) -> Option<&Stop> {
+        let Self {
+            data,
+            stop_ids,
+            slugs,
+            suggester,
+            ..
+        } = self;
+        let station = |stop_id| {
+            data.get_stop(stop_id)
+                .and_then(|stop| data.get_stop(stop.station_id()))
+        };
+        slugs
+            .station(query)
+            .or_else(|| query.parse().ok())
+            .and_then(station)
+            .or_else(|| stop_ids.get(query).and_then(station))
+            .or_else(|| stop_ids.get_ifopt(query).and_then(station))
+            .or_else(|| db::best_match(query, data, suggester))
+    }
+
+    /// The stations with a word starting with each word of the query, the most important first
+    pub fn search_stations(&self, query: &str, limit: usize) -> Vec<&Stop> {
+        self.suggester
+            .rank(query, db::by_importance)
+            .into_iter()
+            .filter_map(|(stop_id, _importance)| self.data.get_stop(stop_id))
+            .take(limit)
+            .collect()
+    }
+
+    /// The slug of a station, which it's radar's links are to
+    pub fn slug(&self, station_id: StopId) -> Option<&str> {
+        self.slugs.slug(station_id)
+    }
+}
+
+/// What to draw on the radar, by default the S-Bahn and U-Bahn for the next half an hour from now
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Now if it isn't set
+    pub departure_time: Option<DateTime<Tz>>,
+    pub max_duration: Duration,
+    pub modes: HashSet<TransitMode>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            departure_time: None,
+            max_duration: Duration::minutes(DEFAULT_MAX_DURATION_MINS),
+            modes: TransitMode::DEFAULTS.iter().copied().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// No station has the slug, id or name
+    UnknownStation(String),
+    /// The bundle only has the timetable of another day
+    DayNotLoaded(Day),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::UnknownStation(query) => write!(f, "no station is known as {}", query),
+            RenderError::DayNotLoaded(day) => {
+                write!(f, "the timetable of {:?} isn't in the bundle", day)
+            }
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+/// Finds the station, searches from it and draws the radar as an SVG document
+pub fn render_svg(
+    feed_bundle: &FeedBundle,
+    station_query: &str,
+    options: &RenderOptions,
+) -> Result<String, RenderError> {
+    let origin = feed_bundle
+        .find_station(station_query)
+        .ok_or_else(|| RenderError::UnknownStation(station_query.to_owned()))?;
+    let departure_time = options
+        .departure_time
+        .unwrap_or_else(|| chrono::Utc::now().with_timezone(&chrono_tz::Europe::Berlin));
+    let (day, _time) = day_time(departure_time);
+    if !feed_bundle.day_filter.includes(day) {
+        return Err(RenderError::DayNotLoaded(day));
+    }
+    let radar = search(
+        &feed_bundle.data,
+        SearchParams {
+            origin,
+            departure_time: Some(departure_time),
+            max_duration: options.max_duration,
+            modes: Cow::Borrowed(&options.modes),
+            direction: None,
+            pois: &[],
+            districts: &[],
+            confidence: None,
+            max_km: None,
+            missed: None,
+            journey_url: None,
+            frequency: false,
+            scale: RadialScale::Linear,
+            max_legs: MaxLegs::default(),
+            grace: None,
+            importance: Some(&feed_bundle.importance),
+            prefer_trips: vec![],
+        },
+    );
+    let mut svg = vec![];
+    radar
+        .write_svg_to(
+            &mut svg,
+            UrlSearchParams {
+                departure_time: options.departure_time,
+                max_duration: options.max_duration,
+                modes: Cow::Borrowed(&options.modes),
+                ..UrlSearchParams::new(origin.stop_id, &feed_bundle.slugs)
+            },
+            false,
+        )
+        .expect("writing to memory");
+    Ok(String::from_utf8(svg).expect("the SVG to be UTF-8"))
+}
+
+#[test]
+fn radar_of_a_station_by_name() {
+    use chrono::TimeZone;
+
+    let feed_bundle = bundle::demo_bundle("embed");
+
+    let monday = RenderOptions {
+        departure_time: Some(
+            chrono_tz::Europe::Berlin
+                .with_ymd_and_hms(2024, 1, 15, 10, 0, 0)
+                .unwrap(),
+        ),
+        ..RenderOptions::default()
+    };
+    let svg = render_svg(&feed_bundle, "alexanderplatz", &monday).unwrap();
+    assert!(svg.contains("<svg"), "{}", svg);
+    assert!(svg.contains(">Friedrichstr.</text>"), "{}", svg);
+    assert_eq!(render_svg(&feed_bundle, "Alexanderplatz", &monday), Ok(svg));
+    let found: Vec<_> = feed_bundle
+        .search_stations("alex", 10)
+        .into_iter()
+        .map(|station| feed_bundle.slug(station.stop_id))
+        .collect();
+    assert_eq!(found, vec![Some("alexanderplatz")]);
+
+    assert_eq!(
+        render_svg(&feed_bundle, "Atlantis", &monday),
+        Err(RenderError::UnknownStation("Atlantis".to_owned()))
+    );
+    let tuesday = RenderOptions {
+        departure_time: Some(
+            chrono_tz::Europe::Berlin
+                .with_ymd_and_hms(2024, 1, 16, 10, 0, 0)
+                .unwrap(),
+        ),
+        ..RenderOptions::default()
+    };
+    assert_eq!(
+        render_svg(&feed_bundle, "alexanderplatz", &tuesday),
+        Err(RenderError::DayNotLoaded(Day::Tuesday))
+    );
+}
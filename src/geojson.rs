@@ -0,0 +1,81 @@
+//! The parts of GeoJSON which are read from the overlay files, coordinates are swapped on reading to (lat, lon) like the stop locations.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub(crate) struct FeatureCollection {
+    pub features: Vec<Feature>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Feature {
+    pub geometry: Option<Geometry>,
+    #[serde(default)]
+    properties: Option<HashMap<String, serde_json::Value>>,
+}
+
+impl Feature {
+    /// A string property of the feature
+    pub fn property(&self, key: &str) -> Option<String> {
+        self.properties
+            .as_ref()?
+            .get(key)
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_owned)
+    }
+}
+
+type Position = Vec<f64>;
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub(crate) enum Geometry {
+    Point {
+        coordinates: Position,
+    },
+    Polygon {
+        coordinates: Vec<Vec<Position>>,
+    },
+    MultiPolygon {
+        coordinates: Vec<Vec<Vec<Position>>>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+pub(crate) fn point(position: &[f64]) -> Option<geo::Point<f64>> {
+    let (lon, lat) = (*position.first()?, *position.get(1)?);
+    Some(geo::Point::new(lat, lon))
+}
+
+fn polygon(rings: &[Vec<Position>]) -> Option<geo::Polygon<f64>> {
+    let ring = |positions: &Vec<Position>| {
+        positions
+            .iter()
+            .map(|position| point(position).map(geo::Coord::from))
+            .collect::<Option<Vec<_>>>()
+            .map(geo::LineString::new)
+    };
+    let (exterior, interiors) = rings.split_first()?;
+    Some(geo::Polygon::new(
+        ring(exterior)?,
+        interiors.iter().map(ring).collect::<Option<_>>()?,
+    ))
+}
+
+impl Geometry {
+    /// The area of a polygon or multi polygon geometry
+    pub fn area(&self) -> Option<geo::MultiPolygon<f64>> {
+        match self {
+            Geometry::Polygon { coordinates } => Some(vec![polygon(coordinates)?].into()),
+            Geometry::MultiPolygon { coordinates } => coordinates
+                .iter()
+                .map(|rings| polygon(rings))
+                .collect::<Option<Vec<_>>>()
+                .map(geo::MultiPolygon::new),
+            Geometry::Point { .. } | Geometry::Other => None,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Other names stations are known by, colloquial ones such as `Alex` for Alexanderplatz and the names they had before they were renamed, which the station search finds them by too.
+//!
+//! They are kept in a CSV file with the columns `alias` and `station`, the station being given by it's GTFS stop id or it's full name in the feed.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use radar_search::search_data::*;
+use serde::Deserialize;
+
+use super::db::StopIds;
+use super::importance::Importance;
+use crate::Suggester;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct StationAlias {
+    pub alias: String,
+    pub station: String,
+}
+
+pub fn read_aliases(path: &Path) -> Result<Vec<StationAlias>, csv::Error> {
+    csv::Reader::from_path(path)?.into_deserialize().collect()
+}
+
+/// Adds the aliases to the station search as if they were the names of their stations, the ones which aren't of a station in the data, or of several stations of the same name, are returned
+pub fn add_aliases<'a>(
+    suggester: &mut Suggester<(StopId, usize)>,
+    aliases: &'a [StationAlias],
+    data: &GTFSData,
+    stop_ids: &StopIds,
+    importance: &Importance,
+) -> Vec<&'a StationAlias> {
+    let mut by_name: HashMap<&str, Vec<StopId>> = HashMap::new();
+    for station in data.stops().filter(|stop| stop.is_station()) {
+        by_name
+            .entry(station.full_stop_name())
+            .or_default()
+            .push(station.stop_id);
+    }
+    let mut unresolved = vec![];
+    for alias in aliases {
+        let by_id = stop_ids
+            .get(&alias.station)
+            .and_then(|stop_id| data.get_stop(stop_id))
+            .map(Stop::station_id);
+        let station_id = by_id.or_else(|| match by_name.get(alias.station.as_str()) {
+            Some(stations) if stations.len() == 1 => Some(stations[0]),
+            _ => None,
+        });
+        match station_id.and_then(|station_id| data.get_stop(station_id)) {
+            Some(station) => suggester.insert(
+                &alias.alias,
+                (station.stop_id, importance.score(station, data)),
+            ),
+            None => unresolved.push(alias),
+        }
+    }
+    unresolved
+}
+
+#[test]
+fn aliases_find_their_stations() {
+    use crate::gtfs::{colors::RouteColors, db, demo};
+
+    let dir = std::env::temp_dir().join(format!("transit-radar-aliases-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    demo::write_demo_feed(&dir).unwrap();
+    let (data, stop_ids) =
+        db::load_feeds_with_ids(&[&dir], db::DayFilter::All, &RouteColors::new()).unwrap();
+    let aliases_path = dir.join("aliases.csv");
+    std::fs::write(
+        &aliases_path,
+        "alias,station\nAlex,Alexanderplatz\nLehrter Bahnhof,400\nBahnhof Zoo,Zoologischer Garten\nPalast der Republik,Schlossplatz\n",
+    )
+    .unwrap();
+    let aliases = read_aliases(&aliases_path).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    let importance = Importance::default();
+    let mut suggester = db::build_station_word_index(&data, &importance);
+    let unresolved = add_aliases(&mut suggester, &aliases, &data, &stop_ids, &importance);
+    assert_eq!(unresolved, vec![&aliases[3]]);
+
+    let found = |query: &str| -> Vec<String> {
+        suggester
+            .search(query)
+            .into_iter()
+            .map(|(stop_id, _importance)| {
+                data.get_stop(stop_id).unwrap().full_stop_name().to_owned()
+            })
+            .collect()
+    };
+    assert_eq!(found("Alex"), vec!["Alexanderplatz"]);
+    assert_eq!(found("lehrter"), vec!["Hauptbahnhof"]);
+    assert_eq!(found("Bahnhof Zoo"), vec!["Zoologischer Garten"]);
+    // the station is still found by it's own name
+    assert_eq!(found("Zoologischer"), vec!["Zoologischer Garten"]);
+}
@@ -0,0 +1,218 @@
+//! A timetable compiled from GTFS feeds into one file, which loads in a fraction of the time of parsing the feeds.
+//!
+//! The file starts with `MAGIC` and the `VERSION` of it's layout, which is read back by the same version only, so a bundle is compiled again after upgrading.
+//! It holds the stops and trips as sent to clients, the interned GTFS ids and the feeds it was compiled from. The departures, walks and station search are built again on load as they are quick to build from the parsed data.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use radar_search::search_data::GTFSData;
+use radar_search::sync::{GTFSSyncIncrement, Manifest};
+
+use crate::gtfs::colors::RouteColors;
+use crate::gtfs::db::{self, DayFilter, StopIds};
+use crate::gtfs::ingest::Strictness;
+use crate::gtfs::short_names::ShortNames;
+
+const MAGIC: &[u8; 8] = b"TRBUNDLE";
+/// Increased whenever the layout of the bundle changes
+pub const VERSION: u32 = 4;
+
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    /// The day filter the feeds were loaded with, as in `DAY_FILTER`
+    day_filter: String,
+    feeds: Vec<BundledFeed>,
+    /// The interned GTFS ids of stops and blocks, the id of each is it's index plus one
+    ids: Vec<String>,
+    timetable: GTFSSyncIncrement,
+}
+
+/// A feed the bundle was compiled from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundledFeed {
+    pub dir: String,
+    /// The `feed_version` of the feed's `feed_info.txt`, which is optional
+    pub version: Option<String>,
+}
+
+/// A timetable read back from a bundle
+pub struct Bundled {
+    pub data: GTFSData,
+    pub stop_ids: StopIds,
+    pub day_filter: DayFilter,
+    pub feeds: Vec<BundledFeed>,
+}
+
+/// Loads the feeds as `db::load_feeds_reporting`, warning of the rows which can't be loaded, and writes them to a bundle
+pub fn compile<P: AsRef<Path>>(
+    gtfs_dirs: &[P],
+    day_filter: DayFilter,
+    route_colors: &RouteColors,
+    short_names: &ShortNames,
+    bundle_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let (data, stop_ids, _report) = db::load_feeds_reporting(
+        gtfs_dirs,
+        day_filter,
+        route_colors,
+        short_names,
+        Strictness::Warn,
+    )?;
+    let feeds = gtfs_dirs
+        .iter()
+        .map(|dir| BundledFeed {
+            dir: dir.as_ref().display().to_string(),
+            version: db::feed_version(dir.as_ref()),
+        })
+        .collect();
+    write(&data, &stop_ids, day_filter, feeds, bundle_path)
+}
+
+/// Writes a bundle of data loaded from the feeds, or from elsewhere
+pub fn write(
+    data: &GTFSData,
+    stop_ids: &StopIds,
+    day_filter: DayFilter,
+    feeds: Vec<BundledFeed>,
+    bundle_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut required = data.build_from();
+    for stop in data.stops() {
+        required.keep_stop(stop.stop_id);
+    }
+    for trip in data.trips() {
+        required.keep_trip(trip.trip_id);
+    }
+    let bundle = Bundle {
+        day_filter: day_filter.to_string(),
+        feeds,
+        ids: stop_ids.strings(),
+        timetable: data.sync_increment(&required.build(), &Manifest::default()),
+    };
+    let mut w = BufWriter::new(File::create(bundle_path)?);
+    w.write_all(MAGIC)?;
+    w.write_all(&VERSION.to_le_bytes())?;
+    bincode::serialize_into(&mut w, &bundle)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Reads a bundle written by the same version
+pub fn load(bundle_path: &Path) -> Result<Bundled, Box<dyn Error>> {
+    let mut r = BufReader::new(File::open(bundle_path)?);
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(format!("{} isn't a transit radar bundle", bundle_path.display()).into());
+    }
+    let mut version = [0; 4];
+    r.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != VERSION {
+        return Err(format!(
+            "{} is a version {} bundle but version {} is read, compile it again",
+            bundle_path.display(),
+            version,
+            VERSION
+        )
+        .into());
+    }
+    let bundle: Bundle = bincode::deserialize_from(&mut r)?;
+    let mut interner = lasso::Rodeo::default();
+    for id in &bundle.ids {
+        interner.get_or_intern(id);
+    }
+    if interner.len() != bundle.ids.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "bundle has repeated ids").into());
+    }
+    Ok(Bundled {
+        data: bundle.timetable.into_data()?,
+        stop_ids: StopIds::new(interner),
+        day_filter: bundle.day_filter.parse()?,
+        feeds: bundle.feeds,
+    })
+}
+
+/// Compiles the Mondays of the demo feed into a bundle in a new temporary directory named for the test, returning the directory, which the test removes, and the bundle's path
+#[cfg(test)]
+pub(crate) fn compile_demo(name: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("transit-radar-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    crate::gtfs::demo::write_demo_feed(&dir).unwrap();
+    let bundle_path = dir.join("radar.bundle");
+    compile(
+        &[&dir],
+        DayFilter::Single(radar_search::search_data::Day::Monday),
+        &RouteColors::new(),
+        &ShortNames::new(),
+        &bundle_path,
+    )
+    .unwrap();
+    (dir, bundle_path)
+}
+
+/// The Mondays of the demo feed loaded from a bundle, as an app embedding the radar loads it
+#[cfg(all(test, feature = "draw"))]
+pub(crate) fn demo_bundle(name: &str) -> crate::embed::FeedBundle {
+    let (dir, bundle_path) = compile_demo(name);
+    let feed_bundle = crate::embed::FeedBundle::load(&bundle_path).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    feed_bundle
+}
+
+#[test]
+fn test_bundle_loads_as_compiled() {
+    let dir =
+        std::env::temp_dir().join(format!("transit-radar-bundle-test-{}", std::process::id()));
+    let gtfs_dir = dir.join("gtfs");
+    std::fs::create_dir_all(&gtfs_dir).unwrap();
+    crate::gtfs::demo::write_demo_feed(&gtfs_dir).unwrap();
+    let bundle_path = dir.join("radar.bundle");
+    let day_filter = DayFilter::All;
+    compile(
+        &[&gtfs_dir],
+        day_filter,
+        &RouteColors::new(),
+        &ShortNames::new(),
+        &bundle_path,
+    )
+    .unwrap();
+    let (data, stop_ids) =
+        db::load_feeds_with_ids(&[&gtfs_dir], day_filter, &RouteColors::new()).unwrap();
+    let bundled = load(&bundle_path).unwrap();
+
+    assert_eq!(
+        bundled.feeds,
+        vec![BundledFeed {
+            dir: gtfs_dir.display().to_string(),
+            version: Some("demo".to_owned()),
+        }]
+    );
+    assert_eq!(bundled.stop_ids.strings(), stop_ids.strings());
+    assert_eq!(bundled.data.footprint().total(), data.footprint().total());
+    for trip in data.trips() {
+        let bundled_trip = bundled
+            .data
+            .trips()
+            .find(|bundled_trip| bundled_trip.trip_id == trip.trip_id)
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", bundled_trip.stop_times),
+            format!("{:?}", trip.stop_times)
+        );
+        assert_eq!(bundled_trip.next_in_block, trip.next_in_block);
+    }
+
+    // a bundle of another version isn't read
+    let mut bytes = std::fs::read(&bundle_path).unwrap();
+    bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    std::fs::write(&bundle_path, bytes).unwrap();
+    let err = load(&bundle_path).err().unwrap();
+    assert!(err.to_string().contains("compile it again"), "{}", err);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
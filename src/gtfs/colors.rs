@@ -0,0 +1,303 @@
+//! Route colours, loaded from one or more CSV files.
+//!
+//! Two CSV profiles are understood:
+//! * `Vbb` - VBB's `Type;Name;Hex;StrokeDashArray;StrokeWidth` file, rows with a `Type` but no `Name` set the default colour for that route type
+//! * `Simple` - `route_short_name,color[,text_color]`, with a header row
+//!
+//! Later sources override earlier ones, so a feed-wide file can be loaded first and local tweaks after it.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek};
+use std::path::Path;
+
+use radar_search::search_data::RouteType;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteColor {
+    pub color: String,
+    pub text_color: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorProfile {
+    Vbb,
+    Simple,
+}
+
+impl ColorProfile {
+    /// Work out the profile of a colour file from its header line
+    pub fn detect(header: &str) -> Option<Self> {
+        let header = header.trim_start_matches('\u{feff}').trim_end();
+        let vbb_columns: Vec<_> = header.split(';').collect();
+        if vbb_columns.contains(&"Name") && vbb_columns.contains(&"Hex") {
+            return Some(Self::Vbb);
+        }
+        let simple_columns: Vec<_> = header.split(',').map(str::trim).collect();
+        if simple_columns.first() == Some(&"route_short_name")
+            && simple_columns.get(1) == Some(&"color")
+        {
+            return Some(Self::Simple);
+        }
+        None
+    }
+
+    const fn delimiter(self) -> u8 {
+        match self {
+            Self::Vbb => b';',
+            Self::Simple => b',',
+        }
+    }
+}
+
+/// Colours for routes by their short name, falling back to a colour for the route type
+#[derive(Debug, Clone, Default)]
+pub struct RouteColors {
+    routes: HashMap<String, RouteColor>,
+    route_types: HashMap<RouteType, String>,
+}
+
+impl RouteColors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a colour file, detecting its profile from the header
+    pub fn load(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let mut header = String::new();
+        BufReader::new(&mut file).read_line(&mut header)?;
+        let profile = ColorProfile::detect(&header)
+            .ok_or_else(|| ColorsError::UnknownProfile(header.trim_end().to_owned()))?;
+        file.rewind()?;
+        self.load_from_reader(file, profile)?;
+        Ok(())
+    }
+
+    /// Load several colour files in order, later files take precedence
+    pub fn load_all<P: AsRef<Path>>(
+        &mut self,
+        paths: impl IntoIterator<Item = P>,
+    ) -> Result<(), Box<dyn Error>> {
+        for path in paths {
+            let path = path.as_ref();
+            eprintln!("Loading route colours from {}", path.display());
+            self.load(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn load_from_reader(
+        &mut self,
+        reader: impl Read,
+        profile: ColorProfile,
+    ) -> Result<(), csv::Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(profile.delimiter())
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let header = reader.headers()?.clone();
+        let column = |name: &str| header.iter().position(|header| header == name);
+        let (type_idx, route_name_idx, colour_idx, text_colour_idx) = match profile {
+            ColorProfile::Vbb => (column("Type"), column("Name"), column("Hex"), None),
+            ColorProfile::Simple => (
+                None,
+                column("route_short_name"),
+                column("color"),
+                column("text_color"),
+            ),
+        };
+        let field = |record: &csv::StringRecord, idx: Option<usize>| {
+            idx.and_then(|idx| record.get(idx))
+                .filter(|s| !s.is_empty())
+                .map(str::to_owned)
+        };
+        for result in reader.into_records() {
+            let record = result?;
+            let colour = if let Some(colour) = field(&record, colour_idx) {
+                colour
+            } else {
+                continue;
+            };
+            if let Some(route_name) = field(&record, route_name_idx) {
+                self.routes.insert(
+                    route_name,
+                    RouteColor {
+                        color: colour,
+                        text_color: field(&record, text_colour_idx),
+                    },
+                );
+            } else if let Some(route_type) = field(&record, type_idx)
+                .as_deref()
+                .and_then(route_type_for_name)
+            {
+                self.route_types.insert(route_type, colour);
+            }
+        }
+        Ok(())
+    }
+
+    /// Override the default colour for routes of a type which have no colour of their own
+    pub fn set_route_type_color(&mut self, route_type: RouteType, color: impl Into<String>) {
+        self.route_types.insert(route_type, color.into());
+    }
+
+    /// Parses route type colour overrides from config such as `BusService=#a01c7d,TramService=red`
+    pub fn set_route_type_colors_from_config(&mut self, config: &str) -> Result<(), ColorsError> {
+        for entry in config.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (name, color) = entry
+                .split_once('=')
+                .ok_or_else(|| ColorsError::InvalidOverride(entry.to_owned()))?;
+            let route_type = route_type_for_name(name.trim())
+                .ok_or_else(|| ColorsError::UnknownRouteType(name.trim().to_owned()))?;
+            self.set_route_type_color(route_type, color.trim());
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, route_short_name: &str) -> Option<&RouteColor> {
+        self.routes.get(route_short_name)
+    }
+
+    /// Colour for a route, from it's own colour, the configured default for it's type or the built in default for the type
+    pub fn route_color(&self, route_short_name: &str, route_type: RouteType) -> &str {
+        self.routes
+            .get(route_short_name)
+            .map(|route_color| route_color.color.as_str())
+            .or_else(|| self.route_types.get(&route_type).map(String::as_str))
+            .unwrap_or_else(|| route_type.default_color())
+    }
+}
+
+/// Route type referred to by a name in a colour file, this is the name of the `RouteType` or one of the names used in the VBB file
+fn route_type_for_name(name: &str) -> Option<RouteType> {
+    use RouteType::*;
+    Some(match name {
+        "Rail" => Rail,
+        "Bus" => Bus,
+        "RailwayService" => RailwayService,
+        "SuburbanRailway" => SuburbanRailway,
+        "UrbanRailway" | "UrbanRailwayService" => UrbanRailway,
+        "BusService" => BusService,
+        "TramService" => TramService,
+        "WaterTransportService" | "Ferry" => WaterTransportService,
+        _ => return None,
+    })
+}
+
+#[derive(Debug)]
+pub enum ColorsError {
+    UnknownProfile(String),
+    InvalidOverride(String),
+    UnknownRouteType(String),
+}
+
+impl Error for ColorsError {}
+
+impl fmt::Display for ColorsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorsError::UnknownProfile(header) => {
+                write!(f, "Unrecognised colour file header \"{}\"", header)
+            }
+            ColorsError::InvalidOverride(entry) => write!(
+                f,
+                "Route type colour \"{}\" should be formatted eg. BusService=#a01c7d",
+                entry
+            ),
+            ColorsError::UnknownRouteType(name) => write!(f, "Unknown route type \"{}\"", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detect_profiles() {
+        assert_eq!(
+            ColorProfile::detect("Type;Name;Hex;StrokeDashArray;StrokeWidth\n"),
+            Some(ColorProfile::Vbb)
+        );
+        assert_eq!(
+            ColorProfile::detect("route_short_name,color,text_color\r\n"),
+            Some(ColorProfile::Simple)
+        );
+        assert_eq!(
+            ColorProfile::detect("\u{feff}route_short_name,color"),
+            Some(ColorProfile::Simple)
+        );
+        assert_eq!(ColorProfile::detect("foo,bar"), None);
+    }
+
+    #[test]
+    fn vbb_profile() {
+        let mut colors = RouteColors::new();
+        colors
+            .load_from_reader(
+                "Type;Name;Hex;StrokeDashArray;StrokeWidth\nRail;;red;;2pt\nFerry;;#0099d6\n;S1;#eb588f\n;S2;\n"
+                    .as_bytes(),
+                ColorProfile::Vbb,
+            )
+            .unwrap();
+        assert_eq!(
+            colors.route_color("S1", RouteType::SuburbanRailway),
+            "#eb588f"
+        );
+        assert_eq!(
+            colors.route_color("S2", RouteType::SuburbanRailway),
+            "lightgray"
+        );
+        assert_eq!(colors.route_color("RE1", RouteType::Rail), "red");
+        assert_eq!(
+            colors.route_color("F10", RouteType::WaterTransportService),
+            "#0099d6"
+        );
+    }
+
+    #[test]
+    fn simple_profile_with_later_overrides() {
+        let mut colors = RouteColors::new();
+        colors
+            .load_from_reader(
+                "route_short_name,color,text_color\nU1,#7dad4c,#ffffff\nU2,#da421e\n".as_bytes(),
+                ColorProfile::Simple,
+            )
+            .unwrap();
+        colors
+            .load_from_reader(
+                "route_short_name,color\nU2,#000000\n".as_bytes(),
+                ColorProfile::Simple,
+            )
+            .unwrap();
+        assert_eq!(
+            colors.get("U1"),
+            Some(&RouteColor {
+                color: "#7dad4c".to_owned(),
+                text_color: Some("#ffffff".to_owned())
+            })
+        );
+        assert_eq!(colors.route_color("U2", RouteType::UrbanRailway), "#000000");
+    }
+
+    #[test]
+    fn route_type_overrides() {
+        let mut colors = RouteColors::new();
+        colors
+            .set_route_type_colors_from_config("BusService=#123456, Ferry = blue")
+            .unwrap();
+        assert_eq!(colors.route_color("100", RouteType::BusService), "#123456");
+        assert_eq!(
+            colors.route_color("F10", RouteType::WaterTransportService),
+            "blue"
+        );
+        assert!(colors
+            .set_route_type_colors_from_config("Zeppelin=red")
+            .is_err());
+        assert!(colors.set_route_type_colors_from_config("red").is_err());
+    }
+}
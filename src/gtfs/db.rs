@@ -1,270 +1,993 @@
-use crate::suggester::Suggester;
+use crate::Suggester;
+use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead};
 use std::num::IntErrorKind;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 
 use crate::gtfs;
+use crate::gtfs::colors::RouteColors;
+use crate::gtfs::importance::Importance;
+use crate::gtfs::ingest::{FileIngest, Ingest, IngestReport, RowError, Strictness};
+use crate::gtfs::short_names::ShortNames;
+use chrono::{Datelike, NaiveDate};
 use csv::DeserializeErrorKind;
 use radar_search::search_data::*;
-use regex::Regex;
 
 /// Refers to a specific stop of a specific trip (an arrival / departure)
 pub type TripStopRef = (TripId, usize); // usize refers to the index of the stop in the trip, should probably instead use stop sequence
 
-impl gtfs::Calendar {
-    /// A Vec of all the days that this servcice runs on between start and end dates
-    pub fn days(&self) -> Vec<Day> {
-        let mut days = vec![];
-        for (day, val) in [
-            Day::Monday,
-            Day::Tuesday,
-            Day::Wednesday,
-            Day::Thursday,
-            Day::Friday,
-            Day::Saturday,
-            Day::Sunday,
-        ]
-        .iter()
-        .zip(
-            [
-                self.monday,
-                self.tuesday,
-                self.wednesday,
-                self.thursday,
-                self.friday,
-                self.saturday,
-                self.sunday,
-            ]
-            .iter(),
-        ) {
-            if *val > 0 {
-                days.push(*day);
-            }
-        }
-        days
-    }
+/// Loads a feed, with a report of the rows which were skipped by the strictness
+pub fn load_data(
+    gtfs_dir: &Path,
+    day_filter: DayFilter,
+    route_colors: &RouteColors,
+    strictness: Strictness,
+) -> Result<(GTFSData, IngestReport), Box<dyn Error>> {
+    load_feeds_reporting(
+        &[gtfs_dir],
+        day_filter,
+        route_colors,
+        &ShortNames::default(),
+        strictness,
+    )
+    .map(|(data, _stop_ids, report)| (data, report))
+}
+
+/// Dates in GTFS files, such as `20240101`
+const GTFS_DATE_FORMAT: &str = "%Y%m%d";
+/// Stations of later feeds closer than this to a station of the same name in an earlier feed are merged into it
+const MERGE_STATION_METRES: f64 = 250.;
+/// Stations of different feeds within this distance are connected by transfers
+const STITCH_TRANSFER_METRES: f64 = 400.;
+const WALKING_METRES_PER_SECOND: f64 = 1.3;
+/// Added to the time to walk a generated transfer, for finding the way to the platform
+const STITCH_TRANSFER_BUFFER_SECONDS: i64 = 120;
+
+/// Loads several GTFS feeds into one data set, so that a search can cross from one to another.
+///
+/// The first feed keeps it's ids, the ids of later feeds are namespaced so that they can't collide. Stations of later feeds are merged into a station of an earlier feed with the same name nearby, and the timetable start date is taken from the first feed.
+pub fn load_feeds<P: AsRef<Path>>(
+    gtfs_dirs: &[P],
+    day_filter: DayFilter,
+    route_colors: &RouteColors,
+) -> Result<GTFSData, Box<dyn Error>> {
+    load_feeds_with_ids(gtfs_dirs, day_filter, route_colors).map(|(data, _stop_ids)| data)
+}
+
+/// Loads the feeds as `load_feeds`, also keeping the GTFS ids of the stops to look them up by.
+///
+/// The names of the stops of every feed are shortened by the default profile.
+pub fn load_feeds_with_ids<P: AsRef<Path>>(
+    gtfs_dirs: &[P],
+    day_filter: DayFilter,
+    route_colors: &RouteColors,
+) -> Result<(GTFSData, StopIds), Box<dyn Error>> {
+    load_feeds_reporting(
+        gtfs_dirs,
+        day_filter,
+        route_colors,
+        &ShortNames::default(),
+        Strictness::Warn,
+    )
+    .map(|(data, stop_ids, _report)| (data, stop_ids))
+}
+
+/// Loads the feeds as `load_feeds_with_ids`, with the stop names shortened by the profile of each feed and the rows which can't be loaded skipped or failing the load by the strictness, and a report of them
+pub fn load_feeds_reporting<P: AsRef<Path>>(
+    gtfs_dirs: &[P],
+    day_filter: DayFilter,
+    route_colors: &RouteColors,
+    short_names: &ShortNames,
+    strictness: Strictness,
+) -> Result<(GTFSData, StopIds, IngestReport), Box<dyn Error>> {
+    load_feeds_by(
+        gtfs_dirs,
+        day_filter,
+        route_colors,
+        short_names,
+        strictness,
+        load_stop_times,
+    )
 }
 
-fn color_for_type(route_type: RouteType) -> &'static str {
-    match route_type {
-        RouteType::SuburbanRailway => "lightgray",
-        RouteType::UrbanRailway => "lightgray",
-        RouteType::TramService => "lightgray",
-        RouteType::Rail => "#e2001a",
-        RouteType::RailwayService => "#e2001a",
-        RouteType::Bus => "#a01c7d", // not sure if this is bus
-        RouteType::BusService => "#a01c7d",
-        RouteType::WaterTransportService => "#0099d6",
+/// The stop times of a feed, once it's stops and trips are loaded
+pub(crate) struct FeedStopTimes<'f> {
+    pub source: &'f GTFSSource,
+    pub feed_index: usize,
+    /// The loaded trips by their id in the feed, trips of services which aren't loaded are left out
+    pub trips: &'f HashMap<gtfs::TripId, TripId>,
+    feed: &'f FeedIds,
+    interner: &'f mut lasso::Rodeo,
+    pub ingest: FileIngest<'f>,
+}
+
+impl FeedStopTimes<'_> {
+    pub fn stop_id(&mut self, gtfs_id: &str) -> StopId {
+        self.feed.stop_id(self.interner, gtfs_id)
     }
 }
 
-pub fn load_data<S: std::hash::BuildHasher>(
-    gtfs_dir: &Path,
+/// Loads the feeds as `load_feeds_with_ids`, with the stop times of each feed left to `stop_times`
+pub(crate) fn load_feeds_by<P: AsRef<Path>>(
+    gtfs_dirs: &[P],
     day_filter: DayFilter,
-    route_colors: HashMap<String, String, S>,
-) -> Result<GTFSData, Box<dyn Error>> {
-    let source = &GTFSSource::new(gtfs_dir);
+    route_colors: &RouteColors,
+    short_names: &ShortNames,
+    strictness: Strictness,
+    mut stop_times: impl FnMut(FeedStopTimes, &mut Builder) -> Result<(), Box<dyn Error>>,
+) -> Result<(GTFSData, StopIds, IngestReport), Box<dyn Error>> {
+    let mut ingest = Ingest::new(strictness);
+    let sources: Vec<_> = gtfs_dirs.iter().map(GTFSSource::new).collect();
+    let mut feeds: Vec<FeedIds> = (0..sources.len()).map(FeedIds::new).collect();
+    let mut id_spaces = IdSpaces::default();
 
     let mut services_by_day: HashMap<_, HashSet<_>> = HashMap::new();
-    let mut timetable_start_date = String::default();
-    for result in source.get_calendar()? {
-        let calendar: gtfs::Calendar = result?;
-        for day in calendar.days() {
-            services_by_day
-                .entry(day)
-                .or_default()
-                .insert(calendar.service_id);
+    let mut timetable_start_date = None;
+    let mut service_exceptions = vec![];
+    for (source, feed) in sources.iter().zip(&mut feeds) {
+        let mut feed_start_date = String::default();
+        let keeps_ids = feed.keeps_ids();
+        for result in source.get_calendar()? {
+            let calendar: gtfs::Calendar = result?;
+            let service_id = ServiceId::try_from(id_spaces.services.place(
+                &mut feed.services,
+                keeps_ids,
+                calendar.service_id.into(),
+            ))?;
+            for day in calendar.days() {
+                services_by_day.entry(day).or_default().insert(service_id);
+                if day_filter.includes(day) {
+                    feed.filtered_services.insert(calendar.service_id);
+                }
+            }
+            feed_start_date = calendar.start_date;
+        }
+        timetable_start_date.get_or_insert(feed_start_date);
+        let mut calendar_dates = ingest.file(feed.index, "calendar_dates.txt");
+        for calendar_date in source.get_calendar_dates()? {
+            calendar_dates.row();
+            let exception = match calendar_date.exception_type {
+                1 => ServiceException::Added,
+                2 => ServiceException::Removed,
+                other => {
+                    calendar_dates.skip(
+                        RowError::UnknownExceptionType,
+                        format_args!(
+                            "service {} has exception type {} on {}",
+                            calendar_date.service_id, other, calendar_date.date
+                        ),
+                    )?;
+                    continue;
+                }
+            };
+            let date = NaiveDate::parse_from_str(&calendar_date.date, GTFS_DATE_FORMAT)?;
+            // a service can run only on the dates it's added on
+            let service_id = ServiceId::try_from(id_spaces.services.place(
+                &mut feed.services,
+                keeps_ids,
+                calendar_date.service_id.into(),
+            ))?;
+            if exception == ServiceException::Added && day_filter.includes(date.weekday().into()) {
+                feed.filtered_services.insert(calendar_date.service_id);
+            }
+            service_exceptions.push((date, service_id, exception));
         }
-        timetable_start_date = calendar.start_date;
+        calendar_dates.finish();
     }
 
-    let mut builder = GTFSData::builder(services_by_day.clone(), timetable_start_date);
+    let mut builder = GTFSData::builder(services_by_day, timetable_start_date.unwrap_or_default());
+    for (date, service_id, exception) in service_exceptions {
+        builder.add_service_exception(date, service_id, exception);
+    }
 
     let mut interner = lasso::Rodeo::default();
+    let mut stations_by_name: HashMap<String, Vec<(StopId, geo::Point<f64>)>> = HashMap::new();
+    let mut feed_stations = vec![];
+
+    for (source, feed) in sources.iter().zip(&mut feeds) {
+        let trips = load_feed(
+            source,
+            feed,
+            &mut id_spaces,
+            &mut interner,
+            &mut stations_by_name,
+            &mut feed_stations,
+            &mut builder,
+            &mut ingest,
+            day_filter,
+            route_colors,
+            short_names,
+        )?;
+        stop_times(
+            FeedStopTimes {
+                source,
+                feed_index: feed.index,
+                trips: &trips,
+                feed,
+                interner: &mut interner,
+                ingest: ingest.file(feed.index, "stop_times.txt"),
+            },
+            &mut builder,
+        )?;
+    }
+    if feeds.len() > 1 {
+        for (from_stop_id, to_stop_id, walk) in stitching_transfers(feed_stations) {
+            builder.add_transfer(from_stop_id, to_stop_id, Some(walk))?;
+        }
+    }
+
+    Ok((builder.build()?, StopIds::new(interner), ingest.report()))
+}
+
+/// The GTFS ids of the loaded stops, the ids of feeds after the first are namespaced as `<feed index>:<id>`
+pub struct StopIds(lasso::RodeoReader);
+
+impl StopIds {
+    pub(crate) fn new(interner: lasso::Rodeo) -> Self {
+        StopIds(interner.into_reader())
+    }
+
+    pub fn get(&self, gtfs_id: &str) -> Option<StopId> {
+        self.0.get(gtfs_id).map(lasso::Spur::into_inner)
+    }
+
+    /// The interned ids, which include the ids of blocks, in the order of their keys
+    pub fn strings(&self) -> Vec<String> {
+        self.0.strings().map(str::to_owned).collect()
+    }
 
-    let mut count_stop_id_invalid_digit = 0;
+    pub fn gtfs_id(&self, stop_id: StopId) -> Option<&str> {
+        use lasso::Key;
+        lasso::Spur::try_from_usize(stop_id.get() as usize - 1)
+            .and_then(|key| self.0.try_resolve(&key))
+    }
+
+    /// Finds a stop by it's IFOPT id, `<country>:<area>:<stop>` with optionally `:<area>:<quay>` for a platform.
+    ///
+    /// Feeds which use IFOPT ids may leave platforms out, so a platform not in the feed falls back to it's stop.
+    pub fn get_ifopt(&self, ifopt_id: &str) -> Option<StopId> {
+        let parts: Vec<_> = ifopt_id.split(':').collect();
+        if parts.len() < 3 || parts[..3].iter().any(|part| part.is_empty()) {
+            return None;
+        }
+        self.get(ifopt_id)
+            .or_else(|| self.get(&parts[..3].join(":")))
+    }
+}
+
+/// The version the publisher gives a feed in it's optional `feed_info.txt`
+pub fn feed_version(gtfs_dir: &Path) -> Option<String> {
+    let path = gtfs_dir.join("feed_info.txt");
+    if !path.exists() {
+        return None;
+    }
+    let feed_info: gtfs::FeedInfo = csv_reader_builder(sniff_delimiter(&path).ok()?)
+        .from_path(path)
+        .ok()?
+        .into_deserialize()
+        .next()?
+        .ok()?;
+    feed_info.feed_version.filter(|version| !version.is_empty())
+}
+
+/// The delimiters of the files of feeds, some exports use semicolons or tabs rather than commas
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// The one of `DELIMITERS` which the file's header has most of, a comma when it has none of them
+pub(crate) fn sniff_delimiter(path: &Path) -> io::Result<u8> {
+    let mut header = vec![];
+    io::BufReader::new(File::open(path)?).read_until(b'\n', &mut header)?;
+    Ok(delimiter_of(&header))
+}
+
+fn delimiter_of(header: &[u8]) -> u8 {
+    let mut counts = [0; DELIMITERS.len()];
+    let mut quoted = false;
+    for &byte in header {
+        if byte == b'"' {
+            quoted = !quoted;
+        } else if let Some(index) = DELIMITERS.iter().position(|&delimiter| delimiter == byte) {
+            if !quoted {
+                counts[index] += 1;
+            }
+        }
+    }
+    // the first of the most common, so that a tie is a comma
+    let most = (0..DELIMITERS.len()).fold(0, |most, index| {
+        if counts[index] > counts[most] {
+            index
+        } else {
+            most
+        }
+    });
+    DELIMITERS[most]
+}
+
+/// Reads a file of a feed, the reader strips a UTF-8 BOM and takes CRLF line endings, and the spaces some exports put after the delimiters of the header are trimmed
+pub(crate) fn csv_reader_builder(delimiter: u8) -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder.delimiter(delimiter).trim(csv::Trim::Headers);
+    builder
+}
+
+/// Where the ids of a feed are placed in the merged data
+struct FeedIds {
+    index: usize,
+    services: HashMap<u32, u32>,
+    routes: HashMap<u32, u32>,
+    trips: HashMap<u32, u32>,
+    /// The original service ids which run on the days being loaded
+    filtered_services: HashSet<ServiceId>,
+    /// Stations merged into the station of an earlier feed, by their original id
+    merged_stations: HashMap<gtfs::StopId, StopId>,
+}
+
+impl FeedIds {
+    fn new(index: usize) -> Self {
+        Self {
+            index,
+            services: HashMap::new(),
+            routes: HashMap::new(),
+            trips: HashMap::new(),
+            filtered_services: HashSet::new(),
+            merged_stations: HashMap::new(),
+        }
+    }
+
+    fn keeps_ids(&self) -> bool {
+        self.index == 0
+    }
+
+    /// The key for a string id of this feed in the interner
+    fn namespaced<'i>(&self, id: &'i str) -> Cow<'i, str> {
+        if self.keeps_ids() {
+            Cow::Borrowed(id)
+        } else {
+            Cow::Owned(format!("{}:{}", self.index, id))
+        }
+    }
+
+    fn stop_id(&self, interner: &mut lasso::Rodeo, id: &str) -> StopId {
+        self.merged_stations
+            .get(id)
+            .copied()
+            .unwrap_or_else(|| interner.get_or_intern(self.namespaced(id)).into_inner())
+    }
+
+    fn service_id(&self, service_id: ServiceId) -> Option<ServiceId> {
+        self.services
+            .get(&service_id.into())
+            .and_then(|&id| ServiceId::try_from(id).ok())
+    }
+}
+
+#[derive(Default)]
+struct IdSpaces {
+    services: IdSpace,
+    routes: IdSpace,
+    trips: IdSpace,
+}
+
+/// Numeric ids of one type across the feeds, ids of the first feed are kept and later feeds are numbered after the highest id so far
+#[derive(Default)]
+struct IdSpace {
+    highest: u32,
+}
+
+impl IdSpace {
+    fn place(&mut self, feed_ids: &mut HashMap<u32, u32>, keep: bool, id: u32) -> u32 {
+        let highest = &mut self.highest;
+        *feed_ids.entry(id).or_insert_with(|| {
+            if keep {
+                *highest = (*highest).max(id);
+                id
+            } else {
+                *highest += 1;
+                *highest
+            }
+        })
+    }
+}
+
+/// Loads the stops, routes and trips of a feed, returning the loaded trips by their id in the feed
+#[allow(clippy::too_many_arguments)]
+fn load_feed(
+    source: &GTFSSource,
+    feed: &mut FeedIds,
+    id_spaces: &mut IdSpaces,
+    interner: &mut lasso::Rodeo,
+    stations_by_name: &mut HashMap<String, Vec<(StopId, geo::Point<f64>)>>,
+    feed_stations: &mut Vec<FeedStation>,
+    builder: &mut Builder,
+    ingest: &mut Ingest,
+    day_filter: DayFilter,
+    route_colors: &RouteColors,
+    short_names: &ShortNames,
+) -> Result<HashMap<gtfs::TripId, TripId>, Box<dyn Error>> {
+    let keeps_ids = feed.keeps_ids();
+    let mut stops: Vec<gtfs::Stop> = vec![];
+    let mut stops_ingest = ingest.file(feed.index, "stops.txt");
     let mut rdr = source.open_csv("stops.txt")?;
     for result in rdr.deserialize() {
+        stops_ingest.row();
         match result {
-            Ok(gtfs::Stop {
+            Ok(stop) => stops.push(stop),
+            // One of VBB's StopIds has 'D_' in front of it, I don't know why. That stop's parent is the same number without the 'D_', it is on a couple of trips but - we just count it and skip it
+            Err(err) if is_non_numeric_id(&err, 0) => {
+                stops_ingest.skip(RowError::NonNumericStopId, err)?
+            }
+            Err(err) => stops_ingest.skip(RowError::Unparsable, err)?,
+        }
+    }
+    stops_ingest.finish();
+
+    if !keeps_ids {
+        // stations are merged before any stops are added, as the platforms of a station can come before it
+        for stop in &stops {
+            if stop.location_type != 1 {
+                continue;
+            }
+            let location = geo::Point::new(stop.stop_lat, stop.stop_lon);
+            let same_station = stations_by_name.get(&stop.stop_name).and_then(|stations| {
+                stations
+                    .iter()
+                    .find(|(_, other)| distance_metres(location, *other) < MERGE_STATION_METRES)
+            });
+            if let Some(&(station_id, _)) = same_station {
+                feed.merged_stations
+                    .insert(stop.stop_id.clone(), station_id);
+            }
+        }
+        eprintln!(
+            "Merged {} stations of feed {} into earlier feeds",
+            feed.merged_stations.len(),
+            feed.index
+        );
+    }
+
+    for gtfs::Stop {
+        stop_id,
+        stop_name,
+        stop_lat,
+        stop_lon,
+        location_type,
+        parent_station,
+        zone_id,
+    } in stops
+    {
+        if location_type == 3 {
+            // generic node, for pathways, not used yet in transit radar
+            continue;
+        }
+        if location_type == 1 && feed.merged_stations.contains_key(&stop_id) {
+            continue;
+        }
+        let stop_id = feed.stop_id(interner, &stop_id);
+        let parent_station = parent_station.map(|stop_id| feed.stop_id(interner, &stop_id));
+        let short_stop_name = short_names.shorten(feed.index, &stop_name);
+        let location = geo::Point::new(stop_lat, stop_lon);
+        if matches!((location_type, parent_station), (0, None) | (1, None)) {
+            feed_stations.push(FeedStation {
+                feed: feed.index,
+                stop_id,
+                location,
+            });
+        }
+        match (location_type, parent_station) {
+            (1, None) => {
+                stations_by_name
+                    .entry(stop_name.clone())
+                    .or_default()
+                    .push((stop_id, location));
+                builder.add_station(stop_id, stop_name, short_stop_name, location)
+            }
+            (0, parent_station) => builder.add_stop_or_platform(
                 stop_id,
                 stop_name,
-                stop_lat,
-                stop_lon,
-                location_type,
+                short_stop_name,
+                location,
                 parent_station,
-            }) => {
-                if location_type == 3 {
-                    // generic node, for pathways, not used yet in transit radar
-                    continue;
-                }
-                let stop_id = interner.get_or_intern(stop_id).into_inner();
-                let parent_station =
-                    parent_station.map(|stop_id| interner.get_or_intern(stop_id).into_inner());
-                let short_stop_name = strip_stop_name(&stop_name);
-                let location = geo::Point::new(stop_lat, stop_lon);
-                match (location_type, parent_station) {
-                    (1, None) => builder.add_station(stop_id, stop_name, short_stop_name, location),
-                    (0, parent_station) => builder.add_stop_or_platform(
-                        stop_id,
-                        stop_name,
-                        short_stop_name,
-                        location,
-                        parent_station,
-                    ),
-                    (2, Some(parent_station)) => builder.add_entrance_or_exit(
-                        stop_id,
-                        stop_name,
-                        short_stop_name,
-                        location,
-                        parent_station,
-                    ),
-                    (1, Some(parent_station)) => {
-                        panic!("station {:?} has parent {:?}", stop_id, parent_station)
-                    }
-                    (2, None) => panic!("entrance {:?} with no parent", stop_id),
-                    (t, _) => panic!("{:?} is unknown location type {}", stop_id, t),
-                };
-            }
-            Err(err) =>
-            // /// One of VBB's StopIds has 'D_' in front of it, I don't know why. That stop's parent is the same number without the 'D_', it is on a couple of trips but - we just show a warning and skip it
-            {
-                if let csv::ErrorKind::Deserialize { pos: _, err } = err.kind() {
-                    if err.field() == Some(0) {
-                        if let DeserializeErrorKind::ParseInt(err) = err.kind() {
-                            if IntErrorKind::InvalidDigit == *err.kind() {
-                                count_stop_id_invalid_digit += 1;
-                                continue;
-                            }
-                        }
-                    }
-                }
-                eprintln!("Error parsing stop - skipped : {}", err)
+            ),
+            (2, Some(parent_station)) => builder.add_entrance_or_exit(
+                stop_id,
+                stop_name,
+                short_stop_name,
+                location,
+                parent_station,
+            ),
+            (1, Some(parent_station)) => {
+                panic!("station {:?} has parent {:?}", stop_id, parent_station)
             }
+            (2, None) => panic!("entrance {:?} with no parent", stop_id),
+            (t, _) => panic!("{:?} is unknown location type {}", stop_id, t),
+        };
+        if let Some(zone_id) = zone_id.filter(|zone_id| !zone_id.is_empty()) {
+            builder.set_zone(stop_id, zone_id)?;
         }
     }
-    log_invalid_digit_count_failures("stops", count_stop_id_invalid_digit);
 
-    let mut count_stop_id_invalid_digit = 0;
+    let mut transfers = ingest.file(feed.index, "transfers.txt");
     for result in source
         .open_csv("transfers.txt")?
         .deserialize::<gtfs::Transfer>()
     {
+        transfers.row();
         match result {
             Ok(transfer) => builder.add_transfer(
-                interner.get_or_intern(transfer.from_stop_id).into_inner(),
-                interner.get_or_intern(transfer.to_stop_id).into_inner(),
+                feed.stop_id(interner, &transfer.from_stop_id),
+                feed.stop_id(interner, &transfer.to_stop_id),
                 transfer.min_transfer_time,
-            ),
-            Err(err) => {
-                if let csv::ErrorKind::Deserialize { pos: _, err } = err.kind() {
-                    if err.field() == Some(0) {
-                        if let DeserializeErrorKind::ParseInt(err) = err.kind() {
-                            if IntErrorKind::InvalidDigit == *err.kind() {
-                                count_stop_id_invalid_digit += 1;
-                                continue;
-                            }
-                        }
-                    }
-                }
-                eprintln!("Error parsing transfer : {}", err)
+            )?,
+            Err(err) if is_non_numeric_id(&err, 0) => {
+                transfers.skip(RowError::NonNumericStopId, err)?
             }
+            Err(err) => transfers.skip(RowError::Unparsable, err)?,
         }
     }
-    log_invalid_digit_count_failures("stops", count_stop_id_invalid_digit);
+    transfers.finish();
 
-    use std::borrow::Cow;
     let mut rdr = source.open_csv("routes.txt")?;
     for result in rdr.deserialize() {
         let route: gtfs::Route = result?;
-        let route_color: Cow<str> = route_colors
-            .get(&route.route_short_name)
-            .map(Into::into)
-            .unwrap_or_else(|| color_for_type(route.route_type).into());
+        let route_name = route_name(&route);
+        let route_color = route_color(&route, &route_name, route_colors);
         builder.add_route(
-            route.route_id.into_inner(),
-            route.route_short_name,
+            id_spaces
+                .routes
+                .place(&mut feed.routes, keeps_ids, route.route_id.into_inner()),
+            route_name,
             route.route_type,
-            route_color.into_owned(),
+            route_color,
         );
     }
 
     let services = match day_filter {
         DayFilter::All => None,
-        DayFilter::Single(day) => Some(services_by_day.get(&day).unwrap().clone()),
+        DayFilter::Single(_day) => Some(feed.filtered_services.clone()),
     };
-    let mut added_trips = HashSet::new();
+    let mut added_trips = HashMap::new();
+    let mut trips = ingest.file(feed.index, "trips.txt");
     for result in source.get_trips(None, services)? {
-        let trip: gtfs::Trip = result?;
-        builder.add_trip(trip.trip_id, trip.route_id.into_inner(), trip.service_id);
-        added_trips.insert(trip.trip_id);
+        trips.row();
+        let trip: gtfs::Trip = match result {
+            Ok(trip) => trip,
+            Err(err) => {
+                trips.skip(RowError::Unparsable, err)?;
+                continue;
+            }
+        };
+        let trip_id = TripId::new(id_spaces.trips.place(
+            &mut feed.trips,
+            keeps_ids,
+            trip.trip_id.get(),
+        ))
+        .expect("trip ids to be non zero");
+        // the first feed keeps trips of services missing from the calendar, as it did before there were several feeds
+        let service_id = if let Some(service_id) = feed
+            .service_id(trip.service_id)
+            .or_else(|| keeps_ids.then_some(trip.service_id))
+        {
+            service_id
+        } else {
+            trips.skip(
+                RowError::UnknownService,
+                format_args!("trip {} has service {}", trip.trip_id, trip.service_id),
+            )?;
+            continue;
+        };
+        builder.add_trip(
+            trip_id,
+            feed.routes
+                .get(&trip.route_id.into_inner())
+                .copied()
+                .unwrap_or_else(|| trip.route_id.into_inner()),
+            service_id,
+            trip.direction_id,
+            trip.block_id.map(|block_id| {
+                interner
+                    .get_or_intern(feed.namespaced(&block_id))
+                    .into_inner()
+            }),
+        )?;
+        added_trips.insert(trip.trip_id, trip_id);
     }
+    trips.finish();
 
-    let mut count_stop_id_invalid_digit = 0;
-    let mut rdr = source.open_csv("stop_times.txt")?;
+    Ok(added_trips)
+}
+
+fn load_stop_times(
+    mut stop_times: FeedStopTimes,
+    builder: &mut Builder,
+) -> Result<(), Box<dyn Error>> {
+    let mut rdr = stop_times.source.open_csv("stop_times.txt")?;
     for result in rdr.deserialize::<gtfs::StopTime>() {
+        stop_times.ingest.row();
         match result {
             Ok(stop_time) => {
-                if added_trips.contains(&stop_time.trip_id) {
+                if let Some(&trip_id) = stop_times.trips.get(&stop_time.trip_id) {
+                    let stop_id = stop_times.stop_id(&stop_time.stop_id);
                     builder.add_trip_stop(
-                        stop_time.trip_id,
+                        trip_id,
                         stop_time.arrival_time,
                         stop_time.departure_time,
-                        interner.get_or_intern(stop_time.stop_id).into_inner(),
-                    );
+                        stop_id,
+                    )?;
                 } else {
-                    eprintln!("Stop time parsed for ignored trip {}", stop_time.trip_id)
+                    stop_times
+                        .ingest
+                        .skip(RowError::TripNotLoaded, stop_time.trip_id)?
                 }
             }
-            Err(err) => {
-                if let csv::ErrorKind::Deserialize { pos: _, err } = err.kind() {
-                    if err.field() == Some(3) {
-                        if let DeserializeErrorKind::ParseInt(err) = err.kind() {
-                            if IntErrorKind::InvalidDigit == *err.kind() {
-                                count_stop_id_invalid_digit += 1;
-                                continue;
-                            }
-                        }
-                    }
-                }
-                eprintln!("Error parsing stop time : {}", err)
+            // stop times of stops which were skipped for their id are skipped too
+            Err(err) if is_non_numeric_id(&err, 3) => {
+                stop_times.ingest.skip(RowError::NonNumericStopId, err)?
+            }
+            Err(err) => stop_times.ingest.skip(RowError::Unparsable, err)?,
+        }
+    }
+    stop_times.ingest.finish();
+
+    Ok(())
+}
+
+/// The row couldn't be parsed as the id in the field isn't a number
+pub(crate) fn is_non_numeric_id(err: &csv::Error, field: u64) -> bool {
+    if let csv::ErrorKind::Deserialize { pos: _, err } = err.kind() {
+        if err.field() == Some(field) {
+            if let DeserializeErrorKind::ParseInt(err) = err.kind() {
+                return IntErrorKind::InvalidDigit == *err.kind();
+            }
+        }
+    }
+    false
+}
+
+/// A top level stop of one of the feeds
+struct FeedStation {
+    feed: usize,
+    stop_id: StopId,
+    location: geo::Point<f64>,
+}
+
+/// Transfers both ways between the nearby stations of different feeds, so that journeys can continue across the boundary of a feed
+fn stitching_transfers(mut stations: Vec<FeedStation>) -> Vec<(StopId, StopId, chrono::Duration)> {
+    // a degree of latitude is about 111km, so stations that are close enough are close by in this order
+    let max_latitude_difference = STITCH_TRANSFER_METRES / 111_000.;
+    stations.sort_by(|a, b| a.location.x().total_cmp(&b.location.x()));
+    let mut transfers = vec![];
+    for (i, from) in stations.iter().enumerate() {
+        for to in stations[i + 1..]
+            .iter()
+            .take_while(|to| to.location.x() - from.location.x() <= max_latitude_difference)
+        {
+            if from.feed == to.feed {
+                continue;
+            }
+            let distance = distance_metres(from.location, to.location);
+            if distance <= STITCH_TRANSFER_METRES {
+                let walk = chrono::Duration::seconds(
+                    (distance / WALKING_METRES_PER_SECOND) as i64 + STITCH_TRANSFER_BUFFER_SECONDS,
+                );
+                transfers.push((from.stop_id, to.stop_id, walk));
+                transfers.push((to.stop_id, from.stop_id, walk));
             }
         }
     }
-    log_invalid_digit_count_failures("stop times", count_stop_id_invalid_digit);
+    eprintln!("Generated {} transfers between the feeds", transfers.len());
+    transfers
+}
 
-    Ok(builder.build())
+/// Haversine distance between locations stored as (lat, lon)
+fn distance_metres(a: geo::Point<f64>, b: geo::Point<f64>) -> f64 {
+    use geo::algorithm::haversine_distance::HaversineDistance;
+    geo::Point::new(a.y(), a.x()).haversine_distance(&geo::Point::new(b.y(), b.x()))
 }
 
-fn strip_stop_name(stop_name: &str) -> String {
-    let pattern = Regex::new(r"Berlin, |S |S\+U |U | Bhf| \(Berlin\)| \[.*]").unwrap();
-    pattern.replace_all(stop_name, "").into_owned()
+#[test]
+fn test_merge_feeds() {
+    let write_feed = |name: &str, station_lat: f64, trip_time: &str| {
+        let dir =
+            std::env::temp_dir().join(format!("transit-radar-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            ("calendar.txt", "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n1,1,1,1,1,1,0,0,20200101,20201231\n".to_owned()),
+            ("stops.txt", format!("stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n1,Hauptbahnhof,{lat},13.37,1,\n2,Hauptbahnhof,{lat},13.37,0,1\n3,Friedrichstr.,52.52,13.39,0,\n", lat = station_lat)),
+            ("transfers.txt", "from_stop_id,to_stop_id,transfer_type,min_transfer_time\n2,3,2,120\n".to_owned()),
+            ("routes.txt", "route_id,agency_id,route_short_name,route_long_name,route_type,route_color\n1,1,S5,,109,\n".to_owned()),
+            ("trips.txt", "route_id,service_id,trip_id,direction_id,block_id\n1,1,1,0,\n".to_owned()),
+            ("stop_times.txt", format!("trip_id,arrival_time,departure_time,stop_id,stop_sequence\n1,{t},{t},2,0\n1,10:10:00,10:10:00,3,1\n", t = trip_time)),
+        ];
+        for (file, contents) in &files {
+            std::fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    };
+    let vbb = write_feed("vbb", 52.525, "10:00:00");
+    let db = write_feed("db", 52.5251, "10:05:00");
+    let data = load_feeds(&[&vbb, &db], DayFilter::All, &RouteColors::new()).unwrap();
+    std::fs::remove_dir_all(vbb).unwrap();
+    std::fs::remove_dir_all(db).unwrap();
+
+    // the nearby Hauptbahnhof stations are merged, the Friedrichstr. stops aren't stations
+    let stations: Vec<_> = data.stops().filter(|stop| stop.is_station()).collect();
+    assert_eq!(stations.len(), 3);
+    let hauptbahnhof: Vec<_> = stations
+        .iter()
+        .filter(|stop| stop.full_stop_name() == "Hauptbahnhof")
+        .collect();
+    assert_eq!(hauptbahnhof.len(), 1);
+    assert_eq!(hauptbahnhof[0].children().count(), 2);
+    // the trips, routes and services with the same ids in both feeds are kept apart
+    assert_eq!(data.trips().count(), 2);
+    assert_eq!(data.services_of_day(Day::Monday).len(), 2);
+    assert!(data.services_of_day(Day::Sunday).is_empty());
+    // the Friedrichstr. stops of each feed are in the same place, so they get a transfer between them
+    let friedrichstr = stations
+        .iter()
+        .find(|stop| stop.full_stop_name() == "Friedrichstr.")
+        .unwrap();
+    assert_eq!(friedrichstr.transfers.len(), 1);
+    assert_eq!(
+        friedrichstr.transfers[0].min_transfer_time,
+        Some(chrono::Duration::seconds(STITCH_TRANSFER_BUFFER_SECONDS))
+    );
+}
+
+#[test]
+fn test_calendar_dates() {
+    let dir = std::env::temp_dir().join(format!(
+        "transit-radar-calendar-dates-{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let files = [
+        ("calendar.txt", "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n1,1,1,1,1,1,0,0,20240101,20241231\n2,0,0,0,0,0,1,1,20240101,20241231\n"),
+        // easter monday runs to the sunday timetable, and service 3 only runs on new year's eve
+        ("calendar_dates.txt", "service_id,date,exception_type\n1,20240401,2\n2,20240401,1\n3,20241231,1\n"),
+        ("stops.txt", "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n1,Hauptbahnhof,52.52,13.37,0,\n"),
+        ("transfers.txt", "from_stop_id,to_stop_id,transfer_type,min_transfer_time\n"),
+        ("routes.txt", "route_id,agency_id,route_short_name,route_long_name,route_type,route_color\n1,1,S5,,109,\n"),
+        ("trips.txt", "route_id,service_id,trip_id,direction_id,block_id\n1,1,1,0,\n1,2,2,0,\n1,3,3,0,\n"),
+        ("stop_times.txt", "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n"),
+    ];
+    for (file, contents) in &files {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+    let date = |date: &str| NaiveDate::parse_from_str(date, GTFS_DATE_FORMAT).unwrap();
+    let (data, _report) =
+        load_data(&dir, DayFilter::All, &RouteColors::new(), Strictness::Fail).unwrap();
+    let (tuesdays, _report) = load_data(
+        &dir,
+        DayFilter::Single(Day::Tuesday),
+        &RouteColors::new(),
+        Strictness::Fail,
+    )
+    .unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(
+        data.services_on(date("20240325")),
+        [1].iter().copied().collect()
+    );
+    assert_eq!(
+        data.services_on(date("20240401")),
+        [2].iter().copied().collect()
+    );
+    assert_eq!(
+        data.services_on(date("20241231")),
+        [1, 3].iter().copied().collect()
+    );
+    // the trips of a service added on a tuesday are loaded with the tuesdays
+    assert_eq!(tuesdays.trips().count(), 2);
+}
+
+#[test]
+fn malformed_rows_by_strictness() {
+    let dir = std::env::temp_dir().join(format!(
+        "transit-radar-malformed-rows-{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let files = [
+        ("calendar.txt", "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n1,1,1,1,1,1,0,0,20240101,20241231\n"),
+        ("calendar_dates.txt", "service_id,date,exception_type\n1,20240401,3\n"),
+        // a stop without a latitude
+        ("stops.txt", "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n1,Hauptbahnhof,52.52,13.37,0,\n2,Friedrichstr.,,13.39,0,\n"),
+        ("transfers.txt", "from_stop_id,to_stop_id,transfer_type,min_transfer_time\n"),
+        ("routes.txt", "route_id,agency_id,route_short_name,route_long_name,route_type,route_color\n1,1,S5,,109,\n"),
+        ("trips.txt", "route_id,service_id,trip_id,direction_id,block_id\n1,1,1,0,\n"),
+        ("stop_times.txt", "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n1,10:00:00,10:00:00,1,0\n1,soon,10:05:00,1,1\n2,10:00:00,10:00:00,1,0\n"),
+    ];
+    for (file, contents) in &files {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+    let loaded = |strictness| load_data(&dir, DayFilter::All, &RouteColors::new(), strictness);
+    let failed = loaded(Strictness::Fail).err().unwrap().to_string();
+    let (_data, report) = loaded(Strictness::Skip).unwrap();
+    let (_data, warned) = loaded(Strictness::Warn).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert!(failed.contains("calendar_dates.txt"), "{}", failed);
+    assert_eq!(report, warned);
+    let skipped = |file: &str| -> Vec<(RowError, usize)> {
+        let file = report
+            .files
+            .iter()
+            .find(|report| report.file == file)
+            .unwrap();
+        file.skipped
+            .iter()
+            .map(|(error, count)| (*error, *count))
+            .collect()
+    };
+    assert_eq!(
+        skipped("calendar_dates.txt"),
+        vec![(RowError::UnknownExceptionType, 1)]
+    );
+    assert_eq!(skipped("stops.txt"), vec![(RowError::Unparsable, 1)]);
+    assert_eq!(
+        skipped("stop_times.txt"),
+        vec![(RowError::Unparsable, 1), (RowError::TripNotLoaded, 1)]
+    );
+    assert_eq!(
+        report
+            .files
+            .iter()
+            .find(|report| report.file == "stop_times.txt")
+            .unwrap()
+            .rows,
+        3
+    );
+    // the quirks of feeds aren't counted as malformed
+    assert_eq!(report.malformed(), 3);
 }
 
 #[test]
-fn test_strip() {
-    for (input, output) in &[
-        ("Berlin, Birkholzer Weg/Straße 8", "Birkholzer Weg/Straße 8"),
-        ("S Mahlsdorf (Berlin) [Tram Bus Treskowstr.]", "Mahlsdorf"),
-        ("S Strausberg [Tram]", "Strausberg"),
-        (
-            "Dallgow-Döberitz, Finkenkruger Str.",
-            "Dallgow-Döberitz, Finkenkruger Str.",
-        ),
-        ("S+U Alexanderplatz (Berlin) [U2]", "Alexanderplatz"),
-        ("S+U Gesundbrunnen Bhf (Berlin)", "Gesundbrunnen"),
-        (
-            "Berlin, S+U Alexanderplatz Bhf/Memhardstr.",
-            "Alexanderplatz/Memhardstr.",
-        ),
-    ] {
-        assert_eq!(strip_stop_name(input), *output);
+fn csv_variants_load_as_commas() {
+    use crate::gtfs::{demo, lazy};
+
+    let load = |dir: &Path| {
+        load_feeds_reporting(
+            &[dir],
+            DayFilter::All,
+            &RouteColors::new(),
+            &ShortNames::default(),
+            Strictness::Fail,
+        )
+        .unwrap()
+    };
+    // the stop times are read again around a station
+    let load_around = |dir: &Path| {
+        let (lazy, stop_ids, _) = lazy::LazyTimetable::load(
+            &[dir],
+            DayFilter::All,
+            &RouteColors::new(),
+            &ShortNames::default(),
+            Strictness::Fail,
+            1,
+            lazy::StopTimesAccess::Read,
+        )
+        .unwrap();
+        let alexanderplatz = stop_ids.get("600").unwrap();
+        lazy.around(alexanderplatz, chrono::Duration::minutes(30))
+            .unwrap()
+            .footprint()
+    };
+    let dir = std::env::temp_dir().join(format!("transit-radar-csv-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    demo::write_demo_feed(&dir).unwrap();
+    let (data, _, commas) = load(&dir);
+    let around = load_around(&dir);
+
+    // stops with a BOM, CRLF line endings and semicolons, and stop times with tabs and spaces after the header's
+    let stops = std::fs::read_to_string(dir.join("stops.txt")).unwrap();
+    let stops = format!("\u{feff}{}", stops.replace(',', ";").replace('\n', "\r\n"));
+    std::fs::write(dir.join("stops.txt"), stops).unwrap();
+    let stop_times = std::fs::read_to_string(dir.join("stop_times.txt")).unwrap();
+    let (header, rows) = stop_times.split_once('\n').unwrap();
+    let stop_times = format!(
+        "{}\n{}",
+        header.replace(',', "\t "),
+        rows.replace(',', "\t")
+    );
+    std::fs::write(dir.join("stop_times.txt"), stop_times).unwrap();
+    let (variants, _, report) = load(&dir);
+    let variants_around = load_around(&dir);
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(report, commas);
+    assert_eq!(variants.footprint(), data.footprint());
+    assert_eq!(variants_around, around);
+
+    assert_eq!(delimiter_of(b"stop_id,stop_name\n"), b',');
+    assert_eq!(delimiter_of(b"\"stop;id\",stop_name\n"), b',');
+    assert_eq!(delimiter_of(b"stop_id|stop_name|stop_lat\n"), b'|');
+    assert_eq!(delimiter_of(b"stop_id\n"), b',');
+}
+
+/// The name to show for a route, the short name if there is one or else the long name, the builder falls back to the id if there is neither
+fn route_name(route: &gtfs::Route) -> String {
+    Some(route.route_short_name.trim())
+        .filter(|name| !name.is_empty())
+        .or_else(|| {
+            route
+                .route_long_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+        })
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// The colour for a route, from the colour files, then the feed and then the default for the route type
+fn route_color(route: &gtfs::Route, route_name: &str, route_colors: &RouteColors) -> String {
+    if let Some(route_color) = route_colors.get(route_name) {
+        return route_color.color.clone();
+    }
+    match route.route_color.as_deref().map(str::trim) {
+        Some(color) if color.len() == 6 && color.chars().all(|c| c.is_ascii_hexdigit()) => {
+            format!("#{}", color)
+        }
+        _ => route_colors
+            .route_color(route_name, route.route_type)
+            .to_owned(),
     }
 }
 
+#[test]
+fn test_route_name_and_color_fallbacks() {
+    let routes = "route_id,agency_id,route_short_name,route_long_name,route_type,route_color\n\
+                  1,1,S1,,109,\n\
+                  2,1,,Airport Express,2,00ff00\n\
+                  3,1,,,700,not a colour\n";
+    let mut route_colors = RouteColors::new();
+    route_colors.set_route_type_color(RouteType::BusService, "#123456");
+    let routes: Vec<gtfs::Route> = csv::Reader::from_reader(routes.as_bytes())
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let described: Vec<_> = routes
+        .iter()
+        .map(|route| {
+            let name = route_name(route);
+            let color = route_color(route, &name, &route_colors);
+            (name, color)
+        })
+        .collect();
+    assert_eq!(
+        described,
+        vec![
+            ("S1".to_owned(), "lightgray".to_owned()),
+            ("Airport Express".to_owned(), "#00ff00".to_owned()),
+            ("".to_owned(), "#123456".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn stops_by_ifopt_id() {
+    let mut interner = lasso::Rodeo::default();
+    let station = interner.get_or_intern("de:11000:900100003").into_inner();
+    let platform = interner.get_or_intern("de:11000:900100003::2").into_inner();
+    let stop_ids = StopIds::new(interner);
+    assert_eq!(stop_ids.gtfs_id(station), Some("de:11000:900100003"));
+    assert_eq!(stop_ids.get_ifopt("de:11000:900100003"), Some(station));
+    assert_eq!(stop_ids.get_ifopt("de:11000:900100003::2"), Some(platform));
+    assert_eq!(stop_ids.get_ifopt("de:11000:900100003::7"), Some(station));
+    assert_eq!(stop_ids.get_ifopt("de:11000:900100004"), None);
+    assert_eq!(stop_ids.get_ifopt("900100003"), None);
+    assert_eq!(stop_ids.get_ifopt("de::900100003"), None);
+}
+
 /// Get a station by exact name
 /// # Issues
 /// * This could be handled by Suggester
@@ -275,7 +998,7 @@ pub fn get_station_by_name<'r>(
 ) -> Result<&'r Stop, SearchError> {
     let mut candidates = vec![];
     for stop in data.stops() {
-        if stop.is_station() && stop.full_stop_name == exact_name {
+        if stop.is_station() && stop.full_stop_name() == exact_name {
             candidates.push(stop);
         }
     }
@@ -289,13 +1012,19 @@ pub fn get_station_by_name<'r>(
     }
 }
 
-/// Build a word search suggester over station names
-pub fn build_station_word_index(data: &GTFSData) -> Suggester<(StopId, usize)> {
+/// Build a word search suggester over station names, ranked by their importance
+pub fn build_station_word_index(
+    data: &GTFSData,
+    importance: &Importance,
+) -> Suggester<(StopId, usize)> {
     let mut suggester = Suggester::new();
 
     for stop in data.stops() {
         if stop.is_station() {
-            suggester.insert(&stop.full_stop_name, (stop.stop_id, stop.importance(data)));
+            suggester.insert(
+                stop.full_stop_name(),
+                (stop.stop_id, importance.score(stop, data)),
+            );
         }
     }
 
@@ -306,6 +1035,24 @@ pub fn build_station_word_index(data: &GTFSData) -> Suggester<(StopId, usize)> {
     suggester
 }
 
+/// The key ranking the most important stations of the station word index first
+pub fn by_importance(&(stop_id, importance): &(StopId, usize)) -> (Reverse<usize>, StopId) {
+    (Reverse(importance), stop_id)
+}
+
+/// The station a name is most likely to mean, the one of exactly that name or otherwise the most important match
+pub fn best_match<'d>(
+    name: &str,
+    data: &'d GTFSData,
+    station_search: &Suggester<(StopId, usize)>,
+) -> Option<&'d Stop> {
+    station_search
+        .rank(name, by_importance)
+        .into_iter()
+        .filter_map(|(stop_id, _importance)| data.get_stop(stop_id))
+        .min_by_key(|stop| !stop.full_stop_name().eq_ignore_ascii_case(name))
+}
+
 #[derive(Debug)]
 pub enum SearchError {
     NotFound(String),
@@ -325,7 +1072,7 @@ impl fmt::Display for SearchError {
                 "Found several stations or search term ({})",
                 stops
                     .iter()
-                    .map(|stop| stop.full_stop_name.clone())
+                    .map(|stop| stop.full_stop_name().to_owned())
                     .collect::<Vec<_>>()
                     .deref()
                     .join(", ")
@@ -334,38 +1081,6 @@ impl fmt::Display for SearchError {
     }
 }
 
-pub fn load_colors(path: &Path) -> Result<HashMap<String, String>, csv::Error> {
-    let mut colors = HashMap::new();
-    let mut reader = csv::ReaderBuilder::new()
-        .delimiter(b';')
-        .flexible(true)
-        .from_path(path)?;
-    let header = reader.headers().expect("headers expected in colors csv");
-    eprintln!("{:?}", header);
-    let route_name_idx = header
-        .iter()
-        .enumerate()
-        .find(|(_, header)| *header == "Name")
-        .expect("Header {Name}")
-        .0;
-    let colour_idx = header
-        .iter()
-        .enumerate()
-        .find(|(_, header)| *header == "Hex")
-        .expect("Header {Hex}")
-        .0;
-    for result in reader.into_records() {
-        let br = result?;
-        if let (Some(route_name), Some(colour)) = (
-            br.get(route_name_idx).filter(|s| !s.is_empty()),
-            br.get(colour_idx).filter(|s| !s.is_empty()),
-        ) {
-            colors.insert(route_name.to_owned(), colour.to_owned());
-        }
-    }
-    Ok(colors)
-}
-
 pub struct GTFSSource {
     dir_path: PathBuf,
 }
@@ -377,11 +1092,15 @@ impl GTFSSource {
         }
     }
 
+    /// Opens a file of the feed, delimited by whichever of `DELIMITERS` it's header is
     pub fn open_csv(&self, filename: &str) -> Result<csv::Reader<std::fs::File>, csv::Error> {
         let path = self.dir_path.join(filename);
         eprintln!("Opening {}", path.to_str().expect("path invalid"));
-        let reader = csv::Reader::from_path(path)?;
-        Ok(reader)
+        let delimiter = sniff_delimiter(&path)?;
+        if delimiter != b',' {
+            eprintln!("{} is delimited by {:?}", filename, char::from(delimiter));
+        }
+        csv_reader_builder(delimiter).from_path(path)
     }
 
     pub fn get_calendar(
@@ -391,6 +1110,16 @@ impl GTFSSource {
         Ok(rdr.into_deserialize())
     }
 
+    /// The exceptions to the calendar, which a feed doesn't need to have
+    pub fn get_calendar_dates(&self) -> Result<Vec<gtfs::CalendarDate>, csv::Error> {
+        if !self.dir_path.join("calendar_dates.txt").exists() {
+            return Ok(vec![]);
+        }
+        self.open_csv("calendar_dates.txt")?
+            .into_deserialize()
+            .collect()
+    }
+
     pub fn get_trips(
         &self,
         route_id: Option<RouteId>,
@@ -422,6 +1151,16 @@ pub enum DayFilter {
     Single(Day),
 }
 
+impl DayFilter {
+    /// Whether data loaded with this filter can answer searches on a day
+    pub fn includes(self, day: Day) -> bool {
+        match self {
+            DayFilter::All => true,
+            DayFilter::Single(filter_day) => filter_day == day,
+        }
+    }
+}
+
 impl std::fmt::Display for DayFilter {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -431,10 +1170,38 @@ impl std::fmt::Display for DayFilter {
     }
 }
 
-fn log_invalid_digit_count_failures(entity: &str, failure_count: u32) {
-    if failure_count != 0 {
-        eprintln!(
-            "{failure_count} {entity} failed to parse due to an invalid digit in the stop id, this happens",
-        );
+/// Parses the format written by `Display`, `all` or a short day name such as `sat`
+impl std::str::FromStr for DayFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "all" => DayFilter::All,
+            "mon" => DayFilter::Single(Day::Monday),
+            "tue" => DayFilter::Single(Day::Tuesday),
+            "wed" => DayFilter::Single(Day::Wednesday),
+            "thu" => DayFilter::Single(Day::Thursday),
+            "fri" => DayFilter::Single(Day::Friday),
+            "sat" => DayFilter::Single(Day::Saturday),
+            "sun" => DayFilter::Single(Day::Sunday),
+            other => {
+                return Err(format!(
+                    "Unknown day filter \"{}\", expected all, mon, tue, wed, thu, fri, sat or sun",
+                    other
+                ))
+            }
+        })
+    }
+}
+
+#[test]
+fn test_day_filter_round_trip() {
+    for filter in &["all", "mon", "tue", "wed", "thu", "fri", "sat", "sun"] {
+        let parsed: DayFilter = filter.parse().unwrap();
+        assert_eq!(parsed.to_string(), *filter);
     }
+    assert!("Sat".parse::<DayFilter>().unwrap().includes(Day::Saturday));
+    assert!(!"sat".parse::<DayFilter>().unwrap().includes(Day::Sunday));
+    assert!("all".parse::<DayFilter>().unwrap().includes(Day::Sunday));
+    assert!("saturday".parse::<DayFilter>().is_err());
 }
@@ -0,0 +1,239 @@
+//! A small made up feed, loosely on Berlin's map, to run the radar without downloading a real feed.
+//!
+//! Four lines cross at a few stations and run all day, every day, so a search from any of the stations at any time shows something.
+
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use radar_search::search_data::GTFSData;
+
+use crate::gtfs::{colors::RouteColors, db};
+
+/// Stations with their location as (lat, lon)
+const STATIONS: &[(&str, f64, f64)] = &[
+    ("Westkreuz", 52.5009, 13.2834),
+    ("Charlottenburg", 52.5051, 13.3046),
+    ("Zoologischer Garten", 52.5067, 13.3326),
+    ("Hauptbahnhof", 52.5251, 13.3694),
+    ("Friedrichstr.", 52.5203, 13.3880),
+    ("Alexanderplatz", 52.5219, 13.4113),
+    ("Ostkreuz", 52.5029, 13.4690),
+    ("Wittenbergplatz", 52.5019, 13.3431),
+    ("Potsdamer Platz", 52.5096, 13.3759),
+    ("Stadtmitte", 52.5116, 13.3900),
+    ("Senefelderplatz", 52.5326, 13.4128),
+    ("Nordbahnhof", 52.5318, 13.3886),
+    ("Eberswalder Str.", 52.5413, 13.4123),
+    ("Großer Stern", 52.5145, 13.3501),
+];
+
+struct Line {
+    name: &'static str,
+    route_type: u16,
+    color: &'static str,
+    /// Indices in `STATIONS` in the order they are called at
+    stations: &'static [usize],
+    headway_minutes: u32,
+    minutes_between_stations: u32,
+}
+
+const LINES: &[Line] = &[
+    Line {
+        name: "S1",
+        route_type: 109,
+        color: "eb588f",
+        stations: &[0, 1, 2, 3, 4, 5, 6],
+        headway_minutes: 10,
+        minutes_between_stations: 3,
+    },
+    Line {
+        name: "U2",
+        route_type: 400,
+        color: "da421e",
+        stations: &[2, 7, 8, 9, 5, 10],
+        headway_minutes: 5,
+        minutes_between_stations: 2,
+    },
+    Line {
+        name: "M10",
+        route_type: 900,
+        color: "d82020",
+        stations: &[3, 11, 12],
+        headway_minutes: 10,
+        minutes_between_stations: 4,
+    },
+    Line {
+        name: "100",
+        route_type: 700,
+        color: "a01c7d",
+        stations: &[2, 13, 3],
+        headway_minutes: 20,
+        minutes_between_stations: 5,
+    },
+];
+
+/// Service runs from 4:00 until the last trips leave just before 1:00 the next morning
+const FIRST_DEPARTURE_MINUTES: u32 = 4 * 60;
+const LAST_DEPARTURE_MINUTES: u32 = 25 * 60;
+const TRANSFER_SECONDS: u32 = 180;
+
+fn station_id(station: usize) -> usize {
+    (station + 1) * 100
+}
+
+fn platform_id(station: usize, line: usize) -> usize {
+    station_id(station) + line + 1
+}
+
+fn gtfs_time(minutes: u32) -> String {
+    format!("{:02}:{:02}:00", minutes / 60, minutes % 60)
+}
+
+/// Writes the feed's GTFS files into `dir`, which must exist
+pub fn write_demo_feed(dir: &Path) -> io::Result<()> {
+    let mut stops =
+        String::from("stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n");
+    let mut transfers = String::from("from_stop_id,to_stop_id,transfer_type,min_transfer_time\n");
+    for (station, &(name, lat, lon)) in STATIONS.iter().enumerate() {
+        writeln!(stops, "{},{},{},{},1,", station_id(station), name, lat, lon).unwrap();
+        writeln!(
+            transfers,
+            "{id},{id},2,{}",
+            TRANSFER_SECONDS,
+            id = station_id(station)
+        )
+        .unwrap();
+        for (line_index, line) in LINES.iter().enumerate() {
+            if line.stations.contains(&station) {
+                writeln!(
+                    stops,
+                    "{},{},{},{},0,{}",
+                    platform_id(station, line_index),
+                    name,
+                    lat,
+                    lon,
+                    station_id(station)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    let mut routes = String::from(
+        "route_id,agency_id,route_short_name,route_long_name,route_type,route_color\n",
+    );
+    let mut trips = String::from("route_id,service_id,trip_id,direction_id,block_id\n");
+    let mut stop_times =
+        String::from("trip_id,arrival_time,departure_time,stop_id,stop_sequence\n");
+    let mut trip_id = 0;
+    for (line_index, line) in LINES.iter().enumerate() {
+        let route_id = line_index + 1;
+        writeln!(
+            routes,
+            "{},1,{},,{},{}",
+            route_id, line.name, line.route_type, line.color
+        )
+        .unwrap();
+        for direction in 0..2 {
+            let mut calls: Vec<usize> = line.stations.to_vec();
+            if direction == 1 {
+                calls.reverse();
+            }
+            for departure in (FIRST_DEPARTURE_MINUTES..LAST_DEPARTURE_MINUTES)
+                .step_by(line.headway_minutes as usize)
+            {
+                trip_id += 1;
+                writeln!(trips, "{},1,{},{},", route_id, trip_id, direction).unwrap();
+                for (sequence, &station) in calls.iter().enumerate() {
+                    let time =
+                        gtfs_time(departure + sequence as u32 * line.minutes_between_stations);
+                    writeln!(
+                        stop_times,
+                        "{},{},{},{},{}",
+                        trip_id,
+                        time,
+                        time,
+                        platform_id(station, line_index),
+                        sequence
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    let calendar = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n1,1,1,1,1,1,1,1,20200101,20991231\n";
+    let feed_info = "feed_publisher_name,feed_publisher_url,feed_lang,feed_version\ntransit radar,https://radar.njk.onl,de,demo\n";
+    for (file, contents) in [
+        ("feed_info.txt", feed_info),
+        ("calendar.txt", calendar),
+        ("stops.txt", &stops),
+        ("transfers.txt", &transfers),
+        ("routes.txt", &routes),
+        ("trips.txt", &trips),
+        ("stop_times.txt", &stop_times),
+    ] {
+        std::fs::write(dir.join(file), contents)?;
+    }
+    Ok(())
+}
+
+/// Loads the feed for all days with it's ids, through a temporary directory of it's own, for the tests of the radar and the server
+pub fn load_demo_feed() -> (GTFSData, db::StopIds) {
+    static LOADS: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "transit-radar-demo-feed-{}-{}",
+        std::process::id(),
+        LOADS.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    write_demo_feed(&dir).unwrap();
+    let feed = db::load_feeds_with_ids(&[&dir], db::DayFilter::All, &RouteColors::new()).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    feed
+}
+
+/// The feed loaded once for all the tests which only read it
+pub fn demo_data() -> &'static GTFSData {
+    static DATA: OnceLock<GTFSData> = OnceLock::new();
+    DATA.get_or_init(|| load_demo_feed().0)
+}
+
+#[test]
+fn demo_feed_loads() {
+    use crate::gtfs::ingest;
+    use radar_search::search_data::Day;
+
+    let dir = std::env::temp_dir().join(format!("transit-radar-demo-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    write_demo_feed(&dir).unwrap();
+    let (data, _report) = db::load_data(
+        &dir,
+        db::DayFilter::All,
+        &RouteColors::new(),
+        ingest::Strictness::Fail,
+    )
+    .unwrap();
+    assert_eq!(db::feed_version(&dir).as_deref(), Some("demo"));
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(
+        data.stops().filter(|stop| stop.is_station()).count(),
+        STATIONS.len()
+    );
+    assert_eq!(data.services_of_day(Day::Sunday).len(), 1);
+    let alexanderplatz = data
+        .stops()
+        .find(|stop| stop.is_station() && stop.full_stop_name() == "Alexanderplatz")
+        .unwrap();
+    assert_eq!(alexanderplatz.children().count(), 2);
+    // every line runs in both directions
+    let lines: std::collections::HashSet<_> = data
+        .trips()
+        .map(|trip| (trip.route.route_short_name.as_str(), trip.direction_id))
+        .collect();
+    assert_eq!(lines.len(), LINES.len() * 2);
+}
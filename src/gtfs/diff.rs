@@ -0,0 +1,254 @@
+//! What changed between two versions of a timetable, for announcing the impact of a timetable change and spotting regressions in loading.
+//!
+//! Stations are matched by their GTFS id, as the ids they are loaded with depend on the order of the feed, and routes by their route id.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use radar_search::search_data::{GTFSData, Route, RouteId};
+
+use crate::gtfs::db::StopIds;
+
+/// A station which is only in one of the versions
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DiffStation {
+    pub gtfs_id: String,
+    pub name: String,
+}
+
+/// The parts of a route which are compared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRoute {
+    pub name: String,
+    pub route_type: String,
+    pub color: String,
+}
+
+impl DiffRoute {
+    fn of(route: &Route) -> Self {
+        DiffRoute {
+            name: route.route_short_name.clone(),
+            route_type: format!("{:?}", route.route_type),
+            color: route.route_color.clone(),
+        }
+    }
+}
+
+impl fmt::Display for DiffRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}, {})", self.name, self.route_type, self.color)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DatasetDiff {
+    pub added_stations: Vec<DiffStation>,
+    pub removed_stations: Vec<DiffStation>,
+    pub added_routes: Vec<(RouteId, DiffRoute)>,
+    pub removed_routes: Vec<(RouteId, DiffRoute)>,
+    /// Routes in both versions with a different name, type or colour, as they were and as they are
+    pub changed_routes: Vec<(RouteId, DiffRoute, DiffRoute)>,
+    /// The number of trips of each route in either version which has a different number in the other
+    pub trip_counts: Vec<(RouteId, String, usize, usize)>,
+}
+
+impl DatasetDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_stations.is_empty()
+            && self.removed_stations.is_empty()
+            && self.added_routes.is_empty()
+            && self.removed_routes.is_empty()
+            && self.changed_routes.is_empty()
+            && self.trip_counts.is_empty()
+    }
+}
+
+/// Compares an old version of a timetable with a new one
+pub fn diff(old: (&GTFSData, &StopIds), new: (&GTFSData, &StopIds)) -> DatasetDiff {
+    let (old_stations, new_stations) = (stations(old.0, old.1), stations(new.0, new.1));
+    let (old_routes, new_routes) = (routes(old.0), routes(new.0));
+
+    // a station which is renamed is reported as removed and added again under it's new name
+    let mut diff = DatasetDiff {
+        added_stations: new_stations.difference(&old_stations).cloned().collect(),
+        removed_stations: old_stations.difference(&new_stations).cloned().collect(),
+        ..DatasetDiff::default()
+    };
+    for (route_id, (new_route, new_trips)) in &new_routes {
+        match old_routes.get(route_id) {
+            None => diff.added_routes.push((*route_id, new_route.clone())),
+            Some((old_route, _)) if old_route != new_route => {
+                diff.changed_routes
+                    .push((*route_id, old_route.clone(), new_route.clone()))
+            }
+            Some(_) => {}
+        }
+        let old_trips = old_routes.get(route_id).map_or(0, |(_, trips)| *trips);
+        if old_trips != *new_trips {
+            diff.trip_counts
+                .push((*route_id, new_route.name.clone(), old_trips, *new_trips));
+        }
+    }
+    for (route_id, (old_route, old_trips)) in &old_routes {
+        if !new_routes.contains_key(route_id) {
+            diff.removed_routes.push((*route_id, old_route.clone()));
+            diff.trip_counts
+                .push((*route_id, old_route.name.clone(), *old_trips, 0));
+        }
+    }
+    diff.trip_counts.sort_by_key(|&(route_id, ..)| route_id);
+    diff
+}
+
+fn stations(data: &GTFSData, stop_ids: &StopIds) -> BTreeSet<DiffStation> {
+    data.stops()
+        .filter(|stop| stop.is_station())
+        .map(|stop| DiffStation {
+            gtfs_id: stop_ids
+                .gtfs_id(stop.stop_id)
+                .map(str::to_owned)
+                .unwrap_or_else(|| stop.stop_id.to_string()),
+            name: stop.full_stop_name().to_owned(),
+        })
+        .collect()
+}
+
+/// The routes with trips, and the number of trips of each
+fn routes(data: &GTFSData) -> BTreeMap<RouteId, (DiffRoute, usize)> {
+    let mut routes = BTreeMap::new();
+    for trip in data.trips() {
+        routes
+            .entry(trip.route.route_id)
+            .or_insert_with(|| (DiffRoute::of(&trip.route), 0))
+            .1 += 1;
+    }
+    routes
+}
+
+impl fmt::Display for DatasetDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        writeln!(
+            f,
+            "Stations: {} added, {} removed",
+            self.added_stations.len(),
+            self.removed_stations.len()
+        )?;
+        for station in &self.added_stations {
+            writeln!(f, "+ {} {}", station.gtfs_id, station.name)?;
+        }
+        for station in &self.removed_stations {
+            writeln!(f, "- {} {}", station.gtfs_id, station.name)?;
+        }
+        writeln!(
+            f,
+            "Routes: {} added, {} removed, {} changed",
+            self.added_routes.len(),
+            self.removed_routes.len(),
+            self.changed_routes.len()
+        )?;
+        for (route_id, route) in &self.added_routes {
+            writeln!(f, "+ {} {}", route_id, route)?;
+        }
+        for (route_id, route) in &self.removed_routes {
+            writeln!(f, "- {} {}", route_id, route)?;
+        }
+        for (route_id, old, new) in &self.changed_routes {
+            writeln!(f, "~ {} {} -> {}", route_id, old, new)?;
+        }
+        writeln!(f, "Trips per route: {} changed", self.trip_counts.len())?;
+        for (route_id, name, old, new) in &self.trip_counts {
+            writeln!(
+                f,
+                "  {} {}: {} -> {} ({:+})",
+                route_id,
+                name,
+                old,
+                new,
+                *new as i64 - *old as i64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_diff() {
+    use radar_search::search_data::{RouteType, TripId};
+    use std::collections::HashMap;
+
+    let version = |stations: &[(&str, &str)], routes: &[(RouteId, &str, &str, u32)]| {
+        let mut interner = lasso::Rodeo::default();
+        let mut builder = GTFSData::builder(HashMap::new(), "20240101".to_owned());
+        for (gtfs_id, name) in stations {
+            let stop_id = interner.get_or_intern(gtfs_id).into_inner();
+            builder.add_station(
+                stop_id,
+                name.to_string(),
+                name.to_string(),
+                geo::Point::new(52.5, 13.4),
+            );
+        }
+        let mut next_trip_id = 1;
+        for &(route_id, name, color, trips) in routes {
+            builder.add_route(
+                route_id,
+                name.to_owned(),
+                RouteType::SuburbanRailway,
+                color.to_owned(),
+            );
+            for _ in 0..trips {
+                builder
+                    .add_trip(TripId::new(next_trip_id).unwrap(), route_id, 1, None, None)
+                    .unwrap();
+                next_trip_id += 1;
+            }
+        }
+        (builder.build().unwrap(), StopIds::new(interner))
+    };
+    let old = version(
+        &[("1", "Hauptbahnhof"), ("2", "Ostkreuz")],
+        &[
+            (1, "S5", "#f00", 10),
+            (2, "S7", "#0f0", 4),
+            (3, "S9", "#00f", 2),
+        ],
+    );
+    let new = version(
+        &[("2", "Ostkreuz"), ("3", "Warschauer Str.")],
+        &[
+            (1, "S5", "#f00", 12),
+            (2, "S7", "#0a0", 4),
+            (4, "S3", "#00f", 6),
+        ],
+    );
+    let diff = diff((&old.0, &old.1), (&new.0, &new.1));
+
+    assert_eq!(
+        diff.added_stations,
+        vec![DiffStation {
+            gtfs_id: "3".to_owned(),
+            name: "Warschauer Str.".to_owned()
+        }]
+    );
+    assert_eq!(diff.removed_stations.len(), 1);
+    assert_eq!(diff.added_routes.len(), 1);
+    assert_eq!(diff.removed_routes[0].0, 3);
+    assert_eq!(diff.changed_routes.len(), 1);
+    assert_eq!(diff.changed_routes[0].2.color, "#0a0");
+    assert_eq!(
+        diff.trip_counts,
+        vec![
+            (1, "S5".to_owned(), 10, 12),
+            (3, "S9".to_owned(), 2, 0),
+            (4, "S3".to_owned(), 0, 6),
+        ]
+    );
+    assert!(
+        diff.to_string().contains("  1 S5: 10 -> 12 (+2)"),
+        "{}",
+        diff
+    );
+}
@@ -0,0 +1,180 @@
+//! How important stations are, which ranks the station search and the lists of the busiest stations, and picks the stations labelled larger on the radar.
+//!
+//! A stop scores the weighted number of transfers from it and departures from it, plus it's weighted ridership, and a station also scores what it's stops do. The weights are given as eg. `transfers=2,departures=1,ridership=0.1`, each is 1 unless it's given, so without a ridership file a station's importance is it's number of transfers and departures.
+//! The ridership file is a CSV with the columns `stop_id` and `ridership`, such as the boardings on an average day, of stations or their stops by their GTFS stop id.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use radar_search::search_data::*;
+use serde::Deserialize;
+
+use super::db::StopIds;
+
+/// What each transfer, departure and rider adds to the score of a stop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    pub transfers: f64,
+    pub departures: f64,
+    pub ridership: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            transfers: 1.,
+            departures: 1.,
+            ridership: 1.,
+        }
+    }
+}
+
+impl FromStr for Weights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Weights::default();
+        for weight in s
+            .split(',')
+            .map(str::trim)
+            .filter(|weight| !weight.is_empty())
+        {
+            let (name, value) = if let Some(name_value) = weight.split_once('=') {
+                name_value
+            } else {
+                return Err(format!("expected <name>=<weight> rather than {}", weight));
+            };
+            let value: f64 = value
+                .trim()
+                .parse()
+                .map_err(|err| format!("weight of {} : {}", name, err))?;
+            match name.trim() {
+                "transfers" => weights.transfers = value,
+                "departures" => weights.departures = value,
+                "ridership" => weights.ridership = value,
+                name => {
+                    return Err(format!(
+                        "unknown weight {}, expected transfers, departures or ridership",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(weights)
+    }
+}
+
+#[derive(Deserialize)]
+struct RidershipRow {
+    stop_id: String,
+    ridership: f64,
+}
+
+/// Scores the importance of stations by the weights and the ridership loaded
+#[derive(Debug, Clone, Default)]
+pub struct Importance {
+    weights: Weights,
+    ridership: HashMap<StopId, f64>,
+}
+
+impl Importance {
+    pub fn new(weights: Weights) -> Self {
+        Importance {
+            weights,
+            ridership: HashMap::new(),
+        }
+    }
+
+    /// Adds the ridership of the stops in the file, returning the stop ids which aren't in the timetable
+    pub fn load_ridership(
+        &mut self,
+        path: &Path,
+        stop_ids: &StopIds,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut unknown = vec![];
+        for row in csv::Reader::from_path(path)?.into_deserialize() {
+            let row: RidershipRow = row?;
+            if let Some(stop_id) = stop_ids.get(&row.stop_id) {
+                *self.ridership.entry(stop_id).or_default() += row.ridership;
+            } else {
+                unknown.push(row.stop_id);
+            }
+        }
+        Ok(unknown)
+    }
+
+    /// The score of a stop, and of a station's stops as well
+    pub fn score(&self, stop: &Stop, data: &GTFSData) -> usize {
+        let stops_score: f64 = stop
+            .children()
+            .filter_map(|&stop_id| data.get_stop(stop_id))
+            .map(|stop| self.own_score(stop))
+            .sum();
+        (self.own_score(stop) + stops_score).round().max(0.) as usize
+    }
+
+    fn own_score(&self, stop: &Stop) -> f64 {
+        self.weights.transfers * stop.transfers.len() as f64
+            + self.weights.departures * stop.departure_count() as f64
+            + self.weights.ridership * self.ridership.get(&stop.stop_id).copied().unwrap_or(0.)
+    }
+}
+
+#[test]
+fn weights_are_parsed() {
+    assert_eq!("".parse(), Ok(Weights::default()));
+    assert_eq!(
+        "departures=0.5, ridership=0.01".parse(),
+        Ok(Weights {
+            transfers: 1.,
+            departures: 0.5,
+            ridership: 0.01,
+        })
+    );
+    assert!("departures".parse::<Weights>().is_err());
+    assert!("walks=2".parse::<Weights>().is_err());
+    assert!("transfers=many".parse::<Weights>().is_err());
+}
+
+#[test]
+fn ridership_boosts_stations() {
+    use crate::gtfs::{colors::RouteColors, db, demo};
+
+    let dir = std::env::temp_dir().join(format!("transit-radar-importance-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    demo::write_demo_feed(&dir).unwrap();
+    let (data, stop_ids) =
+        db::load_feeds_with_ids(&[&dir], db::DayFilter::All, &RouteColors::new()).unwrap();
+    let station = |name: &str| {
+        data.stops()
+            .find(|stop| stop.is_station() && stop.full_stop_name() == name)
+            .unwrap()
+    };
+    let (alex, zoo) = (station("Alexanderplatz"), station("Zoologischer Garten"));
+    let by_default = Importance::default();
+    let alex_score = by_default.score(alex, &data);
+    let departures: usize = alex
+        .children()
+        .map(|&stop_id| data.get_stop(stop_id).unwrap().departure_count())
+        .sum();
+    assert!(departures > 0);
+    assert!(alex_score >= departures);
+
+    let ridership_path = dir.join("ridership.csv");
+    let zoo_gtfs_id = stop_ids.gtfs_id(zoo.stop_id).unwrap().to_owned();
+    std::fs::write(
+        &ridership_path,
+        format!("stop_id,ridership\n{},100000\nnowhere,5\n", zoo_gtfs_id),
+    )
+    .unwrap();
+    let mut importance = Importance::new("departures=0,transfers=0".parse().unwrap());
+    let unknown = importance
+        .load_ridership(&ridership_path, &stop_ids)
+        .unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    assert_eq!(unknown, vec!["nowhere".to_owned()]);
+    assert_eq!(importance.score(zoo, &data), 100_000);
+    assert_eq!(importance.score(alex, &data), 0);
+}
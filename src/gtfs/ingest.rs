@@ -0,0 +1,252 @@
+//! What happens to the rows of a feed which can't be loaded, and a report of them, so that problems with a feed are seen rather than lost in the log.
+//!
+//! Rows of stops, transfers, calendar dates, trips and stop times are skipped or fail the load by the strictness, while the rows skipped because of known quirks of feeds, such as VBB's stop ids which aren't numbers, are skipped in any case.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+/// What's done with a row which can't be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// It's skipped and counted in the report
+    Skip,
+    /// It's skipped, counted in the report and logged
+    #[default]
+    Warn,
+    /// Loading fails
+    Fail,
+}
+
+impl FromStr for Strictness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "skip" => Ok(Strictness::Skip),
+            "warn" => Ok(Strictness::Warn),
+            "fail" => Ok(Strictness::Fail),
+            _ => Err(format!(
+                "unknown strictness {}, expected skip, warn or fail",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Strictness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Strictness::Skip => "skip",
+            Strictness::Warn => "warn",
+            Strictness::Fail => "fail",
+        })
+    }
+}
+
+/// Why a row was skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowError {
+    /// It doesn't have the columns of it's file or a value isn't of it's column's type
+    Unparsable,
+    /// A calendar date which neither adds nor removes it's service
+    UnknownExceptionType,
+    /// A trip of a service which isn't in the calendar, which is only loaded from the first feed
+    UnknownService,
+    /// A stop id which isn't a number, as a few of VBB's are
+    NonNumericStopId,
+    /// A stop time of a trip which wasn't loaded, such as one not running on the days loaded
+    TripNotLoaded,
+}
+
+impl RowError {
+    /// Rows skipped for known quirks of feeds, which are skipped whatever the strictness and only logged as a count
+    fn is_quirk(self) -> bool {
+        matches!(self, RowError::NonNumericStopId | RowError::TripNotLoaded)
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            RowError::Unparsable => "couldn't be parsed",
+            RowError::UnknownExceptionType => "had an unknown exception type",
+            RowError::UnknownService => "had a service which isn't in the calendar",
+            RowError::NonNumericStopId => "had a stop id which isn't a number",
+            RowError::TripNotLoaded => "were of trips which weren't loaded",
+        }
+    }
+}
+
+/// The rows read from each file of the feeds and the ones skipped
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct IngestReport {
+    pub files: Vec<FileReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileReport {
+    /// The index of the feed in the order they were loaded
+    pub feed: usize,
+    pub file: &'static str,
+    /// Including the skipped ones
+    pub rows: usize,
+    pub skipped: BTreeMap<RowError, usize>,
+}
+
+impl IngestReport {
+    /// The number of rows skipped other than for known quirks
+    pub fn malformed(&self) -> usize {
+        self.files
+            .iter()
+            .flat_map(|file| &file.skipped)
+            .filter(|(error, _)| !error.is_quirk())
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// The file's report, which is started the first time
+    fn file(&mut self, feed: usize, file: &'static str) -> &mut FileReport {
+        let index = if let Some(index) = self
+            .files
+            .iter()
+            .position(|report| report.feed == feed && report.file == file)
+        {
+            index
+        } else {
+            self.files.push(FileReport {
+                feed,
+                file,
+                rows: 0,
+                skipped: BTreeMap::new(),
+            });
+            self.files.len() - 1
+        };
+        &mut self.files[index]
+    }
+}
+
+/// The report being made while the feeds are loaded
+pub(crate) struct Ingest {
+    strictness: Strictness,
+    report: IngestReport,
+}
+
+impl Ingest {
+    pub(crate) fn new(strictness: Strictness) -> Self {
+        Ingest {
+            strictness,
+            report: IngestReport::default(),
+        }
+    }
+
+    /// Counts the rows of a feed's file as they are read
+    pub(crate) fn file(&mut self, feed: usize, file: &'static str) -> FileIngest<'_> {
+        FileIngest {
+            strictness: self.strictness,
+            report: self.report.file(feed, file),
+        }
+    }
+
+    pub(crate) fn report(self) -> IngestReport {
+        self.report
+    }
+}
+
+pub(crate) struct FileIngest<'i> {
+    strictness: Strictness,
+    report: &'i mut FileReport,
+}
+
+impl FileIngest<'_> {
+    pub(crate) fn row(&mut self) {
+        self.report.rows += 1;
+    }
+
+    /// Skips the row, unless the strictness fails the load on it
+    pub(crate) fn skip(
+        &mut self,
+        error: RowError,
+        detail: impl fmt::Display,
+    ) -> Result<(), Box<dyn Error>> {
+        *self.report.skipped.entry(error).or_default() += 1;
+        if error.is_quirk() {
+            return Ok(());
+        }
+        match self.strictness {
+            Strictness::Skip => Ok(()),
+            Strictness::Warn => {
+                eprintln!(
+                    "A row of {} of feed {} {} - skipped : {}",
+                    self.report.file,
+                    self.report.feed,
+                    error.describe(),
+                    detail
+                );
+                Ok(())
+            }
+            Strictness::Fail => Err(format!(
+                "a row of {} of feed {} {} : {}",
+                self.report.file,
+                self.report.feed,
+                error.describe(),
+                detail
+            )
+            .into()),
+        }
+    }
+
+    /// Logs the rows skipped for known quirks, which aren't logged one by one
+    pub(crate) fn finish(self) {
+        if self.strictness == Strictness::Skip {
+            return;
+        }
+        for (error, count) in &self.report.skipped {
+            if error.is_quirk() {
+                eprintln!(
+                    "{} rows of {} of feed {} {} - skipped",
+                    count,
+                    self.report.file,
+                    self.report.feed,
+                    error.describe()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn strictness_decides_what_fails() {
+    let mut ingest = Ingest::new(Strictness::Fail);
+    let mut stops = ingest.file(0, "stops.txt");
+    stops.row();
+    stops.skip(RowError::NonNumericStopId, "D_900").unwrap();
+    stops.row();
+    assert!(stops.skip(RowError::Unparsable, "no stop_lat").is_err());
+    stops.finish();
+    let report = ingest.report();
+    assert_eq!(report.files[0].rows, 2);
+    assert_eq!(report.malformed(), 1);
+
+    let mut ingest = Ingest::new(Strictness::Skip);
+    ingest
+        .file(1, "trips.txt")
+        .skip(RowError::UnknownService, 7)
+        .unwrap();
+    ingest
+        .file(1, "trips.txt")
+        .skip(RowError::UnknownService, 8)
+        .unwrap();
+    let report = ingest.report();
+    assert_eq!(report.files.len(), 1);
+    assert_eq!(report.files[0].skipped[&RowError::UnknownService], 2);
+    assert_eq!(
+        serde_json::to_value(&report).unwrap()["files"][0]["skipped"],
+        serde_json::json!({ "unknown_service": 2 })
+    );
+
+    assert_eq!("Fail".parse(), Ok(Strictness::Fail));
+    assert!("lenient".parse::<Strictness>().is_err());
+}
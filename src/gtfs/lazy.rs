@@ -0,0 +1,578 @@
+//! Loading a timetable without holding all of it's stop times, which are most of it.
+//!
+//! At startup the stop times are only scanned, to find where each trip's rows are and the stops of each stopping pattern.
+//! The first search from a station reads in the trips which could reach stations within the searched duration, and the most recently searched stations are kept.
+//! With the `mmap` feature the stop times can be read through a memory map, which leaves keeping the parts read to the system.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::Duration;
+use radar_search::search_data::{GTFSData, StopId, StopTime, TripId};
+use radar_search::sync::Manifest;
+
+use crate::gtfs;
+use crate::gtfs::colors::RouteColors;
+use crate::gtfs::db::{self, DayFilter, FeedStopTimes, StopIds};
+use crate::gtfs::ingest::{IngestReport, RowError, Strictness};
+use crate::gtfs::short_names::ShortNames;
+
+/// The stops, routes and trips of a timetable with an index of it's stop times, which are read in for each station searched from
+pub struct LazyTimetable {
+    /// Everything but the stop times
+    data: GTFSData,
+    /// The `stop_times.txt` of each feed
+    stop_times: Vec<StopTimesFile>,
+    trips: HashMap<TripId, TripRows>,
+    patterns: Vec<Pattern>,
+    /// The patterns calling at each stop, with the index of the stop in the pattern
+    patterns_by_stop: HashMap<StopId, Vec<(usize, usize)>>,
+    /// Most recently searched first
+    materialised: Mutex<VecDeque<Materialised>>,
+    capacity: usize,
+}
+
+/// How the stop times are read back from the feeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopTimesAccess {
+    /// Opening the file and seeking to the rows of each trip
+    Read,
+    /// Mapping the file into memory, the system keeps the pages which were read while it can spare the memory
+    #[cfg(feature = "mmap")]
+    Mapped,
+}
+
+impl StopTimesAccess {
+    pub const ALL: &'static [StopTimesAccess] = &[
+        StopTimesAccess::Read,
+        #[cfg(feature = "mmap")]
+        StopTimesAccess::Mapped,
+    ];
+}
+
+/// The `stop_times.txt` of a feed
+struct StopTimesFile {
+    /// As sniffed when the feed was loaded
+    delimiter: u8,
+    contents: StopTimesContents,
+}
+
+enum StopTimesContents {
+    Path(PathBuf),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl StopTimesFile {
+    fn open(path: PathBuf, access: StopTimesAccess) -> io::Result<Self> {
+        let delimiter = db::sniff_delimiter(&path)?;
+        let contents = match access {
+            StopTimesAccess::Read => StopTimesContents::Path(path),
+            #[cfg(feature = "mmap")]
+            StopTimesAccess::Mapped => {
+                let file = std::fs::File::open(path)?;
+                // safe as long as the feed isn't changed in place, an updated feed is loaded from new files
+                let map = unsafe { memmap2::Mmap::map(&file)? };
+                StopTimesContents::Mapped(map)
+            }
+        };
+        Ok(StopTimesFile {
+            delimiter,
+            contents,
+        })
+    }
+}
+
+/// Where the stop times of a trip are
+struct TripRows {
+    feed_index: usize,
+    /// It's id in the feed, to check the rows read back are the trip's
+    feed_trip_id: gtfs::TripId,
+    /// Of the first stop time, the rest follow it
+    position: csv::Position,
+    pattern: usize,
+}
+
+/// The stops called at by trips, and the shortest ride of any of them between each stop and the next
+struct Pattern {
+    stops: Vec<StopId>,
+    /// A hash of the id in the feed of each stop, to check the rows read back call at them
+    feed_stops: Vec<u64>,
+    min_rides: Vec<u32>,
+    trips: Vec<TripId>,
+}
+
+/// The data to search from a station, with everything reachable within the duration
+struct Materialised {
+    station_id: StopId,
+    max_duration: Duration,
+    data: Arc<GTFSData>,
+}
+
+impl LazyTimetable {
+    /// Loads the feeds as `db::load_feeds_reporting` without their stop times, keeping the data of `capacity` stations
+    pub fn load<P: AsRef<Path>>(
+        gtfs_dirs: &[P],
+        day_filter: DayFilter,
+        route_colors: &RouteColors,
+        short_names: &ShortNames,
+        strictness: Strictness,
+        capacity: usize,
+        access: StopTimesAccess,
+    ) -> Result<(LazyTimetable, StopIds, IngestReport), Box<dyn Error>> {
+        let mut index = StopTimesIndex::default();
+        let (data, stop_ids, report) = db::load_feeds_by(
+            gtfs_dirs,
+            day_filter,
+            route_colors,
+            short_names,
+            strictness,
+            |stop_times, _builder| index.scan(stop_times),
+        )?;
+        eprintln!(
+            "Indexed the stop times of {} trips in {} patterns",
+            index.trips.len(),
+            index.patterns.len()
+        );
+        let mut patterns_by_stop: HashMap<StopId, Vec<(usize, usize)>> = HashMap::new();
+        for (pattern_index, pattern) in index.patterns.iter().enumerate() {
+            for (stop_index, &stop_id) in pattern.stops.iter().enumerate() {
+                patterns_by_stop
+                    .entry(stop_id)
+                    .or_default()
+                    .push((pattern_index, stop_index));
+            }
+        }
+        let lazy = LazyTimetable {
+            data,
+            stop_times: gtfs_dirs
+                .iter()
+                .map(|dir| StopTimesFile::open(dir.as_ref().join("stop_times.txt"), access))
+                .collect::<io::Result<_>>()?,
+            trips: index.trips,
+            patterns: index.patterns,
+            patterns_by_stop,
+            materialised: Mutex::new(VecDeque::new()),
+            capacity,
+        };
+        Ok((lazy, stop_ids, report))
+    }
+
+    /// The stops, routes and trips, without departures
+    pub fn data(&self) -> &GTFSData {
+        &self.data
+    }
+
+    /// The number of stations which have their data kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of stations which have their data read in
+    pub fn materialised_stations(&self) -> usize {
+        self.materialised.lock().unwrap().len()
+    }
+
+    /// Data which searches from the station up to the duration find the same as on the whole timetable, read in on the first search from the station
+    pub fn around(&self, station_id: StopId, max_duration: Duration) -> io::Result<Arc<GTFSData>> {
+        {
+            let mut materialised = self.materialised.lock().unwrap();
+            if let Some(index) = materialised.iter().position(|materialised| {
+                materialised.station_id == station_id && materialised.max_duration >= max_duration
+            }) {
+                let recent = materialised
+                    .remove(index)
+                    .expect("index to be in the queue");
+                let data = recent.data.clone();
+                materialised.push_front(recent);
+                return Ok(data);
+            }
+        }
+        // read without holding the lock, rarely the same station will be read twice
+        let data = Arc::new(self.materialise(station_id, max_duration)?);
+        let mut materialised = self.materialised.lock().unwrap();
+        materialised.retain(|materialised| materialised.station_id != station_id);
+        materialised.push_front(Materialised {
+            station_id,
+            max_duration,
+            data: data.clone(),
+        });
+        materialised.truncate(self.capacity);
+        Ok(data)
+    }
+
+    fn materialise(&self, station_id: StopId, max_duration: Duration) -> io::Result<GTFSData> {
+        let (stops, patterns) = self.reachable(station_id, max_duration);
+        let mut required = self.data.build_from();
+        for stop_id in stops {
+            required.keep_stop(stop_id);
+        }
+        for &pattern in &patterns {
+            let pattern = &self.patterns[pattern];
+            for &stop_id in &pattern.stops {
+                required.keep_stop(stop_id);
+            }
+            for &trip_id in &pattern.trips {
+                required.keep_trip(trip_id);
+            }
+        }
+        let mut increment = self
+            .data
+            .sync_increment(&required.build(), &Manifest::default());
+        let mut stop_times =
+            self.read_stop_times(increment.trips.iter().map(|trip| trip.trip_id))?;
+        for trip in &mut increment.trips {
+            trip.stop_times = stop_times.remove(&trip.trip_id).unwrap_or_default();
+        }
+        increment
+            .into_data()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// The stops which could be reached within the duration and the patterns leaving them, from the shortest rides and walks ignoring any waiting
+    fn reachable(
+        &self,
+        station_id: StopId,
+        max_duration: Duration,
+    ) -> (HashSet<StopId>, HashSet<usize>) {
+        let max_seconds = max_duration.num_seconds().max(0) as u32;
+        let mut earliest: HashMap<StopId, u32> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((0, station_id)));
+        let mut patterns = HashSet::new();
+        while let Some(Reverse((seconds, stop_id))) = queue.pop() {
+            if earliest.contains_key(&stop_id) {
+                continue;
+            }
+            earliest.insert(stop_id, seconds);
+            let mut reach = |stop_id: StopId, seconds: u32| {
+                if seconds <= max_seconds && !earliest.contains_key(&stop_id) {
+                    queue.push(Reverse((seconds, stop_id)));
+                }
+            };
+            let stop = if let Some(stop) = self.data.get_stop(stop_id) {
+                stop
+            } else {
+                continue;
+            };
+            // the stops of a station are all reached together
+            reach(stop.station_id(), seconds);
+            if let Some(station) = self.data.get_stop(stop.station_id()) {
+                for &child_id in station.children() {
+                    reach(child_id, seconds);
+                }
+            }
+            for walk in &stop.walks {
+                reach(
+                    walk.to_stop_id,
+                    seconds + walk.duration.num_seconds().max(0) as u32,
+                );
+            }
+            for &(pattern_index, stop_index) in
+                self.patterns_by_stop.get(&stop_id).into_iter().flatten()
+            {
+                let pattern = &self.patterns[pattern_index];
+                if stop_index + 1 == pattern.stops.len() {
+                    continue;
+                }
+                patterns.insert(pattern_index);
+                let mut ride_seconds = seconds;
+                for (&next_stop_id, &ride) in pattern.stops[stop_index + 1..]
+                    .iter()
+                    .zip(&pattern.min_rides[stop_index..])
+                {
+                    ride_seconds += ride;
+                    if ride_seconds > max_seconds {
+                        break;
+                    }
+                    reach(next_stop_id, ride_seconds);
+                }
+            }
+        }
+        (earliest.into_keys().collect(), patterns)
+    }
+
+    /// Reads the stop times of the trips back from the feeds, in the order they are in the files
+    fn read_stop_times(
+        &self,
+        trip_ids: impl Iterator<Item = TripId>,
+    ) -> io::Result<HashMap<TripId, Vec<StopTime>>> {
+        let mut trips: Vec<(TripId, &TripRows)> = trip_ids
+            .filter_map(|trip_id| Some((trip_id, self.trips.get(&trip_id)?)))
+            .collect();
+        trips.sort_by_key(|(_, rows)| (rows.feed_index, rows.position.byte()));
+        let mut stop_times = HashMap::new();
+        for (feed_index, file) in self.stop_times.iter().enumerate() {
+            let feed_trips: Vec<_> = trips
+                .iter()
+                .filter(|(_, rows)| rows.feed_index == feed_index)
+                .collect();
+            if feed_trips.is_empty() {
+                continue;
+            }
+            match &file.contents {
+                StopTimesContents::Path(path) => {
+                    let mut rdr = db::csv_reader_builder(file.delimiter).from_path(path)?;
+                    let headers = rdr.headers()?.clone();
+                    for &&(trip_id, rows) in &feed_trips {
+                        rdr.seek(rows.position.clone())?;
+                        let trip_stop_times = self.read_trip(&mut rdr, &headers, trip_id, rows)?;
+                        stop_times.insert(trip_id, trip_stop_times);
+                    }
+                }
+                #[cfg(feature = "mmap")]
+                StopTimesContents::Mapped(map) => {
+                    let headers = db::csv_reader_builder(file.delimiter)
+                        .from_reader(&map[..])
+                        .headers()?
+                        .clone();
+                    for &&(trip_id, rows) in &feed_trips {
+                        let mut rdr = db::csv_reader_builder(file.delimiter)
+                            .has_headers(false)
+                            .from_reader(&map[rows.position.byte() as usize..]);
+                        let trip_stop_times = self.read_trip(&mut rdr, &headers, trip_id, rows)?;
+                        stop_times.insert(trip_id, trip_stop_times);
+                    }
+                }
+            }
+        }
+        Ok(stop_times)
+    }
+
+    /// Reads the rows of a trip, from a reader at the first of them
+    fn read_trip<R: io::Read>(
+        &self,
+        rdr: &mut csv::Reader<R>,
+        headers: &csv::StringRecord,
+        trip_id: TripId,
+        rows: &TripRows,
+    ) -> io::Result<Vec<StopTime>> {
+        let pattern = &self.patterns[rows.pattern];
+        let changed = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "stop times of trip {} changed since they were indexed",
+                    trip_id
+                ),
+            )
+        };
+        let mut stop_times = Vec::with_capacity(pattern.stops.len());
+        let mut record = csv::StringRecord::new();
+        for (&stop_id, &feed_stop) in pattern.stops.iter().zip(&pattern.feed_stops) {
+            let stop_time: gtfs::StopTime = loop {
+                if !rdr.read_record(&mut record)? {
+                    return Err(changed());
+                }
+                match record.deserialize(Some(headers)) {
+                    Ok(stop_time) => break stop_time,
+                    // skipped the same way when they were indexed
+                    Err(_) => continue,
+                }
+            };
+            if stop_time.trip_id != rows.feed_trip_id
+                || feed_stop_hash(&stop_time.stop_id) != feed_stop
+            {
+                return Err(changed());
+            }
+            stop_times.push(StopTime {
+                arrival_time: stop_time.arrival_time,
+                departure_time: stop_time.departure_time,
+                stop_id,
+            });
+        }
+        Ok(stop_times)
+    }
+}
+
+/// Built while the stop times of the feeds are scanned
+#[derive(Default)]
+struct StopTimesIndex {
+    trips: HashMap<TripId, TripRows>,
+    patterns: Vec<Pattern>,
+    pattern_ids: HashMap<(Vec<StopId>, Vec<u64>), usize>,
+}
+
+/// The trip being scanned, the rows of a trip have to be together
+struct ScannedTrip {
+    trip_id: TripId,
+    feed_trip_id: gtfs::TripId,
+    position: csv::Position,
+    stops: Vec<StopId>,
+    feed_stops: Vec<u64>,
+    rides: Vec<u32>,
+    last_departure: u32,
+}
+
+impl StopTimesIndex {
+    fn scan(&mut self, mut stop_times: FeedStopTimes) -> Result<(), Box<dyn Error>> {
+        let mut rdr = stop_times.source.open_csv("stop_times.txt")?;
+        let headers = rdr.headers()?.clone();
+        let mut record = csv::StringRecord::new();
+        let mut scanned: Option<ScannedTrip> = None;
+        while rdr.read_record(&mut record)? {
+            stop_times.ingest.row();
+            let stop_time: gtfs::StopTime = match record.deserialize(Some(&headers)) {
+                Ok(stop_time) => stop_time,
+                Err(err) => {
+                    let error = if db::is_non_numeric_id(&err, 3) {
+                        RowError::NonNumericStopId
+                    } else {
+                        RowError::Unparsable
+                    };
+                    stop_times.ingest.skip(error, err)?;
+                    continue;
+                }
+            };
+            let trip_id = if let Some(&trip_id) = stop_times.trips.get(&stop_time.trip_id) {
+                trip_id
+            } else {
+                stop_times
+                    .ingest
+                    .skip(RowError::TripNotLoaded, stop_time.trip_id)?;
+                continue;
+            };
+            let stop_id = stop_times.stop_id(&stop_time.stop_id);
+            match &mut scanned {
+                Some(trip) if trip.trip_id == trip_id => {
+                    trip.rides.push(
+                        stop_time
+                            .arrival_time
+                            .seconds_since_midnight()
+                            .saturating_sub(trip.last_departure),
+                    );
+                    trip.stops.push(stop_id);
+                    trip.feed_stops.push(feed_stop_hash(&stop_time.stop_id));
+                    trip.last_departure = stop_time.departure_time.seconds_since_midnight();
+                }
+                _ => {
+                    if self.trips.contains_key(&trip_id) {
+                        return Err(format!(
+                            "the stop times of trip {} aren't together, they need to be sorted by trip to be read in lazily",
+                            stop_time.trip_id
+                        )
+                        .into());
+                    }
+                    if let Some(trip) = scanned.take() {
+                        self.add(stop_times.feed_index, trip);
+                    }
+                    scanned = Some(ScannedTrip {
+                        trip_id,
+                        feed_trip_id: stop_time.trip_id,
+                        position: record
+                            .position()
+                            .expect("record to have a position")
+                            .clone(),
+                        stops: vec![stop_id],
+                        feed_stops: vec![feed_stop_hash(&stop_time.stop_id)],
+                        rides: vec![],
+                        last_departure: stop_time.departure_time.seconds_since_midnight(),
+                    });
+                }
+            }
+        }
+        if let Some(trip) = scanned {
+            self.add(stop_times.feed_index, trip);
+        }
+        stop_times.ingest.finish();
+        Ok(())
+    }
+
+    fn add(&mut self, feed_index: usize, trip: ScannedTrip) {
+        let ScannedTrip {
+            trip_id,
+            feed_trip_id,
+            position,
+            stops,
+            feed_stops,
+            rides,
+            last_departure: _,
+        } = trip;
+        let patterns = &mut self.patterns;
+        let pattern_index = *self
+            .pattern_ids
+            .entry((stops, feed_stops))
+            .or_insert_with_key(|(stops, feed_stops)| {
+                patterns.push(Pattern {
+                    stops: stops.clone(),
+                    feed_stops: feed_stops.clone(),
+                    min_rides: rides.clone(),
+                    trips: vec![],
+                });
+                patterns.len() - 1
+            });
+        let pattern = &mut self.patterns[pattern_index];
+        for (min_ride, ride) in pattern.min_rides.iter_mut().zip(rides) {
+            *min_ride = (*min_ride).min(ride);
+        }
+        pattern.trips.push(trip_id);
+        self.trips.insert(
+            trip_id,
+            TripRows {
+                feed_index,
+                feed_trip_id,
+                position,
+                pattern: pattern_index,
+            },
+        );
+    }
+}
+
+/// Hashes a stop's id in the feed, the index keeps the hash rather than the id
+fn feed_stop_hash(gtfs_id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    gtfs_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn stop_times_changed_after_indexing_are_refused() {
+    use crate::gtfs::demo;
+
+    let dir = std::env::temp_dir().join(format!("transit-radar-lazy-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    demo::write_demo_feed(&dir).unwrap();
+    let (lazy, stop_ids, _) = LazyTimetable::load(
+        &[&dir],
+        DayFilter::All,
+        &RouteColors::new(),
+        &ShortNames::default(),
+        Strictness::Fail,
+        1,
+        StopTimesAccess::Read,
+    )
+    .unwrap();
+    let alexanderplatz = stop_ids.get("600").unwrap();
+    assert!(lazy.around(alexanderplatz, Duration::minutes(30)).is_ok());
+
+    // every row calls at another platform, the rows are where they were indexed
+    let stop_times = std::fs::read_to_string(dir.join("stop_times.txt")).unwrap();
+    let (header, rows) = stop_times.split_once('\n').unwrap();
+    let rows: Vec<String> = rows
+        .lines()
+        .map(|row| {
+            let mut fields: Vec<&str> = row.split(',').collect();
+            let stop_id = format!("{}9", &fields[3][..fields[3].len() - 1]);
+            fields[3] = &stop_id;
+            fields.join(",")
+        })
+        .collect();
+    std::fs::write(
+        dir.join("stop_times.txt"),
+        format!("{}\n{}\n", header, rows.join("\n")),
+    )
+    .unwrap();
+    let westkreuz = stop_ids.get("100").unwrap();
+    let around = lazy.around(westkreuz, Duration::minutes(30));
+    std::fs::remove_dir_all(dir).unwrap();
+    assert_eq!(
+        around.err().map(|err| err.kind()),
+        Some(io::ErrorKind::InvalidData)
+    );
+}
@@ -1,4 +1,14 @@
+pub mod aliases;
+pub mod bundle;
+pub mod colors;
 pub mod db;
+pub mod demo;
+pub mod diff;
+pub mod importance;
+pub mod ingest;
+pub mod lazy;
 mod model;
+pub mod short_names;
+pub mod slugs;
 pub mod time;
 pub use model::*;
@@ -35,6 +35,39 @@ pub struct Calendar {
     // end_date: Date,
 }
 
+impl Calendar {
+    /// All the days that this service runs on between start and end dates
+    pub fn days(&self) -> Vec<Day> {
+        [
+            (Day::Monday, self.monday),
+            (Day::Tuesday, self.tuesday),
+            (Day::Wednesday, self.wednesday),
+            (Day::Thursday, self.thursday),
+            (Day::Friday, self.friday),
+            (Day::Saturday, self.saturday),
+            (Day::Sunday, self.sunday),
+        ]
+        .iter()
+        .filter(|(_day, available)| *available > 0)
+        .map(|(day, _available)| *day)
+        .collect()
+    }
+}
+
+/// GTFS record
+/// [https://developers.google.com/transit/gtfs/reference#calendar_datestxt]
+/// Explicitly activates or disables service on a date, such as on a public holiday.
+#[derive(Debug, Deserialize)]
+pub struct CalendarDate {
+    // "service_id","date","exception_type"
+    /// Identifies a set of dates when a service exception occurs for one or more routes. A service may only be defined here, rather than in calendar.txt.
+    pub service_id: ServiceId,
+    /// Date when service exception occurs.
+    pub date: Date,
+    /// Indicates whether service is available on the date specified in the date field.
+    pub exception_type: ExceptionType,
+}
+
 /// GTFS record
 /// [https://developers.google.com/transit/gtfs/reference#routestxt]
 #[derive(Debug, Deserialize, Ord, PartialOrd, Eq, PartialEq)]
@@ -45,14 +78,18 @@ pub struct Route {
     /// Agency for the specified route. This field is required when the dataset provides data for routes from more than one agency in agency.txt, otherwise it is optional.
     agency_id: AgencyId,
     /// Short name of a route. This will often be a short, abstract identifier like "32", "100X", or "Green" that riders use to identify a route, but which doesn't give any indication of what places the route serves. Either route_short_name or route_long_name must be specified, or potentially both if appropriate.
+    #[serde(default)]
     pub route_short_name: String,
-    // / Full name of a route. This name is generally more descriptive than the route_short_name and often includes the route's destination or stop. Either route_short_name or route_long_name must be specified, or potentially both if appropriate.
-    // route_long_name: Option<String>,
+    /// Full name of a route. This name is generally more descriptive than the route_short_name and often includes the route's destination or stop. Either route_short_name or route_long_name must be specified, or potentially both if appropriate.
+    #[serde(default)]
+    pub route_long_name: Option<String>,
     // / Description of a route that provides useful, quality information. Do not simply duplicate the name of the route.
     // route_desc: Option<String>,
     /// Indicates the type of transportation used on a route.
     #[serde(with = "route_type_format")]
     pub route_type: RouteType,
+    /// Route color designation that matches public facing material. Defaults to white (FFFFFF) when omitted or left empty.
+    #[serde(default)]
     pub route_color: Option<String>,
     // route_text_color: Option<String>,
 }
@@ -70,8 +107,12 @@ pub struct Trip {
     pub trip_id: TripId,
     // trip_headsign: String,
     // trip_short_name: Option<String>,
-    // pub direction_id: DirectionId,
-    // block_id: Option<BlockId>,
+    /// Indicates the direction of travel for a trip. This field is not used in routing; it provides a way to separate trips by direction when publishing time tables.
+    #[serde(default)]
+    pub direction_id: Option<DirectionId>,
+    /// Identifies the block to which the trip belongs. A block consists of a single trip or many sequential trips made using the same vehicle, defined by shared service days and block_id. A block_id can have trips with different service days, making distinct blocks.
+    #[serde(default)]
+    pub block_id: Option<String>,
     // shape_id: ShapeId,
     // wheelchair_accessible: WheelchairAccessible,
     // bikes_allowed: BikesAllowed,
@@ -140,7 +181,21 @@ pub struct Stop {
     pub parent_station: Option<StopId>,
     // wheelchair_boarding: Option<u8>,
     // platform_code: Option<String>,
-    // zone_id: Option<ZoneId>,
+    /// Identifies the fare zone for a stop.
+    #[serde(default)]
+    pub zone_id: Option<String>,
+}
+
+/// GTFS Record
+/// [https://developers.google.com/transit/gtfs/reference#feed_infotxt]
+/// The dataset metadata, including publisher, version, and expiration information.
+#[derive(Debug, Deserialize)]
+pub struct FeedInfo {
+    /// Full name of the organization that publishes the dataset.
+    pub feed_publisher_name: String,
+    /// String that indicates the current version of their GTFS dataset.
+    #[serde(default)]
+    pub feed_version: Option<String>,
 }
 
 /// GTFS Record
@@ -199,3 +254,28 @@ impl Ord for Stop {
         self.stop_id.cmp(&other.stop_id)
     }
 }
+
+#[test]
+fn calendar_days() {
+    let calendars =
+        "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n\
+                     1,1,1,1,1,1,0,0,20200101,20201231\n\
+                     2,0,0,0,0,0,1,1,20200101,20201231\n";
+    let days: Vec<Vec<Day>> = csv::Reader::from_reader(calendars.as_bytes())
+        .deserialize()
+        .map(|calendar: Result<Calendar, _>| calendar.unwrap().days())
+        .collect();
+    assert_eq!(
+        days,
+        vec![
+            vec![
+                Day::Monday,
+                Day::Tuesday,
+                Day::Wednesday,
+                Day::Thursday,
+                Day::Friday
+            ],
+            vec![Day::Saturday, Day::Sunday],
+        ]
+    );
+}
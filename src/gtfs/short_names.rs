@@ -0,0 +1,176 @@
+//! Shortening the names of stops to draw them, by a profile of rules chosen for each feed.
+//!
+//! A profile is a list of regexes, every match of each of which is replaced in turn. The built in `vbb` profile strips VBB's prefixes of modes, `Berlin, ` and the suffixes of stations, and is used for feeds without a profile, while `none` keeps the names as they are.
+//! More profiles are loaded from a CSV file with the columns `profile`, `pattern` and `replacement`, the rows of each profile being applied in the order they are in. The replacement can refer to groups of the pattern as `$1` or `$name`, and a profile of the same name as a built in one replaces it.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Deserialize;
+
+/// The profile of the feeds which haven't been given one
+pub const DEFAULT_PROFILE: &str = "vbb";
+
+const VBB_PATTERN: &str = r"Berlin, |S |S\+U |U | Bhf| \(Berlin\)| \[.*]";
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Regex,
+    replacement: String,
+}
+
+#[derive(Deserialize)]
+struct RuleRow {
+    profile: String,
+    pattern: String,
+    #[serde(default)]
+    replacement: String,
+}
+
+/// The profiles of rules and the profile of each feed
+#[derive(Debug, Clone)]
+pub struct ShortNames {
+    profiles: HashMap<String, Vec<Rule>>,
+    /// By the index of the feed, the feeds after the last are of the default profile
+    feed_profiles: Vec<String>,
+}
+
+impl Default for ShortNames {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShortNames {
+    /// The built in profiles, with every feed shortened by the VBB rules
+    pub fn new() -> Self {
+        let mut profiles = HashMap::new();
+        profiles.insert(
+            DEFAULT_PROFILE.to_owned(),
+            vec![Rule {
+                pattern: Regex::new(VBB_PATTERN).unwrap(),
+                replacement: String::new(),
+            }],
+        );
+        profiles.insert("none".to_owned(), vec![]);
+        ShortNames {
+            profiles,
+            feed_profiles: vec![],
+        }
+    }
+
+    /// Adds the profiles of a rules file, replacing any of the same names
+    pub fn load_rules(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut loaded: HashMap<String, Vec<Rule>> = HashMap::new();
+        for row in csv::Reader::from_path(path)?.into_deserialize() {
+            let row: RuleRow = row?;
+            let pattern = Regex::new(&row.pattern).map_err(|err| {
+                format!(
+                    "pattern of profile {} in {} : {}",
+                    row.profile,
+                    path.display(),
+                    err
+                )
+            })?;
+            loaded.entry(row.profile).or_default().push(Rule {
+                pattern,
+                replacement: row.replacement,
+            });
+        }
+        self.profiles.extend(loaded);
+        Ok(())
+    }
+
+    /// Sets the profile of each feed from a list in the order of the feeds, eg. `vbb,none`, any profiles loaded from files need to be loaded first
+    pub fn set_feed_profiles(&mut self, config: &str) -> Result<(), String> {
+        let feed_profiles: Vec<String> = config
+            .split(',')
+            .map(|profile| profile.trim().to_owned())
+            .collect();
+        if let Some(unknown) = feed_profiles
+            .iter()
+            .find(|profile| !self.profiles.contains_key(*profile))
+        {
+            return Err(format!("unknown short name profile {}", unknown));
+        }
+        self.feed_profiles = feed_profiles;
+        Ok(())
+    }
+
+    /// The name of a stop of the feed shortened by the feed's profile
+    pub fn shorten(&self, feed: usize, stop_name: &str) -> String {
+        let profile = self
+            .feed_profiles
+            .get(feed)
+            .map_or(DEFAULT_PROFILE, String::as_str);
+        let mut name = stop_name.to_owned();
+        for rule in &self.profiles[profile] {
+            name = rule
+                .pattern
+                .replace_all(&name, rule.replacement.as_str())
+                .into_owned();
+        }
+        name
+    }
+}
+
+#[test]
+fn vbb_names_are_shortened_by_default() {
+    let short_names = ShortNames::new();
+    for (input, output) in &[
+        ("Berlin, Birkholzer Weg/Straße 8", "Birkholzer Weg/Straße 8"),
+        ("S Mahlsdorf (Berlin) [Tram Bus Treskowstr.]", "Mahlsdorf"),
+        ("S Strausberg [Tram]", "Strausberg"),
+        (
+            "Dallgow-Döberitz, Finkenkruger Str.",
+            "Dallgow-Döberitz, Finkenkruger Str.",
+        ),
+        ("S+U Alexanderplatz (Berlin) [U2]", "Alexanderplatz"),
+        ("S+U Gesundbrunnen Bhf (Berlin)", "Gesundbrunnen"),
+        (
+            "Berlin, S+U Alexanderplatz Bhf/Memhardstr.",
+            "Alexanderplatz/Memhardstr.",
+        ),
+    ] {
+        assert_eq!(short_names.shorten(0, input), *output);
+        assert_eq!(short_names.shorten(3, input), *output);
+    }
+}
+
+#[test]
+fn feeds_are_shortened_by_their_profiles() {
+    let path = std::env::temp_dir().join(format!(
+        "transit-radar-short-names-{}.csv",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        "profile,pattern,replacement\n\
+         db,\\s*\\(.*\\)$,\n\
+         db,^(.*) Hbf$,$1 Hauptbahnhof\n",
+    )
+    .unwrap();
+    let mut short_names = ShortNames::new();
+    short_names.load_rules(&path).unwrap();
+    assert!(short_names.set_feed_profiles("vbb,plain").is_err());
+    short_names.set_feed_profiles("none, db").unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        short_names.shorten(0, "S Mahlsdorf (Berlin)"),
+        "S Mahlsdorf (Berlin)"
+    );
+    assert_eq!(
+        short_names.shorten(1, "Leipzig Hbf (tief)"),
+        "Leipzig Hauptbahnhof"
+    );
+    // a feed without a profile is of the default one
+    assert_eq!(short_names.shorten(2, "S Mahlsdorf (Berlin)"), "Mahlsdorf");
+
+    std::fs::write(&path, "profile,pattern,replacement\nbroken,(,\n").unwrap();
+    let err = ShortNames::new().load_rules(&path).err().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.to_string().contains("profile broken"), "{}", err);
+}
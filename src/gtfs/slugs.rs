@@ -0,0 +1,292 @@
+//! Names for stations in urls, `/depart-from/alexanderplatz/now`, which unlike the ids of the loaded data mostly stay the same when the timetable is updated.
+//!
+//! For the links which break anyway, when a station is renamed or the ids change, a table of the slugs and ids links have used can be kept across timetable updates.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::Path;
+
+use radar_search::search_data::*;
+use serde::{Deserialize, Serialize};
+
+use super::db::StopIds;
+
+#[derive(Debug, Default)]
+pub struct StationSlugs {
+    slugs: HashMap<StopId, String>,
+    stations: HashMap<String, StopId>,
+    /// Slugs and ids of earlier timetables, to the station they now mean
+    redirects: HashMap<String, StopId>,
+}
+
+/// A slug or id which was used in links, with the GTFS id of the station it meant then
+#[derive(Serialize, Deserialize)]
+struct Redirect {
+    from: String,
+    gtfs_id: String,
+}
+
+impl StationSlugs {
+    /// Slugs the stations by their short name, where several have the same short name their full names are used, and failing that a number is added in the order of their location
+    pub fn new(data: &GTFSData) -> Self {
+        let mut by_short_name: HashMap<String, Vec<&Stop>> = HashMap::new();
+        for stop in data.stops().filter(|stop| stop.is_station()) {
+            by_short_name
+                .entry(slugify(stop.short_stop_name()))
+                .or_default()
+                .push(stop);
+        }
+        let mut candidates: HashMap<String, Vec<&Stop>> = HashMap::new();
+        for (slug, stations) in by_short_name {
+            if let [station] = stations[..] {
+                candidates.entry(slug).or_default().push(station);
+            } else {
+                for station in stations {
+                    candidates
+                        .entry(slugify(station.full_stop_name()))
+                        .or_default()
+                        .push(station);
+                }
+            }
+        }
+
+        let mut taken: HashSet<String> = candidates
+            .iter()
+            .filter(|(_, stations)| stations.len() == 1)
+            .map(|(slug, _)| slug.clone())
+            .collect();
+        let mut candidates: Vec<_> = candidates.into_iter().collect();
+        candidates.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut slugs = StationSlugs::default();
+        for (slug, mut stations) in candidates {
+            if let [station] = stations[..] {
+                slugs.insert(station.stop_id, slug);
+                continue;
+            }
+            stations.sort_by(|a, b| {
+                (a.location.x(), a.location.y())
+                    .partial_cmp(&(b.location.x(), b.location.y()))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(a.stop_id.cmp(&b.stop_id))
+            });
+            let mut number = 1;
+            for station in stations {
+                let mut numbered = slug.clone();
+                while !taken.insert(numbered.clone()) {
+                    number += 1;
+                    numbered = format!("{}-{}", slug, number);
+                }
+                slugs.insert(station.stop_id, numbered);
+            }
+        }
+        slugs
+    }
+
+    fn insert(&mut self, station_id: StopId, slug: String) {
+        self.stations.insert(slug.clone(), station_id);
+        self.slugs.insert(station_id, slug);
+    }
+
+    pub fn slug(&self, station_id: StopId) -> Option<&str> {
+        self.slugs.get(&station_id).map(String::as_str)
+    }
+
+    pub fn station(&self, slug: &str) -> Option<StopId> {
+        self.stations.get(slug).copied()
+    }
+
+    /// The station which a slug or id of an earlier timetable now means
+    pub fn redirect(&self, old: &str) -> Option<StopId> {
+        self.redirects.get(old).copied()
+    }
+
+    /// Reads the table of slugs and ids which links have used, and adds the current ones which aren't in it yet.
+    ///
+    /// An entry is kept as it was first written, so an id which now belongs to another station still leads to the station of the earlier links.
+    pub fn update_redirects(
+        &mut self,
+        path: &Path,
+        data: &GTFSData,
+        stop_ids: &StopIds,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut table: Vec<Redirect> = if path.exists() {
+            csv::Reader::from_path(path)?
+                .into_deserialize()
+                .collect::<Result<_, _>>()?
+        } else {
+            vec![]
+        };
+        let mut known = HashSet::new();
+        for Redirect { from, gtfs_id } in &table {
+            known.insert(from.clone());
+            let station = stop_ids
+                .get(gtfs_id)
+                .and_then(|stop_id| data.get_stop(stop_id))
+                .map(Stop::station_id);
+            if let Some(station) = station {
+                self.redirects.insert(from.clone(), station);
+            }
+        }
+
+        let mut current: Vec<_> = self.slugs.iter().collect();
+        current.sort();
+        for (&station_id, slug) in current {
+            let gtfs_id = if let Some(gtfs_id) = stop_ids.gtfs_id(station_id) {
+                gtfs_id
+            } else {
+                continue;
+            };
+            for from in [slug.clone(), station_id.to_string()] {
+                if known.insert(from.clone()) {
+                    table.push(Redirect {
+                        from,
+                        gtfs_id: gtfs_id.to_owned(),
+                    });
+                }
+            }
+        }
+
+        let mut writer = csv::Writer::from_path(path)?;
+        for redirect in table {
+            writer.serialize(redirect)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Lower case ascii letters and digits separated by single dashes, letters with accents are written without and German umlauts and ß as they would be without them
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    let mut separated = true;
+    for c in name.chars().flat_map(char::to_lowercase) {
+        let ascii = match c {
+            'ä' => "ae",
+            'ö' => "oe",
+            'ü' => "ue",
+            'ß' => "ss",
+            'à' | 'á' | 'â' | 'ã' | 'å' => "a",
+            'ç' | 'č' | 'ć' => "c",
+            'è' | 'é' | 'ê' | 'ë' => "e",
+            'ì' | 'í' | 'î' | 'ï' => "i",
+            'ł' => "l",
+            'ñ' | 'ń' => "n",
+            'ò' | 'ó' | 'ô' | 'õ' | 'ø' => "o",
+            'ś' | 'š' => "s",
+            'ù' | 'ú' | 'û' => "u",
+            'ý' => "y",
+            'ź' | 'ż' | 'ž' => "z",
+            c if c.is_ascii_alphanumeric() => {
+                slug.push(c);
+                separated = false;
+                continue;
+            }
+            _ => {
+                if !separated {
+                    slug.push('-');
+                    separated = true;
+                }
+                continue;
+            }
+        };
+        slug.push_str(ascii);
+        separated = false;
+    }
+    if slug.ends_with('-') {
+        slug.pop();
+    }
+    slug
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stop_id(id: u32) -> StopId {
+        StopId::new(id).unwrap()
+    }
+
+    /// Stations with their full name and location, the short name is the full one without the town
+    fn data(stations: &[(u32, &str, f64)]) -> GTFSData {
+        let mut builder = GTFSData::builder(HashMap::new(), "20240101".to_owned());
+        for &(id, full_name, lat) in stations {
+            let short_name = full_name.split(", ").last().unwrap();
+            builder.add_station(
+                stop_id(id),
+                full_name.to_owned(),
+                short_name.to_owned(),
+                geo::Point::new(lat, 13.4),
+            );
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn slugs_of_names() {
+        assert_eq!(slugify("Alexanderplatz"), "alexanderplatz");
+        assert_eq!(slugify("S+U Frankfurter Allee"), "s-u-frankfurter-allee");
+        assert_eq!(slugify("Eberswalder Str."), "eberswalder-str");
+        assert_eq!(slugify("Görlitzer Bahnhof"), "goerlitzer-bahnhof");
+        assert_eq!(slugify("Straße des 17. Juni"), "strasse-des-17-juni");
+        assert_eq!(slugify("Café Kranzler / Zoo"), "cafe-kranzler-zoo");
+    }
+
+    #[test]
+    fn same_names_are_told_apart() {
+        let data = data(&[
+            (1, "Berlin, Alexanderplatz", 52.52),
+            (2, "Berlin, Hauptbahnhof", 52.52),
+            (3, "Potsdam, Hauptbahnhof", 52.39),
+            (4, "Dorf, Kirche", 52.3),
+            (5, "Dorf, Kirche", 52.1),
+        ]);
+        let slugs = StationSlugs::new(&data);
+        let slug = |id| slugs.slug(stop_id(id)).unwrap();
+        assert_eq!(slug(1), "alexanderplatz");
+        assert_eq!(slug(2), "berlin-hauptbahnhof");
+        assert_eq!(slug(3), "potsdam-hauptbahnhof");
+        assert_eq!(slug(5), "dorf-kirche");
+        assert_eq!(slug(4), "dorf-kirche-2");
+        assert_eq!(slugs.station("dorf-kirche-2"), Some(stop_id(4)));
+    }
+
+    #[test]
+    fn redirects_survive_timetable_updates() {
+        let path = std::env::temp_dir().join(format!(
+            "transit-radar-slug-redirects-{}.csv",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let stop_ids = |ids: &[&str]| {
+            let mut interner = lasso::Rodeo::default();
+            for id in ids {
+                interner.get_or_intern(id);
+            }
+            StopIds::new(interner)
+        };
+
+        // Zoo is the second station
+        let before = data(&[(1, "Alexanderplatz", 52.52), (2, "Zoo", 52.51)]);
+        let mut slugs = StationSlugs::new(&before);
+        slugs
+            .update_redirects(&path, &before, &stop_ids(&["alex", "zoo"]))
+            .unwrap();
+
+        // then it's renamed and the ids change
+        let after = data(&[
+            (1, "Ostkreuz", 52.5),
+            (2, "Alexanderplatz", 52.52),
+            (3, "Zoologischer Garten", 52.51),
+        ]);
+        let mut slugs = StationSlugs::new(&after);
+        slugs
+            .update_redirects(&path, &after, &stop_ids(&["ostkreuz", "alex", "zoo"]))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(slugs.station("zoologischer-garten"), Some(stop_id(3)));
+        assert_eq!(slugs.redirect("zoo"), Some(stop_id(3)));
+        assert_eq!(slugs.redirect("2"), Some(stop_id(3)));
+        assert_eq!(slugs.redirect("1"), Some(stop_id(2)));
+    }
+}
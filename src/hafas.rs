@@ -0,0 +1,548 @@
+//! Importing a small window of the timetable around a station from a HAFAS style REST API, such as [hafas-rest-api](https://github.com/public-transport/hafas-rest-api), for cities which don't publish GTFS.
+//!
+//! The departures of the station are fetched, then the trips leaving in the window, then the departures of the stations those trips reach, the earliest reached first, until no more stations are reached within the window or the limit of stations is reached.
+//! The data has a single service running on the day of the window, and each stop of the API is a platform of it's station, or of a station of it's own when the API doesn't give one.
+//!
+//! The API is reached through [`Fetch`], [`HttpApi`] speaks plain HTTP so an API served over HTTPS is reached through a local proxy.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime};
+use radar_search::search_data::{Builder, GTFSData, RouteId, RouteType, StopId, TripId};
+use radar_search::time::Time;
+use serde::Deserialize;
+
+use crate::gtfs::colors::RouteColors;
+use crate::gtfs::db::StopIds;
+use crate::gtfs::short_names::ShortNames;
+
+/// The stations whose departures are fetched when the window doesn't say, each station is a request and so is each trip leaving it
+pub const DEFAULT_MAX_STATIONS: usize = 40;
+
+const SERVICE_ID: u16 = 1;
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+pub trait Fetch {
+    /// The body of a `GET` of the path and query, which start with `/`
+    fn get(&self, path: &str) -> Result<String, Box<dyn Error>>;
+}
+
+/// An API reached over plain HTTP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpApi {
+    host: String,
+    port: u16,
+    base_path: String,
+}
+
+impl HttpApi {
+    /// From the API's base URL, such as `http://localhost:3000`
+    pub fn new(url: &str) -> Result<Self, String> {
+        let rest = if let Some(rest) = url.strip_prefix("http://") {
+            rest
+        } else if url.starts_with("https://") {
+            return Err(format!(
+                "{} is HTTPS, which isn't supported, reach it through a local proxy",
+                url
+            ));
+        } else {
+            return Err(format!("{} isn't an http:// URL", url));
+        };
+        let (authority, base_path) = match rest.find('/') {
+            Some(index) => (&rest[..index], rest[index..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("{} has an invalid port", url))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("{} has no host", url));
+        }
+        Ok(HttpApi {
+            host: host.to_owned(),
+            port,
+            base_path: base_path.to_owned(),
+        })
+    }
+}
+
+impl Fetch for HttpApi {
+    fn get(&self, path: &str) -> Result<String, Box<dyn Error>> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        // HTTP/1.0 so that the body isn't chunked and ends when the connection is closed
+        write!(
+            stream,
+            "GET {}{} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\nUser-Agent: transit-radar\r\n\r\n",
+            self.base_path, path, self.host
+        )?;
+        let mut response = vec![];
+        stream.read_to_end(&mut response)?;
+        let response = String::from_utf8(response)?;
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| format!("GET {} : response without a body", path))?;
+        let status = head.lines().next().unwrap_or_default();
+        if status.split(' ').nth(1) != Some("200") {
+            return Err(format!("GET {} : {}", path, status).into());
+        }
+        Ok(body.to_owned())
+    }
+}
+
+/// The part of the timetable to import
+#[derive(Debug, Clone)]
+pub struct Window {
+    /// The API's id of the station searched from
+    pub station: String,
+    pub start: NaiveDateTime,
+    pub duration: Duration,
+    pub max_stations: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct Location {
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiStop {
+    id: String,
+    name: String,
+    location: Option<Location>,
+    station: Option<Box<ApiStop>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Line {
+    name: Option<String>,
+    product: Option<String>,
+    mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Departure {
+    trip_id: String,
+    #[serde(default)]
+    cancelled: bool,
+}
+
+/// Newer versions of the API wrap the departures in an object
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Departures {
+    Wrapped { departures: Vec<Departure> },
+    Bare(Vec<Departure>),
+}
+
+#[derive(Debug, Deserialize)]
+struct Trip {
+    line: Line,
+    #[serde(default)]
+    stopovers: Vec<Stopover>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TripResponse {
+    Wrapped { trip: Trip },
+    Bare(Trip),
+}
+
+/// A call of a trip at a stop, the times are ISO 8601 with the offset of the stop's time zone
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Stopover {
+    stop: ApiStop,
+    arrival: Option<String>,
+    planned_arrival: Option<String>,
+    departure: Option<String>,
+    planned_departure: Option<String>,
+    #[serde(default)]
+    cancelled: bool,
+}
+
+impl Stopover {
+    /// The arrival and departure in local time, the real time ones if the API has them, either is the other if it's missing
+    fn times(&self) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        let time = |real: &Option<String>, planned: &Option<String>| {
+            real.as_ref()
+                .or(planned.as_ref())
+                .and_then(|time| DateTime::<FixedOffset>::parse_from_rfc3339(time).ok())
+                .map(|time| time.naive_local())
+        };
+        let arrival = time(&self.arrival, &self.planned_arrival);
+        let departure = time(&self.departure, &self.planned_departure);
+        Some((arrival.or(departure)?, departure.or(arrival)?))
+    }
+}
+
+/// The route type of a line by the product of the API, which are named as in VBB's and DB's profiles, or otherwise by it's mode
+fn route_type(line: &Line) -> RouteType {
+    match (line.product.as_deref(), line.mode.as_deref()) {
+        (Some("suburban"), _) => RouteType::SuburbanRailway,
+        (Some("subway"), _) => RouteType::UrbanRailway,
+        (Some("tram"), _) => RouteType::TramService,
+        (Some("bus"), _) => RouteType::BusService,
+        (Some("ferry"), _) => RouteType::WaterTransportService,
+        (Some("express"), _)
+        | (Some("regional"), _)
+        | (Some("national"), _)
+        | (Some("nationalExpress"), _)
+        | (Some("regionalExpress"), _) => RouteType::RailwayService,
+        (_, Some("train")) => RouteType::Rail,
+        _ => RouteType::Bus,
+    }
+}
+
+struct Importer<'c> {
+    builder: Builder,
+    interner: lasso::Rodeo,
+    date: NaiveDate,
+    routes: HashMap<String, RouteId>,
+    next_trip_id: u32,
+    route_colors: &'c RouteColors,
+    short_names: &'c ShortNames,
+}
+
+impl Importer<'_> {
+    /// The platform of the stop, which is added with it's station the first time
+    fn platform(&mut self, stop: &ApiStop) -> Result<(String, StopId), Box<dyn Error>> {
+        let (station, platform_id) = match &stop.station {
+            Some(station) => (&**station, stop.id.clone()),
+            None => (stop, format!("{}/platform", stop.id)),
+        };
+        let location = stop
+            .location
+            .as_ref()
+            .or(station.location.as_ref())
+            .map(|location| geo::Point::new(location.latitude, location.longitude))
+            .ok_or_else(|| format!("stop {} has no location", stop.id))?;
+        let station_id = self.stop_id(&station.id);
+        if !self.builder.has_stop(station_id) {
+            let short_name = self.short_names.shorten(0, &station.name);
+            self.builder
+                .add_station(station_id, station.name.clone(), short_name, location);
+            self.builder.add_transfer(station_id, station_id, None)?;
+        }
+        let platform_stop_id = self.stop_id(&platform_id);
+        if !self.builder.has_stop(platform_stop_id) {
+            let short_name = self.short_names.shorten(0, &stop.name);
+            self.builder.add_stop_or_platform(
+                platform_stop_id,
+                stop.name.clone(),
+                short_name,
+                location,
+                Some(station_id),
+            );
+        }
+        Ok((station.id.clone(), platform_stop_id))
+    }
+
+    fn stop_id(&mut self, api_id: &str) -> StopId {
+        self.interner.get_or_intern(api_id).into_inner()
+    }
+
+    fn time(&self, time: NaiveDateTime) -> Time {
+        let since_midnight = time - self.date.and_hms_opt(0, 0, 0).unwrap();
+        Time::from_seconds_since_midnight(since_midnight.num_seconds() as u32)
+    }
+
+    /// Adds the calls of the trip within the window, returning the stations called at and the arrival times, or nothing when fewer than two calls are in the window
+    fn add_trip(
+        &mut self,
+        trip: Trip,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<(String, NaiveDateTime)>, Box<dyn Error>> {
+        let calls: Vec<(Stopover, (NaiveDateTime, NaiveDateTime))> = trip
+            .stopovers
+            .into_iter()
+            .filter(|stopover| !stopover.cancelled)
+            .filter_map(|stopover| {
+                let times = stopover.times()?;
+                Some((stopover, times))
+            })
+            .filter(|(_, (arrival, departure))| *departure >= start && *arrival <= end)
+            .collect();
+        if calls.len() < 2 {
+            return Ok(vec![]);
+        }
+        let route_name = trip.line.name.clone().unwrap_or_default();
+        let route_type = route_type(&trip.line);
+        let next_route_id = self.routes.len() as RouteId + 1;
+        let route_id = *self
+            .routes
+            .entry(format!("{}/{:?}", route_name, route_type))
+            .or_insert(next_route_id);
+        if route_id == next_route_id {
+            let route_color = self.route_colors.route_color(&route_name, route_type);
+            self.builder
+                .add_route(route_id, route_name, route_type, route_color.to_owned());
+        }
+        self.next_trip_id += 1;
+        let trip_id = TripId::new(self.next_trip_id).unwrap();
+        self.builder
+            .add_trip(trip_id, route_id, SERVICE_ID, None, None)?;
+        let mut stations = vec![];
+        for (stopover, (arrival, departure)) in calls {
+            let (station, platform) = self.platform(&stopover.stop)?;
+            let (arrival_time, departure_time) = (self.time(arrival), self.time(departure));
+            self.builder
+                .add_trip_stop(trip_id, arrival_time, departure_time, platform)?;
+            stations.push((station, arrival));
+        }
+        Ok(stations)
+    }
+}
+
+fn encode(value: &str) -> String {
+    urlencoding::encode(value).into_owned()
+}
+
+/// Imports the window of the timetable from the API, with the API's ids of the stops and stations
+pub fn import(
+    api: &impl Fetch,
+    window: &Window,
+    route_colors: &RouteColors,
+    short_names: &ShortNames,
+) -> Result<(GTFSData, StopIds), Box<dyn Error>> {
+    let date = window.start.date();
+    let mut services_by_day = HashMap::new();
+    services_by_day.insert(
+        date.weekday().into(),
+        [SERVICE_ID].iter().copied().collect(),
+    );
+    let mut importer = Importer {
+        builder: GTFSData::builder(services_by_day, date.format("%Y%m%d").to_string()),
+        interner: lasso::Rodeo::default(),
+        date,
+        routes: HashMap::new(),
+        next_trip_id: 0,
+        route_colors,
+        short_names,
+    };
+    let end = window.start + window.duration;
+    // the calls in the window of each trip fetched, by the API's id of the trip
+    let mut trips: HashMap<String, Vec<(String, NaiveDateTime)>> = HashMap::new();
+    let mut reached: BTreeSet<(NaiveDateTime, String)> = BTreeSet::new();
+    reached.insert((window.start, window.station.clone()));
+    let mut fetched = HashSet::new();
+    while let Some((arrival, station)) = reached.pop_first() {
+        if fetched.len() >= window.max_stations {
+            break;
+        }
+        let minutes = (end - arrival).num_minutes();
+        if minutes <= 0 || !fetched.insert(station.clone()) {
+            continue;
+        }
+        let departures: Departures = serde_json::from_str(&api.get(&format!(
+            "/stops/{}/departures?when={}&duration={}",
+            encode(&station),
+            encode(&arrival.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            minutes
+        ))?)?;
+        let departures = match departures {
+            Departures::Wrapped { departures } => departures,
+            Departures::Bare(departures) => departures,
+        };
+        for departure in departures
+            .into_iter()
+            .filter(|departure| !departure.cancelled)
+        {
+            if !trips.contains_key(&departure.trip_id) {
+                let trip: TripResponse = serde_json::from_str(&api.get(&format!(
+                    "/trips/{}?stopovers=true",
+                    encode(&departure.trip_id)
+                ))?)?;
+                let trip = match trip {
+                    TripResponse::Wrapped { trip } => trip,
+                    TripResponse::Bare(trip) => trip,
+                };
+                let calls = importer.add_trip(trip, window.start, end)?;
+                trips.insert(departure.trip_id.clone(), calls);
+            }
+            // the stations called at after the station are reached
+            let calls = &trips[&departure.trip_id];
+            if let Some(boarding) = calls.iter().position(|(called, _)| *called == station) {
+                for (called, arrival) in &calls[boarding + 1..] {
+                    if !fetched.contains(called) {
+                        reached.insert((*arrival, called.clone()));
+                    }
+                }
+            }
+        }
+    }
+    eprintln!(
+        "Imported {} trips from the departures of {} stations",
+        importer.next_trip_id,
+        fetched.len()
+    );
+    Ok((importer.builder.build()?, StopIds::new(importer.interner)))
+}
+
+#[test]
+fn api_urls() {
+    assert_eq!(
+        HttpApi::new("http://localhost:3000/v6/"),
+        Ok(HttpApi {
+            host: "localhost".to_owned(),
+            port: 3000,
+            base_path: "/v6".to_owned(),
+        })
+    );
+    assert_eq!(HttpApi::new("http://example.org").unwrap().port, 80);
+    assert!(HttpApi::new("https://v6.vbb.transport.rest").is_err());
+    assert!(HttpApi::new("http://:80").is_err());
+}
+
+#[test]
+fn window_reaches_stations_through_transfers() {
+    use radar_search::journey_graph::{Item, Plotter};
+    use radar_search::time::Period;
+
+    /// Answers with the bodies by the path, without the query
+    struct Fixture(HashMap<&'static str, String>);
+
+    impl Fetch for Fixture {
+        fn get(&self, path: &str) -> Result<String, Box<dyn Error>> {
+            let path = path.split('?').next().unwrap();
+            Ok(self
+                .0
+                .get(path)
+                .ok_or_else(|| format!("nothing at {}", path))?
+                .clone())
+        }
+    }
+
+    let stop = |id: &str, name: &str| {
+        format!(
+            r#"{{"type": "stop", "id": "{}", "name": "{}", "location": {{"latitude": 52.5, "longitude": 13.4}}}}"#,
+            id, name
+        )
+    };
+    let call = |stop: String, time: &str| {
+        format!(
+            r#"{{"stop": {}, "arrival": "2024-01-15T{}:00+01:00", "departure": "2024-01-15T{}:00+01:00"}}"#,
+            stop, time, time
+        )
+    };
+    let trip = |line: &str, product: &str, calls: Vec<String>| {
+        format!(
+            r#"{{"trip": {{"line": {{"name": "{}", "product": "{}", "mode": "train"}}, "stopovers": [{}]}}}}"#,
+            line,
+            product,
+            calls.join(",")
+        )
+    };
+    // the stops at Bornholmer Str. are a platform and a tram stop of it's station
+    let bornholmer_stop = |id: &str, name: &str| {
+        format!(
+            r#"{{"type": "stop", "id": "{}", "name": "{}", "location": {{"latitude": 52.55, "longitude": 13.39}}, "station": {{"type": "station", "id": "b", "name": "S Bornholmer Str. (Berlin)", "location": {{"latitude": 52.55, "longitude": 13.39}}}}}}"#,
+            id, name
+        )
+    };
+    let platform = bornholmer_stop("b1", "Bornholmer Str. Gleis 1");
+    let mut bodies = HashMap::new();
+    bodies.insert(
+        "/stops/a/departures",
+        r#"{"departures": [{"tripId": "t 1"}, {"tripId": "t2", "cancelled": true}]}"#.to_owned(),
+    );
+    bodies.insert(
+        "/trips/t%201",
+        trip(
+            "S1",
+            "suburban",
+            vec![
+                call(stop("x", "Wannsee"), "09:40"),
+                call(stop("a", "S Gesundbrunnen"), "10:00"),
+                call(platform, "10:05"),
+                call(stop("c", "Wollankstr."), "10:08"),
+                call(stop("far", "Oranienburg"), "11:00"),
+            ],
+        ),
+    );
+    bodies.insert("/stops/b/departures", r#"[{"tripId": "t3"}]"#.to_owned());
+    bodies.insert(
+        "/trips/t3",
+        trip(
+            "M13",
+            "tram",
+            vec![
+                call(
+                    bornholmer_stop("b2", "Bornholmer Str./Malmöer Str."),
+                    "10:10",
+                ),
+                call(stop("d", "Prenzlauer Allee"), "10:20"),
+            ],
+        ),
+    );
+    bodies.insert("/stops/c/departures", "[]".to_owned());
+    bodies.insert("/stops/d/departures", "[]".to_owned());
+    let api = Fixture(bodies);
+
+    let window = |max_stations| Window {
+        station: "a".to_owned(),
+        start: NaiveDate::from_ymd_opt(2024, 1, 15)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap(),
+        duration: Duration::minutes(30),
+        max_stations,
+    };
+    let (data, stop_ids) = import(
+        &api,
+        &window(DEFAULT_MAX_STATIONS),
+        &RouteColors::new(),
+        &ShortNames::new(),
+    )
+    .unwrap();
+    assert_eq!(data.trips().count(), 2);
+    let bornholmer = data.get_stop(stop_ids.get("b").unwrap()).unwrap();
+    assert!(bornholmer.is_station());
+    assert_eq!(bornholmer.short_stop_name(), "Bornholmer Str.");
+    assert_eq!(
+        data.get_stop(stop_ids.get("b1").unwrap())
+            .unwrap()
+            .parent_station(),
+        Some(bornholmer.stop_id)
+    );
+    // the calls outside of the window are left out
+    assert!(stop_ids.get("x").is_none() && stop_ids.get("far").is_none());
+
+    // and a search from the station reaches the tram's stop by changing at Bornholmer Str.
+    let origin = data.get_stop(stop_ids.get("a").unwrap()).unwrap();
+    let mut plotter = Plotter::new(
+        NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+        Period::between(Time::from_hms(10, 0, 0), Time::from_hms(10, 30, 0)),
+        &data,
+    );
+    plotter.add_origin_station(origin);
+    plotter.add_route_type(RouteType::SuburbanRailway);
+    plotter.add_route_type(RouteType::TramService);
+    let reached: Vec<String> = plotter
+        .filter_map(|item| match item {
+            Item::Station { stop, .. } => Some(stop.full_stop_name().to_owned()),
+            _ => None,
+        })
+        .collect();
+    assert!(
+        reached.contains(&"Prenzlauer Allee".to_owned()),
+        "{:?}",
+        reached
+    );
+
+    // the tram isn't fetched when only the first station's departures are
+    let (data, _) = import(&api, &window(1), &RouteColors::new(), &ShortNames::new()).unwrap();
+    assert_eq!(data.trips().count(), 1);
+}
@@ -0,0 +1,208 @@
+//! Serving the radars of a single city from AWS Lambda, for deployments with too little traffic to keep a server running.
+//!
+//! The bundle is loaded once when the function's container starts, from S3 when it's location is `s3://<bucket>/<key>` or otherwise from the container's files, and each invocation is a request to the routes of [`service::router`](crate::service::router). The function is reached by a function URL or an HTTP API, which send events of payload format 2.0.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use axum::body::{to_bytes, Body};
+use axum::http::Request;
+use axum::Router;
+use lambda_runtime::{service_fn, LambdaEvent};
+use serde::{Deserialize, Serialize};
+use tower::ServiceExt;
+
+use crate::embed::FeedBundle;
+use crate::service;
+
+/// The largest response, which Lambda limits to 6MB
+const MAX_RESPONSE_BYTES: usize = 6_000_000;
+
+/// An HTTP request as a function URL or an HTTP API sends it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpRequest {
+    pub raw_path: String,
+    #[serde(default)]
+    pub raw_query_string: String,
+    pub request_context: RequestContext,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestContext {
+    pub http: HttpContext,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpContext {
+    pub method: String,
+}
+
+/// The response to a function URL or an HTTP API, the body is text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    pub is_base64_encoded: bool,
+}
+
+/// Where a bundle is loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleLocation<'l> {
+    S3 { bucket: &'l str, key: &'l str },
+    Local(&'l Path),
+}
+
+impl<'l> BundleLocation<'l> {
+    pub fn parse(location: &'l str) -> Result<Self, String> {
+        if let Some(object) = location.strip_prefix("s3://") {
+            match object.split_once('/') {
+                Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => {
+                    Ok(BundleLocation::S3 { bucket, key })
+                }
+                _ => Err(format!(
+                    "expected s3://<bucket>/<key> rather than {}",
+                    location
+                )),
+            }
+        } else {
+            Ok(BundleLocation::Local(Path::new(location)))
+        }
+    }
+}
+
+/// Loads the bundle, an object in S3 is downloaded to the container's temporary directory first
+pub async fn load_bundle(location: &str) -> Result<FeedBundle, lambda_runtime::Error> {
+    let path = match BundleLocation::parse(location)? {
+        BundleLocation::Local(path) => path.to_owned(),
+        BundleLocation::S3 { bucket, key } => {
+            let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+            let object = aws_sdk_s3::Client::new(&config)
+                .get_object()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await?;
+            let bytes = object.body.collect().await?.into_bytes();
+            let path = std::env::temp_dir().join("radar.bundle");
+            std::fs::write(&path, bytes)?;
+            eprintln!(
+                "Downloaded {} bytes of {}",
+                path.metadata()?.len(),
+                location
+            );
+            path
+        }
+    };
+    FeedBundle::load(&path).map_err(|err| format!("loading {} : {}", location, err).into())
+}
+
+/// Answers the requests of the function's invocations until the container is stopped
+pub async fn run(feed_bundle: FeedBundle) -> Result<(), lambda_runtime::Error> {
+    let router = service::router(Arc::new(feed_bundle));
+    lambda_runtime::run(service_fn(move |event: LambdaEvent<HttpRequest>| {
+        respond(router.clone(), event.payload)
+    }))
+    .await
+}
+
+/// The router's response to the request
+pub async fn respond(
+    router: Router,
+    request: HttpRequest,
+) -> Result<HttpResponse, lambda_runtime::Error> {
+    let uri = if request.raw_query_string.is_empty() {
+        request.raw_path
+    } else {
+        format!("{}?{}", request.raw_path, request.raw_query_string)
+    };
+    let request = Request::builder()
+        .method(request.request_context.http.method.as_str())
+        .uri(uri)
+        .body(Body::empty())?;
+    let response = router.oneshot(request).await?;
+    let status_code = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+        .collect();
+    let body = to_bytes(response.into_body(), MAX_RESPONSE_BYTES).await?;
+    Ok(HttpResponse {
+        status_code,
+        headers,
+        body: String::from_utf8(body.to_vec())?,
+        is_base64_encoded: false,
+    })
+}
+
+#[test]
+fn bundle_locations_are_parsed() {
+    assert_eq!(
+        BundleLocation::parse("s3://radars/berlin/radar.bundle"),
+        Ok(BundleLocation::S3 {
+            bucket: "radars",
+            key: "berlin/radar.bundle"
+        })
+    );
+    assert_eq!(
+        BundleLocation::parse("radar.bundle"),
+        Ok(BundleLocation::Local(Path::new("radar.bundle")))
+    );
+    assert!(BundleLocation::parse("s3://radars").is_err());
+    assert!(BundleLocation::parse("s3:///radar.bundle").is_err());
+}
+
+#[test]
+fn requests_are_routed() {
+    let (dir, bundle_path) = crate::gtfs::bundle::compile_demo("lambda");
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let feed_bundle = runtime
+        .block_on(load_bundle(bundle_path.to_str().unwrap()))
+        .unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    let router = service::router(Arc::new(feed_bundle));
+    let request = |path: &str, query: &str| {
+        serde_json::from_value(serde_json::json!({
+            "version": "2.0",
+            "rawPath": path,
+            "rawQueryString": query,
+            "headers": {"accept": "*/*"},
+            "requestContext": {"http": {"method": "GET", "path": path}},
+            "isBase64Encoded": false
+        }))
+        .unwrap()
+    };
+
+    let svg = runtime
+        .block_on(respond(
+            router.clone(),
+            request(
+                "/depart-from/alexanderplatz/2024-01-15T10:00:00",
+                "minutes=20",
+            ),
+        ))
+        .unwrap();
+    assert_eq!(svg.status_code, 200);
+    assert_eq!(svg.headers["content-type"], "image/svg+xml");
+    assert!(svg.body.contains(">Friedrichstr.</text>"));
+
+    let found = runtime
+        .block_on(respond(router.clone(), request("/search.json", "q=alex")))
+        .unwrap();
+    assert_eq!(found.headers["content-type"], "application/json");
+    assert_eq!(
+        found.body,
+        r#"[{"name":"Alexanderplatz","slug":"alexanderplatz"}]"#
+    );
+
+    let unknown = runtime
+        .block_on(respond(router, request("/depart-from/atlantis/now", "")))
+        .unwrap();
+    assert_eq!(unknown.status_code, 404);
+}
@@ -1,5 +1,20 @@
+pub mod districts;
+#[cfg(feature = "draw")]
 pub mod draw;
+#[cfg(feature = "draw")]
+pub mod embed;
+mod geojson;
+#[cfg(feature = "loader")]
 pub mod gtfs;
-mod suggester;
+#[cfg(feature = "hafas")]
+pub mod hafas;
+#[cfg(feature = "lambda")]
+pub mod lambda;
+pub mod poi;
+#[cfg(feature = "service")]
+pub mod service;
+#[cfg(feature = "draw")]
+pub use embed::{render_svg, FeedBundle, RenderError, RenderOptions};
 pub use radar_search::search_data::GTFSData;
-pub use suggester::Suggester;
+#[cfg(feature = "loader")]
+pub use radar_suggester::Suggester;
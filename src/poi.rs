@@ -0,0 +1,73 @@
+//! Points of interest loaded from GeoJSON, such as supermarkets, lakes or clubs, which can be shown on the radar where they can be reached.
+//!
+//! Only `Point` features are used, they are named by their `name` property and categorised by their `category` or `amenity` property.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::geojson::{point, FeatureCollection, Geometry};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Poi {
+    pub name: String,
+    pub category: Option<String>,
+    /// Stored as (lat, lon) like the stop locations
+    pub location: geo::Point<f64>,
+}
+
+pub fn load_pois(path: &Path) -> Result<Vec<Poi>, Box<dyn Error>> {
+    Ok(pois_from_reader(BufReader::new(File::open(path)?))?)
+}
+
+/// Reads the named points of a GeoJSON feature collection, other features are skipped
+pub fn pois_from_reader(reader: impl Read) -> Result<Vec<Poi>, serde_json::Error> {
+    let collection: FeatureCollection = serde_json::from_reader(reader)?;
+    Ok(collection
+        .features
+        .into_iter()
+        .filter_map(|feature| {
+            let location = match feature.geometry.as_ref()? {
+                Geometry::Point { coordinates } => point(coordinates)?,
+                _ => return None,
+            };
+            Some(Poi {
+                name: feature.property("name")?,
+                category: feature
+                    .property("category")
+                    .or_else(|| feature.property("amenity")),
+                location,
+            })
+        })
+        .collect())
+}
+
+#[test]
+fn read_geojson_points() {
+    let geojson = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [13.64, 52.44]}, "properties": {"name": "Müggelsee", "category": "lake"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [13.45, 52.51]}, "properties": {"name": "Berghain", "amenity": "nightclub"}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [13.4, 52.5]}, "properties": {}},
+            {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[13.4, 52.5], [13.5, 52.5]]}, "properties": {"name": "Spree"}}
+        ]
+    }"#;
+    let pois = pois_from_reader(geojson.as_bytes()).unwrap();
+    assert_eq!(
+        pois,
+        vec![
+            Poi {
+                name: "Müggelsee".to_owned(),
+                category: Some("lake".to_owned()),
+                location: geo::Point::new(52.44, 13.64),
+            },
+            Poi {
+                name: "Berghain".to_owned(),
+                category: Some("nightclub".to_owned()),
+                location: geo::Point::new(52.51, 13.45),
+            },
+        ]
+    );
+}
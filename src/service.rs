@@ -0,0 +1,167 @@
+//! The radar as an axum [`Router`], so that an axum app can serve radars itself rather than run the server next to it.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use transit_radar::{service, FeedBundle};
+//!
+//! let bundle = Arc::new(FeedBundle::load("radar.bundle".as_ref()).unwrap());
+//! let app = axum::Router::new().merge(service::router(bundle));
+//! ```
+//!
+//! It serves `/depart-from/<station>/<time>?minutes=<minutes>&mode=<modes>` as the server does, where the time is `now` or a local time such as `2024-01-15T10:00:00`, and the modes are separated by commas, and `/search.json?q=<query>` lists the names and slugs of the stations matching a query. The links on the radars are to these paths, so the router is merged at the root of the app rather than nested.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::{Duration, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::draw::radar::{TransitMode, DEFAULT_MAX_DURATION_MINS};
+use crate::embed::{render_svg, FeedBundle, RenderError, RenderOptions};
+
+/// Routes to the radars of the bundle's timetable
+pub fn router(feed_bundle: Arc<FeedBundle>) -> Router {
+    Router::new()
+        .route("/depart-from/:station/:time", get(depart_from))
+        .route("/search.json", get(search_stations))
+        .with_state(feed_bundle)
+}
+
+#[derive(Deserialize)]
+struct RadarQuery {
+    minutes: Option<i64>,
+    mode: Option<String>,
+}
+
+async fn depart_from(
+    Path((station, time)): Path<(String, String)>,
+    Query(RadarQuery { minutes, mode }): Query<RadarQuery>,
+    State(feed_bundle): State<Arc<FeedBundle>>,
+) -> Result<Response, StatusCode> {
+    let departure_time = if time == "now" {
+        None
+    } else {
+        let time: NaiveDateTime = time.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+        Some(
+            chrono_tz::Europe::Berlin
+                .from_local_datetime(&time)
+                .earliest()
+                .ok_or(StatusCode::BAD_REQUEST)?,
+        )
+    };
+    let options = RenderOptions {
+        departure_time,
+        max_duration: Duration::minutes(minutes.unwrap_or(DEFAULT_MAX_DURATION_MINS)),
+        modes: modes(mode.as_deref()).ok_or(StatusCode::BAD_REQUEST)?,
+    };
+    match render_svg(&feed_bundle, &station, &options) {
+        Ok(svg) => Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response()),
+        // this bundle only has the timetable for one day
+        Err(RenderError::UnknownStation(_)) | Err(RenderError::DayNotLoaded(_)) => {
+            Err(StatusCode::NOT_FOUND)
+        }
+    }
+}
+
+/// The most stations listed by a search
+const SEARCH_LIMIT: usize = 20;
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+}
+
+#[derive(Serialize)]
+struct FoundStation<'s> {
+    name: &'s str,
+    slug: Option<&'s str>,
+}
+
+/// The stations matching the query, for suggesting them as it's typed
+async fn search_stations(
+    Query(SearchQuery { q }): Query<SearchQuery>,
+    State(feed_bundle): State<Arc<FeedBundle>>,
+) -> Response {
+    let found: Vec<_> = feed_bundle
+        .search_stations(&q, SEARCH_LIMIT)
+        .into_iter()
+        .map(|station| FoundStation {
+            name: station.full_stop_name(),
+            slug: feed_bundle.slug(station.stop_id),
+        })
+        .collect();
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        serde_json::to_string(&found).unwrap(),
+    )
+        .into_response()
+}
+
+/// The modes by their keys separated by commas, the default modes if there are none
+fn modes(keys: Option<&str>) -> Option<HashSet<TransitMode>> {
+    match keys {
+        Some(keys) if !keys.is_empty() => keys.split(',').map(TransitMode::from_key).collect(),
+        _ => Some(TransitMode::DEFAULTS.iter().copied().collect()),
+    }
+}
+
+#[test]
+fn radars_are_served() {
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let router = router(Arc::new(crate::gtfs::bundle::demo_bundle("service")));
+    let get = |uri: &str| {
+        router
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+    };
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let response =
+            get("/depart-from/alexanderplatz/2024-01-15T10:00:00?minutes=20&mode=sbahn,ubahn")
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+        let svg = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(svg.to_vec())
+            .unwrap()
+            .contains(">Friedrichstr.</text>"));
+
+        let found = to_bytes(
+            get("/search.json?q=alex").await.unwrap().into_body(),
+            usize::MAX,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            &found[..],
+            br#"[{"name":"Alexanderplatz","slug":"alexanderplatz"}]"#
+        );
+
+        for (uri, status) in [
+            ("/depart-from/atlantis/now", StatusCode::NOT_FOUND),
+            (
+                "/depart-from/alexanderplatz/2024-01-16T10:00:00",
+                StatusCode::NOT_FOUND,
+            ),
+            ("/depart-from/alexanderplatz/soon", StatusCode::BAD_REQUEST),
+            (
+                "/depart-from/alexanderplatz/now?mode=zeppelin",
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            assert_eq!(get(uri).await.unwrap().status(), status, "{}", uri);
+        }
+    });
+}